@@ -0,0 +1,56 @@
+//! Build metadata shown by `GET /about`, the commit can be given with `FREECASTER_GIT_COMMIT` where git is missing
+
+use std::env;
+use std::process::Command;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+fn output(program: &str, args: &[&str]) -> Option<String> {
+    let output = Command::new(program).args(args).output().ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    let output = String::from_utf8(output.stdout).ok()?;
+    Some(output.trim().to_string())
+}
+
+fn main() {
+    let commit = env::var("FREECASTER_GIT_COMMIT")
+        .ok()
+        .filter(|commit| !commit.is_empty())
+        .or_else(|| output("git", &["rev-parse", "--short=12", "HEAD"]))
+        .unwrap_or_else(|| "unknown".to_string());
+
+    let rustc = env::var("RUSTC").unwrap_or_else(|_| "rustc".to_string());
+    let rustc_version = output(&rustc, &["--version"]).unwrap_or_else(|| "unknown".to_string());
+
+    // reproducible builds pin the time
+    let built_at = env::var("SOURCE_DATE_EPOCH")
+        .ok()
+        .and_then(|epoch| epoch.parse::<u64>().ok())
+        .unwrap_or_else(|| {
+            SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|since| since.as_secs())
+                .unwrap_or_default()
+        });
+
+    let mut features = env::vars()
+        .filter_map(|(var, _)| {
+            var.strip_prefix("CARGO_FEATURE_")
+                .map(|feature| feature.to_lowercase().replace('_', "-"))
+        })
+        .collect::<Vec<_>>();
+    features.sort();
+
+    println!("cargo::rustc-env=FREECASTER_GIT_COMMIT={commit}");
+    println!("cargo::rustc-env=FREECASTER_RUSTC_VERSION={rustc_version}");
+    println!("cargo::rustc-env=FREECASTER_BUILT_AT={built_at}");
+    println!(
+        "cargo::rustc-env=FREECASTER_FEATURES={}",
+        features.join(",")
+    );
+    println!("cargo::rerun-if-env-changed=FREECASTER_GIT_COMMIT");
+    println!("cargo::rerun-if-env-changed=SOURCE_DATE_EPOCH");
+    println!("cargo::rerun-if-changed=.git/HEAD");
+    println!("cargo::rerun-if-changed=.git/refs/heads");
+}
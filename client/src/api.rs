@@ -0,0 +1,551 @@
+//! Requests and responses of the HTTP API of nodes, as the nodes themselves send and read them
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::str::FromStr;
+
+/// Names the calling node on calls between nodes
+pub const NODE_HEADER: &str = "X-Freecaster-Node";
+/// Id of the caller's poll cycle on calls between nodes, logged on both sides
+pub const TRACE_HEADER: &str = "X-Freecaster-Trace";
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct StatusResponse {
+    pub version: String,
+    pub name: String,
+    /// The node's poller hasn't completed a cycle in a while
+    #[serde(default)]
+    pub degraded: bool,
+    /// Only reported with `report_system_metrics`, missing from older nodes
+    #[serde(default)]
+    pub system: Option<SystemMetrics>,
+    /// No announcements go out until then
+    #[serde(default)]
+    pub muted_until: Option<DateTime<Utc>>,
+    /// The node reaches its own listener, `false` means the process is up but the listener is broken.
+    /// Missing before the first check and from older nodes
+    #[serde(default)]
+    pub self_check_ok: Option<bool>,
+    /// How the node expects to be reached, if configured
+    #[serde(default)]
+    pub advertised_address: Option<String>,
+    /// Of the node's config files, missing when configured from the environment alone
+    #[serde(default)]
+    pub config_hash: Option<String>,
+    /// Optional parts of the protocol the node supports, empty from older nodes
+    #[serde(default)]
+    pub features: Vec<String>,
+    /// Bumped on every change of a node's status, announcement or silence, missing from older nodes
+    #[serde(default)]
+    pub revision: u64,
+}
+
+/// Takes `cancelled` silence broadcasts, older nodes would apply them as new silences
+pub const FEATURE_SILENCE_CANCEL: &str = "silence-cancel";
+/// Has `POST /mute-broadcast`
+pub const FEATURE_MUTE_BROADCAST: &str = "mute-broadcast";
+/// Reads the `X-Freecaster-Node` header of callers
+pub const FEATURE_NODE_HEADER: &str = "node-header";
+/// Has `GET /obituary/v2`, with a verdict on every node
+pub const FEATURE_OBITUARY_V2: &str = "obituary-v2";
+/// Has `POST /claim`, winners of a roll claim the death from the other confirmers before announcing it
+pub const FEATURE_ANNOUNCEMENT_CLAIM: &str = "announcement-claim";
+/// Has `GET /introspect`, a compact view of the node's state
+pub const FEATURE_INTROSPECT: &str = "introspect";
+
+/// `GET /introspect`, what the node currently thinks, small enough to be asked every cycle
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IntrospectResponse {
+    pub name: String,
+    pub version: String,
+    /// Bumped on every change of a node's status, announcement or silence
+    pub revision: u64,
+    /// The `GridNodeStatus::code` of every node but the responder
+    pub statuses: BTreeMap<String, u8>,
+    /// Ids of the silences in effect, local ones left out
+    pub silences: Vec<String>,
+    pub announcements_suppressed: bool,
+    pub muted: bool,
+}
+
+/// The sender won the roll for `node`'s death and is about to announce it
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AnnouncementClaimRequest {
+    pub node: String,
+    pub announcer: String,
+    pub roll: usize,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AnnouncementClaimResponse {
+    /// `false` when the responder knows of a stronger claim, the sender backs off
+    pub accepted: bool,
+    /// The claim in effect on the responder, by the node making it
+    pub claimed_by: Option<String>,
+    pub roll: Option<usize>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DeadNodeResponse {
+    pub name: String,
+    pub roll: usize,
+    /// The responder has an active silence for the node, its broadcast may not have reached everyone yet
+    #[serde(default)]
+    pub silenced: bool,
+    /// Rolls of the other nodes confirming the death, as far as the responder knows, missing from older nodes
+    #[serde(default)]
+    pub rolls: BTreeMap<String, usize>,
+}
+
+/// A node failing to answer the responder, but not dead for it yet
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DyingNodeResponse {
+    pub name: String,
+    pub fail_count: usize,
+    /// The responder's `dead_after`
+    pub threshold: usize,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AnnouncedDeathResponse {
+    pub name: String,
+    pub announcer: String,
+    pub announced_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ObituaryResponse {
+    pub dead_nodes: Vec<DeadNodeResponse>,
+    /// Deaths the responder knows were announced, so restarted or new nodes don't announce them again
+    #[serde(default)]
+    pub announced_deaths: Vec<AnnouncedDeathResponse>,
+    /// Missing from older nodes, their dying nodes count as alive
+    #[serde(default)]
+    pub dying_nodes: Vec<DyingNodeResponse>,
+    /// Every node as the responder sees it, only from `/obituary/v2`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub verdicts: Option<Vec<NodeVerdictResponse>>,
+}
+
+/// What the responder thinks of a node
+#[derive(Debug, Serialize, Deserialize, Copy, Clone, Eq, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum NodeVerdict {
+    Alive,
+    Dying,
+    Dead,
+    /// Not polled by the responder yet, it has no opinion
+    Unknown,
+    /// Under a silence of the responder, whatever its status
+    Silenced,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct NodeVerdictResponse {
+    pub name: String,
+    pub verdict: NodeVerdict,
+    pub fail_count: usize,
+    pub last_ok: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Copy, Clone, Eq, PartialEq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum GridNodeStatus {
+    Alive,
+    Dying,
+    Dead,
+    /// Not polled yet
+    Unknown,
+}
+
+/// Why the last poll of a node failed
+#[derive(Debug, Serialize, Deserialize, Clone, Eq, PartialEq)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum PollError {
+    Unreachable,
+    /// Answered with another name than configured, with `strict_name_check`
+    IdentityMismatch {
+        reported_name: String,
+    },
+}
+
+impl std::fmt::Display for PollError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PollError::Unreachable => write!(f, "unreachable"),
+            PollError::IdentityMismatch { reported_name } => {
+                write!(f, "identity mismatch, reports itself as `{reported_name}`")
+            }
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct GridNodeResponse {
+    pub name: String,
+    /// How the node is called in announcements, `None` without a `display_name`
+    #[serde(default)]
+    pub display_name: Option<String>,
+    /// Last time the node was polled, whether it answered or not
+    pub last_poll: Option<DateTime<Utc>>,
+    /// Last time the node answered a poll, missing from older nodes
+    #[serde(default)]
+    pub last_ok: Option<DateTime<Utc>>,
+    /// Last time the node's status or announcement changed, missing from older nodes
+    #[serde(default)]
+    pub last_changed: Option<DateTime<Utc>>,
+    pub status: GridNodeStatus,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    /// Days until the node's TLS certificate expires, if it serves one
+    #[serde(default)]
+    pub cert_days_remaining: Option<i64>,
+    #[serde(default)]
+    pub system: Option<SystemMetrics>,
+    /// Why the last poll failed, `None` if it succeeded
+    #[serde(default)]
+    pub poll_error: Option<PollError>,
+    /// How the node expects to be reached, as it reported in its status
+    #[serde(default)]
+    pub advertised_address: Option<String>,
+    /// Node which announced the death, only for dead nodes
+    #[serde(default)]
+    pub announced_by: Option<String>,
+    #[serde(default)]
+    pub announced_at: Option<DateTime<Utc>>,
+    /// Roll that won the announcement, unknown when the death was learned from a peer
+    #[serde(default)]
+    pub announcement_roll: Option<usize>,
+    /// This node's obituary calls to the node, `None` before the first one
+    #[serde(default)]
+    pub obituary_calls: Option<ObituaryCalls>,
+    /// Last call of the node to this one, a recent one while polls of it fail means it reaches us but not the other way
+    #[serde(default)]
+    pub last_inbound: Option<DateTime<Utc>>,
+    /// As reported in the node's last status, `None` before the first one
+    #[serde(default)]
+    pub version: Option<String>,
+    /// The version doesn't satisfy the node's `expected_version`
+    #[serde(default)]
+    pub version_mismatch: bool,
+    /// Dies and recovers too often, neither is announced
+    #[serde(default)]
+    pub flapping: bool,
+    /// Dead, and its death was announced
+    #[serde(default)]
+    pub announced: bool,
+    /// Protocol features the node advertised in its last status, `None` before the first one
+    #[serde(default)]
+    pub features: Option<Vec<String>>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct GridResponse {
+    pub nodes: Vec<GridNodeResponse>,
+    /// Nodes matching the filters, `nodes` only holds the page of them asked for with `offset` and `limit`
+    #[serde(default)]
+    pub total: usize,
+    /// Bumped on every change of a node's status, announcement or silence, missing from older nodes
+    #[serde(default)]
+    pub revision: u64,
+
+    #[serde(flatten)]
+    pub summary: GridSummary,
+    /// Totals of the nodes carrying each tag
+    #[serde(default)]
+    pub tag_totals: BTreeMap<String, GridSummary>,
+    /// This node holds back death announcements, too few peers are reachable
+    #[serde(default)]
+    pub announcements_suppressed: bool,
+    #[serde(default)]
+    pub announcements_suppressed_reason: Option<String>,
+    /// No announcements go out until then
+    #[serde(default)]
+    pub muted_until: Option<DateTime<Utc>>,
+    /// How long this node's poll cycles take, `None` before the first one
+    #[serde(default)]
+    pub cycle: Option<CycleResponse>,
+    /// The latest config reload, `None` without one
+    #[serde(default)]
+    pub config_reload: Option<ConfigReload>,
+    #[serde(default)]
+    pub config: Option<ConfigResponse>,
+}
+
+/// The config in effect
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ConfigResponse {
+    /// `None` when configured from the environment alone
+    pub source: Option<String>,
+    pub hash: Option<String>,
+    pub loaded_at: DateTime<Utc>,
+    /// Loaded longer ago than `config_stale_after`
+    pub stale: bool,
+}
+
+/// Durations of a poll cycle's phases, in seconds
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct CycleTimings {
+    pub internet_check: f64,
+    /// Expiring and broadcasting silences and the mute
+    pub silences: f64,
+    pub polling: f64,
+    /// Certificate, disk, digest and own listener checks
+    pub checks: f64,
+    pub obituaries: f64,
+    pub announcements: f64,
+    pub total: f64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CycleResponse {
+    pub latest: CycleTimings,
+    /// Of the last `cycles` cycles
+    pub average: CycleTimings,
+    pub cycles: usize,
+    /// Configured time between cycles, in seconds
+    pub poll_time: f64,
+}
+
+impl GridNodeStatus {
+    /// Worst first, for sorting
+    pub fn severity(self) -> u8 {
+        match self {
+            GridNodeStatus::Dead => 0,
+            GridNodeStatus::Dying => 1,
+            GridNodeStatus::Unknown => 2,
+            GridNodeStatus::Alive => 3,
+        }
+    }
+
+    /// Its byte in `/introspect`
+    pub fn code(self) -> u8 {
+        match self {
+            GridNodeStatus::Alive => 0,
+            GridNodeStatus::Dying => 1,
+            GridNodeStatus::Dead => 2,
+            GridNodeStatus::Unknown => 3,
+        }
+    }
+
+    pub fn from_code(code: u8) -> Option<Self> {
+        match code {
+            0 => Some(GridNodeStatus::Alive),
+            1 => Some(GridNodeStatus::Dying),
+            2 => Some(GridNodeStatus::Dead),
+            3 => Some(GridNodeStatus::Unknown),
+            _ => None,
+        }
+    }
+}
+
+impl FromStr for GridNodeStatus {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "alive" => Ok(GridNodeStatus::Alive),
+            "dying" => Ok(GridNodeStatus::Dying),
+            "dead" => Ok(GridNodeStatus::Dead),
+            "unknown" => Ok(GridNodeStatus::Unknown),
+            _ => Err(format!("Unknown status `{s}`")),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Copy, Clone, Default, Eq, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum GridHealth {
+    #[default]
+    Ok,
+    /// Some nodes are dying or unknown
+    Degraded,
+    /// Some nodes are dead
+    Critical,
+}
+
+/// Totals of a set of nodes, with the verdict following from them
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct GridSummary {
+    pub alive_nodes: usize,
+    pub dead_nodes: usize,
+    pub dying_nodes: usize,
+    #[serde(default)]
+    pub unknown_nodes: usize,
+    /// Nodes with an active silence, counted by their status too
+    #[serde(default)]
+    pub silenced_nodes: usize,
+    pub total_nodes: usize,
+    #[serde(default)]
+    pub health: GridHealth,
+}
+
+impl GridSummary {
+    pub fn add(&mut self, status: GridNodeStatus, silenced: bool) {
+        match status {
+            GridNodeStatus::Alive => self.alive_nodes += 1,
+            GridNodeStatus::Dying => self.dying_nodes += 1,
+            GridNodeStatus::Dead => self.dead_nodes += 1,
+            GridNodeStatus::Unknown => self.unknown_nodes += 1,
+        }
+        self.silenced_nodes += usize::from(silenced);
+        self.total_nodes += 1;
+
+        self.health = if self.dead_nodes > 0 {
+            GridHealth::Critical
+        } else if self.dying_nodes > 0 || self.unknown_nodes > 0 {
+            GridHealth::Degraded
+        } else {
+            GridHealth::Ok
+        };
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SilenceResponse {
+    pub name: String,
+    pub silent_until: DateTime<Utc>,
+}
+
+/// A silence in effect on the responder
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ActiveSilenceResponse {
+    pub id: String,
+    pub node_name: String,
+    pub silent_until: DateTime<Utc>,
+    /// Every peer has received it
+    pub broadcasted: bool,
+}
+
+/// What a key may do, the secret key everything, the `readonly_key` only read the grid
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum KeyScope {
+    Full,
+    ReadOnly,
+}
+
+/// `GET /webui-config`, what the webui offers to the holder of the key
+#[derive(Debug, Serialize, Deserialize)]
+pub struct WebuiConfigResponse {
+    pub scope: KeyScope,
+    /// Actions the key may take, the webui hides the others
+    pub actions: Vec<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SilencesResponse {
+    pub silences: Vec<ActiveSilenceResponse>,
+    /// Silences kept on the node, which only stop its own announcements
+    #[serde(default)]
+    pub local: Vec<ActiveSilenceResponse>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SilenceBroadcastRequest {
+    /// A uuid, or a number from older nodes
+    #[serde(with = "crate::silence_id")]
+    pub id: String,
+    pub node_name: String,
+    pub silent_until: DateTime<Utc>,
+    /// Name of the node that created the silence, missing from older nodes
+    #[serde(default)]
+    pub origin: Option<String>,
+    /// Bumped on every change, peers only apply revisions newer than their copy
+    #[serde(default)]
+    pub revision: u64,
+    #[serde(default)]
+    pub cancelled: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct MuteBroadcastRequest {
+    /// `None` lifts the mute
+    pub muted_until: Option<DateTime<Utc>>,
+    pub changed_at: DateTime<Utc>,
+    #[serde(default)]
+    pub origin: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct MuteResponse {
+    pub muted_until: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ReadinessResponse {
+    pub ready: bool,
+    #[serde(default)]
+    pub reason: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ErrorResponse {
+    pub error: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct MetricsResponse {
+    pub rate_limited_requests: u64,
+    /// Silence broadcasts accepted from callers which couldn't be verified
+    #[serde(default)]
+    pub suspicious_silence_broadcasts: u64,
+    /// Outcomes of this node's obituary calls by peer
+    #[serde(default)]
+    pub obituary_calls: BTreeMap<String, ObituaryCalls>,
+    #[serde(default)]
+    pub cycle: Option<CycleResponse>,
+    /// Outcomes of the announcement deliveries by channel, like `telegram`
+    #[serde(default)]
+    pub announcements: BTreeMap<String, ChannelDeliveries>,
+}
+
+/// Outcomes of the deliveries over an announcement channel, latencies in seconds
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ChannelDeliveries {
+    pub delivered: u64,
+    /// Given up on after the retries
+    pub failed: u64,
+    /// Given up on without an answer in time, these are tried again
+    pub timed_out: u64,
+    /// Of the last delivered announcement's successful attempt
+    pub last_latency: Option<f64>,
+    pub average_latency: Option<f64>,
+}
+
+/// Outcomes of the obituary calls to a peer, its `/` may answer while `/obituary` doesn't
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ObituaryCalls {
+    pub succeeded: u64,
+    pub failed: u64,
+    pub last_error: Option<String>,
+}
+
+/// Basic host health, every field is optional as not every platform can report it
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SystemMetrics {
+    pub load_average: Option<[f64; 3]>,
+    pub memory_used_bytes: Option<u64>,
+    pub memory_total_bytes: Option<u64>,
+    pub disk_free_bytes: Option<u64>,
+    pub disk_total_bytes: Option<u64>,
+    pub uptime_secs: Option<u64>,
+}
+
+impl SystemMetrics {
+    pub fn disk_used_percent(&self) -> Option<f64> {
+        let free = self.disk_free_bytes? as f64;
+        let total = self.disk_total_bytes? as f64;
+        (total > 0.0).then(|| (total - free) / total * 100.0)
+    }
+}
+
+/// Outcome of the latest reload
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ConfigReload {
+    pub at: DateTime<Utc>,
+    /// Why the reload was rejected, `None` if it was applied
+    pub error: Option<String>,
+}
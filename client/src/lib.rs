@@ -0,0 +1,274 @@
+//! Types and a typed client of the HTTP API of freecaster-grid nodes.
+//! The grid calls its peers with them too, so they can't drift from what the nodes serve
+//!
+//! ```no_run
+//! # async fn run() -> Result<(), freecaster_grid_client::Error> {
+//! let node = freecaster_grid_client::FreecasterClient::new("https://hal9000.local:3037", "secret");
+//! let grid = node.grid().await?;
+//! println!("{} of {} nodes dead", grid.summary.dead_nodes, grid.summary.total_nodes);
+//! # Ok(())
+//! # }
+//! ```
+
+pub mod api;
+mod silence_id;
+
+use crate::api::{
+    AnnouncementClaimRequest, AnnouncementClaimResponse, GridResponse, GridSummary,
+    IntrospectResponse, MuteBroadcastRequest, ObituaryResponse, SilenceBroadcastRequest,
+    SilenceResponse, SilencesResponse, StatusResponse,
+};
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue};
+use reqwest::{Client, RequestBuilder, StatusCode};
+use serde::Deserialize;
+use serde::de::DeserializeOwned;
+
+/// Why a call to a node failed
+#[derive(Debug)]
+pub enum Error {
+    /// The node couldn't be reached, or the connection broke
+    Request(reqwest::Error),
+    /// The node answered with an error status, like 406 for a wrong secret key
+    Status(StatusCode),
+    /// The answer isn't what the API promises, the node may be something else
+    Decode(reqwest::Error),
+}
+
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::Request(err) => write!(f, "request failed: {err}"),
+            Error::Status(status) => write!(f, "node returned error status: {status}"),
+            Error::Decode(err) => write!(f, "invalid response: {err}"),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Request(err) | Error::Decode(err) => Some(err),
+            Error::Status(_) => None,
+        }
+    }
+}
+
+pub type Result<T, E = Error> = std::result::Result<T, E>;
+
+/// A tag target answers with every silenced node
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum Silenced {
+    One(SilenceResponse),
+    Many(Vec<SilenceResponse>),
+}
+
+/// The API of one node, the secret key is left out of the errors
+#[derive(Debug, Clone)]
+pub struct FreecasterClient {
+    client: Client,
+    base_url: String,
+    key: String,
+    headers: HeaderMap,
+}
+
+impl FreecasterClient {
+    pub fn new(base_url: impl Into<String>, key: impl Into<String>) -> Self {
+        Self::with_client(Client::new(), base_url, key)
+    }
+
+    /// Calls through `client`, for its TLS, proxy and timeout settings
+    pub fn with_client(
+        client: Client,
+        base_url: impl Into<String>,
+        key: impl Into<String>,
+    ) -> Self {
+        Self {
+            client,
+            base_url: base_url.into().trim_end_matches('/').to_string(),
+            key: key.into(),
+            headers: HeaderMap::new(),
+        }
+    }
+
+    /// Sent with every call, a later value replaces an earlier one of the same name
+    pub fn header(mut self, name: HeaderName, value: HeaderValue) -> Self {
+        self.headers.insert(name, value);
+        self
+    }
+
+    pub fn headers(mut self, headers: HeaderMap) -> Self {
+        self.headers.extend(headers);
+        self
+    }
+
+    pub fn base_url(&self) -> &str {
+        &self.base_url
+    }
+
+    fn url(&self, path: &str) -> String {
+        format!("{}{path}", self.base_url)
+    }
+
+    async fn call<T: DeserializeOwned>(&self, request: RequestBuilder) -> Result<T> {
+        let res = request
+            .headers(self.headers.clone())
+            .send()
+            .await
+            .map_err(|err| Error::Request(err.without_url()))?;
+        if !res.status().is_success() {
+            return Err(Error::Status(res.status()));
+        }
+        res.json()
+            .await
+            .map_err(|err| Error::Decode(err.without_url()))
+    }
+
+    /// Sends `body` to a peer endpoint, which answers with no content
+    async fn broadcast(&self, path: &str, body: &impl serde::Serialize) -> Result<()> {
+        let res = self
+            .client
+            .post(self.url(&format!("{path}/{}", self.key)))
+            .json(body)
+            .headers(self.headers.clone())
+            .send()
+            .await
+            .map_err(|err| Error::Request(err.without_url()))?;
+        if !res.status().is_success() {
+            return Err(Error::Status(res.status()));
+        }
+        Ok(())
+    }
+
+    /// `GET /`, needs no key
+    pub async fn status(&self) -> Result<StatusResponse> {
+        self.call(self.client.get(self.url("/"))).await
+    }
+
+    /// `GET /introspect`, the node's view in brief, for nodes with `introspect`
+    pub async fn introspect(&self) -> Result<IntrospectResponse> {
+        self.call(
+            self.client
+                .get(self.url(&format!("/introspect/{}", self.key))),
+        )
+        .await
+    }
+
+    /// `GET /grid`, every node as the node sees them
+    pub async fn grid(&self) -> Result<GridResponse> {
+        self.call(self.client.get(self.url(&format!("/grid/{}", self.key))))
+            .await
+    }
+
+    /// `GET /grid?summary=true`, only the totals
+    pub async fn summary(&self) -> Result<GridSummary> {
+        self.call(
+            self.client
+                .get(self.url(&format!("/grid/{}?summary=true", self.key))),
+        )
+        .await
+    }
+
+    /// `GET /obituary`, the nodes the node found dead
+    pub async fn obituary(&self) -> Result<ObituaryResponse> {
+        self.call(
+            self.client
+                .get(self.url(&format!("/obituary/{}", self.key))),
+        )
+        .await
+    }
+
+    /// `GET /obituary/v2`, the obituary with a verdict on every node, for nodes with `obituary-v2`
+    pub async fn obituary_v2(&self) -> Result<ObituaryResponse> {
+        self.call(
+            self.client
+                .get(self.url(&format!("/obituary/v2/{}", self.key))),
+        )
+        .await
+    }
+
+    /// `GET /silence`, silences `target` (the node itself without one) until `until`,
+    /// a unix timestamp or a duration like `2h`. A `tag:` target silences every node carrying the tag
+    pub async fn silence(&self, until: &str, target: Option<&str>) -> Result<Vec<SilenceResponse>> {
+        self.silence_with(until, target, "").await
+    }
+
+    /// `GET /silence?local=true`, like `silence`, but only the node stops announcing the target,
+    /// it isn't broadcast and the rest of the grid keeps watching it
+    pub async fn silence_locally(
+        &self,
+        until: &str,
+        target: Option<&str>,
+    ) -> Result<Vec<SilenceResponse>> {
+        self.silence_with(until, target, "?local=true").await
+    }
+
+    async fn silence_with(
+        &self,
+        until: &str,
+        target: Option<&str>,
+        query: &str,
+    ) -> Result<Vec<SilenceResponse>> {
+        let path = match target {
+            Some(target) => format!("/silence/{}/{until}/{target}{query}", self.key),
+            None => format!("/silence/{}/{until}{query}", self.key),
+        };
+        Ok(match self.call(self.client.get(self.url(&path))).await? {
+            Silenced::One(silence) => vec![silence],
+            Silenced::Many(silences) => silences,
+        })
+    }
+
+    /// `GET /silences`, the silences in effect on the node
+    pub async fn silences(&self) -> Result<SilencesResponse> {
+        self.call(
+            self.client
+                .get(self.url(&format!("/silences/{}", self.key))),
+        )
+        .await
+    }
+
+    /// `POST /silence-broadcast`, as peers hand on their silences
+    pub async fn silence_broadcast(&self, silence: &SilenceBroadcastRequest) -> Result<()> {
+        self.broadcast("/silence-broadcast", silence).await
+    }
+
+    /// `POST /claim`, as the winner of a roll claims the death before announcing it
+    pub async fn claim(
+        &self,
+        claim: &AnnouncementClaimRequest,
+    ) -> Result<AnnouncementClaimResponse> {
+        self.call(
+            self.client
+                .post(self.url(&format!("/claim/{}", self.key)))
+                .json(claim),
+        )
+        .await
+    }
+
+    /// `POST /mute-broadcast`, as peers hand on the mute of the grid
+    pub async fn mute_broadcast(&self, mute: &MuteBroadcastRequest) -> Result<()> {
+        self.broadcast("/mute-broadcast", mute).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn silences_of_one_node_or_a_tag() {
+        let one: Silenced =
+            serde_json::from_str(r#"{"name":"hal9001","silent_until":"2030-01-01T00:00:00Z"}"#)
+                .unwrap();
+        assert!(matches!(one, Silenced::One(silence) if silence.name == "hal9001"));
+        let many: Silenced =
+            serde_json::from_str(r#"[{"name":"hal9001","silent_until":"2030-01-01T00:00:00Z"}]"#)
+                .unwrap();
+        assert!(matches!(many, Silenced::Many(silences) if silences.len() == 1));
+
+        let node = FreecasterClient::new("http://hal9000.local:3037/", "key");
+        assert_eq!(node.base_url(), "http://hal9000.local:3037");
+        assert_eq!(node.url("/grid/key"), "http://hal9000.local:3037/grid/key");
+    }
+}
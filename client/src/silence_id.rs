@@ -0,0 +1,25 @@
+//! Silence ids are uuids, older nodes used random numbers.
+//! Numeric ids are kept as their decimal string and sent as numbers again, so older peers still understand them
+
+use serde::{Deserialize, Deserializer, Serializer};
+
+pub fn serialize<S: Serializer>(id: &str, serializer: S) -> Result<S::Ok, S::Error> {
+    match id.parse::<u64>() {
+        Ok(legacy) => serializer.serialize_u64(legacy),
+        Err(_) => serializer.serialize_str(id),
+    }
+}
+
+pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<String, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Id {
+        Uuid(String),
+        Legacy(u64),
+    }
+
+    Ok(match Id::deserialize(deserializer)? {
+        Id::Uuid(id) => id,
+        Id::Legacy(id) => id.to_string(),
+    })
+}
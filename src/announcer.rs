@@ -0,0 +1,1596 @@
+use crate::ChannelDeliveries;
+use crate::audit::{AuditAction, AuditSource};
+use crate::config::{
+    AnnouncementLimitConfig, AnnouncementMode, AnnouncementOverflow, AnnouncementRetryConfig,
+    CheckFailure, Config, GotifyConfig, NamedNodeConfig, PushoverConfig, TelegramStartupCheck,
+};
+use crate::events::GridEvent;
+use crate::exec::{self, Exec};
+use crate::messages;
+use crate::pending::PendingKind;
+use crate::poller::State;
+use crate::proxy;
+use anyhow::{Context, Result, bail};
+use chrono::{DateTime, Utc};
+use reqwest::Client;
+use serde::Deserialize;
+use serde::de::DeserializeOwned;
+use std::collections::{HashMap, VecDeque};
+use std::time::{Duration, Instant};
+use tracing::{Instrument, error, info, info_span, warn};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AnnouncementKind {
+    Died,
+    Returned,
+    Joined,
+    /// Still dead after its silence
+    Reminder,
+    Warning,
+    Digest,
+    /// Still dead a while after its death was announced
+    Escalated,
+    /// Announcements held back by a channel's limit
+    Summary,
+    /// This node's own listener doesn't answer
+    SelfCheck,
+    /// This node started, with `announce_lifecycle`
+    Started,
+    /// This node is shutting down gracefully, with `announce_lifecycle`
+    Stopped,
+}
+
+impl AnnouncementKind {
+    /// Name of the event in exec arguments and gotify priorities
+    pub fn as_str(self) -> &'static str {
+        match self {
+            AnnouncementKind::Died => "dead",
+            AnnouncementKind::Returned => "recovered",
+            AnnouncementKind::Joined => "joined",
+            AnnouncementKind::Reminder => "reminder",
+            AnnouncementKind::Warning => "warning",
+            AnnouncementKind::Digest => "digest",
+            AnnouncementKind::Escalated => "escalated",
+            AnnouncementKind::Summary => "summary",
+            AnnouncementKind::SelfCheck => "self_check",
+            AnnouncementKind::Started => "started",
+            AnnouncementKind::Stopped => "stopped",
+        }
+    }
+}
+
+/// How the announcer of a death was picked
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AnnouncerChoice {
+    Roll,
+    /// The node's `preferred_announcer`
+    Preferred,
+    /// The node's `preferred_announcer` didn't confirm the death, it was rolled
+    PreferredUnavailable(String),
+}
+
+/// The grid's totals when an event is announced, this node included
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct GridSnapshot {
+    pub dead: usize,
+    pub total: usize,
+}
+
+/// What's known of a death when it's announced
+#[derive(Debug, Clone, Default)]
+pub struct DeathDetails {
+    /// Why it died, when it's not the obvious
+    pub reason: Option<String>,
+    /// Its last successful poll
+    pub last_ok: Option<DateTime<Utc>>,
+    /// Since its first failed poll
+    pub down_for: Option<Duration>,
+    /// How its polls failed, like `connection refused`
+    pub failure: Option<String>,
+    pub grid: Option<GridSnapshot>,
+}
+
+/// Something that happened to a node, for the announcement channel
+#[derive(Debug, Clone)]
+pub struct AnnouncementEvent {
+    pub kind: AnnouncementKind,
+    pub node: String,
+    /// How the node is called in the message
+    pub display_name: String,
+    /// The node announcing it, this one
+    pub announcer: String,
+    pub time: DateTime<Utc>,
+    /// Why it happened, when it's not the obvious
+    pub reason: Option<String>,
+    pub message: String,
+    pub tags: Vec<String>,
+    /// How long the node was down, on its recovery
+    pub down_for: Option<Duration>,
+    pub grid: Option<GridSnapshot>,
+}
+
+impl AnnouncementEvent {
+    /// A free form announcement about `node`
+    pub fn new(config: &Config, kind: AnnouncementKind, node: &str, message: String) -> Self {
+        Self {
+            kind,
+            node: node.to_string(),
+            display_name: config.display_name(node).to_string(),
+            announcer: config.name.clone(),
+            time: Utc::now(),
+            reason: None,
+            message,
+            tags: config.node_tags(node).to_vec(),
+            down_for: None,
+            grid: None,
+        }
+    }
+
+    pub fn died(
+        config: &Config,
+        target: NamedNodeConfig<'_>,
+        death: DeathDetails,
+        choice: &AnnouncerChoice,
+    ) -> Self {
+        let because = death
+            .reason
+            .as_ref()
+            .or(death.failure.as_ref())
+            .map(|reason| format!(" ({reason})"))
+            .unwrap_or_default();
+        let last_seen = death
+            .last_ok
+            .map(|last_ok| {
+                let time = last_ok.format("%Y-%m-%d %H:%M:%S UTC").to_string();
+                messages::render(config, "last_seen", &[("time", &time)])
+            })
+            .unwrap_or_default();
+        let (down_for, failing) = death
+            .down_for
+            .map(|down_for| {
+                let down_for = format_down_for(down_for);
+                let failing = messages::render(config, "failing", &[("down_for", &down_for)]);
+                (down_for, failing)
+            })
+            .unwrap_or_default();
+        let failure = death.failure.clone().unwrap_or_default();
+        let chosen = match choice {
+            AnnouncerChoice::Roll => String::new(),
+            AnnouncerChoice::Preferred => messages::render(config, "preferred_announcer", &[]),
+            AnnouncerChoice::PreferredUnavailable(preferred) => messages::render(
+                config,
+                "preferred_unavailable",
+                &[("preferred", config.display_name(preferred))],
+            ),
+        };
+        Self::about(
+            config,
+            AnnouncementKind::Died,
+            target,
+            death.reason,
+            death.grid,
+            &[
+                ("because", &because),
+                ("last_seen", &last_seen),
+                ("down_for", &down_for),
+                ("failing", &failing),
+                ("failure", &failure),
+                ("chosen", &chosen),
+            ],
+        )
+    }
+
+    /// `announced_at` is when this node announced its death
+    pub fn returned(
+        config: &Config,
+        target: NamedNodeConfig<'_>,
+        announced_at: DateTime<Utc>,
+        down_for: Duration,
+        grid: Option<GridSnapshot>,
+    ) -> Self {
+        let announced_at = announced_at.format("%Y-%m-%d %H:%M:%S UTC").to_string();
+        let down_for_text = format_down_for(down_for);
+        Self {
+            down_for: Some(down_for),
+            ..Self::about(
+                config,
+                AnnouncementKind::Returned,
+                target,
+                None,
+                grid,
+                &[
+                    ("announced_by", &config.name),
+                    ("announced_at", &announced_at),
+                    ("down_for", &down_for_text),
+                ],
+            )
+        }
+    }
+
+    pub fn escalated(config: &Config, target: NamedNodeConfig<'_>, dead_for: Duration) -> Self {
+        let duration = humantime::format_duration(dead_for).to_string();
+        Self::about(
+            config,
+            AnnouncementKind::Escalated,
+            target,
+            None,
+            None,
+            &[("duration", &duration)],
+        )
+    }
+
+    /// Rendered from the message of `kind`, with the node, its group, owner and the grid's totals filled in
+    fn about(
+        config: &Config,
+        kind: AnnouncementKind,
+        target: NamedNodeConfig<'_>,
+        reason: Option<String>,
+        grid: Option<GridSnapshot>,
+        values: &[(&str, &str)],
+    ) -> Self {
+        let (group, mention) = (group(&target), mention(&target));
+        let display_name = target
+            .config
+            .display_name
+            .clone()
+            .unwrap_or_else(|| target.name.clone());
+        let (dead, total) = grid
+            .map(|grid| (grid.dead.to_string(), grid.total.to_string()))
+            .unwrap_or_default();
+        let grid_text = grid
+            .map(|_| messages::render(config, "grid_dead", &[("dead", &dead), ("total", &total)]))
+            .unwrap_or_default();
+        let mut values = values.to_vec();
+        values.extend([
+            ("node", display_name.as_str()),
+            ("group", &group),
+            ("announcer", &config.name),
+            ("mention", &mention),
+            ("grid", &grid_text),
+            ("dead", &dead),
+            ("total", &total),
+        ]);
+        let message = messages::render(config, kind.as_str(), &values);
+        Self {
+            kind,
+            node: target.name.clone(),
+            display_name,
+            announcer: config.name.clone(),
+            time: Utc::now(),
+            reason,
+            message,
+            tags: target.config.tags.clone(),
+            down_for: None,
+            grid,
+        }
+    }
+
+    fn title(&self) -> String {
+        format!(
+            "freecaster-grid: {} {}",
+            self.display_name,
+            self.kind.as_str()
+        )
+    }
+}
+
+/// Whole seconds, like `25m 1s`
+fn format_down_for(down_for: Duration) -> String {
+    humantime::format_duration(Duration::from_secs(down_for.as_secs())).to_string()
+}
+
+fn group(target: &NamedNodeConfig<'_>) -> String {
+    if target.config.tags.is_empty() {
+        "".to_string()
+    } else {
+        format!(" ({})", target.config.tags.join(", "))
+    }
+}
+
+/// The owner's telegram handle
+fn mention(target: &NamedNodeConfig<'_>) -> String {
+    if let Some(tg) = target.config.telegram_handle.as_ref() {
+        format!("- @{tg}")
+    } else {
+        "".to_string()
+    }
+}
+
+/// A channel announcements are delivered to, failures are retried by `deliver`
+pub trait Announcer {
+    fn name(&self) -> &'static str;
+
+    async fn announce(&self, event: &AnnouncementEvent) -> Result<()>;
+}
+
+enum Channel {
+    Log(Log),
+    Telegram(Telegram),
+    Exec(Exec),
+    Gotify(Gotify),
+    Pushover(Pushover),
+}
+
+impl Channel {
+    fn new(config: &Config, mode: AnnouncementMode) -> Result<Self> {
+        Ok(match mode {
+            AnnouncementMode::Log => Channel::Log(Log),
+            AnnouncementMode::Telegram => {
+                let telegram = config
+                    .telegram
+                    .as_ref()
+                    .context("Telegram announcements need a `telegram` config")?;
+                Channel::Telegram(Telegram {
+                    client: http_client(config)?,
+                    api_url: TELEGRAM_API.to_string(),
+                    token: telegram.token.clone(),
+                    chat_id: telegram.chat_id,
+                })
+            }
+            AnnouncementMode::Exec => Channel::Exec(Exec {
+                config: config
+                    .exec
+                    .clone()
+                    .context("Exec announcements need an `exec` config")?,
+            }),
+            AnnouncementMode::Gotify => Channel::Gotify(Gotify {
+                client: http_client(config)?,
+                config: config
+                    .gotify
+                    .clone()
+                    .context("Gotify announcements need a `gotify` config")?,
+            }),
+            AnnouncementMode::Pushover => Channel::Pushover(Pushover {
+                client: http_client(config)?,
+                config: config
+                    .pushover
+                    .clone()
+                    .context("Pushover announcements need a `pushover` config")?,
+            }),
+        })
+    }
+}
+
+const HOUR: chrono::Duration = chrono::Duration::hours(1);
+/// The sliding window of a rate limited channel, and the deliveries of every channel.
+/// What it holds back waits in the state's `pending_announcements`
+#[derive(Debug, Default)]
+pub struct ChannelWindow {
+    /// Times of the announcements sent within the last hour, oldest first
+    sent: VecDeque<DateTime<Utc>>,
+    suppressed: usize,
+    deliveries: ChannelDeliveries,
+}
+
+impl ChannelWindow {
+    pub fn deliveries(&self) -> &ChannelDeliveries {
+        &self.deliveries
+    }
+
+    /// Takes a slot of the window if one is free
+    fn try_send(&mut self, max_per_hour: usize, now: DateTime<Utc>) -> bool {
+        while self.sent.front().is_some_and(|sent| now - *sent >= HOUR) {
+            self.sent.pop_front();
+        }
+        if self.sent.len() >= max_per_hour {
+            return false;
+        }
+        self.sent.push_back(now);
+        true
+    }
+
+    /// When the oldest announcement of the window falls out of it
+    fn frees_at(&self, now: DateTime<Utc>) -> DateTime<Utc> {
+        self.sent.front().map_or(now, |sent| *sent + HOUR)
+    }
+}
+
+/// The configured announcement channels, built once at startup
+pub struct Announcers {
+    mode: AnnouncementMode,
+    channel: Channel,
+    /// Channels of the `escalation` configs, by mode
+    escalation_channels: HashMap<AnnouncementMode, Channel>,
+    limits: HashMap<AnnouncementMode, AnnouncementLimitConfig>,
+    retry: AnnouncementRetryConfig,
+    timeout: Duration,
+    /// Of the `notify_url`s of nodes
+    webhook_client: Client,
+    state: State,
+}
+
+impl Announcers {
+    pub fn new(config: &Config, state: &State) -> Result<Self> {
+        let mut escalation_channels = HashMap::new();
+        for mode in config
+            .escalation
+            .values()
+            .flat_map(|escalation| escalation.channels.iter())
+        {
+            if !escalation_channels.contains_key(mode) {
+                escalation_channels.insert(*mode, Channel::new(config, *mode)?);
+            }
+        }
+
+        Ok(Self {
+            mode: config.announcement_mode,
+            channel: Channel::new(config, config.announcement_mode)?,
+            escalation_channels,
+            limits: config.announcement_limits.clone(),
+            retry: config.announcement_retry.clone(),
+            timeout: config.announcement_timeout,
+            webhook_client: http_client(config)?,
+            state: state.clone(),
+        })
+    }
+
+    /// Delivers over the announcement channel
+    pub async fn announce(&self, event: AnnouncementEvent) {
+        self.send(self.mode, &self.channel, event).await;
+    }
+
+    /// Delivers over each of `modes`
+    pub async fn escalate(&self, modes: &[AnnouncementMode], event: AnnouncementEvent) {
+        for mode in modes {
+            match self.escalation_channels.get(mode) {
+                Some(channel) => self.send(*mode, channel, event.clone()).await,
+                None => error!("No escalation channel `{mode:?}`"),
+            }
+        }
+    }
+
+    /// Tells a recovered node with a `notify_url` about its incident, with retries.
+    /// Its death goes first, it couldn't be told while it was dead
+    pub async fn notify_node(
+        &self,
+        config: &Config,
+        target: NamedNodeConfig<'_>,
+        died_at: DateTime<Utc>,
+        recovered: &AnnouncementEvent,
+    ) {
+        let Some(url) = target.config.notify_url.clone() else {
+            return;
+        };
+        let webhook = Webhook {
+            client: self.webhook_client.clone(),
+            url,
+            headers: target.config.header_map(),
+        };
+        let mut died = AnnouncementEvent::died(
+            config,
+            target,
+            DeathDetails::default(),
+            &AnnouncerChoice::Roll,
+        );
+        died.time = died_at;
+        for event in [died, recovered.clone()] {
+            deliver(&webhook, &self.retry, self.timeout, &event).await;
+        }
+    }
+
+    /// Tries the timed out deliveries again, then sends what the rate limited channels held back,
+    /// as far as their windows allow
+    pub async fn flush(&self, config: &Config) {
+        let now = Utc::now();
+        let channels =
+            std::iter::once((&self.mode, &self.channel)).chain(self.escalation_channels.iter());
+        for (mode, channel) in channels {
+            loop {
+                let retry = {
+                    let mut gr = self.state.lock().expect("Failed to lock state");
+                    if gr.muted_until(now).is_some() {
+                        return;
+                    }
+                    gr.pending_announcements.pop(PendingKind::Retry, *mode)
+                };
+                let Some(retry) = retry else {
+                    break;
+                };
+                info!(
+                    "Retrying the timed out announcement of `{}` ({}) on {mode:?}, {} attempts so far",
+                    retry.event.node,
+                    retry.event.kind.as_str(),
+                    retry.attempts
+                );
+                // a channel still not answering would hold up the cycle with every one of them
+                if self
+                    .dispatch(*mode, channel, retry.event, retry.attempts + 1)
+                    .await
+                    == Some(Delivery::TimedOut)
+                {
+                    break;
+                }
+            }
+
+            let Some(limit) = self.limits.get(mode) else {
+                continue;
+            };
+
+            let (events, summary) = {
+                let mut gr = self.state.lock().expect("Failed to lock state");
+                if gr.muted_until(now).is_some() {
+                    return;
+                }
+                let grid = gr.grid_snapshot();
+                let (alive, total) = (grid.total - grid.dead, grid.total);
+
+                let gr = &mut *gr;
+                let window = gr.announcement_windows.entry(*mode).or_default();
+                let mut events = vec![];
+                while gr.pending_announcements.has(PendingKind::Queued, *mode)
+                    && window.try_send(limit.max_announcements_per_hour, now)
+                {
+                    events.extend(
+                        gr.pending_announcements
+                            .pop(PendingKind::Queued, *mode)
+                            .map(|queued| queued.event),
+                    );
+                }
+                let summary = (window.suppressed > 0
+                    && window.try_send(limit.max_announcements_per_hour, now))
+                .then(|| {
+                    let suppressed = std::mem::take(&mut window.suppressed);
+                    messages::render(
+                        config,
+                        "summary",
+                        &[
+                            ("count", &suppressed.to_string()),
+                            ("alive", &alive.to_string()),
+                            ("total", &total.to_string()),
+                        ],
+                    )
+                });
+                (events, summary)
+            };
+
+            for event in events {
+                self.dispatch(*mode, channel, event, 1).await;
+            }
+            if let Some(message) = summary {
+                let event = AnnouncementEvent::new(
+                    config,
+                    AnnouncementKind::Summary,
+                    &config.name,
+                    message,
+                );
+                self.dispatch(*mode, channel, event, 1).await;
+            }
+        }
+    }
+
+    /// Only recorded in the audit log while muted, over the channel's limit it overflows
+    async fn send(&self, mode: AnnouncementMode, channel: &Channel, event: AnnouncementEvent) {
+        {
+            let now = Utc::now();
+            let mut gr = self.state.lock().expect("Failed to lock state");
+            if let Some(until) = gr.muted_until(now) {
+                info!(
+                    "Grid is muted until {until}, not announcing `{}` ({})",
+                    event.node,
+                    event.kind.as_str()
+                );
+                gr.audit.record(
+                    AuditSource::Poller,
+                    AuditAction::AnnouncementMuted {
+                        node_name: event.node,
+                        event: event.kind.as_str().to_string(),
+                    },
+                );
+                return;
+            }
+            if gr.silenced_locally(&event.node, now) {
+                info!(
+                    "`{}` is silenced on this node, not announcing it ({})",
+                    event.node,
+                    event.kind.as_str()
+                );
+                return;
+            }
+
+            if let Some(limit) = self.limits.get(&mode) {
+                let gr = &mut *gr;
+                let window = gr.announcement_windows.entry(mode).or_default();
+                // queued ones go first
+                if gr.pending_announcements.has(PendingKind::Queued, mode)
+                    || !window.try_send(limit.max_announcements_per_hour, now)
+                {
+                    warn!(
+                        "Over {} announcements this hour on {mode:?}, {:?} the announcement of `{}` ({})",
+                        limit.max_announcements_per_hour,
+                        limit.overflow,
+                        event.node,
+                        event.kind.as_str()
+                    );
+                    match limit.overflow {
+                        AnnouncementOverflow::Drop => {}
+                        AnnouncementOverflow::Queue => gr.pending_announcements.push(
+                            PendingKind::Queued,
+                            mode,
+                            event,
+                            window.frees_at(now),
+                            0,
+                        ),
+                        AnnouncementOverflow::Summarize => window.suppressed += 1,
+                    }
+                    return;
+                }
+            }
+        }
+
+        self.dispatch(mode, channel, event, 1).await;
+    }
+
+    /// With retries, exec commands run in the background and have no outcome here.
+    /// `attempts` counts this delivery of the event too
+    async fn dispatch(
+        &self,
+        mode: AnnouncementMode,
+        channel: &Channel,
+        event: AnnouncementEvent,
+        attempts: u32,
+    ) -> Option<Delivery> {
+        let trace = self
+            .state
+            .lock()
+            .expect("Failed to lock state")
+            .audit
+            .cycle_trace
+            .clone();
+        let span = info_span!(
+            "announcement",
+            kind = event.kind.as_str(),
+            node = %event.node,
+            trace = trace.as_deref().unwrap_or("-")
+        );
+        self.dispatch_in(mode, channel, event, attempts)
+            .instrument(span)
+            .await
+    }
+
+    async fn dispatch_in(
+        &self,
+        mode: AnnouncementMode,
+        channel: &Channel,
+        event: AnnouncementEvent,
+        attempts: u32,
+    ) -> Option<Delivery> {
+        let (retry, timeout) = (&self.retry, self.timeout);
+        let delivery = match channel {
+            Channel::Log(log) => deliver(log, retry, timeout, &event).await,
+            Channel::Telegram(telegram) => deliver(telegram, retry, timeout, &event).await,
+            Channel::Exec(exec) => {
+                exec::spawn(
+                    exec.clone(),
+                    self.retry.clone(),
+                    &self.state,
+                    event,
+                    attempts,
+                );
+                return None;
+            }
+            Channel::Gotify(gotify) => deliver(gotify, retry, timeout, &event).await,
+            Channel::Pushover(pushover) => deliver(pushover, retry, timeout, &event).await,
+        };
+        record_delivery(&self.state, mode, event, delivery, attempts);
+        Some(delivery)
+    }
+}
+
+/// How a delivery ended
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Delivery {
+    /// The successful attempt took this long
+    Delivered(Duration),
+    Failed,
+    /// The last attempt got no answer in time, the channel may answer later
+    TimedOut,
+}
+
+/// Counts the delivery over `mode`, a timed out event is pending for the next `flush`
+/// with the `attempts` of it so far
+pub fn record_delivery(
+    state: &State,
+    mode: AnnouncementMode,
+    event: AnnouncementEvent,
+    delivery: Delivery,
+    attempts: u32,
+) {
+    let mut gr = state.lock().expect("Failed to lock state");
+    let gr = &mut *gr;
+    let window = gr.announcement_windows.entry(mode).or_default();
+    let deliveries = &mut window.deliveries;
+    match delivery {
+        Delivery::Delivered(latency) => {
+            gr.events.record(GridEvent::AnnouncementSent {
+                node_name: event.node.clone(),
+                event: event.kind.as_str().to_string(),
+                channel: mode,
+            });
+            deliveries.delivered += 1;
+            let latency = latency.as_secs_f64();
+            let average = deliveries.average_latency.unwrap_or_default();
+            deliveries.last_latency = Some(latency);
+            deliveries.average_latency =
+                Some(average + (latency - average) / deliveries.delivered as f64);
+        }
+        Delivery::Failed => deliveries.failed += 1,
+        Delivery::TimedOut => {
+            deliveries.timed_out += 1;
+            gr.pending_announcements
+                .push(PendingKind::Retry, mode, event, Utc::now(), attempts);
+        }
+    }
+}
+
+/// Announces until it succeeds or the attempts run out, doubling the wait after every failure.
+/// Every attempt gets `timeout` to finish
+pub async fn deliver(
+    announcer: &impl Announcer,
+    retry: &AnnouncementRetryConfig,
+    timeout: Duration,
+    event: &AnnouncementEvent,
+) -> Delivery {
+    let attempts = retry.attempts.max(1);
+    let mut delay = retry.delay;
+    for attempt in 1..=attempts {
+        let started = Instant::now();
+        let res = match tokio::time::timeout(timeout, announcer.announce(event)).await {
+            Ok(res) => res,
+            Err(_) => Err(TimedOut(timeout).into()),
+        };
+        match res {
+            Ok(()) => return Delivery::Delivered(started.elapsed()),
+            Err(err) if attempt < attempts => {
+                // retrying sooner than the channel asked would only fail again
+                let wait = match err.downcast_ref::<RetryAfter>() {
+                    Some(RetryAfter(after)) => delay.max((*after).min(MAX_RETRY_AFTER)),
+                    None => delay,
+                };
+                warn!(
+                    "{} announcement of `{}` ({}) failed, attempt {attempt}/{attempts}, retrying in {wait:?}: {err:?}",
+                    announcer.name(),
+                    event.node,
+                    event.kind.as_str()
+                );
+                tokio::time::sleep(wait).await;
+                delay *= 2;
+            }
+            Err(err) => {
+                error!(
+                    "{} announcement of `{}` ({}) failed, giving up after {attempts} attempts: {err:?}",
+                    announcer.name(),
+                    event.node,
+                    event.kind.as_str()
+                );
+                if is_timeout(&err) {
+                    return Delivery::TimedOut;
+                }
+            }
+        }
+    }
+    Delivery::Failed
+}
+
+/// The channel didn't answer within the announcement timeout
+#[derive(Debug)]
+pub struct TimedOut(pub Duration);
+
+impl std::fmt::Display for TimedOut {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "no answer within {:?}", self.0)
+    }
+}
+
+impl std::error::Error for TimedOut {}
+
+fn is_timeout(err: &anyhow::Error) -> bool {
+    err.downcast_ref::<TimedOut>().is_some()
+        || err
+            .downcast_ref::<reqwest::Error>()
+            .is_some_and(|err| err.is_timeout())
+}
+
+/// A channel rate limited the announcement, and asked to wait this long before the next one
+#[derive(Debug)]
+pub struct RetryAfter(pub Duration);
+
+impl std::fmt::Display for RetryAfter {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "rate limited, retry after {:?}", self.0)
+    }
+}
+
+impl std::error::Error for RetryAfter {}
+
+/// Longest wait for a `RetryAfter`, the rest of the poll cycle waits for it
+const MAX_RETRY_AFTER: Duration = Duration::from_secs(30);
+
+fn http_client(config: &Config) -> Result<Client> {
+    Ok(proxy::client_builder(config)?
+        .timeout(config.announcement_timeout)
+        .build()?)
+}
+
+/// Fails on transport errors and non-success statuses
+async fn check_response(res: reqwest::Result<reqwest::Response>) -> Result<()> {
+    // urls may hold tokens
+    let resp = res.map_err(|err| err.without_url())?;
+    let status = resp.status();
+    if !status.is_success() {
+        let body = resp.text().await.unwrap_or_default();
+        bail!("{status}: {}", body.trim());
+    }
+    Ok(())
+}
+
+pub struct Log;
+
+impl Announcer for Log {
+    fn name(&self) -> &'static str {
+        "Log"
+    }
+
+    async fn announce(&self, event: &AnnouncementEvent) -> Result<()> {
+        error!("Announcement!!!: {}", event.message);
+        Ok(())
+    }
+}
+
+/// The `notify_url` of a node, posted to about the node itself
+pub struct Webhook {
+    client: Client,
+    url: String,
+    headers: reqwest::header::HeaderMap,
+}
+
+impl Announcer for Webhook {
+    fn name(&self) -> &'static str {
+        "Webhook"
+    }
+
+    async fn announce(&self, event: &AnnouncementEvent) -> Result<()> {
+        let res = self
+            .client
+            .post(&self.url)
+            .headers(self.headers.clone())
+            .json(&serde_json::json!({
+                "event": event.kind.as_str(),
+                "node": event.node,
+                "announcer": event.announcer,
+                "time": event.time,
+                "downtime_secs": event.down_for.map(|down_for| down_for.as_secs()),
+                "dead_nodes": event.grid.map(|grid| grid.dead),
+                "total_nodes": event.grid.map(|grid| grid.total),
+                "message": event.message,
+            }))
+            .send()
+            .await;
+        check_response(res).await
+    }
+}
+
+pub const TELEGRAM_API: &str = "https://api.telegram.org";
+
+pub struct Telegram {
+    client: Client,
+    api_url: String,
+    token: String,
+    chat_id: i64,
+}
+
+/// What every Bot API method answers, whatever the status
+#[derive(Deserialize)]
+struct BotApiAnswer<T> {
+    ok: bool,
+    result: Option<T>,
+    description: Option<String>,
+}
+
+impl Telegram {
+    async fn call<T: DeserializeOwned>(&self, method: &str, body: serde_json::Value) -> Result<T> {
+        // urls hold the token
+        let answer = self
+            .client
+            .post(format!("{}/bot{}/{method}", self.api_url, self.token))
+            .json(&body)
+            .send()
+            .await
+            .map_err(|err| err.without_url())?
+            .json::<BotApiAnswer<T>>()
+            .await
+            .map_err(|err| err.without_url())?;
+        match answer {
+            BotApiAnswer {
+                ok: true,
+                result: Some(result),
+                ..
+            } => Ok(result),
+            answer => bail!(
+                "`{method}` failed: {}",
+                answer.description.as_deref().unwrap_or("no description")
+            ),
+        }
+    }
+
+    /// `getMe` fails with a wrong token, `getChat` or posting `message` with a wrong chat id.
+    /// Answers with the bot's name
+    async fn check(&self, message: Option<&str>) -> Result<String> {
+        let bot = self
+            .call::<serde_json::Value>("getMe", serde_json::json!({}))
+            .await
+            .context("Invalid Telegram token")?;
+        let chat = match message {
+            Some(text) => {
+                self.call::<serde_json::Value>(
+                    "sendMessage",
+                    serde_json::json!({ "chat_id": self.chat_id, "text": text }),
+                )
+                .await
+            }
+            None => {
+                self.call::<serde_json::Value>(
+                    "getChat",
+                    serde_json::json!({ "chat_id": self.chat_id }),
+                )
+                .await
+            }
+        };
+        chat.with_context(|| format!("Invalid Telegram chat id {}", self.chat_id))?;
+        Ok(bot["username"].as_str().unwrap_or_default().to_string())
+    }
+}
+
+/// Checks the Telegram token and chat id, so a typo shows before the first death is announced into nowhere.
+/// At `startup` the `monitoring_started` message is posted if so configured, otherwise nothing is
+pub async fn check_telegram(config: &Config, startup: bool) -> Result<()> {
+    let Some(telegram) = config.telegram.as_ref() else {
+        return Ok(());
+    };
+    if config.skip_announce_check || !config.announces_to_telegram() {
+        return Ok(());
+    }
+    let message = (startup && telegram.startup_check == TelegramStartupCheck::Message).then(|| {
+        messages::render(
+            config,
+            "monitoring_started",
+            &[("node", config.display_name(&config.name))],
+        )
+    });
+    let channel = Telegram {
+        client: http_client(config)?,
+        api_url: TELEGRAM_API.to_string(),
+        token: telegram.token.clone(),
+        chat_id: telegram.chat_id,
+    };
+    verify_telegram(&channel, message.as_deref(), telegram.on_check_failure).await
+}
+
+async fn verify_telegram(
+    channel: &Telegram,
+    message: Option<&str>,
+    on_failure: CheckFailure,
+) -> Result<()> {
+    match channel.check(message).await {
+        Ok(bot) => {
+            info!(
+                "Announcing as Telegram bot @{bot} to chat {}",
+                channel.chat_id
+            );
+            Ok(())
+        }
+        Err(err) if on_failure == CheckFailure::Warn => {
+            error!("Telegram announcements will fail: {err:#}");
+            Ok(())
+        }
+        Err(err) => Err(err.context("Telegram check failed, `--skip-announce-check` skips it")),
+    }
+}
+
+impl Announcer for Telegram {
+    fn name(&self) -> &'static str {
+        "Telegram"
+    }
+
+    async fn announce(&self, event: &AnnouncementEvent) -> Result<()> {
+        let res = self
+            .client
+            .post(format!("{}/bot{}/sendMessage", self.api_url, self.token))
+            .json(&serde_json::json!({
+                "chat_id": self.chat_id,
+                "text": event.message,
+            }))
+            .send()
+            .await;
+        if let Ok(resp) = res.as_ref()
+            && resp.status() == reqwest::StatusCode::TOO_MANY_REQUESTS
+        {
+            let body = res?.json::<serde_json::Value>().await.unwrap_or_default();
+            let retry_after = body["parameters"]["retry_after"].as_u64().unwrap_or(1);
+            return Err(RetryAfter(Duration::from_secs(retry_after)).into());
+        }
+        check_response(res).await
+    }
+}
+
+pub struct Gotify {
+    client: Client,
+    config: GotifyConfig,
+}
+
+impl Announcer for Gotify {
+    fn name(&self) -> &'static str {
+        "Gotify"
+    }
+
+    async fn announce(&self, event: &AnnouncementEvent) -> Result<()> {
+        let priority = self
+            .config
+            .priorities
+            .get(event.kind.as_str())
+            .copied()
+            .unwrap_or(self.config.default_priority);
+        let res = self
+            .client
+            .post(format!("{}/message", self.config.url.trim_end_matches('/')))
+            .header("X-Gotify-Key", &self.config.app_token)
+            .json(&serde_json::json!({
+                "title": event.title(),
+                "message": event.message,
+                "priority": priority,
+            }))
+            .send()
+            .await;
+        check_response(res).await
+    }
+}
+
+pub struct Pushover {
+    client: Client,
+    config: PushoverConfig,
+}
+
+impl Announcer for Pushover {
+    fn name(&self) -> &'static str {
+        "Pushover"
+    }
+
+    async fn announce(&self, event: &AnnouncementEvent) -> Result<()> {
+        let mut body = serde_json::json!({
+            "token": self.config.app_token,
+            "user": self.config.user_key,
+            "title": event.title(),
+            "message": event.message,
+        });
+        if let Some(device) = self.config.device.as_ref() {
+            body["device"] = device.as_str().into();
+        }
+        if let Some(sound) = self.config.sound.as_ref() {
+            body["sound"] = sound.as_str().into();
+        }
+
+        let res = self
+            .client
+            .post("https://api.pushover.net/1/messages.json")
+            .json(&body)
+            .send()
+            .await;
+        check_response(res).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::NodeConfig;
+    use crate::testing::test_config;
+    use std::sync::{Arc, Mutex};
+
+    /// Fails the first `failures` announcements, records every attempt
+    struct MockAnnouncer {
+        failures: usize,
+        attempts: Mutex<Vec<AnnouncementKind>>,
+    }
+
+    impl Announcer for MockAnnouncer {
+        fn name(&self) -> &'static str {
+            "Mock"
+        }
+
+        async fn announce(&self, event: &AnnouncementEvent) -> Result<()> {
+            let mut attempts = self.attempts.lock().unwrap();
+            attempts.push(event.kind);
+            if attempts.len() <= self.failures {
+                bail!("attempt {} failed", attempts.len());
+            }
+            Ok(())
+        }
+    }
+
+    fn node() -> NodeConfig {
+        NodeConfig {
+            telegram_handle: Some("dave".to_string()),
+            address: "http://hal9001:3037".to_string(),
+            skip_peer_ip_check: false,
+            tags: vec!["home".to_string()],
+            reuse_connections: true,
+            ca_path: None,
+            severity: Default::default(),
+            expected_version: None,
+            display_name: None,
+            preferred_announcer: None,
+            notify_url: None,
+            extra_headers: Default::default(),
+        }
+    }
+
+    fn retry(attempts: u32) -> AnnouncementRetryConfig {
+        AnnouncementRetryConfig {
+            attempts,
+            delay: Duration::ZERO,
+        }
+    }
+
+    #[tokio::test]
+    async fn retries_until_delivered() {
+        let node = node();
+        let name = "hal9001".to_string();
+        let event = AnnouncementEvent::died(
+            &test_config(""),
+            node.with_name(&name),
+            DeathDetails::default(),
+            &AnnouncerChoice::Roll,
+        );
+        let mock = MockAnnouncer {
+            failures: 2,
+            attempts: Mutex::new(vec![]),
+        };
+
+        assert!(matches!(
+            deliver(&mock, &retry(3), Duration::from_secs(5), &event).await,
+            Delivery::Delivered(_)
+        ));
+        assert_eq!(mock.attempts.lock().unwrap().len(), 3);
+    }
+
+    #[tokio::test]
+    async fn gives_up_after_the_attempts() {
+        let node = node();
+        let name = "hal9001".to_string();
+        let event = AnnouncementEvent::returned(
+            &test_config(""),
+            node.with_name(&name),
+            Utc::now(),
+            Duration::ZERO,
+            None,
+        );
+        let mock = MockAnnouncer {
+            failures: 5,
+            attempts: Mutex::new(vec![]),
+        };
+
+        assert_eq!(
+            deliver(&mock, &retry(2), Duration::from_secs(5), &event).await,
+            Delivery::Failed
+        );
+        assert_eq!(
+            *mock.attempts.lock().unwrap(),
+            vec![AnnouncementKind::Returned; 2]
+        );
+    }
+
+    /// A server on the loopback, like Telegram's API, answering every call with `answer`
+    fn mock_server(
+        answer: impl Fn(&rouille::Request) -> rouille::Response + Send + Sync + 'static,
+    ) -> (String, impl FnOnce()) {
+        let server = rouille::Server::new("127.0.0.1:0", answer).unwrap();
+        let url = format!("http://{}", server.server_addr());
+        let (handle, stop) = server.stoppable();
+        (url, move || {
+            stop.send(()).unwrap();
+            handle.join().unwrap();
+        })
+    }
+
+    fn telegram(api_url: &str, timeout: Duration) -> Telegram {
+        Telegram {
+            client: Client::builder().timeout(timeout).build().unwrap(),
+            api_url: api_url.to_string(),
+            token: "token".to_string(),
+            chat_id: 42,
+        }
+    }
+
+    #[tokio::test]
+    async fn hung_telegram_times_out() {
+        let (url, stop) = mock_server(|_| {
+            std::thread::sleep(Duration::from_secs(1));
+            rouille::Response::empty_204()
+        });
+        let name = "hal9001".to_string();
+        let event = AnnouncementEvent::died(
+            &test_config(""),
+            node().with_name(&name),
+            DeathDetails::default(),
+            &AnnouncerChoice::Roll,
+        );
+
+        let started = std::time::Instant::now();
+        assert_eq!(
+            deliver(
+                &telegram(&url, Duration::from_millis(100)),
+                &retry(1),
+                Duration::from_secs(5),
+                &event
+            )
+            .await,
+            Delivery::TimedOut
+        );
+        assert!(started.elapsed() < Duration::from_millis(800));
+        stop();
+    }
+
+    #[tokio::test]
+    async fn wrong_telegram_chat_fails_the_check() {
+        let (url, stop) = mock_server(|request| match request.url().as_str() {
+            "/bottoken/getMe" => rouille::Response::json(&serde_json::json!({
+                "ok": true,
+                "result": {"id": 42, "is_bot": true, "username": "freecaster_bot"},
+            })),
+            "/bottypo/getMe" => rouille::Response::json(&serde_json::json!({
+                "ok": false,
+                "error_code": 401,
+                "description": "Unauthorized",
+            }))
+            .with_status_code(401),
+            _ => rouille::Response::json(&serde_json::json!({
+                "ok": false,
+                "error_code": 400,
+                "description": "Bad Request: chat not found",
+            }))
+            .with_status_code(400),
+        });
+        let channel = telegram(&url, Duration::from_secs(5));
+
+        assert_eq!(
+            channel.check(None).await.unwrap_err().to_string(),
+            "Invalid Telegram chat id 42"
+        );
+        assert!(
+            verify_telegram(&channel, Some("started"), CheckFailure::Fail)
+                .await
+                .is_err()
+        );
+        assert!(
+            verify_telegram(&channel, None, CheckFailure::Warn)
+                .await
+                .is_ok()
+        );
+        let wrong_token = Telegram {
+            token: "typo".to_string(),
+            ..telegram(&url, Duration::from_secs(5))
+        };
+        assert_eq!(
+            format!("{:#}", wrong_token.check(None).await.unwrap_err()),
+            "Invalid Telegram token: `getMe` failed: Unauthorized"
+        );
+        stop();
+    }
+
+    #[tokio::test]
+    async fn telegram_rate_limit_is_waited_out() {
+        let calls = Arc::new(Mutex::new(0));
+        let counted = calls.clone();
+        let (url, stop) = mock_server(move |_| {
+            let mut calls = counted.lock().unwrap();
+            *calls += 1;
+            if *calls == 1 {
+                rouille::Response::json(&serde_json::json!({
+                    "ok": false,
+                    "error_code": 429,
+                    "parameters": {"retry_after": 1},
+                }))
+                .with_status_code(429)
+            } else {
+                rouille::Response::json(&serde_json::json!({"ok": true}))
+            }
+        });
+        let name = "hal9001".to_string();
+        let event = AnnouncementEvent::died(
+            &test_config(""),
+            node().with_name(&name),
+            DeathDetails::default(),
+            &AnnouncerChoice::Roll,
+        );
+
+        let started = std::time::Instant::now();
+        assert!(matches!(
+            deliver(
+                &telegram(&url, Duration::from_secs(5)),
+                &retry(2),
+                Duration::from_secs(5),
+                &event
+            )
+            .await,
+            Delivery::Delivered(_)
+        ));
+        assert!(started.elapsed() >= Duration::from_secs(1));
+        assert_eq!(*calls.lock().unwrap(), 2);
+        stop();
+    }
+
+    #[tokio::test]
+    async fn recovered_node_is_told_about_its_incident() {
+        let bodies = Arc::new(Mutex::new(vec![]));
+        let received = bodies.clone();
+        let (url, stop) = mock_server(move |request| {
+            let body: serde_json::Value = rouille::input::json_input(request).unwrap();
+            received.lock().unwrap().push(body);
+            rouille::Response::empty_204()
+        });
+        let mut config = test_config("");
+        config.announcement_mode = AnnouncementMode::Log;
+        let announcers = Announcers::new(&config, &State::new(&config)).unwrap();
+        let mut node = node();
+        node.notify_url = Some(format!("{url}/grid-events"));
+        let name = "hal9001".to_string();
+        let died_at = Utc::now() - chrono::Duration::minutes(25);
+        let recovered = AnnouncementEvent::returned(
+            &config,
+            node.with_name(&name),
+            died_at,
+            Duration::from_secs(1500),
+            Some(GridSnapshot { dead: 0, total: 3 }),
+        );
+
+        announcers
+            .notify_node(&config, node.with_name(&name), died_at, &recovered)
+            .await;
+        stop();
+        let bodies = bodies.lock().unwrap();
+        assert_eq!(
+            bodies
+                .iter()
+                .map(|body| body["event"].as_str().unwrap())
+                .collect::<Vec<_>>(),
+            ["dead", "recovered"]
+        );
+        assert_eq!(bodies[0]["time"], serde_json::json!(died_at));
+        assert_eq!(bodies[1]["downtime_secs"], 1500);
+        assert_eq!(bodies[1]["dead_nodes"], 0);
+        assert_eq!(bodies[1]["total_nodes"], 3);
+        assert_eq!(bodies[1]["announcer"], "hal9000");
+    }
+
+    #[tokio::test]
+    async fn exec_runs_a_death_once_at_a_time() {
+        let log = std::env::temp_dir().join(format!("freecaster-grid-exec-{}", std::process::id()));
+        let config = test_config(&format!(
+            r#"
+announcement_mode: exec
+exec:
+  command: sh
+  args: ["-c", "echo $1 $2 >> '{}'; sleep 0.3", "sh", "{{node}}", "{{event}}"]
+"#,
+            log.display()
+        ));
+        let state = State::new(&config);
+        let announcers = Announcers::new(&config, &state).unwrap();
+        let node = node();
+        let died = |name: &str| {
+            AnnouncementEvent::died(
+                &config,
+                node.with_name(&name.to_string()),
+                DeathDetails::default(),
+                &AnnouncerChoice::Roll,
+            )
+        };
+        let finished = || async {
+            for _ in 0..100 {
+                if state.lock().unwrap().running_commands.is_empty() {
+                    return;
+                }
+                tokio::time::sleep(Duration::from_millis(20)).await;
+            }
+            panic!("Commands still running");
+        };
+
+        // a repeat while the first run goes on is skipped, another node's death runs alongside
+        announcers.announce(died("hal9001")).await;
+        announcers.announce(died("hal9001")).await;
+        announcers.announce(died("hal9002")).await;
+        finished().await;
+        // a new death once it's done runs again
+        announcers.announce(died("hal9001")).await;
+        finished().await;
+
+        let mut runs = std::fs::read_to_string(&log)
+            .unwrap()
+            .lines()
+            .map(str::to_string)
+            .collect::<Vec<_>>();
+        std::fs::remove_file(&log).unwrap();
+        runs.sort();
+        assert_eq!(runs, ["hal9001 dead", "hal9001 dead", "hal9002 dead"]);
+    }
+
+    /// Never answers
+    struct HungAnnouncer;
+
+    impl Announcer for HungAnnouncer {
+        fn name(&self) -> &'static str {
+            "Hung"
+        }
+
+        async fn announce(&self, _event: &AnnouncementEvent) -> Result<()> {
+            std::future::pending().await
+        }
+    }
+
+    #[tokio::test]
+    async fn timed_out_announcements_are_kept_for_a_retry() {
+        let config = test_config("");
+        let state = State::new(&config);
+        let name = "hal9001".to_string();
+        let event = AnnouncementEvent::died(
+            &config,
+            node().with_name(&name),
+            DeathDetails::default(),
+            &AnnouncerChoice::Roll,
+        );
+
+        let delivery = deliver(&HungAnnouncer, &retry(2), Duration::from_millis(50), &event).await;
+        assert_eq!(delivery, Delivery::TimedOut);
+        record_delivery(&state, AnnouncementMode::Gotify, event.clone(), delivery, 1);
+        record_delivery(
+            &state,
+            AnnouncementMode::Gotify,
+            event,
+            Delivery::Delivered(Duration::from_millis(200)),
+            2,
+        );
+
+        let gr = state.lock().unwrap();
+        let window = &gr.announcement_windows[&AnnouncementMode::Gotify];
+        let retries = gr.pending_announcements.sorted();
+        assert_eq!(retries.len(), 1);
+        assert_eq!(
+            (retries[0].kind, retries[0].attempts),
+            (PendingKind::Retry, 1)
+        );
+        assert_eq!(
+            window.deliveries,
+            ChannelDeliveries {
+                delivered: 1,
+                failed: 0,
+                timed_out: 1,
+                last_latency: Some(0.2),
+                average_latency: Some(0.2),
+            }
+        );
+    }
+
+    #[test]
+    fn death_message_mentions_reason_and_owner() {
+        let node = node();
+        let name = "hal9001".to_string();
+        let event = AnnouncementEvent::died(
+            &test_config(""),
+            node.with_name(&name),
+            DeathDetails {
+                reason: Some("identity mismatch, reports itself as `hal9002`".to_string()),
+                ..Default::default()
+            },
+            &AnnouncerChoice::Roll,
+        );
+
+        assert_eq!(
+            event.message,
+            "Grid announcement, `hal9001` (home) has unfortunately died (identity mismatch, reports itself as `hal9002`), announced by: `hal9000`- @dave"
+        );
+        assert_eq!(event.tags, vec!["home".to_string()]);
+
+        let last_ok = "2026-06-01T10:12:00Z".parse().unwrap();
+        let event = AnnouncementEvent::died(
+            &test_config(""),
+            node.with_name(&name),
+            DeathDetails {
+                last_ok: Some(last_ok),
+                ..Default::default()
+            },
+            &AnnouncerChoice::Preferred,
+        );
+        assert_eq!(
+            event.message,
+            "Grid announcement, `hal9001` (home) has unfortunately died, last seen 2026-06-01 10:12:00 UTC, announced by: `hal9000` as its preferred announcer- @dave"
+        );
+
+        let event = AnnouncementEvent::died(
+            &test_config(""),
+            node.with_name(&name),
+            DeathDetails {
+                last_ok: Some(last_ok),
+                down_for: Some(Duration::from_millis(90_400)),
+                failure: Some("connection refused".to_string()),
+                grid: Some(GridSnapshot { dead: 2, total: 5 }),
+                ..Default::default()
+            },
+            &AnnouncerChoice::Roll,
+        );
+        assert_eq!(
+            event.message,
+            "Grid announcement, `hal9001` (home) has unfortunately died (connection refused), last seen 2026-06-01 10:12:00 UTC, failing for 1m 30s, 2/5 nodes of the grid dead, announced by: `hal9000`- @dave"
+        );
+        assert_eq!(event.reason, None);
+        assert_eq!(event.grid, Some(GridSnapshot { dead: 2, total: 5 }));
+    }
+
+    #[test]
+    fn recovery_message_tells_who_announced() {
+        let node = node();
+        let name = "hal9001".to_string();
+        let announced_at = DateTime::from_timestamp(1_700_000_000, 0).unwrap();
+        let event = AnnouncementEvent::returned(
+            &test_config(""),
+            node.with_name(&name),
+            announced_at,
+            Duration::from_millis(1_501_500),
+            Some(GridSnapshot { dead: 1, total: 5 }),
+        );
+
+        assert_eq!(
+            event.message,
+            "Grid announcement, `hal9001` (home) has fortunately RETURNED, was announced by `hal9000` at 2023-11-14 22:13:20 UTC, down for 25m 1s, 1/5 nodes of the grid dead, announced by: `hal9000`- @dave"
+        );
+    }
+
+    #[test]
+    fn window_slides_after_an_hour() {
+        let start = Utc::now();
+        let mut window = ChannelWindow::default();
+
+        assert!(window.try_send(2, start));
+        assert!(window.try_send(2, start + chrono::Duration::minutes(10)));
+        assert!(!window.try_send(2, start + chrono::Duration::minutes(59)));
+        // the first one fell out of the window
+        assert!(window.try_send(2, start + HOUR));
+        assert!(!window.try_send(2, start + HOUR));
+    }
+
+    #[test]
+    fn queued_announcements_of_removed_nodes_are_dropped() {
+        let config = test_config("");
+        let node = node();
+        let state = State::new(&config);
+        let mut gr = state.lock().unwrap();
+        let mut nodes = HashMap::new();
+        for name in ["hal9001", "hal9002"] {
+            let name = name.to_string();
+            nodes.insert(name.clone(), node.clone());
+            gr.pending_announcements.push(
+                PendingKind::Queued,
+                AnnouncementMode::Telegram,
+                AnnouncementEvent::returned(
+                    &config,
+                    node.with_name(&name),
+                    Utc::now(),
+                    Duration::ZERO,
+                    None,
+                ),
+                Utc::now(),
+                0,
+            );
+        }
+
+        gr.reconcile_nodes(&nodes, Utc::now());
+        nodes.remove("hal9001");
+        gr.reconcile_nodes(&nodes, Utc::now());
+        assert_eq!(
+            gr.pending_announcements
+                .sorted()
+                .iter()
+                .map(|pending| pending.event.node.as_str())
+                .collect::<Vec<_>>(),
+            vec!["hal9002"]
+        );
+    }
+}
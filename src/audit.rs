@@ -0,0 +1,292 @@
+use crate::config::AuditConfig;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::sync::mpsc::{Sender, channel};
+use tracing::error;
+
+/// Who caused an audited action
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum AuditSource {
+    /// Call to this node's own API
+    Api { client_ip: String },
+    /// Broadcast received from another node of the grid
+    Peer {
+        node_name: Option<String>,
+        client_ip: String,
+    },
+    /// This node's own poller
+    Poller,
+    /// Telegram bot command
+    Telegram { user_id: i64 },
+    /// Signal sent to the process, like `SIGHUP`
+    Signal { name: String },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum AuditAction {
+    SilenceCreated {
+        silence_id: String,
+        node_name: String,
+        silent_until: DateTime<Utc>,
+        /// Kept on this node, not broadcast
+        #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+        local: bool,
+    },
+    SilenceUpdated {
+        silence_id: String,
+        node_name: String,
+        silent_until: DateTime<Utc>,
+        revision: u64,
+        #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+        local: bool,
+    },
+    SilenceCancelled {
+        silence_id: String,
+        node_name: String,
+        #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+        local: bool,
+    },
+    DeathAnnouncementDecided {
+        node_name: String,
+        announcer: String,
+    },
+    RecoveryAnnouncementDecided {
+        node_name: String,
+    },
+    /// Confirmed dead, but silenced here or on a peer
+    DeathAnnouncementSuppressed {
+        node_name: String,
+    },
+    MuteSet {
+        muted_until: DateTime<Utc>,
+    },
+    MuteCleared,
+    /// Not sent, the grid was muted
+    AnnouncementMuted {
+        node_name: String,
+        event: String,
+    },
+    /// A peer's obituary told the death was already announced
+    DeathAnnouncementLearned {
+        node_name: String,
+        announcer: String,
+        announced_at: DateTime<Utc>,
+    },
+    /// Refused for its origin or target
+    SilenceBroadcastRejected {
+        node_name: String,
+        reason: String,
+    },
+    /// Applied, but its sender couldn't be verified
+    SilenceBroadcastSuspicious {
+        node_name: String,
+        reason: String,
+    },
+    /// Died and recovered `transitions` times within the flapping window
+    NodeFlapping {
+        node_name: String,
+        transitions: usize,
+    },
+    /// Neither died nor recovered for the flapping window
+    NodeStabilized {
+        node_name: String,
+    },
+    ConfigReloaded,
+    /// The config file was read again, but kept out of effect
+    ConfigReloadRejected {
+        reason: String,
+    },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AuditEntry {
+    pub seq: u64,
+    pub time: DateTime<Utc>,
+    pub source: AuditSource,
+    pub action: AuditAction,
+    /// Of the poll cycle which caused it, here or on the calling peer
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub trace: Option<String>,
+}
+
+/// Append-only log of administrative actions, the newest `capacity` entries are kept in memory,
+/// every entry is also appended to the audit file as a json line if one is configured.
+pub struct AuditLog {
+    entries: VecDeque<AuditEntry>,
+    capacity: usize,
+    /// Entries go to the audit file on a thread of their own, a slow disk doesn't hold the state lock
+    writer: Option<Sender<AuditEntry>>,
+    next_seq: u64,
+    /// Of the running poll cycle, given to the entries of the poller
+    pub cycle_trace: Option<String>,
+}
+
+impl AuditLog {
+    pub fn new(config: &AuditConfig) -> Self {
+        Self {
+            entries: VecDeque::with_capacity(config.capacity),
+            capacity: config.capacity,
+            writer: config.file_path.clone().map(spawn_writer),
+            next_seq: 0,
+            cycle_trace: None,
+        }
+    }
+
+    pub fn record(&mut self, source: AuditSource, action: AuditAction) {
+        let trace = match source {
+            AuditSource::Poller => self.cycle_trace.clone(),
+            _ => None,
+        };
+        self.record_traced(source, action, trace);
+    }
+
+    pub fn record_traced(
+        &mut self,
+        source: AuditSource,
+        action: AuditAction,
+        trace: Option<String>,
+    ) {
+        let entry = AuditEntry {
+            seq: self.next_seq,
+            time: Utc::now(),
+            source,
+            action,
+            trace,
+        };
+        self.next_seq += 1;
+
+        if let Some(writer) = self.writer.as_ref()
+            && writer.send(entry.clone()).is_err()
+        {
+            error!(
+                "The audit log writer stopped, entry {} not written",
+                entry.seq
+            );
+        }
+
+        if self.capacity == 0 {
+            return;
+        }
+        while self.entries.len() >= self.capacity {
+            self.entries.pop_front();
+        }
+        self.entries.push_back(entry);
+    }
+
+    /// Newest first
+    pub fn page(&self, offset: usize, limit: usize) -> Vec<AuditEntry> {
+        self.entries
+            .iter()
+            .rev()
+            .skip(offset)
+            .take(limit)
+            .cloned()
+            .collect()
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+}
+
+/// Appends the entries in the order they were recorded, until the log is dropped
+fn spawn_writer(path: String) -> Sender<AuditEntry> {
+    let (sender, receiver) = channel::<AuditEntry>();
+    std::thread::Builder::new()
+        .name("audit-writer".to_string())
+        .spawn(move || {
+            for entry in receiver {
+                if let Err(err) = append_line(&path, &entry) {
+                    error!("Failed to write audit log `{path}`: {err:?}");
+                }
+            }
+        })
+        .expect("Failed to start the audit log writer");
+    sender
+}
+
+fn append_line(path: &str, entry: &AuditEntry) -> anyhow::Result<()> {
+    let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+    let mut line = serde_json::to_vec(entry)?;
+    line.push(b'\n');
+    file.write_all(&line)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn log_of(capacity: usize, file_path: Option<String>) -> AuditLog {
+        AuditLog::new(&AuditConfig {
+            capacity,
+            file_path,
+        })
+    }
+
+    fn seqs(entries: Vec<AuditEntry>) -> Vec<u64> {
+        entries.into_iter().map(|entry| entry.seq).collect()
+    }
+
+    #[test]
+    fn ring_keeps_the_newest() {
+        let mut log = log_of(3, None);
+        for _ in 0..5 {
+            log.record(AuditSource::Poller, AuditAction::MuteCleared);
+        }
+        assert_eq!(log.len(), 3);
+        assert_eq!(seqs(log.page(0, 10)), [4, 3, 2]);
+
+        let mut off = log_of(0, None);
+        off.record(AuditSource::Poller, AuditAction::MuteCleared);
+        assert_eq!(off.len(), 0);
+    }
+
+    #[test]
+    fn pages() {
+        let mut log = log_of(10, None);
+        for _ in 0..5 {
+            log.record(AuditSource::Poller, AuditAction::MuteCleared);
+        }
+        assert_eq!(seqs(log.page(0, 2)), [4, 3]);
+        assert_eq!(seqs(log.page(2, 2)), [2, 1]);
+        assert_eq!(seqs(log.page(4, 2)), [0]);
+        assert!(log.page(5, 2).is_empty());
+        assert!(log.page(0, 0).is_empty());
+    }
+
+    #[test]
+    fn file_gets_every_entry() {
+        let path = std::env::temp_dir().join(format!(
+            "freecaster-grid-audit-{}.jsonl",
+            std::process::id()
+        ));
+        let _ = std::fs::remove_file(&path);
+        // the ring keeps one, the file all of them
+        let mut log = log_of(1, Some(path.display().to_string()));
+        for _ in 0..3 {
+            log.record(AuditSource::Poller, AuditAction::MuteCleared);
+        }
+        drop(log);
+
+        let mut lines = vec![];
+        for _ in 0..100 {
+            lines = std::fs::read_to_string(&path)
+                .unwrap_or_default()
+                .lines()
+                .map(|line| serde_json::from_str::<AuditEntry>(line).unwrap().seq)
+                .collect();
+            if lines.len() == 3 {
+                break;
+            }
+            std::thread::sleep(std::time::Duration::from_millis(10));
+        }
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(lines, [0, 1, 2]);
+    }
+}
@@ -0,0 +1,90 @@
+use crate::{GridHealth, GridNodeStatus, GridSummary};
+
+/// Badges are embedded in pages cached elsewhere, keep them from going stale for long
+pub const BADGE_MAX_AGE: u32 = 30;
+
+const LABEL_COLOR: &str = "#555";
+const UNKNOWN_COLOR: &str = "#9f9f9f";
+
+fn health_color(health: GridHealth) -> &'static str {
+    match health {
+        GridHealth::Ok => "#4c1",
+        GridHealth::Degraded => "#dfb317",
+        GridHealth::Critical => "#e05d44",
+    }
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// Close enough to Verdana at 11px for the text to fit
+fn text_width(text: &str) -> usize {
+    text.chars().count() * 7 + 10
+}
+
+/// A flat two part badge, `label` on gray, `message` on `color`
+fn render(label: &str, message: &str, color: &str) -> String {
+    let label_width = text_width(label);
+    let message_width = text_width(message);
+    let width = label_width + message_width;
+    let (label, message) = (escape(label), escape(message));
+    format!(
+        r##"<svg xmlns="http://www.w3.org/2000/svg" width="{width}" height="20" role="img" aria-label="{label}: {message}"><title>{label}: {message}</title><rect width="{label_width}" height="20" fill="{LABEL_COLOR}"/><rect x="{label_width}" width="{message_width}" height="20" fill="{color}"/><g fill="#fff" text-anchor="middle" font-family="Verdana,Geneva,DejaVu Sans,sans-serif" font-size="11"><text x="{label_x}" y="14">{label}</text><text x="{message_x}" y="14">{message}</text></g></svg>"##,
+        label_x = label_width / 2,
+        message_x = label_width + message_width / 2,
+    )
+}
+
+/// Like `grid | 6/7 up`, colored by the grid's health
+pub fn grid(summary: &GridSummary) -> String {
+    render(
+        "grid",
+        &format!("{}/{} up", summary.alive_nodes, summary.total_nodes),
+        health_color(summary.health),
+    )
+}
+
+/// The status of a node, colored like a grid of that node alone. Gray `unknown` for nodes not in the grid,
+/// embeds of renamed nodes don't break
+pub fn node(label: &str, status: Option<GridNodeStatus>) -> String {
+    let Some(status) = status else {
+        return render(label, "unknown", UNKNOWN_COLOR);
+    };
+    let mut summary = GridSummary::default();
+    summary.add(status, false);
+    let message = match status {
+        GridNodeStatus::Alive => "alive",
+        GridNodeStatus::Dying => "dying",
+        GridNodeStatus::Dead => "dead",
+        GridNodeStatus::Unknown => "unknown",
+    };
+    render(label, message, health_color(summary.health))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn badges() {
+        let mut summary = GridSummary::default();
+        for status in [GridNodeStatus::Alive, GridNodeStatus::Alive] {
+            summary.add(status, false);
+        }
+        let badge = grid(&summary);
+        assert!(badge.contains(">2/2 up</text>"));
+        assert!(badge.contains(r##"fill="#4c1""##));
+
+        summary.add(GridNodeStatus::Dead, false);
+        assert!(grid(&summary).contains(r##"fill="#e05d44""##));
+
+        let badge = node("hal<9001>", Some(GridNodeStatus::Dying));
+        assert!(badge.contains(">hal&lt;9001&gt;</text>"));
+        assert!(badge.contains(r##"fill="#dfb317""##));
+        assert!(node("hal9002", None).contains(r##"fill="#9f9f9f""##));
+    }
+}
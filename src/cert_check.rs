@@ -0,0 +1,145 @@
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::net::TcpStream;
+use tokio_rustls::TlsConnector;
+use tokio_rustls::rustls::client::danger::{
+    HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier,
+};
+use tokio_rustls::rustls::crypto::{CryptoProvider, ring};
+use tokio_rustls::rustls::pki_types::{CertificateDer, ServerName, UnixTime};
+use tokio_rustls::rustls::{ClientConfig, DigitallySignedStruct, SignatureScheme};
+
+#[derive(Clone, Debug, Default)]
+pub struct CertStatus {
+    pub not_after: Option<DateTime<Utc>>,
+    pub checked: Option<DateTime<Utc>>,
+    pub warned: Option<DateTime<Utc>>,
+}
+
+impl CertStatus {
+    pub fn days_remaining(&self) -> Option<i64> {
+        self.not_after.map(|na| (na - Utc::now()).num_days())
+    }
+
+    pub fn needs_check(&self, now: DateTime<Utc>, interval: chrono::Duration) -> bool {
+        self.checked.is_none_or(|checked| now - checked >= interval)
+    }
+
+    /// Expiring within `threshold` and not warned about in the last `interval`
+    pub fn needs_warning(
+        &self,
+        now: DateTime<Utc>,
+        threshold: chrono::Duration,
+        interval: chrono::Duration,
+    ) -> bool {
+        let Some(not_after) = self.not_after else {
+            return false;
+        };
+        not_after - now <= threshold && self.warned.is_none_or(|warned| now - warned >= interval)
+    }
+}
+
+/// Expiry of a PEM certificate, like the one this node serves
+pub fn pem_not_after(pem: &[u8]) -> Result<DateTime<Utc>> {
+    let (_, pem) = x509_parser::pem::parse_x509_pem(pem).context("Invalid PEM certificate")?;
+    let cert = pem.parse_x509().context("Invalid certificate")?;
+    DateTime::from_timestamp(cert.validity().not_after.timestamp(), 0)
+        .context("Certificate expiry out of range")
+}
+
+/// What `doctor` tells of a peer's certificate
+pub struct PeerCertificate {
+    pub subject: String,
+    pub not_after: DateTime<Utc>,
+}
+
+/// Expiry of the certificate a node serves, `None` if it isn't served over https
+pub async fn peer_not_after(address: &str) -> Result<Option<DateTime<Utc>>> {
+    Ok(peer_certificate(address)
+        .await?
+        .map(|certificate| certificate.not_after))
+}
+
+/// The certificate a node serves, `None` if it isn't served over https
+pub async fn peer_certificate(address: &str) -> Result<Option<PeerCertificate>> {
+    let url =
+        reqwest::Url::parse(address).with_context(|| format!("Invalid address `{address}`"))?;
+    if url.scheme() != "https" {
+        return Ok(None);
+    }
+    let host = url.host_str().context("Address has no host")?.to_string();
+    let port = url.port_or_known_default().unwrap_or(443);
+
+    let provider = Arc::new(ring::default_provider());
+    let config = ClientConfig::builder_with_provider(provider.clone())
+        .with_safe_default_protocol_versions()?
+        .dangerous()
+        .with_custom_certificate_verifier(Arc::new(AcceptAnyCert(provider)))
+        .with_no_client_auth();
+
+    let tls = tokio::time::timeout(Duration::from_secs(5), async {
+        let stream = TcpStream::connect((host.as_str(), port)).await?;
+        let server_name = ServerName::try_from(host.clone())?;
+        anyhow::Ok(
+            TlsConnector::from(Arc::new(config))
+                .connect(server_name, stream)
+                .await?,
+        )
+    })
+    .await
+    .context("TLS handshake timed out")??;
+
+    let (_, connection) = tls.get_ref();
+    let der = connection
+        .peer_certificates()
+        .and_then(|certs| certs.first())
+        .context("No certificate presented")?;
+    let (_, cert) = x509_parser::parse_x509_certificate(der).context("Invalid certificate")?;
+    let not_after = DateTime::from_timestamp(cert.validity().not_after.timestamp(), 0)
+        .context("Certificate expiry out of range")?;
+    Ok(Some(PeerCertificate {
+        subject: cert.subject().to_string(),
+        not_after,
+    }))
+}
+
+/// We only read the expiry, same as the poller, self-signed certificates are fine
+#[derive(Debug)]
+struct AcceptAnyCert(Arc<CryptoProvider>);
+
+impl ServerCertVerifier for AcceptAnyCert {
+    fn verify_server_cert(
+        &self,
+        _end_entity: &CertificateDer<'_>,
+        _intermediates: &[CertificateDer<'_>],
+        _server_name: &ServerName<'_>,
+        _ocsp_response: &[u8],
+        _now: UnixTime,
+    ) -> Result<ServerCertVerified, tokio_rustls::rustls::Error> {
+        Ok(ServerCertVerified::assertion())
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        _message: &[u8],
+        _cert: &CertificateDer<'_>,
+        _dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, tokio_rustls::rustls::Error> {
+        Ok(HandshakeSignatureValid::assertion())
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        _message: &[u8],
+        _cert: &CertificateDer<'_>,
+        _dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, tokio_rustls::rustls::Error> {
+        Ok(HandshakeSignatureValid::assertion())
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        self.0.signature_verification_algorithms.supported_schemes()
+    }
+}
@@ -0,0 +1,284 @@
+use crate::config::{Config, find_config, load_config};
+use crate::{GridHealth, GridSummary};
+use anyhow::{Context, Result, bail};
+use freecaster_grid_client::FreecasterClient;
+use rand::Rng;
+use std::io::{BufRead, IsTerminal, Write};
+use std::path::PathBuf;
+use std::time::Duration;
+
+/// Exit code when the node can't be asked
+const UNREACHABLE: i32 = 3;
+
+/// `grid <address> <secret_key>`, prints the grid totals of the node at `address`,
+/// exiting with 0 for `ok`, 1 for `degraded`, 2 for `critical` grids
+pub async fn grid(args: &[String]) -> i32 {
+    let [address, key] = args else {
+        eprintln!("Usage: freecaster-grid grid <address> <secret_key>");
+        return UNREACHABLE;
+    };
+
+    match fetch_summary(address, key).await {
+        Ok(summary) => {
+            println!(
+                "Alive: {}, Dying: {}, Dead: {}, Unknown: {}, Silenced: {}, Total: {}, Health: {:?}",
+                summary.alive_nodes,
+                summary.dying_nodes,
+                summary.dead_nodes,
+                summary.unknown_nodes,
+                summary.silenced_nodes,
+                summary.total_nodes,
+                summary.health
+            );
+            match summary.health {
+                GridHealth::Ok => 0,
+                GridHealth::Degraded => 1,
+                GridHealth::Critical => 2,
+            }
+        }
+        Err(err) => {
+            eprintln!("Failed to get the grid from `{address}`: {err:#}");
+            UNREACHABLE
+        }
+    }
+}
+
+/// Answers of `init`, asked for when missing from the flags and stdin is a terminal
+#[derive(Debug, Default, PartialEq)]
+struct InitOptions {
+    name: Option<String>,
+    host: Option<String>,
+    port: Option<u16>,
+    secret_key: Option<String>,
+    output: Option<PathBuf>,
+    force: bool,
+}
+
+fn parse_init(args: &[String]) -> Result<InitOptions> {
+    let mut options = InitOptions::default();
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let mut value = || {
+            args.next()
+                .with_context(|| format!("`{arg}` needs a value"))
+        };
+        match arg.as_str() {
+            "--name" => options.name = Some(value()?.clone()),
+            "--host" => options.host = Some(value()?.clone()),
+            "--port" => options.port = Some(value()?.parse().context("Invalid `--port`")?),
+            "--secret-key" => options.secret_key = Some(value()?.clone()),
+            "--output" => options.output = Some(PathBuf::from(value()?)),
+            "--force" => options.force = true,
+            _ => bail!("Unknown argument `{arg}`"),
+        }
+    }
+    Ok(options)
+}
+
+/// `init [--name <name>] [--host <host>] [--port <port>] [--secret-key <key>] [--output <file>] [--force]`,
+/// writes a starter config, `./freecaster.yaml` by default, and prints this node's entry for the other nodes
+pub fn init(args: &[String]) -> i32 {
+    match run_init(args) {
+        Ok(()) => 0,
+        Err(err) => {
+            eprintln!("Failed to create the config: {err:#}");
+            1
+        }
+    }
+}
+
+fn run_init(args: &[String]) -> Result<()> {
+    let options = parse_init(args)?;
+    let output = options
+        .output
+        .unwrap_or_else(|| PathBuf::from("freecaster.yaml"));
+    if output.exists() && !options.force {
+        bail!("`{}` exists, overwrite it with `--force`", output.display());
+    }
+
+    let name = match options.name {
+        Some(name) => name,
+        None => ask("Name of this node")?,
+    };
+    let host = match options.host {
+        Some(host) => host,
+        None => ask("Host name or address the other nodes reach this node at")?,
+    };
+    let port = options.port.unwrap_or(3037);
+    let secret_key = match options.secret_key {
+        Some(key) => key,
+        None => generate_secret_key(),
+    };
+
+    let config = starter_config(&name, &host, port, &secret_key);
+    ::config::Config::builder()
+        .add_source(::config::File::from_str(
+            &config,
+            ::config::FileFormat::Yaml,
+        ))
+        .build()?
+        .try_deserialize::<Config>()?
+        .without_self()
+        .validate()?;
+
+    let mut file = std::fs::OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .create_new(!options.force)
+        .open(&output)
+        .with_context(|| format!("Failed to create `{}`", output.display()))?;
+    file.write_all(config.as_bytes())?;
+
+    println!("Wrote `{}`", output.display());
+    println!("Add this node to the `nodes` of the other nodes, with the same `secret_key`:");
+    print!("{}", node_entry(&name, &host, port));
+    Ok(())
+}
+
+fn ask(question: &str) -> Result<String> {
+    if !std::io::stdin().is_terminal() {
+        bail!("{question} is missing, pass it as a flag");
+    }
+    print!("{question}: ");
+    std::io::stdout().flush()?;
+    let mut answer = String::new();
+    std::io::stdin().lock().read_line(&mut answer)?;
+    let answer = answer.trim();
+    if answer.is_empty() {
+        bail!("{question} is missing");
+    }
+    Ok(answer.to_string())
+}
+
+fn generate_secret_key() -> String {
+    rand::rng()
+        .sample_iter(rand::distr::Alphanumeric)
+        .take(32)
+        .map(char::from)
+        .collect()
+}
+
+/// JSON strings are valid YAML, whatever the value
+fn quote(value: &str) -> String {
+    serde_json::to_string(value).expect("Failed to quote string")
+}
+
+fn node_entry(name: &str, host: &str, port: u16) -> String {
+    format!(
+        "  {}:\n    address: {}\n",
+        quote(name),
+        quote(&format!("http://{host}:{port}"))
+    )
+}
+
+fn starter_config(name: &str, host: &str, port: u16, secret_key: &str) -> String {
+    format!(
+        r#"# Config of `{name}`, see the README for every setting
+name: {}
+# the same on every node of the grid
+secret_key: {}
+# how often the other nodes are polled
+poll_time: 10s
+# log, telegram, exec, gotify or pushover
+announcement_mode: log
+webui_enabled: true
+
+server:
+  ip_address: "0.0.0.0"
+  port: {port}
+
+# every node of the grid, this one too, the same list on every node
+nodes:
+{}"#,
+        quote(name),
+        quote(secret_key),
+        node_entry(name, host, port)
+    )
+}
+
+/// `schema <output_path>`, writes the JSON schema of the config
+#[cfg(feature = "json_schema")]
+pub fn schema(args: &[String]) -> i32 {
+    let [output_path] = args else {
+        eprintln!("Usage: freecaster-grid schema <output_path>");
+        return 1;
+    };
+    match std::fs::write(output_path, crate::config::json_schema()) {
+        Ok(()) => 0,
+        Err(err) => {
+            eprintln!("Failed to write JSON schema to `{output_path}`: {err}");
+            1
+        }
+    }
+}
+
+/// `validate [config]`, loads the config like a run would, exiting with 1 if it's invalid
+pub async fn validate(args: &[String]) -> i32 {
+    let path = find_config(args.first().map(String::as_str));
+    let file = match path.as_ref() {
+        Some(path) => format!("`{}`", path.display()),
+        None => "the environment".to_string(),
+    };
+    match load_config(path).await {
+        Ok(config) => {
+            println!("Config of `{}` from {file} is valid", config.name);
+            0
+        }
+        Err(err) => {
+            eprintln!("Config from {file} is invalid: {err:#}");
+            1
+        }
+    }
+}
+
+async fn fetch_summary(address: &str, key: &str) -> Result<GridSummary> {
+    // nodes often serve self-signed certificates, like peers they are accepted
+    let client = reqwest::Client::builder()
+        .use_rustls_tls()
+        .danger_accept_invalid_certs(true)
+        .timeout(Duration::from_secs(10))
+        .build()?;
+    Ok(FreecasterClient::with_client(client, address, key)
+        .summary()
+        .await?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn init_flags_and_starter_config() {
+        let args = ["--name", "hal9000", "--port", "4242", "--force"].map(String::from);
+        assert_eq!(
+            parse_init(&args).unwrap(),
+            InitOptions {
+                name: Some("hal9000".to_string()),
+                port: Some(4242),
+                force: true,
+                ..Default::default()
+            }
+        );
+        assert!(parse_init(&["--port".to_string()]).is_err());
+        assert!(parse_init(&["--grid".to_string()]).is_err());
+
+        let key = generate_secret_key();
+        assert_eq!(key.len(), 32);
+        let config: Config = ::config::Config::builder()
+            .add_source(::config::File::from_str(
+                &starter_config("hal: 9000", "hal9000.local", 4242, &key),
+                ::config::FileFormat::Yaml,
+            ))
+            .build()
+            .unwrap()
+            .try_deserialize()
+            .unwrap();
+        assert_eq!(config.name, "hal: 9000");
+        assert_eq!(config.secret_key, key);
+        assert_eq!(
+            config.nodes["hal: 9000"].address,
+            "http://hal9000.local:4242"
+        );
+    }
+}
@@ -0,0 +1,226 @@
+use crate::config::ServerConfig;
+use anyhow::{Context, Result};
+use ipnet::IpNet;
+use rouille::Request;
+use std::net::IpAddr;
+
+/// Set by proxies like nginx to the one address they got the call from
+const REAL_IP_HEADER: &str = "X-Real-IP";
+
+/// A network like `10.8.0.0/24`, or a single address
+pub fn parse_cidr(cidr: &str) -> Result<IpNet> {
+    let cidr = cidr.trim();
+    cidr.parse::<IpNet>()
+        .or_else(|_| cidr.parse::<IpAddr>().map(IpNet::from))
+        .with_context(|| format!("Invalid CIDR `{cidr}`"))
+}
+
+/// Checked by `Config::validate`, so the unparsable ones can be skipped here
+fn parse_all(cidrs: &[String]) -> Vec<IpNet> {
+    cidrs
+        .iter()
+        .filter_map(|cidr| parse_cidr(cidr).ok())
+        .collect()
+}
+
+/// Whether `ip` is in any of `nets`, IPv4 clients of an IPv6 listener included
+fn contains(nets: &[IpNet], ip: IpAddr) -> bool {
+    let ip = ip.to_canonical();
+    nets.iter().any(|net| net.contains(&ip))
+}
+
+/// Address of the client for logs, rate limits, allowlists and the audit log.
+/// The forwarded header, or `X-Real-IP` without one, is only believed when a trusted proxy sent it
+pub fn resolve(config: &ServerConfig, request: &Request) -> IpAddr {
+    if config.trusted_proxies.is_empty() {
+        return request.remote_addr().ip().to_canonical();
+    }
+    forwarded_client(
+        request.remote_addr().ip(),
+        request
+            .header(&config.forwarded_header)
+            .or_else(|| request.header(REAL_IP_HEADER)),
+        &parse_all(&config.trusted_proxies),
+    )
+}
+
+/// Walks the forwarded addresses from the nearest hop back while they are trusted proxies,
+/// anything before the first untrusted one could be made up by the client
+fn forwarded_client(remote: IpAddr, forwarded: Option<&str>, trusted: &[IpNet]) -> IpAddr {
+    let mut client = remote.to_canonical();
+    let Some(forwarded) = forwarded else {
+        return client;
+    };
+    for hop in forwarded.rsplit(',') {
+        if !contains(trusted, client) {
+            break;
+        }
+        match hop.trim().parse::<IpAddr>() {
+            Ok(hop) => client = hop.to_canonical(),
+            // the proxy is the last address known for sure
+            Err(_) => break,
+        }
+    }
+    client
+}
+
+/// Silences are accepted from anywhere without `silence_allowed_cidrs`
+pub fn silence_allowed(config: &ServerConfig, request: &Request) -> bool {
+    if config.silence_allowed_cidrs.is_empty() {
+        return true;
+    }
+    contains(
+        &parse_all(&config.silence_allowed_cidrs),
+        resolve(config, request),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn nets(cidrs: &[&str]) -> Vec<IpNet> {
+        cidrs.iter().map(|cidr| parse_cidr(cidr).unwrap()).collect()
+    }
+
+    fn ip(ip: &str) -> IpAddr {
+        ip.parse().unwrap()
+    }
+
+    fn server_config(yaml: &str) -> ServerConfig {
+        ::config::Config::builder()
+            .add_source(::config::File::from_str(
+                &format!("port: 3037\n{yaml}"),
+                ::config::FileFormat::Yaml,
+            ))
+            .build()
+            .unwrap()
+            .try_deserialize()
+            .unwrap()
+    }
+
+    fn request_from(remote: &str, headers: &[(&str, &str)]) -> Request {
+        Request::fake_http_from(
+            remote.parse().unwrap(),
+            "GET",
+            "/",
+            headers
+                .iter()
+                .map(|(name, value)| (name.to_string(), value.to_string()))
+                .collect(),
+            vec![],
+        )
+    }
+
+    #[test]
+    fn cidrs_are_parsed() {
+        assert_eq!(
+            parse_cidr("10.8.0.0/24").unwrap(),
+            "10.8.0.0/24".parse::<IpNet>().unwrap()
+        );
+        assert_eq!(
+            parse_cidr(" 192.168.1.7 ").unwrap(),
+            "192.168.1.7/32".parse::<IpNet>().unwrap()
+        );
+        assert_eq!(
+            parse_cidr("fd00::1").unwrap(),
+            "fd00::1/128".parse::<IpNet>().unwrap()
+        );
+        for invalid in ["", "10.8.0.0/33", "10.8.0/24", "vpn", "10.8.0.0/"] {
+            assert!(parse_cidr(invalid).is_err(), "{invalid}");
+        }
+    }
+
+    #[test]
+    fn cidrs_are_matched() {
+        let allowed = nets(&["10.8.0.0/24", "fd00::/8"]);
+        assert!(contains(&allowed, ip("10.8.0.1")));
+        assert!(contains(&allowed, ip("10.8.0.255")));
+        assert!(!contains(&allowed, ip("10.8.1.0")));
+        assert!(contains(&allowed, ip("fd12::3")));
+        assert!(!contains(&allowed, ip("fe80::1")));
+        // an IPv4 client of a dual stack listener
+        assert!(contains(&allowed, ip("::ffff:10.8.0.9")));
+        assert!(!contains(&allowed, ip("::ffff:10.9.0.9")));
+        assert!(!contains(&[], ip("10.8.0.1")));
+    }
+
+    #[test]
+    fn forwarded_header_is_only_believed_from_trusted_proxies() {
+        let trusted = nets(&["172.16.0.0/12"]);
+        let proxy = ip("172.17.0.2");
+
+        // straight from the client, whatever it claims
+        assert_eq!(
+            forwarded_client(ip("203.0.113.5"), Some("10.8.0.1"), &trusted),
+            ip("203.0.113.5")
+        );
+        // no proxies trusted, the header is ignored
+        assert_eq!(forwarded_client(proxy, Some("10.8.0.1"), &[]), proxy);
+        assert_eq!(
+            forwarded_client(proxy, Some("10.8.0.1"), &trusted),
+            ip("10.8.0.1")
+        );
+        assert_eq!(forwarded_client(proxy, None, &trusted), proxy);
+        // the client prepended an address, the proxy appended the real one
+        assert_eq!(
+            forwarded_client(proxy, Some("10.8.0.1, 203.0.113.5"), &trusted),
+            ip("203.0.113.5")
+        );
+        // through two trusted proxies
+        assert_eq!(
+            forwarded_client(proxy, Some("10.8.0.1, 172.20.0.3"), &trusted),
+            ip("10.8.0.1")
+        );
+        // garbage stops at the proxy
+        assert_eq!(
+            forwarded_client(proxy, Some("10.8.0.1, nonsense"), &trusted),
+            proxy
+        );
+        assert_eq!(
+            forwarded_client(ip("::ffff:172.17.0.2"), Some("10.8.0.1"), &trusted),
+            ip("10.8.0.1")
+        );
+    }
+
+    #[test]
+    fn spoofed_headers_are_ignored() {
+        let behind_nginx = server_config("trusted_proxies: [127.0.0.1]");
+        let spoofed = [("X-Forwarded-For", "10.8.0.1"), ("X-Real-IP", "10.8.0.2")];
+
+        // not through the proxy, nor without trusted proxies
+        let direct = request_from("203.0.113.5:50000", &spoofed);
+        assert_eq!(resolve(&behind_nginx, &direct), ip("203.0.113.5"));
+        let open = server_config("");
+        let proxied = request_from("127.0.0.1:50000", &spoofed);
+        assert_eq!(resolve(&open, &proxied), ip("127.0.0.1"));
+
+        // nginx appends the address it got the call from to whatever the client sent
+        let proxied = request_from(
+            "127.0.0.1:50000",
+            &[("X-Forwarded-For", "10.8.0.1, 203.0.113.5")],
+        );
+        assert_eq!(resolve(&behind_nginx, &proxied), ip("203.0.113.5"));
+        assert!(!silence_allowed(
+            &server_config("trusted_proxies: [127.0.0.1]\nsilence_allowed_cidrs: [10.8.0.0/24]"),
+            &proxied
+        ));
+
+        // `X-Real-IP` only counts without the forwarded header
+        let proxied = request_from("127.0.0.1:50000", &[("X-Real-IP", "203.0.113.5")]);
+        assert_eq!(resolve(&behind_nginx, &proxied), ip("203.0.113.5"));
+        let proxied = request_from("127.0.0.1:50000", &spoofed);
+        assert_eq!(resolve(&behind_nginx, &proxied), ip("10.8.0.1"));
+
+        let custom =
+            server_config("trusted_proxies: [127.0.0.1]\nforwarded_header: CF-Connecting-IP");
+        let proxied = request_from(
+            "127.0.0.1:50000",
+            &[
+                ("X-Forwarded-For", "10.8.0.1"),
+                ("CF-Connecting-IP", "203.0.113.5"),
+            ],
+        );
+        assert_eq!(resolve(&custom, &proxied), ip("203.0.113.5"));
+    }
+}
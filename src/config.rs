@@ -1,9 +1,15 @@
 #![allow(dead_code)]
 
+use crate::version::VersionReq;
 use anyhow::{Context, Result};
 use config::Case;
 use serde::{Deserialize, Serialize};
-use std::{collections::HashMap, path::PathBuf};
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap, HashSet},
+    net::IpAddr,
+    path::{Path, PathBuf},
+};
+use tracing::warn;
 
 #[derive(Debug, Deserialize)]
 #[cfg_attr(feature = "json_schema", derive(schemars::JsonSchema))]
@@ -13,12 +19,105 @@ pub struct ServerConfig {
     pub port: u16,
     #[serde(default)]
     pub ssl: Option<SSLConfig>,
+    /// Largest accepted request body in bytes
+    #[serde(default = "default_max_body_size")]
+    pub max_body_size: usize,
+    /// Compress json responses for clients accepting gzip or brotli
+    #[serde(default = "default_true")]
+    pub compression: bool,
+    /// Smaller responses are sent uncompressed
+    #[serde(default = "default_compression_min_size")]
+    pub compression_min_size: usize,
+    /// Tag json responses so clients can revalidate with `If-None-Match`
+    #[serde(default = "default_true")]
+    pub etag: bool,
+    /// Lets dashboards on other origins call the api, no cors headers are sent without it
+    #[serde(default)]
+    pub cors: Option<CorsConfig>,
+    /// Networks silences and mutes, and their cancellations, are accepted from, like `10.8.0.0/24`, from anywhere when empty
+    #[serde(default)]
+    pub silence_allowed_cidrs: Vec<String>,
+    /// Proxies in front of the node, only their `forwarded_header` is believed
+    #[serde(default)]
+    pub trusted_proxies: Vec<String>,
+    #[serde(default = "default_forwarded_header")]
+    pub forwarded_header: String,
+}
+
+fn default_forwarded_header() -> String {
+    "X-Forwarded-For".to_string()
+}
+
+#[derive(Debug, Deserialize, Clone)]
+#[cfg_attr(feature = "json_schema", derive(schemars::JsonSchema))]
+pub struct CorsConfig {
+    /// Exact origins like `https://dash.example.com`, or `*` for any
+    #[serde(default)]
+    pub allowed_origins: Vec<String>,
 }
 
+/// Events announcements are made for
+pub const ANNOUNCEMENT_EVENTS: &[&str] = &[
+    "dead",
+    "recovered",
+    "joined",
+    "reminder",
+    "warning",
+    "digest",
+    "escalated",
+    "summary",
+    "self_check",
+    "started",
+    "stopped",
+];
+
+/// Messages of the announcements, for `announcement_templates`
+pub const MESSAGE_KEYS: &[&str] = &[
+    "dead",
+    "recovered",
+    "escalated",
+    "joined",
+    "reminder",
+    "cert_expiry",
+    "disk_full",
+    "version_mismatch",
+    "dying",
+    "flapping",
+    "stabilized",
+    "preferred_announcer",
+    "preferred_unavailable",
+    "last_seen",
+    "failing",
+    "grid_dead",
+    "summary",
+    "self_check",
+    "monitoring_started",
+    "online",
+    "crash_loop",
+    "offline",
+    "digest",
+    "digest_quiet",
+    "digest_incidents",
+    "incident",
+    "incidents",
+    "today",
+    "this_week",
+];
+
+pub const DEFAULT_POLL_INTERVAL: std::time::Duration = std::time::Duration::from_secs(10);
+
 fn default_ip_address() -> String {
     "0.0.0.0".into()
 }
 
+fn default_max_body_size() -> usize {
+    4096
+}
+
+fn default_compression_min_size() -> usize {
+    1024
+}
+
 #[derive(Debug, Deserialize, Clone)]
 #[cfg_attr(feature = "json_schema", derive(schemars::JsonSchema))]
 pub struct SSLConfig {
@@ -32,12 +131,168 @@ pub struct NodeConfig {
     #[serde(default)]
     pub telegram_handle: Option<String>,
     pub address: String,
+    /// Accept this node on peer endpoints by the name it gives instead of its IP, for proxied setups.
+    /// The name is whatever the caller sends, so any host with the key gets in as this node
+    #[serde(default)]
+    pub skip_peer_ip_check: bool,
+    /// Groups the node belongs to, like `home` or `cloud`
+    #[serde(default)]
+    pub tags: Vec<String>,
+    /// Name of the node in announcements and the webui, like `Dad's home server`
+    #[serde(default)]
+    pub display_name: Option<String>,
+    /// CA certificate (PEM) the node's certificate is verified with
+    #[serde(default)]
+    pub ca_path: Option<String>,
+    /// Keep connections to the node open between polls, turn off for dynamic DNS addresses
+    #[serde(default = "default_true")]
+    pub reuse_connections: bool,
+    /// Picks the `escalation` of the node's death
+    #[serde(default)]
+    pub severity: NodeSeverity,
+    /// Version the node should run, like `0.3.1` or `>=0.3, <0.5`, overrides the top level `expected_version`
+    #[serde(default)]
+    pub expected_version: Option<String>,
+    /// Node announcing this node's death whenever it confirms it, like the only one able to power-cycle it
+    #[serde(default)]
+    pub preferred_announcer: Option<String>,
+    /// The node's own endpoint, told about its death and recovery by the node announcing its recovery
+    #[serde(default)]
+    pub notify_url: Option<String>,
+    /// Headers of every call to the node, like a token of a proxy in front of it, over the top level `extra_headers`
+    #[serde(default, skip_serializing)]
+    pub extra_headers: BTreeMap<String, HeaderSource>,
+}
+
+/// Value of an extra header, `${VAR}`s are taken from the environment, a `file` is read on loading the config.
+/// Values may be secrets, they are never logged
+#[derive(Deserialize, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "json_schema", derive(schemars::JsonSchema))]
+#[serde(untagged)]
+pub enum HeaderSource {
+    Value(String),
+    File { file: PathBuf },
+}
+
+impl std::fmt::Debug for HeaderSource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            HeaderSource::Value(_) => write!(f, "<redacted>"),
+            HeaderSource::File { file } => write!(f, "<redacted from {}>", file.display()),
+        }
+    }
+}
+
+impl HeaderSource {
+    fn resolve(&self, env: &impl Fn(&str) -> Option<String>) -> Result<String> {
+        match self {
+            HeaderSource::Value(value) => interpolate(value, env),
+            HeaderSource::File { file } => Ok(std::fs::read_to_string(file)
+                .with_context(|| format!("Failed to read `{}`", file.display()))?
+                .trim_end_matches(['\r', '\n'])
+                .to_string()),
+        }
+    }
+}
+
+/// `value` with every `${VAR}` replaced by the variable, a missing one is an error
+fn interpolate(value: &str, env: &impl Fn(&str) -> Option<String>) -> Result<String> {
+    let mut out = String::new();
+    let mut rest = value;
+    while let Some(start) = rest.find("${") {
+        out.push_str(&rest[..start]);
+        let Some(end) = rest[start..].find('}') else {
+            anyhow::bail!("Unclosed `${{`");
+        };
+        let var = &rest[start + 2..start + end];
+        out.push_str(&env(var).with_context(|| format!("`{var}` is not set"))?);
+        rest = &rest[start + end + 1..];
+    }
+    out.push_str(rest);
+    Ok(out)
+}
+
+#[derive(Debug, Deserialize, Default, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
+#[cfg_attr(feature = "json_schema", derive(schemars::JsonSchema))]
+#[serde(rename_all = "snake_case")]
+pub enum NodeSeverity {
+    Low,
+    #[default]
+    Normal,
+    Critical,
+}
+
+/// Verbosity of this node's log, `debug` adds a line per polled node
+#[derive(Debug, Deserialize, Default, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "json_schema", derive(schemars::JsonSchema))]
+#[serde(rename_all = "snake_case")]
+pub enum LogLevel {
+    Error,
+    Warn,
+    #[default]
+    Info,
+    Debug,
+    Trace,
+}
+
+/// Language of the built-in announcement texts
+#[derive(Debug, Deserialize, Default, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "json_schema", derive(schemars::JsonSchema))]
+#[serde(rename_all = "snake_case")]
+pub enum Language {
+    #[default]
+    En,
+    Hu,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+#[cfg_attr(feature = "json_schema", derive(schemars::JsonSchema))]
+pub struct EscalationConfig {
+    /// How long the node stays dead after its death was announced
+    #[serde(with = "humantime_serde")]
+    #[cfg_attr(feature = "json_schema", schemars(with = "String"))]
+    pub after: std::time::Duration,
+    /// Announced on these too, each needs its config section
+    pub channels: Vec<AnnouncementMode>,
 }
 
 impl NodeConfig {
     pub fn with_name<'a>(&'a self, name: &'a String) -> NamedNodeConfig<'a> {
         NamedNodeConfig { name, config: self }
     }
+
+    /// The `extra_headers` resolved on loading the config, checked to be valid by then
+    pub fn header_map(&self) -> reqwest::header::HeaderMap {
+        self.extra_headers
+            .iter()
+            .filter_map(|(name, source)| {
+                let HeaderSource::Value(value) = source else {
+                    return None;
+                };
+                Some((
+                    reqwest::header::HeaderName::from_bytes(name.as_bytes()).ok()?,
+                    reqwest::header::HeaderValue::from_str(value).ok()?,
+                ))
+            })
+            .collect()
+    }
+
+    /// Resolve the host of the node's address to the IPs it can call us from
+    pub async fn resolve_ips(&self) -> Result<Vec<IpAddr>> {
+        let url = reqwest::Url::parse(&self.address)
+            .with_context(|| format!("Invalid node address `{}`", self.address))?;
+        let host = url
+            .host_str()
+            .with_context(|| format!("Node address `{}` has no host", self.address))?;
+        let port = url.port_or_known_default().unwrap_or(80);
+
+        let ips = tokio::net::lookup_host((host, port))
+            .await
+            .with_context(|| format!("Failed to resolve `{host}`"))?
+            .map(|addr| addr.ip())
+            .collect();
+        Ok(ips)
+    }
 }
 
 pub struct NamedNodeConfig<'a> {
@@ -45,13 +300,145 @@ pub struct NamedNodeConfig<'a> {
     pub config: &'a NodeConfig,
 }
 
-#[derive(Debug, Deserialize, Default, Clone, Copy, Serialize)]
+#[derive(Debug, Deserialize, Default, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
 #[cfg_attr(feature = "json_schema", derive(schemars::JsonSchema))]
 #[serde(rename_all = "snake_case")]
 pub enum AnnouncementMode {
     #[default]
     Telegram,
     Log,
+    /// Run the command of the `exec` config
+    Exec,
+    /// Push to the Gotify server of the `gotify` config
+    Gotify,
+    /// Push through Pushover with the `pushover` config
+    Pushover,
+}
+
+impl AnnouncementMode {
+    /// Name of the channel in the config and the metrics
+    pub fn as_str(self) -> &'static str {
+        match self {
+            AnnouncementMode::Telegram => "telegram",
+            AnnouncementMode::Log => "log",
+            AnnouncementMode::Exec => "exec",
+            AnnouncementMode::Gotify => "gotify",
+            AnnouncementMode::Pushover => "pushover",
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Clone)]
+#[cfg_attr(feature = "json_schema", derive(schemars::JsonSchema))]
+pub struct GotifyConfig {
+    /// Base url of the Gotify server
+    pub url: String,
+    /// Token of the application the messages are posted as
+    pub app_token: String,
+    /// Message priority (0-10) by event, like `dead: 8`
+    #[serde(default = "default_gotify_priorities")]
+    pub priorities: HashMap<String, u8>,
+    /// Priority of the events missing from `priorities`
+    #[serde(default = "default_gotify_priority")]
+    pub default_priority: u8,
+}
+
+fn default_gotify_priorities() -> HashMap<String, u8> {
+    HashMap::from([("dead".to_string(), 8)])
+}
+
+fn default_gotify_priority() -> u8 {
+    5
+}
+
+#[derive(Debug, Deserialize, Clone)]
+#[cfg_attr(feature = "json_schema", derive(schemars::JsonSchema))]
+pub struct PushoverConfig {
+    pub user_key: String,
+    pub app_token: String,
+    /// Only push to this device of the user
+    #[serde(default)]
+    pub device: Option<String>,
+    /// One of Pushover's sounds, the user's default otherwise
+    #[serde(default)]
+    pub sound: Option<String>,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+#[cfg_attr(feature = "json_schema", derive(schemars::JsonSchema))]
+pub struct AnnouncementLimitConfig {
+    /// Announcements sent on the channel within any hour
+    pub max_announcements_per_hour: usize,
+    #[serde(default)]
+    pub overflow: AnnouncementOverflow,
+}
+
+/// What happens to announcements over the limit
+#[derive(Debug, Deserialize, Default, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "json_schema", derive(schemars::JsonSchema))]
+#[serde(rename_all = "snake_case")]
+pub enum AnnouncementOverflow {
+    Drop,
+    /// Sent as the window allows, in order
+    Queue,
+    /// Counted, and a single summary is sent when the window allows
+    #[default]
+    Summarize,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+#[cfg_attr(feature = "json_schema", derive(schemars::JsonSchema))]
+pub struct AnnouncementRetryConfig {
+    /// Tries of a delivery, the first one included
+    #[serde(default = "default_retry_attempts")]
+    pub attempts: u32,
+    /// Wait before the first retry, doubled for every further one
+    #[serde(default = "default_retry_delay")]
+    #[serde(with = "humantime_serde")]
+    #[cfg_attr(feature = "json_schema", schemars(with = "String"))]
+    pub delay: std::time::Duration,
+}
+
+fn default_dead_peer_timeout() -> std::time::Duration {
+    std::time::Duration::from_secs(3)
+}
+
+fn default_announcement_timeout() -> std::time::Duration {
+    std::time::Duration::from_secs(10)
+}
+
+fn default_retry_attempts() -> u32 {
+    3
+}
+
+fn default_retry_delay() -> std::time::Duration {
+    std::time::Duration::from_secs(2)
+}
+
+impl Default for AnnouncementRetryConfig {
+    fn default() -> Self {
+        Self {
+            attempts: default_retry_attempts(),
+            delay: default_retry_delay(),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Clone)]
+#[cfg_attr(feature = "json_schema", derive(schemars::JsonSchema))]
+pub struct ExecConfig {
+    pub command: String,
+    /// Arguments, `{node}`, `{event}`, `{announcer}`, `{timestamp}`, `{message}`, `{tags}` and `{reason}` get replaced
+    #[serde(default)]
+    pub args: Vec<String>,
+    #[serde(default = "default_exec_timeout")]
+    #[serde(with = "humantime_serde")]
+    #[cfg_attr(feature = "json_schema", schemars(with = "String"))]
+    pub timeout: std::time::Duration,
+}
+
+fn default_exec_timeout() -> std::time::Duration {
+    std::time::Duration::from_secs(30)
 }
 
 #[derive(Debug, Deserialize)]
@@ -59,40 +446,1341 @@ pub enum AnnouncementMode {
 pub struct TelegramConfig {
     pub token: String,
     pub chat_id: i64,
+    #[serde(default)]
+    pub bot_commands: Option<BotCommandsConfig>,
+    /// How the token and chat id are checked at startup, reloads always check silently
+    #[serde(default)]
+    pub startup_check: TelegramStartupCheck,
+    /// Whether invalid credentials stop the node from starting, or the reload from taking effect
+    #[serde(default)]
+    pub on_check_failure: CheckFailure,
+}
+
+#[derive(Debug, Deserialize, Default, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "json_schema", derive(schemars::JsonSchema))]
+#[serde(rename_all = "snake_case")]
+pub enum TelegramStartupCheck {
+    /// `getChat` of the chat id, nothing is posted
+    #[default]
+    Chat,
+    /// Posts the `monitoring_started` message to the chat
+    Message,
+}
+
+#[derive(Debug, Deserialize, Default, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "json_schema", derive(schemars::JsonSchema))]
+#[serde(rename_all = "snake_case")]
+pub enum CheckFailure {
+    #[default]
+    Fail,
+    /// Logs an error and goes on, announcements will fail
+    Warn,
+}
+
+#[derive(Debug, Deserialize)]
+#[cfg_attr(feature = "json_schema", derive(schemars::JsonSchema))]
+pub struct BotCommandsConfig {
+    /// The only node of the grid answering bot commands
+    pub node: String,
+    /// Telegram user ids allowed to use the commands
+    #[serde(default)]
+    pub allowed_user_ids: Vec<i64>,
+}
+
+#[derive(Debug, Deserialize, Default, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "json_schema", derive(schemars::JsonSchema))]
+#[serde(rename_all = "snake_case")]
+pub enum DiscoveryRecord {
+    /// Nodes named by the first label of their target
+    #[default]
+    Srv,
+    /// Records of `name=address`
+    Txt,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+#[cfg_attr(feature = "json_schema", derive(schemars::JsonSchema))]
+pub struct DiscoveryConfig {
+    /// Record listing the nodes, like `_freecaster._tcp.example.com`
+    pub name: String,
+    #[serde(default)]
+    pub record: DiscoveryRecord,
+    /// Scheme of the addresses made from SRV records
+    #[serde(default = "default_discovery_scheme")]
+    pub scheme: String,
+    #[serde(default = "default_discovery_interval")]
+    #[serde(with = "humantime_serde")]
+    #[cfg_attr(feature = "json_schema", schemars(with = "String"))]
+    pub interval: std::time::Duration,
+    #[serde(default = "default_true")]
+    pub reuse_connections: bool,
+}
+
+fn default_discovery_scheme() -> String {
+    "https".into()
+}
+
+fn default_discovery_interval() -> std::time::Duration {
+    std::time::Duration::from_secs(5 * 60)
+}
+
+#[derive(Debug, Deserialize, Default, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "json_schema", derive(schemars::JsonSchema))]
+#[serde(rename_all = "snake_case")]
+pub enum DigestScope {
+    #[default]
+    Daily,
+    Weekly,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+#[cfg_attr(feature = "json_schema", derive(schemars::JsonSchema))]
+pub struct DigestConfig {
+    #[serde(default)]
+    pub scope: DigestScope,
+    /// Hour of the day (UTC) the digest is sent at
+    #[serde(default = "default_digest_hour")]
+    pub hour: u32,
+    /// Day the weekly digest is sent on, like `mon` or `friday`
+    #[serde(default = "default_digest_weekday")]
+    #[cfg_attr(feature = "json_schema", schemars(with = "String"))]
+    pub weekday: chrono::Weekday,
+}
+
+/// Nodes dying and recovering often are announced as flapping, instead of every death and recovery
+#[derive(Debug, Deserialize, Clone)]
+#[cfg_attr(feature = "json_schema", derive(schemars::JsonSchema))]
+pub struct FlappingConfig {
+    /// Deaths and recoveries within `window` that make a node flapping
+    #[serde(default = "default_flapping_transitions")]
+    pub transitions: usize,
+    /// A node stops flapping once it neither died nor recovered for this long
+    #[serde(default = "default_flapping_window")]
+    #[serde(with = "humantime_serde")]
+    #[cfg_attr(feature = "json_schema", schemars(with = "String"))]
+    pub window: std::time::Duration,
+}
+
+/// The views of the peers compared with this node's, where they differ is logged
+#[derive(Debug, Deserialize, Clone)]
+#[cfg_attr(feature = "json_schema", derive(schemars::JsonSchema))]
+pub struct CrossCheckConfig {
+    #[serde(default = "default_cross_check_interval")]
+    #[serde(with = "humantime_serde")]
+    #[cfg_attr(feature = "json_schema", schemars(with = "String"))]
+    pub interval: std::time::Duration,
+}
+
+fn default_cross_check_interval() -> std::time::Duration {
+    std::time::Duration::from_secs(5 * 60)
+}
+
+#[derive(Debug, Deserialize, Clone)]
+#[cfg_attr(feature = "json_schema", derive(schemars::JsonSchema))]
+pub struct StateFileConfig {
+    pub path: PathBuf,
+    /// Announced deaths older than this are dropped, a node rebuilt since is announced as usual
+    #[serde(default = "default_state_file_max_age")]
+    #[serde(with = "humantime_serde")]
+    #[cfg_attr(feature = "json_schema", schemars(with = "String"))]
+    pub max_age: std::time::Duration,
+}
+
+fn default_max_restarts_per_hour() -> usize {
+    3
+}
+
+fn default_state_file_max_age() -> std::time::Duration {
+    std::time::Duration::from_secs(7 * 24 * 60 * 60)
+}
+
+fn default_dying_after() -> usize {
+    1
+}
+
+fn default_dead_after() -> usize {
+    3
+}
+
+fn default_flapping_transitions() -> usize {
+    6
+}
+
+fn default_flapping_window() -> std::time::Duration {
+    std::time::Duration::from_secs(2 * 60 * 60)
+}
+
+fn default_digest_hour() -> u32 {
+    9
+}
+
+fn default_digest_weekday() -> chrono::Weekday {
+    chrono::Weekday::Mon
+}
+
+#[derive(Debug, Deserialize, Clone)]
+#[cfg_attr(feature = "json_schema", derive(schemars::JsonSchema))]
+pub struct HttpClientConfig {
+    /// Idle connections kept open per node
+    #[serde(default)]
+    pub pool_max_idle_per_host: Option<usize>,
+    /// Close idle connections after this long
+    #[serde(default)]
+    #[serde(with = "humantime_serde")]
+    #[cfg_attr(feature = "json_schema", schemars(with = "Option<String>"))]
+    pub pool_idle_timeout: Option<std::time::Duration>,
+    /// Limit on connecting alone, within `request_timeout`
+    #[serde(default)]
+    #[serde(with = "humantime_serde")]
+    #[cfg_attr(feature = "json_schema", schemars(with = "Option<String>"))]
+    pub connect_timeout: Option<std::time::Duration>,
+    /// Limit on a whole call to a node
+    #[serde(default = "default_request_timeout")]
+    #[serde(with = "humantime_serde")]
+    #[cfg_attr(feature = "json_schema", schemars(with = "String"))]
+    pub request_timeout: std::time::Duration,
+    /// Interval of TCP keepalive probes on connections to nodes
+    #[serde(default)]
+    #[serde(with = "humantime_serde")]
+    #[cfg_attr(feature = "json_schema", schemars(with = "Option<String>"))]
+    pub tcp_keepalive: Option<std::time::Duration>,
+    /// Calls made at once when asking every node, like obituaries and broadcasts
+    #[serde(default = "default_max_concurrent_calls")]
+    pub max_concurrent_calls: usize,
+}
+
+fn default_request_timeout() -> std::time::Duration {
+    std::time::Duration::from_secs(5)
+}
+
+fn default_max_concurrent_calls() -> usize {
+    16
+}
+
+impl Default for HttpClientConfig {
+    fn default() -> Self {
+        Self {
+            pool_max_idle_per_host: None,
+            pool_idle_timeout: None,
+            connect_timeout: None,
+            request_timeout: default_request_timeout(),
+            tcp_keepalive: None,
+            max_concurrent_calls: default_max_concurrent_calls(),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Clone)]
+#[cfg_attr(feature = "json_schema", derive(schemars::JsonSchema))]
+pub struct ProxyConfig {
+    /// Like `http://proxy.local:3128`
+    pub url: String,
+    #[serde(default)]
+    pub username: Option<String>,
+    #[serde(default)]
+    pub password: Option<String>,
+    /// Hosts reached directly, besides the nodes
+    #[serde(default)]
+    pub no_proxy: Vec<String>,
+    /// Reach the nodes through the proxy as well
+    #[serde(default)]
+    pub proxy_peers: bool,
+}
+
+#[derive(Debug, Deserialize, Clone, PartialEq)]
+#[cfg_attr(feature = "json_schema", derive(schemars::JsonSchema))]
+pub struct MqttConfig {
+    /// Like `mqtt://broker.local:1883`, or `mqtts://broker.local:8883` for TLS
+    pub url: String,
+    #[serde(default)]
+    pub username: Option<String>,
+    #[serde(default)]
+    pub password: Option<String>,
+    /// CA certificates (PEM bundle) of the broker with `mqtts`, the public roots without it
+    #[serde(default)]
+    pub ca_path: Option<String>,
+    /// States and availability go under `<base_topic>/<name>`
+    #[serde(default = "default_mqtt_base_topic")]
+    pub base_topic: String,
+    /// Home Assistant's discovery prefix
+    #[serde(default = "default_mqtt_discovery_prefix")]
+    pub discovery_prefix: String,
+}
+
+fn default_mqtt_base_topic() -> String {
+    "freecaster_grid".to_string()
+}
+
+fn default_mqtt_discovery_prefix() -> String {
+    "homeassistant".to_string()
+}
+
+#[derive(Debug, Deserialize, Clone)]
+#[cfg_attr(feature = "json_schema", derive(schemars::JsonSchema))]
+pub struct AuditConfig {
+    /// How many entries are kept in memory and served by `/audit`
+    #[serde(default = "default_audit_capacity")]
+    pub capacity: usize,
+    /// Entries are also appended here as json lines, if set
+    #[serde(default)]
+    pub file_path: Option<String>,
+}
+
+fn default_audit_capacity() -> usize {
+    1000
+}
+
+impl Default for AuditConfig {
+    fn default() -> Self {
+        Self {
+            capacity: default_audit_capacity(),
+            file_path: None,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Clone)]
+#[cfg_attr(feature = "json_schema", derive(schemars::JsonSchema))]
+pub struct RateLimitConfig {
+    #[serde(default = "default_true")]
+    pub enabled: bool,
+    /// Requests a client can make in a burst
+    #[serde(default = "default_rate_limit_burst")]
+    pub burst: u32,
+    /// Sustained requests per second a client can make
+    #[serde(default = "default_rate_limit_per_second")]
+    pub per_second: f64,
+}
+
+fn default_stale_cycle_factor() -> u32 {
+    3
+}
+
+fn default_max_silence_duration() -> std::time::Duration {
+    std::time::Duration::from_secs(30 * 24 * 60 * 60)
+}
+
+fn default_cert_expiry_warning() -> std::time::Duration {
+    std::time::Duration::from_secs(14 * 24 * 60 * 60)
+}
+
+fn default_disk_usage_warning() -> f64 {
+    90.0
+}
+
+fn default_internet_check_url() -> String {
+    "http://clients3.google.com/generate_204".to_string()
+}
+
+fn default_true() -> bool {
+    true
+}
+
+fn default_rate_limit_burst() -> u32 {
+    30
+}
+
+fn default_rate_limit_per_second() -> f64 {
+    2.0
+}
+
+impl Default for RateLimitConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            burst: default_rate_limit_burst(),
+            per_second: default_rate_limit_per_second(),
+        }
+    }
 }
 
 #[derive(Debug, Deserialize)]
 #[cfg_attr(feature = "json_schema", derive(schemars::JsonSchema))]
 pub struct Config {
+    /// Further files merged into this one, relative to it, later files win
+    #[serde(default)]
+    pub include: Vec<PathBuf>,
+    /// File setting each top level key, `nodes.<name>` for nodes, filled when files are included
+    #[serde(skip)]
+    pub origins: HashMap<String, PathBuf>,
+    /// File the config was read from, `None` when only from the environment
+    #[serde(skip)]
+    pub source: Option<PathBuf>,
+    /// Of the config files' contents, tells apart the configs nodes run
+    #[serde(skip)]
+    pub hash: Option<String>,
+    #[serde(skip)]
+    pub loaded_at: chrono::DateTime<chrono::Utc>,
+    /// Set by `--skip-announce-check`, the announcement credentials aren't checked, for offline testing
+    #[serde(skip)]
+    pub skip_announce_check: bool,
+    /// Warn once the config was last loaded longer ago, for grids reloading it regularly
+    #[serde(default)]
+    #[serde(with = "humantime_serde")]
+    #[cfg_attr(feature = "json_schema", schemars(with = "Option<String>"))]
+    pub config_stale_after: Option<std::time::Duration>,
     pub name: String,
+    /// Tags of this node, its own entry in `nodes` adds to these
+    #[serde(default)]
+    pub tags: Vec<String>,
+    /// Name of this node in announcements and the webui, its own entry in `nodes` may give it too
+    #[serde(default)]
+    pub display_name: Option<String>,
     #[serde(default)]
     pub telegram: Option<TelegramConfig>,
+    #[serde(default)]
+    pub exec: Option<ExecConfig>,
+    #[serde(default)]
+    pub gotify: Option<GotifyConfig>,
+    #[serde(default)]
+    pub pushover: Option<PushoverConfig>,
+    /// Language of the announcements
+    #[serde(default)]
+    pub language: Language,
+    /// Verbosity of the log, also settable with `FC_LOG_LEVEL`
+    #[serde(default)]
+    pub log_level: LogLevel,
+    /// Announcement texts by message key, used instead of the built-in ones in any language
+    #[serde(default)]
+    pub announcement_templates: HashMap<String, String>,
+    /// Retrying of failed announcement deliveries
+    #[serde(default)]
+    pub announcement_retry: AnnouncementRetryConfig,
+    /// Wait for an answer of the announcement channel, per attempt. Timed out deliveries are tried again next poll cycle
+    #[serde(default = "default_announcement_timeout")]
+    #[serde(with = "humantime_serde")]
+    #[cfg_attr(feature = "json_schema", schemars(with = "String"))]
+    pub announcement_timeout: std::time::Duration,
+    /// Limits of announcements per channel
+    #[serde(default)]
+    pub announcement_limits: HashMap<AnnouncementMode, AnnouncementLimitConfig>,
+    /// Louder announcements of deaths lasting long, by node severity
+    #[serde(default)]
+    pub escalation: HashMap<NodeSeverity, EscalationConfig>,
     pub secret_key: String,
+    /// Accepted by `/grid`, `/history` and the webui, but nothing silencing, muting or asking peers,
+    /// for a dashboard link without the secret key
+    #[serde(default)]
+    pub readonly_key: Option<String>,
     #[serde(default)]
     #[serde(with = "humantime_serde")]
     #[cfg_attr(feature = "json_schema", schemars(with = "String"))]
     pub poll_time: Option<std::time::Duration>,
 
+    /// This node reports itself dying when its last poll cycle is older than this many poll times
+    #[serde(default = "default_stale_cycle_factor")]
+    pub stale_cycle_factor: u32,
+
     #[serde(default)]
     pub announcement_mode: AnnouncementMode,
 
     pub server: ServerConfig,
 
+    /// Address peers should reach this node at, like `https://hal9000.example.com:4242`, when it isn't the bind address
+    #[serde(default)]
+    pub advertised_address: Option<String>,
+
     #[serde(default)]
     pub nodes: HashMap<String, NodeConfig>,
 
+    /// CA certificates (PEM bundle) trusted for every node
+    #[serde(default)]
+    pub ca_bundle_path: Option<String>,
+
+    /// Nodes found in DNS, in addition to `nodes`
+    #[serde(default)]
+    pub discovery: Option<DiscoveryConfig>,
+
+    /// Proxy for outgoing calls, the internet check and announcements
+    #[serde(default)]
+    pub proxy: Option<ProxyConfig>,
+
+    /// Polls are skipped while this doesn't answer `204 No Content`, like on a grid without internet access
+    #[serde(default = "default_internet_check_url")]
+    pub internet_check_url: String,
+
+    /// Tuning of the client calling the other nodes
+    #[serde(default)]
+    pub http_client: HttpClientConfig,
+
     #[serde(default)]
     pub webui_enabled: bool,
+
+    #[serde(default)]
+    pub audit: AuditConfig,
+
+    /// Headers of every call to the nodes, a node's own `extra_headers` win over these
+    #[serde(default)]
+    pub extra_headers: BTreeMap<String, HeaderSource>,
+
+    /// Announced deaths are kept here across restarts, forgotten on a restart without it
+    #[serde(default)]
+    pub state_file: Option<StateFileConfig>,
+
+    /// Periodic summary of the grid, sent by a single node
+    #[serde(default)]
+    pub digest: Option<DigestConfig>,
+
+    /// Flap detection, off without it
+    #[serde(default)]
+    pub flapping: Option<FlappingConfig>,
+
+    /// Peers asked for their `/introspect` on an interval, off without it
+    #[serde(default)]
+    pub cross_check: Option<CrossCheckConfig>,
+
+    /// Nodes published to an MQTT broker for Home Assistant's discovery
+    #[serde(default)]
+    pub mqtt: Option<MqttConfig>,
+
+    #[serde(default)]
+    pub rate_limit: RateLimitConfig,
+
+    /// Longest silence this node accepts, locally or from a broadcast
+    #[serde(default = "default_max_silence_duration")]
+    #[serde(with = "humantime_serde")]
+    #[cfg_attr(feature = "json_schema", schemars(with = "String"))]
+    pub max_silence_duration: std::time::Duration,
+
+    /// Warn about certificates of the grid expiring within this
+    #[serde(default = "default_cert_expiry_warning")]
+    #[serde(with = "humantime_serde")]
+    #[cfg_attr(feature = "json_schema", schemars(with = "String"))]
+    pub cert_expiry_warning: std::time::Duration,
+
+    /// Report load, memory, disk and uptime of this host in the status endpoint
+    #[serde(default)]
+    pub report_system_metrics: bool,
+
+    /// Warn when a node's root filesystem is fuller than this percentage
+    #[serde(default = "default_disk_usage_warning")]
+    pub disk_usage_warning: f64,
+
+    /// Announce a node still dead when its silence ends, if its death wasn't announced before the silence
+    #[serde(default = "default_true")]
+    pub announce_after_silence: bool,
+
+    /// Remind that a node is still dead when its silence ends, if its death was announced before
+    #[serde(default)]
+    pub remind_after_silence: bool,
+
+    /// Announce nodes the first time they are reachable
+    #[serde(default)]
+    pub announce_new_nodes: bool,
+
+    /// Announce this node starting and shutting down gracefully
+    #[serde(default)]
+    pub announce_lifecycle: bool,
+
+    /// Restarts within an hour after which starts are no longer announced, counted in the `state_file`
+    #[serde(default = "default_max_restarts_per_hour")]
+    pub max_restarts_per_hour: usize,
+
+    /// Only accept calls to peer endpoints (obituary, silence broadcast) from the configured nodes
+    #[serde(default)]
+    pub restrict_peer_endpoints: bool,
+
+    /// Refuse silence broadcasts of a node calling from another address than its own
+    #[serde(default)]
+    pub verify_broadcast_source: bool,
+
+    /// Hold back death announcements while fewer peers than this answer polls
+    #[serde(default)]
+    pub min_peers_reachable: usize,
+
+    /// Announce deaths confirmed during a mute once it's over, if the node is still dead
+    #[serde(default = "default_true")]
+    pub announce_after_mute: bool,
+
+    /// Count a node reporting another name than configured as a failing poll
+    #[serde(default)]
+    pub strict_name_check: bool,
+
+    /// Failed polls in a row after which a node is dying
+    #[serde(default = "default_dying_after")]
+    pub dying_after: usize,
+
+    /// Failed polls in a row after which a node is dead, and its death goes to the quorum
+    #[serde(default = "default_dead_after")]
+    pub dead_after: usize,
+
+    /// Peers failing to poll a node for at least half of their `dead_after` confirm its death too
+    #[serde(default)]
+    pub count_dying_as_confirmation: bool,
+
+    /// Call the obituary of peers this node finds dead too, only their status may be broken
+    #[serde(default = "default_true")]
+    pub ask_dead_peers: bool,
+    /// Obituary calls to dead peers give up sooner, so they don't hold up the cycle
+    #[serde(default = "default_dead_peer_timeout")]
+    #[serde(with = "humantime_serde")]
+    #[cfg_attr(feature = "json_schema", schemars(with = "String"))]
+    pub dead_peer_timeout: std::time::Duration,
+
+    /// Announce nodes becoming dying, before they are dead
+    #[serde(default)]
+    pub announce_dying: bool,
+
+    /// `/grid` shows the statuses of older versions: `dead` only once announced, `dying` for dead but unannounced
+    #[serde(default)]
+    pub legacy_grid_status: bool,
+
+    /// Version every node should run, like `0.3.1` or `^0.3`, this one included
+    #[serde(default)]
+    pub expected_version: Option<String>,
+
+    /// Announce a node running another version than expected, once until it runs an expected one again
+    #[serde(default)]
+    pub announce_version_mismatch: bool,
+}
+
+impl Config {
+    /// Checks of the announcement channels, so mistakes show on startup rather than at the first death
+    pub fn validate(&self) -> Result<()> {
+        if let Some(readonly_key) = self.readonly_key.as_ref() {
+            let origin = self.origin("readonly_key");
+            if readonly_key.is_empty() {
+                anyhow::bail!("`readonly_key` is empty{origin}");
+            }
+            if *readonly_key == self.secret_key {
+                anyhow::bail!("`readonly_key` is the `secret_key`{origin}");
+            }
+        }
+
+        match self.announcement_mode {
+            AnnouncementMode::Gotify if self.gotify.is_none() => {
+                anyhow::bail!(
+                    "`announcement_mode: gotify` needs a `gotify` config{}",
+                    self.origin("announcement_mode")
+                )
+            }
+            AnnouncementMode::Pushover if self.pushover.is_none() => {
+                anyhow::bail!(
+                    "`announcement_mode: pushover` needs a `pushover` config{}",
+                    self.origin("announcement_mode")
+                )
+            }
+            _ => {}
+        }
+
+        if let Some(gotify) = self.gotify.as_ref() {
+            let origin = self.origin("gotify");
+            let url = reqwest::Url::parse(&gotify.url)
+                .with_context(|| format!("Invalid gotify url `{}`{origin}", gotify.url))?;
+            if !["http", "https"].contains(&url.scheme()) {
+                anyhow::bail!("Gotify url `{}` is not http(s){origin}", gotify.url);
+            }
+            if gotify.app_token.is_empty() {
+                anyhow::bail!("Gotify `app_token` is empty{origin}");
+            }
+            for (event, priority) in gotify
+                .priorities
+                .iter()
+                .map(|(event, priority)| (event.as_str(), *priority))
+                .chain([("default_priority", gotify.default_priority)])
+            {
+                if event != "default_priority" && !ANNOUNCEMENT_EVENTS.contains(&event) {
+                    anyhow::bail!(
+                        "Unknown gotify priority event `{event}`{origin}, expected one of {ANNOUNCEMENT_EVENTS:?}"
+                    );
+                }
+                if priority > 10 {
+                    anyhow::bail!("Gotify priority of `{event}` is {priority}, at most 10{origin}");
+                }
+            }
+        }
+
+        if let Some(pushover) = self.pushover.as_ref() {
+            // both are 30 character alphanumeric keys
+            for (field, key) in [
+                ("user_key", &pushover.user_key),
+                ("app_token", &pushover.app_token),
+            ] {
+                if key.len() != 30 || !key.chars().all(|c| c.is_ascii_alphanumeric()) {
+                    anyhow::bail!(
+                        "Pushover `{field}` must be 30 letters and digits{}",
+                        self.origin("pushover")
+                    );
+                }
+            }
+        }
+
+        for (field, cidrs) in [
+            ("silence_allowed_cidrs", &self.server.silence_allowed_cidrs),
+            ("trusted_proxies", &self.server.trusted_proxies),
+        ] {
+            for cidr in cidrs {
+                if let Err(err) = crate::client_ip::parse_cidr(cidr) {
+                    anyhow::bail!("{err} in `server.{field}`{}", self.origin("server"));
+                }
+            }
+        }
+
+        if let Some(address) = self.advertised_address.as_ref() {
+            let origin = self.origin("advertised_address");
+            let url = reqwest::Url::parse(address)
+                .with_context(|| format!("Invalid advertised address `{address}`{origin}"))?;
+            if !["http", "https"].contains(&url.scheme()) {
+                anyhow::bail!("Advertised address `{address}` is not http(s){origin}");
+            }
+        }
+
+        if self.dying_after == 0 || self.dying_after > self.dead_after {
+            anyhow::bail!(
+                "`dying_after` has to be between 1 and `dead_after` ({}){}",
+                self.dead_after,
+                self.origin("dying_after")
+            );
+        }
+
+        if let Some(flapping) = self.flapping.as_ref()
+            && flapping.transitions < 2
+        {
+            anyhow::bail!(
+                "`flapping.transitions` has to be at least 2{}",
+                self.origin("flapping")
+            );
+        }
+
+        let mut names: HashMap<String, BTreeSet<&str>> = HashMap::new();
+        for name in self.nodes.keys().chain([&self.name]) {
+            names.entry(node_name_key(name)).or_default().insert(name);
+        }
+        if let Some(same) = names.into_values().find(|names| names.len() > 1) {
+            anyhow::bail!(
+                "Nodes {same:?} only differ in case, peers couldn't tell them apart{}",
+                self.origin("nodes")
+            );
+        }
+
+        let mut display_names: HashMap<&str, Vec<&str>> = HashMap::new();
+        for (name, display_name) in self.display_names() {
+            if display_name.trim().is_empty() {
+                anyhow::bail!(
+                    "Empty `display_name` of `{name}`{}",
+                    self.origin(&format!("nodes.{name}"))
+                );
+            }
+            display_names.entry(display_name).or_default().push(name);
+        }
+        for (display_name, mut names) in display_names {
+            names.sort();
+            if names.len() > 1 {
+                warn!(
+                    "Nodes {names:?} share the display name `{display_name}`, silences can't target them by it"
+                );
+            } else if display_name != names[0]
+                && (display_name == self.name || self.nodes.contains_key(display_name))
+            {
+                warn!(
+                    "The display name of `{}` is the name of node `{display_name}`, silences can't target either by it",
+                    names[0]
+                );
+            }
+        }
+
+        for key in self.announcement_templates.keys() {
+            if !MESSAGE_KEYS.contains(&key.as_str()) {
+                anyhow::bail!(
+                    "Unknown announcement template `{key}`{}, expected one of {MESSAGE_KEYS:?}",
+                    self.origin("announcement_templates")
+                );
+            }
+        }
+
+        let own = self.own_addresses();
+        for (name, node) in self.nodes.iter() {
+            if *name == self.name
+                || normalized_address(&node.address).is_some_and(|address| own.contains(&address))
+            {
+                anyhow::bail!(
+                    "Node `{name}` at `{}` is this node{}",
+                    node.address,
+                    self.origin(&format!("nodes.{name}"))
+                );
+            }
+        }
+
+        let requirements = self
+            .nodes
+            .iter()
+            .filter_map(|(name, node)| {
+                Some((format!("nodes.{name}"), node.expected_version.as_ref()?))
+            })
+            .chain(
+                self.expected_version
+                    .iter()
+                    .map(|req| ("expected_version".to_string(), req)),
+            );
+        for (key, req) in requirements {
+            VersionReq::parse(req).with_context(|| {
+                format!("Invalid `expected_version` of `{key}`{}", self.origin(&key))
+            })?;
+        }
+
+        for (name, node) in self.nodes.iter() {
+            let Some(preferred) = node.preferred_announcer.as_ref() else {
+                continue;
+            };
+            if preferred == name {
+                anyhow::bail!(
+                    "Node `{name}` can't announce its own death{}",
+                    self.origin(&format!("nodes.{name}"))
+                );
+            }
+            if *preferred != self.name && !self.nodes.contains_key(preferred) {
+                warn!(
+                    "`preferred_announcer` `{preferred}` of `{name}` is not a node of the grid, its death is announced by roll"
+                );
+            }
+        }
+
+        for (mode, limit) in self.announcement_limits.iter() {
+            if limit.max_announcements_per_hour == 0 {
+                anyhow::bail!(
+                    "`max_announcements_per_hour` of {mode:?} must be at least 1{}",
+                    self.origin("announcement_limits")
+                );
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Trims the names of this node, the nodes and their `preferred_announcer`, names becoming the same are rejected
+    pub fn trim_node_names(&mut self) -> Result<()> {
+        self.name = self.name.trim().to_string();
+        let mut nodes = HashMap::new();
+        for (name, mut node) in std::mem::take(&mut self.nodes) {
+            if let Some(preferred) = node.preferred_announcer.as_mut() {
+                *preferred = preferred.trim().to_string();
+            }
+            let trimmed = name.trim().to_string();
+            if trimmed.is_empty() {
+                anyhow::bail!("Empty node name{}", self.origin(&format!("nodes.{name}")));
+            }
+            if nodes.contains_key(&trimmed) {
+                anyhow::bail!(
+                    "Node `{name}` is listed twice, with and without whitespace around its name{}",
+                    self.origin(&format!("nodes.{name}"))
+                );
+            }
+            nodes.insert(trimmed, node);
+        }
+        self.nodes = nodes;
+        Ok(())
+    }
+
+    /// ` in <file>` when `key` came from one of several config files
+    fn origin(&self, key: &str) -> String {
+        self.origins
+            .get(key)
+            .map(|path| format!(" in `{}`", path.display()))
+            .unwrap_or_default()
+    }
+
+    /// Loaded longer ago than `config_stale_after`
+    pub fn is_stale(&self, now: chrono::DateTime<chrono::Utc>) -> bool {
+        self.config_stale_after.is_some_and(|stale_after| {
+            (now - self.loaded_at).to_std().unwrap_or_default() > stale_after
+        })
+    }
+
+    /// Reads the values of the extra headers, and gives every node the top level ones it doesn't set itself
+    pub fn resolve_headers(&mut self, env: impl Fn(&str) -> Option<String>) -> Result<()> {
+        let resolve = |headers: &mut BTreeMap<String, HeaderSource>, of: &str| -> Result<()> {
+            for (name, source) in headers.iter_mut() {
+                let value = source
+                    .resolve(&env)
+                    .with_context(|| format!("Invalid value of header `{name}` of {of}"))?;
+                reqwest::header::HeaderName::from_bytes(name.as_bytes())
+                    .with_context(|| format!("Invalid header name `{name}` of {of}"))?;
+                reqwest::header::HeaderValue::from_str(&value)
+                    .with_context(|| format!("Invalid value of header `{name}` of {of}"))?;
+                *source = HeaderSource::Value(value);
+            }
+            Ok(())
+        };
+        resolve(&mut self.extra_headers, "`extra_headers`")?;
+        for (node_name, node) in self.nodes.iter_mut() {
+            resolve(&mut node.extra_headers, &format!("node `{node_name}`"))?;
+            for (name, source) in self.extra_headers.iter() {
+                if !node
+                    .extra_headers
+                    .keys()
+                    .any(|own| own.eq_ignore_ascii_case(name))
+                {
+                    node.extra_headers.insert(name.clone(), source.clone());
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// This node's own entry of `nodes` is left out, keeping its tags, found by its name or address
+    pub fn without_self(mut self) -> Self {
+        let own = self.own_addresses();
+        let mine = self
+            .nodes
+            .iter()
+            .filter(|(name, node)| {
+                **name == self.name
+                    || normalized_address(&node.address)
+                        .is_some_and(|address| own.contains(&address))
+            })
+            .map(|(name, _)| name.clone())
+            .collect::<Vec<_>>();
+        for name in mine {
+            let Some(me) = self.nodes.remove(&name) else {
+                continue;
+            };
+            if name != self.name {
+                warn!(
+                    "Node `{name}` at `{}` is this node's address, but this node is named `{}`, it's left out as this node",
+                    me.address, self.name
+                );
+            }
+            self.tags.extend(me.tags);
+            if self.display_name.is_none() {
+                self.display_name = me.display_name;
+            }
+        }
+
+        // another instance on the same machine, or a mistyped port
+        let own_hosts = own
+            .iter()
+            .filter(|(host, _)| !LOOPBACK_HOSTS.contains(&host.as_str()))
+            .map(|(host, _)| host)
+            .collect::<HashSet<_>>();
+        for (name, node) in self.nodes.iter() {
+            if let Some((host, _)) = normalized_address(&node.address)
+                && own_hosts.contains(&host)
+            {
+                warn!(
+                    "Node `{name}` at `{}` is on this node's host, check whether it's this node",
+                    node.address
+                );
+            }
+        }
+        self
+    }
+
+    /// (host, port) pairs this node is reached at: its advertised address, its bind address,
+    /// or the loopback addresses when bound to every interface
+    fn own_addresses(&self) -> Vec<(String, u16)> {
+        let mut own = vec![];
+        if let Some(address) = self
+            .advertised_address
+            .as_deref()
+            .and_then(normalized_address)
+        {
+            own.push(address);
+        }
+        match self.server.ip_address.as_str() {
+            "0.0.0.0" | "::" | "[::]" => own.extend(
+                LOOPBACK_HOSTS
+                    .iter()
+                    .map(|host| (host.to_string(), self.server.port)),
+            ),
+            ip if ip.contains(':') && !ip.starts_with('[') => {
+                own.push((format!("[{ip}]"), self.server.port))
+            }
+            ip => own.push((ip.to_lowercase(), self.server.port)),
+        }
+        own
+    }
+
+    pub fn poll_interval(&self) -> std::time::Duration {
+        self.poll_time.unwrap_or(DEFAULT_POLL_INTERVAL)
+    }
+
+    /// How old the last poll cycle can get before this node counts as stuck
+    pub fn stale_after(&self) -> chrono::Duration {
+        chrono::Duration::from_std(self.poll_interval() * self.stale_cycle_factor)
+            .unwrap_or(chrono::Duration::MAX)
+    }
+
+    /// The CA certificates of `ca_bundle_path` and every node's `ca_path`
+    pub async fn load_ca_certificates(&self) -> Result<Vec<reqwest::Certificate>> {
+        let mut sources = vec![];
+        if let Some(path) = self.ca_bundle_path.as_ref() {
+            sources.push(("ca_bundle_path".to_string(), path));
+        }
+        for (name, node) in self.nodes.iter() {
+            if let Some(path) = node.ca_path.as_ref() {
+                sources.push((format!("ca_path of node `{name}`"), path));
+            }
+        }
+
+        let mut certificates = vec![];
+        for (source, path) in sources {
+            let pem = tokio::fs::read(path)
+                .await
+                .with_context(|| format!("Failed to read {source} from `{path}`"))?;
+            let bundle = reqwest::Certificate::from_pem_bundle(&pem)
+                .with_context(|| format!("Invalid certificate in {source} `{path}`"))?;
+            if bundle.is_empty() {
+                anyhow::bail!("No certificate in {source} `{path}`");
+            }
+            certificates.extend(bundle);
+        }
+        Ok(certificates)
+    }
+
+    /// Version `name` should run, its own requirement or the grid's
+    pub fn expected_version(&self, name: &str) -> Option<&str> {
+        self.nodes
+            .get(name)
+            .and_then(|node| node.expected_version.as_deref())
+            .or(self.expected_version.as_deref())
+    }
+
+    /// `version` doesn't satisfy what `name` should run, `false` without a requirement
+    pub fn version_mismatch(&self, name: &str, version: &str) -> bool {
+        self.expected_version(name)
+            .and_then(|req| VersionReq::parse(req).ok())
+            .is_some_and(|req| !req.matches(version))
+    }
+
+    /// Announcements go to Telegram, on the main channel or an escalation
+    pub fn announces_to_telegram(&self) -> bool {
+        std::iter::once(&self.announcement_mode)
+            .chain(
+                self.escalation
+                    .values()
+                    .flat_map(|escalation| &escalation.channels),
+            )
+            .any(|mode| *mode == AnnouncementMode::Telegram)
+    }
+
+    /// How `name` is called in announcements and the webui, its `display_name` or else its name
+    pub fn display_name<'a>(&'a self, name: &'a str) -> &'a str {
+        self.configured_display_name(name).unwrap_or(name)
+    }
+
+    /// The `display_name` of `name` if it has one, nodes found by discovery included
+    pub fn node_display_name<'a>(
+        &'a self,
+        name: &str,
+        discovered: &'a HashMap<String, NodeConfig>,
+    ) -> Option<&'a str> {
+        self.configured_display_name(name)
+            .or_else(|| discovered.get(name)?.display_name.as_deref())
+    }
+
+    fn configured_display_name(&self, name: &str) -> Option<&str> {
+        if name == self.name {
+            self.display_name.as_deref()
+        } else {
+            self.nodes
+                .get(name)
+                .and_then(|node| node.display_name.as_deref())
+        }
+    }
+
+    /// (name, display name) of the nodes with a `display_name`, this one included
+    fn display_names(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.display_name
+            .as_deref()
+            .map(|display_name| (self.name.as_str(), display_name))
+            .into_iter()
+            .chain(
+                self.nodes.iter().filter_map(|(name, node)| {
+                    Some((name.as_str(), node.display_name.as_deref()?))
+                }),
+            )
+    }
+
+    /// The name of the node `target` means, by its name or display name,
+    /// `Err` with the candidates when it means several; unknown targets are returned as they are
+    pub fn resolve_target(&self, target: &str) -> Result<String, Vec<String>> {
+        let mut names = self
+            .display_names()
+            .filter(|(_, display_name)| *display_name == target)
+            .map(|(name, _)| name.to_string())
+            .collect::<Vec<_>>();
+        if target == self.name || self.nodes.contains_key(target) {
+            names.push(target.to_string());
+        }
+        names.sort();
+        names.dedup();
+        match names.len() {
+            0 => Ok(target.to_string()),
+            1 => Ok(names.remove(0)),
+            _ => Err(names),
+        }
+    }
+
+    /// Tags of any node of the grid, this one included
+    pub fn node_tags(&self, name: &str) -> &[String] {
+        if name == self.name {
+            return &self.tags;
+        }
+        self.nodes
+            .get(name)
+            .map(|node| node.tags.as_slice())
+            .unwrap_or_default()
+    }
+
+    /// Nodes a silence target means, `tag:<tag>` is every node with that tag
+    pub fn expand_target(&self, target: &str) -> Vec<String> {
+        let Some(tag) = target.strip_prefix("tag:") else {
+            return vec![target.to_string()];
+        };
+        let mut names = self
+            .nodes
+            .iter()
+            .filter(|(_, node)| node.tags.iter().any(|t| t == tag))
+            .map(|(name, _)| name.clone())
+            .collect::<Vec<_>>();
+        if self.tags.iter().any(|t| t == tag) {
+            names.push(self.name.clone());
+        }
+        names.sort();
+        names
+    }
+}
+
+const LOOPBACK_HOSTS: &[&str] = &["localhost", "127.0.0.1", "[::1]"];
+
+/// Node names differing only in case or surrounding whitespace are the same node
+pub fn node_name_key(name: &str) -> String {
+    name.trim().to_lowercase()
+}
+
+/// `name` as it's spelled among `known`, only trimmed when it's none of them
+pub fn canonical_node_name<'a>(name: &str, mut known: impl Iterator<Item = &'a str>) -> String {
+    let key = node_name_key(name);
+    known
+        .find(|known| node_name_key(known) == key)
+        .unwrap_or(name.trim())
+        .to_string()
+}
+
+/// (host, port) of an address, however it's written
+fn normalized_address(address: &str) -> Option<(String, u16)> {
+    let url = reqwest::Url::parse(address).ok()?;
+    Some((url.host_str()?.to_string(), url.port_or_known_default()?))
+}
+
+/// JSON schema of the config files, YAML, JSON and TOML alike
+#[cfg(feature = "json_schema")]
+pub fn json_schema() -> String {
+    serde_json::to_string(&schemars::schema_for!(Config)).expect("Failed to serialize JSON schema")
+}
+
+/// A config file merged with the files it includes
+#[derive(Debug, Clone)]
+struct MergedFiles(config::Map<String, config::Value>);
+
+impl config::Source for MergedFiles {
+    fn clone_into_box(&self) -> Box<dyn config::Source + Send + Sync> {
+        Box::new(self.clone())
+    }
+
+    fn collect(&self) -> Result<config::Map<String, config::Value>, config::ConfigError> {
+        Ok(self.0.clone())
+    }
+}
+
+/// Reads `path` and merges the files it includes into it, `chain` holds the including files
+fn load_file(
+    path: &Path,
+    chain: &mut Vec<PathBuf>,
+    origins: &mut HashMap<String, PathBuf>,
+    contents: &mut Vec<u8>,
+) -> Result<config::Map<String, config::Value>> {
+    let canonical = path
+        .canonicalize()
+        .with_context(|| format!("Failed to read config `{}`", path.display()))?;
+    if chain.contains(&canonical) {
+        anyhow::bail!(
+            "Config `{}` includes itself through {:?}",
+            path.display(),
+            chain
+        );
+    }
+    chain.push(canonical);
+    contents.extend(
+        std::fs::read(path)
+            .with_context(|| format!("Failed to read config `{}`", path.display()))?,
+    );
+
+    let mut table = match file_format(path) {
+        Some(format) => read_file(path, format)?,
+        // likely generated, tried as either
+        None => read_file(path, config::FileFormat::Yaml).or_else(|yaml_err| {
+            read_file(path, config::FileFormat::Json).map_err(|json_err| {
+                anyhow::anyhow!(
+                    "Config `{}` is neither YAML nor JSON: {yaml_err:#}, {json_err:#}",
+                    path.display()
+                )
+            })
+        })?,
+    };
+    for (key, value) in table.iter() {
+        origins.insert(key.clone(), path.to_path_buf());
+        if key == "nodes"
+            && let config::ValueKind::Table(nodes) = &value.kind
+        {
+            for name in nodes.keys() {
+                origins.insert(format!("nodes.{name}"), path.to_path_buf());
+            }
+        }
+    }
+
+    let includes = match table.get("include") {
+        Some(include) => include
+            .clone()
+            .into_array()
+            .with_context(|| format!("`include` of `{}` is not a list", path.display()))?
+            .into_iter()
+            .map(|file| file.into_string())
+            .collect::<Result<Vec<_>, _>>()?,
+        None => vec![],
+    };
+    let dir = path.parent().unwrap_or(Path::new("."));
+    for include in includes {
+        let included = load_file(&dir.join(include), chain, origins, contents)?;
+        merge(&mut table, included);
+    }
+
+    chain.pop();
+    Ok(table)
+}
+
+/// Format of a config file by its extension, `None` for unknown ones
+fn file_format(path: &Path) -> Option<config::FileFormat> {
+    match path.extension()?.to_str()? {
+        "yaml" | "yml" => Some(config::FileFormat::Yaml),
+        "json" => Some(config::FileFormat::Json),
+        "toml" => Some(config::FileFormat::Toml),
+        _ => None,
+    }
+}
+
+fn read_file(
+    path: &Path,
+    format: config::FileFormat,
+) -> Result<config::Map<String, config::Value>> {
+    let name = match format {
+        config::FileFormat::Json => "JSON",
+        config::FileFormat::Toml => "TOML",
+        _ => "YAML",
+    };
+    config::Source::collect(&config::File::from(path).format(format))
+        .with_context(|| format!("Failed to read config `{}` as {name}", path.display()))
+}
+
+/// FNV-1a, stable across versions and platforms unlike the std hasher
+fn content_hash(contents: &[u8]) -> String {
+    let hash = contents.iter().fold(0xcbf29ce484222325u64, |hash, byte| {
+        (hash ^ u64::from(*byte)).wrapping_mul(0x100000001b3)
+    });
+    format!("{hash:016x}")
+}
+
+/// Tables are merged key by key and lists concatenated, anything else in `other` wins
+fn merge(base: &mut config::Map<String, config::Value>, other: config::Map<String, config::Value>) {
+    for (key, value) in other {
+        let Some(existing) = base.get_mut(&key) else {
+            base.insert(key, value);
+            continue;
+        };
+        match (&mut existing.kind, value.kind) {
+            (config::ValueKind::Table(existing), config::ValueKind::Table(table)) => {
+                merge(existing, table)
+            }
+            (config::ValueKind::Array(existing), config::ValueKind::Array(list)) => {
+                existing.extend(list)
+            }
+            (_, kind) => *existing = config::Value::new(None, kind),
+        }
+    }
+}
+
+/// The config file given on the command line, otherwise the first one found of
+/// `$FREECASTER_CONFIG`, `./freecaster.yaml`, `$XDG_CONFIG_HOME/freecaster-grid/config.yaml`
+/// and `/etc/freecaster-grid/config.yaml`, `None` to configure from the environment alone
+pub fn find_config(arg: Option<&str>) -> Option<PathBuf> {
+    discover_config(arg, |var| std::env::var(var).ok(), |path| path.is_file())
+}
+
+fn discover_config(
+    arg: Option<&str>,
+    env: impl Fn(&str) -> Option<String>,
+    exists: impl Fn(&Path) -> bool,
+) -> Option<PathBuf> {
+    if let Some(arg) = arg {
+        return Some(PathBuf::from(arg));
+    }
+    // the variable's file is taken even if missing, so its mistakes show
+    if let Some(path) = env("FREECASTER_CONFIG").filter(|path| !path.is_empty()) {
+        return Some(PathBuf::from(path));
+    }
+    let xdg_config = env("XDG_CONFIG_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| env("HOME").map(|home| Path::new(&home).join(".config")));
+    [
+        Some(PathBuf::from("freecaster.yaml")),
+        xdg_config.map(|dir| dir.join("freecaster-grid").join("config.yaml")),
+        Some(PathBuf::from("/etc/freecaster-grid/config.yaml")),
+    ]
+    .into_iter()
+    .flatten()
+    .find(|path| exists(path))
+}
+
+/// An entry of `FREECASTER_NODES` or `FREECASTER_EXTRA_NODES`
+#[derive(Deserialize)]
+struct EnvNode {
+    name: String,
+    #[serde(flatten)]
+    config: NodeConfig,
+}
+
+/// `FREECASTER_NODES` replaces the nodes, `FREECASTER_EXTRA_NODES` adds to them,
+/// both JSON arrays of nodes with their `name`
+fn apply_node_overrides(config: &mut Config, env: impl Fn(&str) -> Option<String>) -> Result<()> {
+    let parse = |var: &str| -> Result<Option<Vec<EnvNode>>> {
+        env(var)
+            .map(|json| {
+                serde_json::from_str(&json)
+                    .with_context(|| format!("`{var}` is not a JSON array of nodes"))
+            })
+            .transpose()
+    };
+
+    if let Some(nodes) = parse("FREECASTER_NODES")? {
+        config.nodes = nodes
+            .into_iter()
+            .map(|node| (node.name, node.config))
+            .collect();
+    }
+    if let Some(nodes) = parse("FREECASTER_EXTRA_NODES")? {
+        config
+            .nodes
+            .extend(nodes.into_iter().map(|node| (node.name, node.config)));
+    }
+    Ok(())
 }
 
 pub async fn load_config(path: Option<PathBuf>) -> Result<Config> {
     let config = config::Config::builder();
-    let config = if let Some(path) = path {
-        config.add_source(config::File::from(path.clone()))
+    let mut origins = HashMap::new();
+    let mut contents = vec![];
+    let config = if let Some(path) = path.as_ref() {
+        let table = load_file(path, &mut vec![], &mut origins, &mut contents)?;
+        config.add_source(MergedFiles(table))
     } else {
         config
     };
-    let config = config
+    // of a single file the origin goes without saying
+    if origins.values().collect::<HashSet<_>>().len() < 2 {
+        origins.clear();
+    }
+    let mut config = config
         .add_source(
             config::Environment::with_prefix("FC")
                 .prefix_separator("_")
@@ -101,8 +1789,454 @@ pub async fn load_config(path: Option<PathBuf>) -> Result<Config> {
         )
         .build()
         .context("Failed to build config")?
-        .try_deserialize()
+        .try_deserialize::<Config>()
         .context("Failed to deserialize config")?;
+    config.origins = origins;
+    config.hash = path.is_some().then(|| content_hash(&contents));
+    config.source = path;
+    config.loaded_at = chrono::Utc::now();
+    apply_node_overrides(&mut config, |var| std::env::var(var).ok())?;
+    config.trim_node_names()?;
+    config.resolve_headers(|var| std::env::var(var).ok())?;
+    let config = config.without_self();
+    config.validate()?;
 
     Ok(config)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::test_config;
+    use serde_json::Value;
+
+    /// Keys of `value` the schema doesn't know, or requires and `value` lacks
+    fn schema_mismatches(root: &Value, schema: &Value, value: &Value, path: &str) -> Vec<String> {
+        if let Some(reference) = schema["$ref"].as_str() {
+            let name = reference.trim_start_matches("#/$defs/");
+            return schema_mismatches(root, &root["$defs"][name], value, path);
+        }
+        if let Some(alternatives) = schema["anyOf"].as_array().or(schema["oneOf"].as_array()) {
+            let mismatches = alternatives
+                .iter()
+                .map(|alternative| schema_mismatches(root, alternative, value, path))
+                .collect::<Vec<_>>();
+            return match mismatches.iter().find(|m| m.is_empty()) {
+                Some(_) => vec![],
+                None => mismatches.concat(),
+            };
+        }
+        if schema["type"] == "null" && !value.is_null() {
+            return vec![format!("{path} is not null")];
+        }
+        let Some(object) = value.as_object() else {
+            return vec![];
+        };
+
+        let mut mismatches = vec![];
+        if let Some(required) = schema["required"].as_array() {
+            for key in required.iter().filter_map(Value::as_str) {
+                if !object.contains_key(key) {
+                    mismatches.push(format!("{path}.{key} is missing"));
+                }
+            }
+        }
+        for (key, value) in object {
+            let path = format!("{path}.{key}");
+            match schema["properties"].get(key) {
+                Some(property) => {
+                    mismatches.extend(schema_mismatches(root, property, value, &path))
+                }
+                None if schema["additionalProperties"].is_object() => mismatches.extend(
+                    schema_mismatches(root, &schema["additionalProperties"], value, &path),
+                ),
+                None if schema["properties"].is_object() => {
+                    mismatches.push(format!("{path} is unknown"))
+                }
+                None => {}
+            }
+        }
+        mismatches
+    }
+
+    #[test]
+    fn example_configs_match_the_schema() {
+        let schema: Value = serde_json::from_str(include_str!("../config.schema.json")).unwrap();
+        for (file, yaml) in [
+            ("config.test.yaml", include_str!("../config.test.yaml")),
+            ("config.test2.yaml", include_str!("../config.test2.yaml")),
+            ("config.test3.yaml", include_str!("../config.test3.yaml")),
+        ] {
+            let value: Value = ::config::Config::builder()
+                .add_source(::config::File::from_str(yaml, ::config::FileFormat::Yaml))
+                .build()
+                .unwrap()
+                .try_deserialize()
+                .unwrap();
+            assert_eq!(
+                schema_mismatches(&schema, &schema, &value, file),
+                Vec::<String>::new()
+            );
+        }
+        assert_eq!(
+            schema_mismatches(
+                &schema,
+                &schema,
+                &serde_json::json!({"name": "hal9000", "server": {"prot": 3037}}),
+                "broken"
+            ),
+            [
+                "broken.secret_key is missing",
+                "broken.server.port is missing",
+                "broken.server.prot is unknown"
+            ]
+        );
+    }
+
+    #[test]
+    fn node_list_from_the_environment() {
+        let mut config = test_config("nodes:\n  hal9001:\n    address: http://hal9001:3037\n");
+
+        apply_node_overrides(&mut config, |var| {
+            (var == "FREECASTER_EXTRA_NODES").then(|| {
+                r#"[{"name": "hal9002", "address": "http://hal9002:3037", "tags": ["test"]}]"#
+                    .to_string()
+            })
+        })
+        .unwrap();
+        assert_eq!(config.nodes.len(), 2);
+        assert_eq!(config.nodes["hal9002"].tags, ["test"]);
+        assert!(config.nodes["hal9002"].reuse_connections);
+
+        apply_node_overrides(&mut config, |var| {
+            (var == "FREECASTER_NODES")
+                .then(|| r#"[{"name": "hal9003", "address": "http://hal9003:3037"}]"#.to_string())
+        })
+        .unwrap();
+        assert_eq!(config.nodes.keys().collect::<Vec<_>>(), ["hal9003"]);
+
+        let err = apply_node_overrides(&mut config, |var| {
+            (var == "FREECASTER_EXTRA_NODES").then(|| r#"[{"name": "hal9004"}]"#.to_string())
+        })
+        .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "`FREECASTER_EXTRA_NODES` is not a JSON array of nodes"
+        );
+    }
+
+    #[test]
+    fn extra_headers_resolved() {
+        let secret =
+            std::env::temp_dir().join(format!("freecaster-grid-header-{}", std::process::id()));
+        std::fs::write(&secret, "from-file\n").unwrap();
+        let mut config = test_config(&format!(
+            r#"
+extra_headers:
+  CF-Access-Client-Id: "grid-${{GRID_ID}}"
+  X-Shared: shared
+nodes:
+  hal9001:
+    address: http://hal9001:3037
+    extra_headers:
+      cf-access-client-id: own
+      X-Token:
+        file: "{}"
+  hal9002:
+    address: http://hal9002:3037
+"#,
+            secret.display()
+        ));
+
+        let env = |var: &str| (var == "GRID_ID").then(|| "42".to_string());
+        config.resolve_headers(env).unwrap();
+        std::fs::remove_file(&secret).unwrap();
+        let headers = config.nodes["hal9001"].header_map();
+        assert_eq!(headers.len(), 3);
+        assert_eq!(headers["CF-Access-Client-Id"], "own");
+        assert_eq!(headers["X-Token"], "from-file");
+        assert_eq!(headers["X-Shared"], "shared");
+        assert_eq!(
+            config.nodes["hal9002"].header_map()["CF-Access-Client-Id"],
+            "grid-42"
+        );
+        assert!(!format!("{config:?}").contains("grid-42"));
+
+        config.extra_headers = BTreeMap::from([(
+            "X-Missing".to_string(),
+            HeaderSource::Value("${MISSING}".to_string()),
+        )]);
+        assert!(config.resolve_headers(env).is_err());
+    }
+
+    #[test]
+    fn messy_node_names() {
+        let config = |nodes: &str| -> Config {
+            ::config::Config::builder()
+                .add_source(::config::File::from_str(
+                    &format!(
+                        "name: \" hal9000\"\nsecret_key: key\nserver:\n  port: 3037\nnodes:\n{nodes}"
+                    ),
+                    ::config::FileFormat::Yaml,
+                ))
+                .build()
+                .unwrap()
+                .try_deserialize()
+                .unwrap()
+        };
+
+        let mut trimmed = config(
+            "  \"Node-1 \":\n    address: http://node1:3037\n  node-2:\n    address: http://node2:3037\n    preferred_announcer: \"Node-1 \"\n",
+        );
+        trimmed.trim_node_names().unwrap();
+        assert_eq!(trimmed.name, "hal9000");
+        assert_eq!(
+            trimmed.nodes["node-2"].preferred_announcer.as_deref(),
+            Some("Node-1")
+        );
+        assert!(trimmed.validate().is_ok());
+        assert_eq!(
+            canonical_node_name("node-1 ", trimmed.nodes.keys().map(String::as_str)),
+            "Node-1"
+        );
+        assert_eq!(
+            canonical_node_name(" hal9009", ["hal9000"].into_iter()),
+            "hal9009"
+        );
+
+        let mut padded = config(
+            "  node-1:\n    address: http://node1:3037\n  \"node-1 \":\n    address: http://node1b:3037\n",
+        );
+        assert!(padded.trim_node_names().is_err());
+
+        let mut cased = config(
+            "  Node-1:\n    address: http://node1:3037\n  node-1:\n    address: http://node1b:3037\n",
+        );
+        cased.trim_node_names().unwrap();
+        assert!(cased.validate().is_err());
+        let mut like_this_node = config("  HAL9000:\n    address: http://elsewhere:3037\n");
+        like_this_node.trim_node_names().unwrap();
+        assert!(like_this_node.without_self().validate().is_err());
+    }
+
+    #[test]
+    fn self_is_left_out_by_name_or_address() {
+        let by_name = test_config(
+            "nodes:\n  hal9000:\n    address: http://hal9000:3037\n    tags: [main]\n  hal9001:\n    address: http://hal9001:3037\n",
+        );
+        assert!(by_name.validate().is_err());
+        let by_name = by_name.without_self();
+        assert_eq!(by_name.nodes.keys().collect::<Vec<_>>(), ["hal9001"]);
+        assert_eq!(by_name.tags, ["main"]);
+        assert!(by_name.validate().is_ok());
+
+        let by_address = test_config(
+            "advertised_address: https://hal9000.example.com\nnodes:\n  HAL9000:\n    address: https://HAL9000.example.com:443/\n  hal9001:\n    address: https://hal9000.example.com:3038\n",
+        )
+        .without_self();
+        assert_eq!(by_address.nodes.keys().collect::<Vec<_>>(), ["hal9001"]);
+
+        let loopback = test_config(
+            "nodes:\n  local:\n    address: http://127.0.0.1:3037\n  hal9001:\n    address: http://localhost:3038\n",
+        )
+        .without_self();
+        assert_eq!(loopback.nodes.keys().collect::<Vec<_>>(), ["hal9001"]);
+
+        let bound = test_config(
+            "  ip_address: \"::1\"\nnodes:\n  local:\n    address: http://[::1]:3037\n",
+        );
+        assert!(bound.validate().is_err());
+        assert!(bound.without_self().nodes.is_empty());
+    }
+
+    #[test]
+    fn targets_by_display_name() {
+        let config: Config = ::config::Config::builder()
+            .add_source(::config::File::from_str(
+                "name: hv01
+secret_key: key
+server:
+  port: 3037
+display_name: Dad's home server
+nodes:
+  hv02:
+    address: http://hv02:3037
+    display_name: Office
+  hv03:
+    address: http://hv03:3037
+    display_name: Office
+  hv04:
+    address: http://hv04:3037
+    display_name: hv02
+",
+                ::config::FileFormat::Yaml,
+            ))
+            .build()
+            .unwrap()
+            .try_deserialize()
+            .unwrap();
+        assert!(config.validate().is_ok());
+        assert_eq!(config.display_name("hv01"), "Dad's home server");
+        assert_eq!(config.display_name("hv03"), "Office");
+        assert_eq!(config.display_name("hv05"), "hv05");
+
+        assert_eq!(config.resolve_target("Dad's home server").unwrap(), "hv01");
+        assert_eq!(config.resolve_target("hv03").unwrap(), "hv03");
+        assert_eq!(config.resolve_target("tag:home").unwrap(), "tag:home");
+        assert_eq!(config.resolve_target("hv05").unwrap(), "hv05");
+        assert_eq!(
+            config.resolve_target("Office").unwrap_err(),
+            ["hv02", "hv03"]
+        );
+        assert_eq!(config.resolve_target("hv02").unwrap_err(), ["hv02", "hv04"]);
+    }
+
+    #[test]
+    fn config_discovery_order() {
+        let env = |var: &str| match var {
+            "XDG_CONFIG_HOME" => Some("/home/hal/.config".to_string()),
+            _ => None,
+        };
+        let found = |files: &'static [&'static str]| {
+            move |path: &Path| files.iter().any(|file| path == Path::new(file))
+        };
+        let all = found(&[
+            "freecaster.yaml",
+            "/home/hal/.config/freecaster-grid/config.yaml",
+            "/etc/freecaster-grid/config.yaml",
+        ]);
+
+        assert_eq!(
+            discover_config(Some("grid.yaml"), env, all),
+            Some(PathBuf::from("grid.yaml"))
+        );
+        assert_eq!(
+            discover_config(
+                None,
+                |var| (var == "FREECASTER_CONFIG").then(|| "/srv/grid.json".to_string()),
+                all
+            ),
+            Some(PathBuf::from("/srv/grid.json"))
+        );
+        assert_eq!(
+            discover_config(None, env, all),
+            Some(PathBuf::from("freecaster.yaml"))
+        );
+        assert_eq!(
+            discover_config(
+                None,
+                env,
+                found(&[
+                    "/home/hal/.config/freecaster-grid/config.yaml",
+                    "/etc/freecaster-grid/config.yaml"
+                ])
+            ),
+            Some(PathBuf::from(
+                "/home/hal/.config/freecaster-grid/config.yaml"
+            ))
+        );
+        assert_eq!(
+            discover_config(
+                None,
+                |var| (var == "HOME").then(|| "/home/hal".to_string()),
+                found(&["/home/hal/.config/freecaster-grid/config.yaml"])
+            ),
+            Some(PathBuf::from(
+                "/home/hal/.config/freecaster-grid/config.yaml"
+            ))
+        );
+        assert_eq!(
+            discover_config(None, env, found(&["/etc/freecaster-grid/config.yaml"])),
+            Some(PathBuf::from("/etc/freecaster-grid/config.yaml"))
+        );
+        assert_eq!(discover_config(None, env, found(&[])), None);
+    }
+
+    fn write_files(dir: &str, files: &[(&str, &str)]) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("freecaster-grid-{dir}-{}", std::process::id()));
+        std::fs::create_dir_all(dir.join("sites")).unwrap();
+        for (name, content) in files {
+            std::fs::write(dir.join(name), content).unwrap();
+        }
+        dir
+    }
+
+    #[tokio::test]
+    async fn included_files_are_merged() {
+        let dir = write_files(
+            "include",
+            &[
+                (
+                    "grid.yaml",
+                    "include: [sites/a.yaml, sites/b.yaml]\nname: hal9000\nsecret_key: key\ntags: [main]\nserver:\n  port: 3037\nnodes:\n  hal9001:\n    address: http://hal9001:3037\n",
+                ),
+                (
+                    "sites/a.yaml",
+                    "tags: [a]\nnodes:\n  hal9002:\n    address: http://hal9002:3037\n",
+                ),
+                (
+                    "sites/b.yaml",
+                    "poll_time: 20s\nserver:\n  port: 4242\nadvertised_address: ftp://hal9000\n",
+                ),
+            ],
+        );
+
+        let err = load_config(Some(dir.join("grid.yaml"))).await.unwrap_err();
+        assert!(err.to_string().ends_with("b.yaml`"), "{err}");
+
+        std::fs::write(
+            dir.join("sites/b.yaml"),
+            "poll_time: 20s\nserver:\n  port: 4242\n",
+        )
+        .unwrap();
+        let config = load_config(Some(dir.join("grid.yaml"))).await.unwrap();
+        assert_eq!(config.tags, ["main", "a"]);
+        assert_eq!(config.nodes.len(), 2);
+        assert_eq!(config.server.port, 4242);
+        assert_eq!(config.poll_interval(), std::time::Duration::from_secs(20));
+        assert_eq!(config.origins["nodes.hal9002"], dir.join("sites/a.yaml"));
+        assert_eq!(config.source, Some(dir.join("grid.yaml")));
+        assert_eq!(content_hash(b"a"), "af63dc4c8601ec8c");
+        std::fs::write(
+            dir.join("sites/b.yaml"),
+            "poll_time: 30s\nserver:\n  port: 4242\n",
+        )
+        .unwrap();
+        let changed = load_config(Some(dir.join("grid.yaml"))).await.unwrap();
+        assert_ne!(changed.hash, config.hash);
+
+        std::fs::write(dir.join("sites/a.yaml"), "include: [../grid.yaml]\n").unwrap();
+        let err = load_config(Some(dir.join("grid.yaml"))).await.unwrap_err();
+        assert!(err.to_string().contains("includes itself"), "{err}");
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn format_by_extension() {
+        let dir = write_files(
+            "formats",
+            &[
+                (
+                    "grid.json",
+                    r#"{"name": "hal9000", "secret_key": "key", "server": {"port": 3037}, "include": ["sites/a.toml", "sites/b.conf"]}"#,
+                ),
+                (
+                    "sites/a.toml",
+                    "[nodes.hal9001]\naddress = \"http://hal9001:3037\"\n",
+                ),
+                ("sites/b.conf", r#"{"tags": ["b"]}"#),
+                ("broken.yml", "name: [hal9000"),
+            ],
+        );
+
+        let config = load_config(Some(dir.join("grid.json"))).await.unwrap();
+        assert_eq!(config.name, "hal9000");
+        assert!(config.nodes.contains_key("hal9001"));
+        assert_eq!(config.tags, ["b"]);
+
+        let err = load_config(Some(dir.join("broken.yml"))).await.unwrap_err();
+        assert!(err.to_string().ends_with("as YAML"), "{err}");
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,149 @@
+use crate::config::CorsConfig;
+use rouille::{Request, Response};
+
+const ALLOWED_METHODS: &str = "GET, POST, DELETE, OPTIONS";
+const ALLOWED_HEADERS: &str = "Content-Type, If-None-Match";
+
+/// Answers preflights, `None` for any other request
+pub fn preflight(config: Option<&CorsConfig>, request: &Request) -> Option<Response> {
+    let config = config?;
+    if request.method() != "OPTIONS" {
+        return None;
+    }
+
+    let response = Response::empty_204();
+    let Some(origin) = allowed_origin(config, request) else {
+        // no cors headers, the browser refuses the request
+        return Some(response);
+    };
+    Some(
+        response
+            .with_additional_header("Access-Control-Allow-Origin", origin)
+            .with_additional_header("Access-Control-Allow-Methods", ALLOWED_METHODS)
+            .with_additional_header("Access-Control-Allow-Headers", ALLOWED_HEADERS)
+            .with_additional_header("Access-Control-Max-Age", "600")
+            .with_additional_header("Vary", "Origin"),
+    )
+}
+
+/// Adds the cors headers to a response for an allowed origin
+pub fn apply(config: Option<&CorsConfig>, request: &Request, response: Response) -> Response {
+    let Some(origin) = config.and_then(|config| allowed_origin(config, request)) else {
+        return response;
+    };
+    response
+        .with_additional_header("Access-Control-Allow-Origin", origin)
+        .with_additional_header("Access-Control-Allow-Methods", ALLOWED_METHODS)
+        .with_additional_header("Access-Control-Allow-Headers", ALLOWED_HEADERS)
+        .with_additional_header("Access-Control-Expose-Headers", "ETag")
+        .with_additional_header("Vary", "Origin")
+}
+
+fn allowed_origin(config: &CorsConfig, request: &Request) -> Option<String> {
+    let origin = request.header("Origin")?;
+    config
+        .allowed_origins
+        .iter()
+        .find(|allowed| *allowed == "*" || *allowed == origin)
+        .map(|allowed| {
+            if allowed == "*" {
+                "*".to_string()
+            } else {
+                origin.to_string()
+            }
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(method: &str, origin: &str) -> Request {
+        Request::fake_http(
+            method,
+            "/grid/key",
+            vec![("Origin".to_string(), origin.to_string())],
+            vec![],
+        )
+    }
+
+    fn header<'a>(response: &'a Response, name: &str) -> Option<&'a str> {
+        response
+            .headers
+            .iter()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.as_ref())
+    }
+
+    #[test]
+    fn only_allowed_origins_get_headers() {
+        let config = CorsConfig {
+            allowed_origins: vec!["https://dash.example.com".to_string()],
+        };
+
+        let allowed = preflight(
+            Some(&config),
+            &request("OPTIONS", "https://dash.example.com"),
+        )
+        .unwrap();
+        assert_eq!(allowed.status_code, 204);
+        assert_eq!(
+            header(&allowed, "Access-Control-Allow-Origin"),
+            Some("https://dash.example.com")
+        );
+        assert_eq!(
+            header(&allowed, "Access-Control-Allow-Methods"),
+            Some(ALLOWED_METHODS)
+        );
+        assert_eq!(header(&allowed, "Vary"), Some("Origin"));
+
+        for origin in [
+            "https://evil.example.com",
+            "https://dash.example.com.evil.com",
+            "http://dash.example.com",
+        ] {
+            let refused = preflight(Some(&config), &request("OPTIONS", origin)).unwrap();
+            assert_eq!(refused.status_code, 204);
+            assert!(
+                refused
+                    .headers
+                    .iter()
+                    .all(|(key, _)| !key.starts_with("Access-Control"))
+            );
+            let response = apply(
+                Some(&config),
+                &request("GET", origin),
+                Response::empty_204(),
+            );
+            assert_eq!(header(&response, "Access-Control-Allow-Origin"), None);
+        }
+
+        let response = apply(
+            Some(&config),
+            &request("GET", "https://dash.example.com"),
+            Response::empty_204(),
+        );
+        assert_eq!(
+            header(&response, "Access-Control-Expose-Headers"),
+            Some("ETag")
+        );
+        assert!(preflight(Some(&config), &request("GET", "https://dash.example.com")).is_none());
+    }
+
+    #[test]
+    fn no_cors_without_config() {
+        let request = request("OPTIONS", "https://dash.example.com");
+        assert!(preflight(None, &request).is_none());
+        assert!(
+            apply(None, &request, Response::empty_204())
+                .headers
+                .is_empty()
+        );
+
+        let any = CorsConfig {
+            allowed_origins: vec!["*".to_string()],
+        };
+        let response = apply(Some(&any), &request, Response::empty_204());
+        assert_eq!(header(&response, "Access-Control-Allow-Origin"), Some("*"));
+    }
+}
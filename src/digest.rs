@@ -0,0 +1,108 @@
+use crate::config::{Config, DigestConfig, DigestScope};
+use crate::messages;
+use crate::poller::Incident;
+use chrono::{DateTime, Datelike, Days, NaiveTime, Utc};
+use std::collections::HashMap;
+
+impl DigestScope {
+    fn period(self) -> chrono::Duration {
+        match self {
+            DigestScope::Daily => chrono::Duration::days(1),
+            DigestScope::Weekly => chrono::Duration::weeks(1),
+        }
+    }
+
+    /// Message key of the period
+    fn label(self) -> &'static str {
+        match self {
+            DigestScope::Daily => "today",
+            DigestScope::Weekly => "this_week",
+        }
+    }
+}
+
+/// The latest scheduled digest time at or before `now`, `None` if the hour is invalid
+pub fn last_scheduled(config: &DigestConfig, now: DateTime<Utc>) -> Option<DateTime<Utc>> {
+    let at = NaiveTime::from_hms_opt(config.hour, 0, 0)?;
+    let days_back = match config.scope {
+        DigestScope::Daily => 0,
+        DigestScope::Weekly => {
+            (7 + now.weekday().num_days_from_monday() - config.weekday.num_days_from_monday()) % 7
+        }
+    };
+
+    let scheduled = now
+        .date_naive()
+        .checked_sub_days(Days::new(days_back.into()))?
+        .and_time(at)
+        .and_utc();
+    if scheduled > now {
+        Some(scheduled - config.scope.period())
+    } else {
+        Some(scheduled)
+    }
+}
+
+/// Summary like `Grid report: 7/7 alive, 2 incidents this week totalling 43 minutes, noisiest node: pi-4`
+pub fn render(
+    grid_config: &Config,
+    config: &DigestConfig,
+    alive: usize,
+    total: usize,
+    incidents: &[Incident],
+    now: DateTime<Utc>,
+) -> String {
+    let since = now - config.scope.period();
+    let incidents = incidents
+        .iter()
+        .filter(|inc| inc.ended.is_none_or(|ended| ended > since))
+        .collect::<Vec<_>>();
+
+    let period = messages::render(grid_config, config.scope.label(), &[]);
+    let mut report = messages::render(
+        grid_config,
+        "digest",
+        &[("alive", &alive.to_string()), ("total", &total.to_string())],
+    );
+    if incidents.is_empty() {
+        report.push_str(&messages::render(
+            grid_config,
+            "digest_quiet",
+            &[("period", &period)],
+        ));
+        return report;
+    }
+
+    let mut per_node: HashMap<&str, (usize, chrono::Duration)> = HashMap::new();
+    let mut downtime = chrono::Duration::zero();
+    for inc in incidents.iter() {
+        let duration = inc.ended.unwrap_or(now) - inc.started.max(since);
+        downtime += duration;
+        let node = per_node.entry(&inc.node_name).or_default();
+        node.0 += 1;
+        node.1 += duration;
+    }
+    let noisiest = per_node
+        .iter()
+        .max_by(|(name1, stats1), (name2, stats2)| {
+            stats1.cmp(stats2).then_with(|| name2.cmp(name1))
+        })
+        .map(|(name, _)| *name)
+        .unwrap_or_default();
+
+    let count = match incidents.len() {
+        1 => messages::render(grid_config, "incident", &[]),
+        count => messages::render(grid_config, "incidents", &[("count", &count.to_string())]),
+    };
+    report.push_str(&messages::render(
+        grid_config,
+        "digest_incidents",
+        &[
+            ("incidents", &count),
+            ("period", &period),
+            ("minutes", &downtime.num_minutes().to_string()),
+            ("node", grid_config.display_name(noisiest)),
+        ],
+    ));
+    report
+}
@@ -0,0 +1,290 @@
+use crate::config::{Config, DiscoveryConfig, DiscoveryRecord, NodeConfig};
+use crate::peers::PeerAddresses;
+use crate::poller::State;
+use crate::reload::SharedConfig;
+use anyhow::{Context, Result, bail};
+use rand::Rng;
+use std::collections::HashMap;
+use std::ops::Range;
+use std::time::Duration;
+use tokio::net::UdpSocket;
+use tracing::{info, warn};
+
+const DNS_TIMEOUT: Duration = Duration::from_secs(5);
+const TYPE_TXT: u16 = 16;
+const TYPE_SRV: u16 = 33;
+
+/// Every node of the grid, the configured ones win over discovered ones with the same name
+pub fn grid_nodes(config: &Config, state: &State) -> HashMap<String, NodeConfig> {
+    let gr = state.lock().expect("Failed to lock state");
+    let mut nodes = gr.discovered_nodes.clone();
+    nodes.extend(config.nodes.clone());
+    nodes
+}
+
+/// Resolves the discovery record on an interval, failures keep the last known nodes.
+/// The addresses of the grid are resolved again when the nodes found change
+pub async fn discover(
+    config: SharedConfig,
+    state: State,
+    peer_addresses: PeerAddresses,
+) -> Result<()> {
+    let Some(discovery) = config.get().discovery.clone() else {
+        return Ok(());
+    };
+
+    info!("Discovering nodes from `{}`", discovery.name);
+    loop {
+        match resolve(&discovery).await {
+            Ok(mut nodes) => {
+                let config = config.get();
+                nodes.retain(|name, _| *name != config.name && !config.nodes.contains_key(name));
+                for node in nodes.values_mut() {
+                    node.extra_headers = config.extra_headers.clone();
+                }
+                let changed = {
+                    let mut gr = state.lock().expect("Failed to lock state");
+                    let changed = gr.discovered_nodes != nodes;
+                    if changed {
+                        info!("Discovered nodes: {:?}", nodes.keys().collect::<Vec<_>>());
+                        gr.discovered_nodes = nodes;
+                    }
+                    changed
+                };
+                if changed {
+                    peer_addresses.refresh(&grid_nodes(&config, &state)).await;
+                }
+            }
+            Err(err) => warn!(
+                "Failed to discover nodes from `{}`, keeping the last known: {err:?}",
+                discovery.name
+            ),
+        }
+
+        tokio::time::sleep(discovery.interval).await;
+    }
+}
+
+async fn resolve(discovery: &DiscoveryConfig) -> Result<HashMap<String, NodeConfig>> {
+    let record_type = match discovery.record {
+        DiscoveryRecord::Srv => TYPE_SRV,
+        DiscoveryRecord::Txt => TYPE_TXT,
+    };
+    let (message, answers) = query(&discovery.name, record_type).await?;
+
+    let mut nodes = HashMap::new();
+    for rdata in answers {
+        let (name, address) = match discovery.record {
+            // `<priority> <weight> <port> <target>`, named by the first label of the target
+            DiscoveryRecord::Srv => {
+                let (port, target) = parse_srv(&message, rdata.start)?;
+                let name = target.split('.').next().unwrap_or_default().to_string();
+                (name, format!("{}://{target}:{port}", discovery.scheme))
+            }
+            // `<name>=<address>`
+            DiscoveryRecord::Txt => {
+                let text = parse_txt(&message[rdata]);
+                let Some((name, address)) = text.split_once('=') else {
+                    warn!("Ignoring TXT record `{text}`, expected `name=address`");
+                    continue;
+                };
+                (name.trim().to_string(), address.trim().to_string())
+            }
+        };
+        if name.is_empty() {
+            continue;
+        }
+
+        nodes.insert(
+            name,
+            NodeConfig {
+                telegram_handle: None,
+                address,
+                skip_peer_ip_check: false,
+                tags: vec![],
+                ca_path: None,
+                reuse_connections: discovery.reuse_connections,
+                severity: Default::default(),
+                expected_version: None,
+                display_name: None,
+                preferred_announcer: None,
+                notify_url: None,
+                extra_headers: Default::default(),
+            },
+        );
+    }
+    Ok(nodes)
+}
+
+/// A single recursive query to the first nameserver of `/etc/resolv.conf`,
+/// returns the response and where the data of each matching answer is in it
+async fn query(name: &str, record_type: u16) -> Result<(Vec<u8>, Vec<Range<usize>>)> {
+    let nameserver = std::fs::read_to_string("/etc/resolv.conf")
+        .ok()
+        .and_then(|conf| {
+            conf.lines()
+                .filter_map(|line| line.trim().strip_prefix("nameserver"))
+                .map(|ns| ns.trim().to_string())
+                .next()
+        })
+        .unwrap_or_else(|| "127.0.0.1".to_string());
+    let nameserver: std::net::IpAddr = nameserver
+        .parse()
+        .with_context(|| format!("Invalid nameserver `{nameserver}`"))?;
+
+    let id: u16 = rand::rng().random();
+    let mut request = Vec::with_capacity(512);
+    request.extend_from_slice(&id.to_be_bytes());
+    // recursion desired, one question
+    request.extend_from_slice(&[0x01, 0x00, 0, 1, 0, 0, 0, 0, 0, 0]);
+    for label in name.trim_end_matches('.').split('.') {
+        if label.is_empty() || label.len() > 63 {
+            bail!("Invalid name `{name}`");
+        }
+        request.push(label.len() as u8);
+        request.extend_from_slice(label.as_bytes());
+    }
+    request.push(0);
+    request.extend_from_slice(&record_type.to_be_bytes());
+    request.extend_from_slice(&1u16.to_be_bytes());
+
+    let bind = if nameserver.is_ipv4() {
+        "0.0.0.0:0"
+    } else {
+        "[::]:0"
+    };
+    let socket = UdpSocket::bind(bind).await?;
+    socket.connect((nameserver, 53)).await?;
+    socket.send(&request).await?;
+
+    let mut buf = vec![0; 4096];
+    let len = tokio::time::timeout(DNS_TIMEOUT, socket.recv(&mut buf))
+        .await
+        .context("DNS query timed out")??;
+    buf.truncate(len);
+    let message = buf;
+
+    let header = message.get(..12).context("Short DNS response")?;
+    if header[..2] != id.to_be_bytes() {
+        bail!("DNS response for another query");
+    }
+    if header[2] & 0x02 != 0 {
+        bail!("DNS response truncated");
+    }
+    let rcode = header[3] & 0x0f;
+    if rcode != 0 {
+        bail!("DNS query failed with rcode {rcode}");
+    }
+    let questions = u16::from_be_bytes([header[4], header[5]]);
+    let answers = u16::from_be_bytes([header[6], header[7]]);
+
+    let mut offset = 12;
+    for _ in 0..questions {
+        offset = skip_name(&message, offset)? + 4;
+    }
+
+    let mut found = vec![];
+    for _ in 0..answers {
+        offset = skip_name(&message, offset)?;
+        let fixed = message
+            .get(offset..offset + 10)
+            .context("Short DNS answer")?;
+        let answer_type = u16::from_be_bytes([fixed[0], fixed[1]]);
+        let rdata_len = u16::from_be_bytes([fixed[8], fixed[9]]) as usize;
+        let rdata_offset = offset + 10;
+        if message.len() < rdata_offset + rdata_len {
+            bail!("Short DNS answer");
+        }
+        // CNAMEs and the like come along in recursive answers
+        if answer_type == record_type {
+            found.push(rdata_offset..rdata_offset + rdata_len);
+        }
+        offset = rdata_offset + rdata_len;
+    }
+    Ok((message, found))
+}
+
+fn skip_name(message: &[u8], mut offset: usize) -> Result<usize> {
+    loop {
+        let len = *message.get(offset).context("Short DNS name")?;
+        match len {
+            0 => return Ok(offset + 1),
+            // compression pointer, the name ends here
+            len if len & 0xc0 == 0xc0 => return Ok(offset + 2),
+            len => offset += 1 + len as usize,
+        }
+    }
+}
+
+fn read_name(message: &[u8], mut offset: usize) -> Result<String> {
+    let mut labels = vec![];
+    // bounds pointer loops
+    for _ in 0..128 {
+        let len = *message.get(offset).context("Short DNS name")?;
+        if len == 0 {
+            return Ok(labels.join("."));
+        }
+        if len & 0xc0 == 0xc0 {
+            let low = *message.get(offset + 1).context("Short DNS name")?;
+            offset = (((len & 0x3f) as usize) << 8) | low as usize;
+            continue;
+        }
+        let label = message
+            .get(offset + 1..offset + 1 + len as usize)
+            .context("Short DNS name")?;
+        labels.push(String::from_utf8_lossy(label).to_string());
+        offset += 1 + len as usize;
+    }
+    bail!("DNS name too long")
+}
+
+/// (port, target) of an SRV record
+fn parse_srv(message: &[u8], rdata_offset: usize) -> Result<(u16, String)> {
+    let port = message
+        .get(rdata_offset + 4..rdata_offset + 6)
+        .context("Short SRV record")?;
+    let port = u16::from_be_bytes([port[0], port[1]]);
+    Ok((port, read_name(message, rdata_offset + 6)?))
+}
+
+/// The character strings of a TXT record joined together
+fn parse_txt(rdata: &[u8]) -> String {
+    let mut text = String::new();
+    let mut rest = rdata;
+    while let Some((&len, tail)) = rest.split_first() {
+        let len = (len as usize).min(tail.len());
+        text.push_str(&String::from_utf8_lossy(&tail[..len]));
+        rest = &tail[len..];
+    }
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn srv_target_with_compression() {
+        // `example.com` at 12, the SRV data at 25 points back to it
+        let mut message = vec![0; 12];
+        message.extend_from_slice(b"\x07example\x03com\x00");
+        message.extend_from_slice(&[0, 10, 0, 5, 0x10, 0x92]);
+        message.extend_from_slice(b"\x07hal9000\xc0\x0c");
+
+        assert_eq!(
+            parse_srv(&message, 25).unwrap(),
+            (4242, "hal9000.example.com".to_string())
+        );
+        assert_eq!(skip_name(&message, 31).unwrap(), 41);
+    }
+
+    #[test]
+    fn txt_strings_are_joined() {
+        assert_eq!(
+            parse_txt(b"\x0ahal9000=ht\x0etps://hal:4242"),
+            "hal9000=https://hal:4242"
+        );
+        // a length past the end is cut short
+        assert_eq!(parse_txt(b"\x09hal"), "hal");
+    }
+}
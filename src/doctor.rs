@@ -0,0 +1,472 @@
+use crate::announcer::TELEGRAM_API;
+use crate::cert_check;
+use crate::config::{Config, NamedNodeConfig, find_config, load_config};
+use crate::poller::{build_client, peer_api};
+use crate::proxy;
+use crate::self_check;
+use chrono::Utc;
+use freecaster_grid_client::Error as ClientError;
+use reqwest::{Client, StatusCode};
+use serde::Deserialize;
+use std::io::ErrorKind;
+use std::net::SocketAddr;
+use std::time::{Duration, Instant};
+use tokio::net::TcpStream;
+
+/// Longest wait for a lookup or a connection, the calls have the configured request timeout
+const STEP_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Sent along with the calls to the peers, so their logs tell the doctor's calls apart
+const TRACE: &str = "doctor";
+
+/// A row of the report
+#[derive(Debug)]
+struct Check {
+    /// The peer, or `local` for this node's own checks
+    subject: String,
+    name: &'static str,
+    latency: Option<Duration>,
+    /// What was found, or what's wrong and what to look at
+    outcome: Result<String, String>,
+}
+
+#[derive(Default)]
+struct Report(Vec<Check>);
+
+impl Report {
+    /// Whether the check passed, a peer's later checks are pointless after a failed one
+    fn record(
+        &mut self,
+        subject: &str,
+        name: &'static str,
+        started: Option<Instant>,
+        outcome: Result<String, String>,
+    ) -> bool {
+        let passed = outcome.is_ok();
+        self.0.push(Check {
+            subject: subject.to_string(),
+            name,
+            latency: started.map(|started| started.elapsed()),
+            outcome,
+        });
+        passed
+    }
+
+    fn failed(&self) -> bool {
+        self.0.iter().any(|check| check.outcome.is_err())
+    }
+
+    fn table(&self) -> String {
+        let rows = self
+            .0
+            .iter()
+            .map(|check| {
+                let (result, detail) = match &check.outcome {
+                    Ok(detail) => ("ok", detail),
+                    Err(detail) => ("FAIL", detail),
+                };
+                let latency = check
+                    .latency
+                    .map(|latency| format!("{}ms", latency.as_millis()))
+                    .unwrap_or_default();
+                [
+                    check.subject.clone(),
+                    check.name.to_string(),
+                    result.to_string(),
+                    latency,
+                    detail.clone(),
+                ]
+            })
+            .collect::<Vec<_>>();
+        let header = ["NODE", "CHECK", "RESULT", "LATENCY", "DETAIL"].map(String::from);
+        let widths = std::iter::once(&header)
+            .chain(rows.iter())
+            .fold([0; 4], |mut widths, row| {
+                for (width, cell) in widths.iter_mut().zip(row) {
+                    *width = (*width).max(cell.chars().count());
+                }
+                widths
+            });
+        std::iter::once(&header)
+            .chain(rows.iter())
+            .map(|row| {
+                let mut line = String::new();
+                for (width, cell) in widths.iter().zip(row) {
+                    line.push_str(&format!("{cell:width$}  "));
+                }
+                line.push_str(&row[4]);
+                line.trim_end().to_string() + "\n"
+            })
+            .collect()
+    }
+}
+
+/// `doctor [config]`, checks that the peers are reachable and accept this node's key,
+/// that the listener can bind and that the Telegram token works, exiting with 1 if anything fails
+pub async fn doctor(args: &[String]) -> i32 {
+    let path = find_config(args.first().map(String::as_str));
+    let config = match load_config(path).await {
+        Ok(config) => config,
+        Err(err) => {
+            eprintln!("Config is invalid: {err:#}");
+            return 1;
+        }
+    };
+    let client = match config
+        .load_ca_certificates()
+        .await
+        .and_then(|trust_anchors| build_client(&config, &trust_anchors, false))
+    {
+        Ok(client) => client,
+        Err(err) => {
+            eprintln!("Failed to create the HTTP client: {err:#}");
+            return 1;
+        }
+    };
+
+    let mut report = Report::default();
+    check_bind(&config, &mut report).await;
+    if let Some(token) = telegram_token(&config) {
+        match proxy::client_builder(&config).and_then(|builder| Ok(builder.build()?)) {
+            Ok(telegram_client) => {
+                check_telegram(&telegram_client, TELEGRAM_API, token, &mut report).await
+            }
+            Err(err) => {
+                report.record("local", "telegram", None, Err(format!("{err:#}")));
+            }
+        }
+    }
+    let mut names = config.nodes.keys().collect::<Vec<_>>();
+    names.sort();
+    for name in names {
+        check_peer(
+            &config,
+            &client,
+            config.nodes[name].with_name(name),
+            &mut report,
+        )
+        .await;
+    }
+
+    print!("{}", report.table());
+    if report.failed() { 1 } else { 0 }
+}
+
+/// The Telegram token, when announcements or bot commands go through Telegram
+fn telegram_token(config: &Config) -> Option<&str> {
+    let telegram = config.telegram.as_ref()?;
+    (config.announces_to_telegram() || telegram.bot_commands.is_some())
+        .then_some(telegram.token.as_str())
+}
+
+/// The listener's address is free, or taken by this node running already
+async fn check_bind(config: &Config, report: &mut Report) {
+    let address = format!("{}:{}", config.server.ip_address, config.server.port);
+    let ip = config.server.ip_address.trim_matches(['[', ']']);
+    let outcome = match std::net::TcpListener::bind((ip, config.server.port)) {
+        Ok(_) => Ok(format!("`{address}` is free")),
+        Err(err) if err.kind() == ErrorKind::AddrInUse => {
+            let running = match self_check::client(config) {
+                Ok(client) => self_check::check(&client, &self_check::url(config), &config.name)
+                    .await
+                    .is_ok(),
+                Err(_) => false,
+            };
+            if running {
+                Ok(format!("`{address}` is served by this node, it's running"))
+            } else {
+                Err(format!(
+                    "`{address}` is in use by another program, change `server.port` or stop it"
+                ))
+            }
+        }
+        Err(err) => Err(format!(
+            "Can't listen on `{address}`: {err}, is `server.ip_address` an address of this machine?"
+        )),
+    };
+    report.record("local", "bind", None, outcome);
+}
+
+#[derive(Deserialize)]
+struct GetMe {
+    result: Option<Bot>,
+}
+
+#[derive(Deserialize)]
+struct Bot {
+    username: Option<String>,
+}
+
+/// `getMe` of the bot API, answering only for a valid token
+async fn check_telegram(client: &Client, api_url: &str, token: &str, report: &mut Report) {
+    let started = Instant::now();
+    let res = client
+        .get(format!("{api_url}/bot{token}/getMe"))
+        .send()
+        .await;
+    let outcome = match res {
+        Ok(res) if res.status().is_success() => match res.json::<GetMe>().await {
+            Ok(GetMe {
+                result: Some(Bot {
+                    username: Some(username),
+                }),
+            }) => Ok(format!("bot @{username}")),
+            _ => Err("Unexpected answer to `getMe`, is it Telegram's API?".to_string()),
+        },
+        Ok(res)
+            if res.status() == StatusCode::UNAUTHORIZED
+                || res.status() == StatusCode::NOT_FOUND =>
+        {
+            Err(format!(
+                "{} from Telegram: check `telegram.token`",
+                res.status()
+            ))
+        }
+        Ok(res) => Err(format!("{} from Telegram", res.status())),
+        Err(err) => Err(format!(
+            "Telegram is unreachable: {}",
+            causes(&err.without_url())
+        )),
+    };
+    report.record("local", "telegram", Some(started), outcome);
+}
+
+/// DNS, connection, certificate, then the status and obituary of `node`, as far as they succeed
+async fn check_peer(
+    config: &Config,
+    client: &Client,
+    node: NamedNodeConfig<'_>,
+    report: &mut Report,
+) {
+    let name = node.name.as_str();
+    let url = match reqwest::Url::parse(&node.config.address) {
+        Ok(url) => url,
+        Err(err) => {
+            report.record(
+                name,
+                "address",
+                None,
+                Err(format!("Invalid `address`: {err}")),
+            );
+            return;
+        }
+    };
+    let (Some(host), Some(port)) = (url.host_str(), url.port_or_known_default()) else {
+        report.record(
+            name,
+            "address",
+            None,
+            Err("`address` has no host".to_string()),
+        );
+        return;
+    };
+
+    let started = Instant::now();
+    let resolved = tokio::time::timeout(STEP_TIMEOUT, tokio::net::lookup_host((host, port))).await;
+    let addresses = match resolved {
+        Ok(Ok(addresses)) => addresses.collect::<Vec<SocketAddr>>(),
+        Ok(Err(err)) => {
+            report.record(
+                name,
+                "dns",
+                Some(started),
+                Err(format!(
+                    "`{host}` doesn't resolve: {err}, check the node's `address`"
+                )),
+            );
+            return;
+        }
+        Err(_) => {
+            report.record(
+                name,
+                "dns",
+                Some(started),
+                Err(format!("Looking up `{host}` timed out, is DNS reachable?")),
+            );
+            return;
+        }
+    };
+    let listed = addresses
+        .iter()
+        .map(|address| address.ip().to_string())
+        .collect::<Vec<_>>()
+        .join(", ");
+    report.record(name, "dns", Some(started), Ok(listed));
+
+    let started = Instant::now();
+    let connected =
+        tokio::time::timeout(STEP_TIMEOUT, TcpStream::connect(addresses.as_slice())).await;
+    let outcome = match connected {
+        Ok(Ok(stream)) => Ok(stream
+            .peer_addr()
+            .map(|address| address.to_string())
+            .unwrap_or_default()),
+        Ok(Err(err)) if err.kind() == ErrorKind::ConnectionRefused => Err(format!(
+            "Connection refused, is freecaster-grid running on `{name}` and listening on port {port}?"
+        )),
+        Ok(Err(err)) => Err(format!("Can't connect: {err}")),
+        Err(_) => Err(format!(
+            "No answer within {}s, is a firewall in the way?",
+            STEP_TIMEOUT.as_secs()
+        )),
+    };
+    if !report.record(name, "connect", Some(started), outcome) {
+        return;
+    }
+
+    if url.scheme() == "https" {
+        let started = Instant::now();
+        let outcome = match cert_check::peer_certificate(&node.config.address).await {
+            Ok(Some(certificate)) => {
+                let expiry = certificate.not_after.format("%Y-%m-%d");
+                let days = (certificate.not_after - Utc::now()).num_days();
+                if days < 0 {
+                    Err(format!(
+                        "The certificate of `{}` expired {expiry}, renew it",
+                        certificate.subject
+                    ))
+                } else {
+                    Ok(format!(
+                        "`{}`, expires {expiry}, in {days} days",
+                        certificate.subject
+                    ))
+                }
+            }
+            Ok(None) => Ok("not https".to_string()),
+            Err(err) => Err(format!(
+                "TLS handshake failed: {err:#}, does `{name}` serve https on port {port}?"
+            )),
+        };
+        if !report.record(name, "tls", Some(started), outcome) {
+            return;
+        }
+    }
+
+    let api = peer_api(client, &config.name, TRACE, &node, &config.secret_key);
+    let started = Instant::now();
+    let outcome = match api.status().await {
+        Ok(status) if status.name != *name => Err(format!(
+            "Answers as `{}`, is the `address` of `{name}` right?",
+            status.name
+        )),
+        Ok(status) => Ok(format!("`{}` v{}", status.name, status.version)),
+        Err(err) => Err(hint(config, name, &err)),
+    };
+    if !report.record(name, "status", Some(started), outcome) {
+        return;
+    }
+
+    let started = Instant::now();
+    let outcome = match api.obituary().await {
+        Ok(obituary) => Ok(format!("{} dead nodes", obituary.dead_nodes.len())),
+        Err(err) => Err(hint(config, name, &err)),
+    };
+    report.record(name, "obituary", Some(started), outcome);
+}
+
+/// What to look at when a call to `node` failed
+fn hint(config: &Config, node: &str, err: &ClientError) -> String {
+    match err {
+        ClientError::Status(StatusCode::NOT_ACCEPTABLE) => {
+            format!("406 from `{node}`: secret key mismatch?")
+        }
+        ClientError::Status(StatusCode::FORBIDDEN) => format!(
+            "403 from `{node}`: it doesn't know this node's address, is `{}` in its `nodes`?",
+            config.name
+        ),
+        ClientError::Status(status) => format!("{status} from `{node}`"),
+        ClientError::Request(err) if err.is_timeout() => format!(
+            "No answer within {}s, is `{node}` overloaded?",
+            config.http_client.request_timeout.as_secs()
+        ),
+        ClientError::Request(err) if causes(err).contains("certificate") => format!(
+            "Its certificate is rejected: {}, is it issued by a CA of `ca_bundle_path`?",
+            causes(err)
+        ),
+        ClientError::Request(err) => causes(err),
+        ClientError::Decode(err) => format!(
+            "Not the API of freecaster-grid, is something else listening? {}",
+            causes(err)
+        ),
+    }
+}
+
+/// The error and its causes, reqwest's own message rarely tells what went wrong
+fn causes(err: &dyn std::error::Error) -> String {
+    std::iter::successors(Some(err), |err| err.source())
+        .map(ToString::to_string)
+        .collect::<Vec<_>>()
+        .join(": ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::test_config;
+
+    fn config(port: u16) -> Config {
+        test_config(&format!(
+            "  ip_address: 127.0.0.1\nnodes:\n  hal9001:\n    address: http://127.0.0.1:{port}\n"
+        ))
+    }
+
+    #[tokio::test]
+    async fn key_mismatch_and_bad_token_are_explained() {
+        let server = rouille::Server::new("127.0.0.1:0", |request| match request.url().as_str() {
+            "/" => rouille::Response::text(r#"{"version":"1.0.0","name":"hal9001"}"#),
+            "/botbad/getMe" => rouille::Response::empty_400().with_status_code(401),
+            _ => rouille::Response::empty_406(),
+        })
+        .unwrap();
+        let port = server.server_addr().port();
+        let (handle, stop) = server.stoppable();
+
+        let config = config(port);
+        let client = Client::new();
+        let mut report = Report::default();
+        check_peer(
+            &config,
+            &client,
+            config.nodes["hal9001"].with_name(&"hal9001".to_string()),
+            &mut report,
+        )
+        .await;
+        check_telegram(
+            &client,
+            &format!("http://127.0.0.1:{port}"),
+            "bad",
+            &mut report,
+        )
+        .await;
+        stop.send(()).unwrap();
+        handle.join().unwrap();
+
+        let outcomes = report
+            .0
+            .iter()
+            .map(|check| (check.name, check.outcome.clone()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            outcomes,
+            [
+                ("dns", Ok("127.0.0.1".to_string())),
+                ("connect", Ok(format!("127.0.0.1:{port}"))),
+                ("status", Ok("`hal9001` v1.0.0".to_string())),
+                (
+                    "obituary",
+                    Err("406 from `hal9001`: secret key mismatch?".to_string())
+                ),
+                (
+                    "telegram",
+                    Err("401 Unauthorized from Telegram: check `telegram.token`".to_string())
+                ),
+            ]
+        );
+        assert!(report.failed());
+        assert!(
+            report
+                .table()
+                .starts_with("NODE     CHECK     RESULT  LATENCY  DETAIL\n")
+        );
+    }
+}
@@ -0,0 +1,61 @@
+use crate::config::ServerConfig;
+use rouille::{Request, Response, ResponseBody};
+use std::hash::{DefaultHasher, Hasher};
+use std::io::Read;
+
+/// Adds a weak ETag to json responses, answering matching `If-None-Match` with 304,
+/// and compresses them for clients accepting it
+pub fn finish(config: &ServerConfig, request: &Request, mut response: Response) -> Response {
+    if response.status_code != 200 || !is_json(&response) || (!config.etag && !config.compression) {
+        return response;
+    }
+
+    let (mut reader, size) =
+        std::mem::replace(&mut response.data, ResponseBody::empty()).into_reader_and_size();
+    let mut body = Vec::with_capacity(size.unwrap_or_default());
+    if reader.read_to_end(&mut body).is_err() {
+        return Response::text("Failed to read response").with_status_code(500);
+    }
+
+    if config.etag {
+        let mut hasher = DefaultHasher::new();
+        hasher.write(&body);
+        let etag = format!("W/\"{:016x}\"", hasher.finish());
+
+        if request
+            .header("If-None-Match")
+            .is_some_and(|tags| matches_etag(tags, &etag))
+        {
+            return Response {
+                status_code: 304,
+                headers: vec![("ETag".into(), etag.into())],
+                data: ResponseBody::empty(),
+                upgrade: None,
+            };
+        }
+        response.headers.push(("ETag".into(), etag.into()));
+    }
+
+    let compress = config.compression && body.len() >= config.compression_min_size;
+    response.data = ResponseBody::from_data(body);
+    if compress {
+        rouille::content_encoding::apply(request, response)
+    } else {
+        response
+    }
+}
+
+fn is_json(response: &Response) -> bool {
+    response.headers.iter().any(|(key, value)| {
+        key.eq_ignore_ascii_case("Content-Type") && value.contains("application/json")
+    })
+}
+
+/// Weak comparison, `W/` prefixes are ignored on both sides
+fn matches_etag(if_none_match: &str, etag: &str) -> bool {
+    let etag = etag.trim_start_matches("W/");
+    if_none_match
+        .split(',')
+        .map(str::trim)
+        .any(|tag| tag == "*" || tag.trim_start_matches("W/") == etag)
+}
@@ -0,0 +1,181 @@
+use crate::GridNodeStatus;
+use crate::audit::AuditAction;
+use crate::config::AnnouncementMode;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+
+/// Events kept for consumers polling `/events`, older ones are dropped
+pub const EVENT_LOG_CAPACITY: usize = 1000;
+pub const DEFAULT_EVENTS_PAGE_SIZE: usize = 100;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum GridEvent {
+    StatusChanged {
+        node_name: String,
+        from: GridNodeStatus,
+        to: GridNodeStatus,
+    },
+    SilenceCreated {
+        silence_id: String,
+        node_name: String,
+        silent_until: DateTime<Utc>,
+    },
+    /// Extended or shortened
+    SilenceUpdated {
+        silence_id: String,
+        node_name: String,
+        silent_until: DateTime<Utc>,
+    },
+    SilenceExpired {
+        silence_id: String,
+        node_name: String,
+    },
+    SilenceCancelled {
+        silence_id: String,
+        node_name: String,
+    },
+    /// Delivered over `channel`, `event` like `dead` or `recovered`
+    AnnouncementSent {
+        node_name: String,
+        event: String,
+        channel: AnnouncementMode,
+    },
+    ConfigReloaded,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EventEntry {
+    pub seq: u64,
+    pub time: DateTime<Utc>,
+    #[serde(flatten)]
+    pub event: GridEvent,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct EventsResponse {
+    pub events: Vec<EventEntry>,
+    /// Of the newest event, the cursor to continue from once caught up
+    pub latest: u64,
+    /// Events after the cursor were dropped, the client should resync from `/grid`
+    pub truncated: bool,
+}
+
+/// What happened to the grid, numbered from 1, the newest `EVENT_LOG_CAPACITY` kept in memory
+#[derive(Debug, Default)]
+pub struct EventLog {
+    entries: VecDeque<EventEntry>,
+    latest: u64,
+}
+
+impl EventLog {
+    pub fn record(&mut self, event: GridEvent) {
+        self.latest += 1;
+        if self.entries.len() >= EVENT_LOG_CAPACITY {
+            self.entries.pop_front();
+        }
+        self.entries.push_back(EventEntry {
+            seq: self.latest,
+            time: Utc::now(),
+            event,
+        });
+    }
+
+    /// The silence and reload events of the audit log, local silences stay out
+    pub fn record_action(&mut self, action: &AuditAction) {
+        let event = match action.clone() {
+            AuditAction::SilenceCreated {
+                silence_id,
+                node_name,
+                silent_until,
+                local: false,
+            } => GridEvent::SilenceCreated {
+                silence_id,
+                node_name,
+                silent_until,
+            },
+            AuditAction::SilenceUpdated {
+                silence_id,
+                node_name,
+                silent_until,
+                local: false,
+                ..
+            } => GridEvent::SilenceUpdated {
+                silence_id,
+                node_name,
+                silent_until,
+            },
+            AuditAction::SilenceCancelled {
+                silence_id,
+                node_name,
+                local: false,
+            } => GridEvent::SilenceCancelled {
+                silence_id,
+                node_name,
+            },
+            AuditAction::ConfigReloaded => GridEvent::ConfigReloaded,
+            _ => return,
+        };
+        self.record(event);
+    }
+
+    /// At most `limit` events after `since`, oldest first
+    pub fn since(&self, since: u64, limit: usize) -> EventsResponse {
+        let oldest = self
+            .entries
+            .front()
+            .map_or(self.latest + 1, |entry| entry.seq);
+        EventsResponse {
+            events: self
+                .entries
+                .iter()
+                .filter(|entry| entry.seq > since)
+                .take(limit)
+                .cloned()
+                .collect(),
+            latest: self.latest,
+            truncated: since + 1 < oldest,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cursoring_and_overflow() {
+        let mut log = EventLog::default();
+        assert!(!log.since(0, 10).truncated);
+        for _ in 0..EVENT_LOG_CAPACITY + 5 {
+            log.record(GridEvent::ConfigReloaded);
+        }
+
+        let page = log.since(EVENT_LOG_CAPACITY as u64, 3);
+        assert_eq!(
+            page.events
+                .iter()
+                .map(|entry| entry.seq)
+                .collect::<Vec<_>>(),
+            [1001, 1002, 1003]
+        );
+        assert_eq!(page.latest, 1005);
+        assert!(!page.truncated);
+
+        // the first five are gone
+        let page = log.since(0, 1);
+        assert_eq!(page.events[0].seq, 6);
+        assert!(page.truncated);
+        assert!(!log.since(5, 1).truncated);
+        assert!(log.since(1005, 10).events.is_empty());
+
+        log.record_action(&AuditAction::SilenceCancelled {
+            silence_id: "s1".to_string(),
+            node_name: "hal9001".to_string(),
+            local: true,
+        });
+        log.record_action(&AuditAction::MuteCleared);
+        assert_eq!(log.since(1005, 10).latest, 1005);
+    }
+}
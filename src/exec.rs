@@ -0,0 +1,140 @@
+use crate::announcer::{self, AnnouncementEvent, Announcer};
+use crate::config::{AnnouncementMode, AnnouncementRetryConfig, ExecConfig};
+use crate::poller::State;
+use anyhow::{Result, bail};
+use tokio::process::Command;
+use tracing::{info, warn};
+
+/// Runs the command in the background, the same node and event won't run twice at once.
+/// Its own `timeout` takes the place of the `announcement_timeout`, `attempts` counts this run too
+pub fn spawn(
+    exec: Exec,
+    retry: AnnouncementRetryConfig,
+    state: &State,
+    event: AnnouncementEvent,
+    attempts: u32,
+) {
+    let key = (event.node.clone(), event.kind.as_str().to_string());
+    {
+        let mut gr = state.lock().expect("Failed to lock state");
+        if !gr.running_commands.insert(key.clone()) {
+            warn!(
+                "Command for `{}` ({}) is still running, skipping",
+                event.node,
+                event.kind.as_str()
+            );
+            return;
+        }
+    }
+
+    let state = state.clone();
+    tokio::spawn(async move {
+        let delivery = announcer::deliver(&exec, &retry, exec.config.timeout, &event).await;
+        state
+            .lock()
+            .expect("Failed to lock state")
+            .running_commands
+            .remove(&key);
+        announcer::record_delivery(&state, AnnouncementMode::Exec, event, delivery, attempts);
+    });
+}
+
+#[derive(Clone)]
+pub struct Exec {
+    pub config: ExecConfig,
+}
+
+impl Announcer for Exec {
+    fn name(&self) -> &'static str {
+        "Exec"
+    }
+
+    async fn announce(&self, event: &AnnouncementEvent) -> Result<()> {
+        run(&self.config, event).await
+    }
+}
+
+async fn run(exec: &ExecConfig, event: &AnnouncementEvent) -> Result<()> {
+    let timestamp = event.time.to_rfc3339();
+    let tags = event.tags.join(",");
+    let values = [
+        ("node", event.node.as_str()),
+        ("display_name", event.display_name.as_str()),
+        ("event", event.kind.as_str()),
+        ("announcer", event.announcer.as_str()),
+        ("timestamp", timestamp.as_str()),
+        ("message", event.message.as_str()),
+        ("tags", tags.as_str()),
+        ("reason", event.reason.as_deref().unwrap_or_default()),
+    ];
+    let args = exec
+        .args
+        .iter()
+        .map(|arg| render(arg, &values))
+        .collect::<Vec<_>>();
+
+    info!(
+        "Running `{}` for `{}` ({})",
+        exec.command,
+        event.node,
+        event.kind.as_str()
+    );
+    let mut command = Command::new(&exec.command);
+    command.args(&args).kill_on_drop(true);
+
+    match command.output().await {
+        Err(err) => bail!("Failed to run `{}`: {err:?}", exec.command),
+        Ok(output) => {
+            let stdout = String::from_utf8_lossy(&output.stdout);
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            if !output.status.success() {
+                bail!(
+                    "Command `{}` for `{}` failed with {}, stdout: `{}`, stderr: `{}`",
+                    exec.command,
+                    event.node,
+                    output.status,
+                    stdout.trim(),
+                    stderr.trim()
+                );
+            }
+            info!(
+                "Command `{}` for `{}` done, stdout: `{}`, stderr: `{}`",
+                exec.command,
+                event.node,
+                stdout.trim(),
+                stderr.trim()
+            );
+            Ok(())
+        }
+    }
+}
+
+/// Replaces `{name}` placeholders in a single pass, so values can't inject placeholders
+pub fn render(template: &str, values: &[(&str, &str)]) -> String {
+    let mut out = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        out.push_str(&rest[..start]);
+        rest = &rest[start..];
+
+        let value = rest.find('}').and_then(|end| {
+            let name = &rest[1..end];
+            values
+                .iter()
+                .find(|(key, _)| *key == name)
+                .map(|(_, value)| (*value, end))
+        });
+        match value {
+            Some((value, end)) => {
+                out.push_str(value);
+                rest = &rest[end + 1..];
+            }
+            None => {
+                out.push('{');
+                rest = &rest[1..];
+            }
+        }
+    }
+    out.push_str(rest);
+    out
+}
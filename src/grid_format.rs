@@ -0,0 +1,248 @@
+use crate::{GridNodeStatus, GridResponse};
+use std::fmt::Write;
+
+pub const SUPPORTED_FORMATS: &str = "json, text, prom";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GridFormat {
+    Json,
+    Text,
+    Prometheus,
+}
+
+impl GridFormat {
+    /// From `?format=`, falling back to the Accept header, `None` for unknown formats
+    pub fn negotiate(format: Option<&str>, accept: Option<&str>) -> Option<Self> {
+        match format {
+            Some("json") => return Some(GridFormat::Json),
+            Some("text") => return Some(GridFormat::Text),
+            Some("prom") => return Some(GridFormat::Prometheus),
+            Some(_) => return None,
+            None => {}
+        }
+
+        let Some(accept) = accept else {
+            return Some(GridFormat::Json);
+        };
+        // what prometheus scrapers ask for
+        if accept.contains("openmetrics") || accept.contains("version=0.0.4") {
+            return Some(GridFormat::Prometheus);
+        }
+        let preferred = rouille::input::priority_header_preferred(
+            accept,
+            ["application/json", "text/plain"].into_iter(),
+        );
+        Some(match preferred {
+            Some(1) => GridFormat::Text,
+            _ => GridFormat::Json,
+        })
+    }
+}
+
+pub fn status_name(status: GridNodeStatus) -> &'static str {
+    match status {
+        GridNodeStatus::Alive => "alive",
+        GridNodeStatus::Dying => "dying",
+        GridNodeStatus::Dead => "dead",
+        GridNodeStatus::Unknown => "unknown",
+    }
+}
+
+/// Aligned table for reading in a terminal
+pub fn text(grid: &GridResponse) -> String {
+    let rows = grid
+        .nodes
+        .iter()
+        .map(|node| {
+            [
+                node.name.clone(),
+                status_name(node.status).to_string(),
+                node.last_poll
+                    .map(|lp| lp.format("%Y-%m-%d %H:%M:%S").to_string())
+                    .unwrap_or_else(|| "-".to_string()),
+                node.tags.join(","),
+            ]
+        })
+        .collect::<Vec<_>>();
+
+    let header = ["NAME", "STATUS", "LAST POLL", "TAGS"].map(str::to_string);
+    let mut widths = header.clone().map(|h| h.len());
+    for row in rows.iter() {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.len());
+        }
+    }
+
+    let mut out = String::new();
+    for row in [header].iter().chain(rows.iter()) {
+        let line = row
+            .iter()
+            .zip(widths)
+            .map(|(cell, width)| format!("{cell:width$}"))
+            .collect::<Vec<_>>()
+            .join("  ");
+        out.push_str(line.trim_end());
+        out.push('\n');
+    }
+    let _ = writeln!(
+        out,
+        "\nAlive: {}, Dying: {}, Dead: {}, Unknown: {}, Total: {}",
+        grid.summary.alive_nodes,
+        grid.summary.dying_nodes,
+        grid.summary.dead_nodes,
+        grid.summary.unknown_nodes,
+        grid.summary.total_nodes
+    );
+    out
+}
+
+/// Prometheus text exposition of the listed nodes and the grid totals
+pub fn prometheus(grid: &GridResponse) -> String {
+    let mut out = String::new();
+
+    let _ = writeln!(
+        out,
+        "# HELP freecaster_grid_nodes Nodes of the grid by status"
+    );
+    let _ = writeln!(out, "# TYPE freecaster_grid_nodes gauge");
+    for (status, count) in [
+        ("alive", grid.summary.alive_nodes),
+        ("dying", grid.summary.dying_nodes),
+        ("dead", grid.summary.dead_nodes),
+        ("unknown", grid.summary.unknown_nodes),
+    ] {
+        let _ = writeln!(out, "freecaster_grid_nodes{{status=\"{status}\"}} {count}");
+    }
+
+    let _ = writeln!(
+        out,
+        "# HELP freecaster_grid_node_up Whether the node is alive"
+    );
+    let _ = writeln!(out, "# TYPE freecaster_grid_node_up gauge");
+    for node in grid.nodes.iter() {
+        let _ = writeln!(
+            out,
+            "freecaster_grid_node_up{{node=\"{}\",status=\"{}\"}} {}",
+            escape(&node.name),
+            status_name(node.status),
+            u8::from(node.status == GridNodeStatus::Alive)
+        );
+    }
+
+    let _ = writeln!(
+        out,
+        "# HELP freecaster_grid_node_last_poll_timestamp_seconds Last time the node was polled"
+    );
+    let _ = writeln!(
+        out,
+        "# TYPE freecaster_grid_node_last_poll_timestamp_seconds gauge"
+    );
+    for node in grid.nodes.iter() {
+        if let Some(last_poll) = node.last_poll {
+            let _ = writeln!(
+                out,
+                "freecaster_grid_node_last_poll_timestamp_seconds{{node=\"{}\"}} {}",
+                escape(&node.name),
+                last_poll.timestamp()
+            );
+        }
+    }
+
+    let _ = writeln!(
+        out,
+        "# HELP freecaster_grid_node_last_ok_timestamp_seconds Last time the node answered a poll"
+    );
+    let _ = writeln!(
+        out,
+        "# TYPE freecaster_grid_node_last_ok_timestamp_seconds gauge"
+    );
+    for node in grid.nodes.iter() {
+        if let Some(last_ok) = node.last_ok {
+            let _ = writeln!(
+                out,
+                "freecaster_grid_node_last_ok_timestamp_seconds{{node=\"{}\"}} {}",
+                escape(&node.name),
+                last_ok.timestamp()
+            );
+        }
+    }
+
+    let _ = writeln!(
+        out,
+        "# HELP freecaster_grid_node_cert_days_remaining Days until the node's certificate expires"
+    );
+    let _ = writeln!(out, "# TYPE freecaster_grid_node_cert_days_remaining gauge");
+    for node in grid.nodes.iter() {
+        if let Some(days) = node.cert_days_remaining {
+            let _ = writeln!(
+                out,
+                "freecaster_grid_node_cert_days_remaining{{node=\"{}\"}} {days}",
+                escape(&node.name)
+            );
+        }
+    }
+
+    if let Some(cycle) = grid.cycle.as_ref() {
+        for (metric, help, timings) in [
+            (
+                "freecaster_grid_cycle_seconds",
+                "Duration of the latest poll cycle by phase",
+                &cycle.latest,
+            ),
+            (
+                "freecaster_grid_cycle_average_seconds",
+                "Average duration of the recent poll cycles by phase",
+                &cycle.average,
+            ),
+        ] {
+            let _ = writeln!(out, "# HELP {metric} {help}");
+            let _ = writeln!(out, "# TYPE {metric} gauge");
+            for (phase, seconds) in [
+                ("internet_check", timings.internet_check),
+                ("silences", timings.silences),
+                ("polling", timings.polling),
+                ("checks", timings.checks),
+                ("obituaries", timings.obituaries),
+                ("announcements", timings.announcements),
+                ("total", timings.total),
+            ] {
+                let _ = writeln!(out, "{metric}{{phase=\"{phase}\"}} {seconds}");
+            }
+        }
+        let _ = writeln!(
+            out,
+            "# HELP freecaster_grid_poll_time_seconds Configured time between poll cycles"
+        );
+        let _ = writeln!(out, "# TYPE freecaster_grid_poll_time_seconds gauge");
+        let _ = writeln!(out, "freecaster_grid_poll_time_seconds {}", cycle.poll_time);
+    }
+
+    let _ = writeln!(
+        out,
+        "# HELP freecaster_grid_node_obituary_calls_total Obituary calls to the node by outcome"
+    );
+    let _ = writeln!(
+        out,
+        "# TYPE freecaster_grid_node_obituary_calls_total counter"
+    );
+    for node in grid.nodes.iter() {
+        if let Some(calls) = node.obituary_calls.as_ref() {
+            for (result, count) in [("succeeded", calls.succeeded), ("failed", calls.failed)] {
+                let _ = writeln!(
+                    out,
+                    "freecaster_grid_node_obituary_calls_total{{node=\"{}\",result=\"{result}\"}} {count}",
+                    escape(&node.name)
+                );
+            }
+        }
+    }
+
+    out
+}
+
+fn escape(label: &str) -> String {
+    label
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
@@ -0,0 +1,186 @@
+//! Incidents of the grid within a time range, as json or streamed as CSV for reports
+
+use crate::poller::Incident;
+use chrono::{DateTime, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+use std::io::Read;
+
+pub const CSV_HEADER: &str = "node,start,end,duration_seconds,announced_by,silenced,trace\n";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HistoryIncident {
+    pub node: String,
+    pub start: DateTime<Utc>,
+    /// `None` while still going on at the end of the range
+    pub end: Option<DateTime<Utc>>,
+    /// Until the end of the range for incidents going on then
+    pub duration_seconds: i64,
+    pub announced_by: Option<String>,
+    pub silenced: bool,
+    /// Of the poll cycle which found the node dead, to look up in the logs of the grid
+    #[serde(default)]
+    pub trace: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct HistoryResponse {
+    pub from: DateTime<Utc>,
+    pub to: DateTime<Utc>,
+    pub incidents: Vec<HistoryIncident>,
+}
+
+/// `from` and `to` of a query, a unix timestamp, an RFC 3339 time or a date, which means its start
+pub fn parse_time(time: &str) -> Option<DateTime<Utc>> {
+    if let Ok(timestamp) = time.parse::<i64>() {
+        return DateTime::from_timestamp(timestamp, 0);
+    }
+    if let Ok(time) = DateTime::parse_from_rfc3339(time) {
+        return Some(time.to_utc());
+    }
+    let date = NaiveDate::parse_from_str(time, "%Y-%m-%d").ok()?;
+    Some(date.and_hms_opt(0, 0, 0)?.and_utc())
+}
+
+/// Incidents overlapping `from..to`, oldest first, the ones ending after `to` are still going on
+pub fn incidents_between<'a>(
+    incidents: impl IntoIterator<Item = &'a Incident>,
+    from: DateTime<Utc>,
+    to: DateTime<Utc>,
+) -> Vec<HistoryIncident> {
+    let mut found = incidents
+        .into_iter()
+        .filter(|inc| inc.started < to && inc.ended.is_none_or(|ended| ended > from))
+        .map(|inc| {
+            let end = inc.ended.filter(|ended| *ended <= to);
+            HistoryIncident {
+                node: inc.node_name.clone(),
+                start: inc.started,
+                end,
+                duration_seconds: (end.unwrap_or(to) - inc.started).num_seconds(),
+                announced_by: inc.announced_by.clone(),
+                silenced: inc.silenced,
+                trace: inc.trace.clone(),
+            }
+        })
+        .collect::<Vec<_>>();
+    found.sort_by_key(|inc| inc.start);
+    found
+}
+
+/// Suggested name of the CSV file, like `freecaster-grid-history_2026-09-01_2026-10-01.csv`
+pub fn csv_filename(from: DateTime<Utc>, to: DateTime<Utc>) -> String {
+    format!(
+        "freecaster-grid-history_{}_{}.csv",
+        from.format("%Y-%m-%d"),
+        to.format("%Y-%m-%d")
+    )
+}
+
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+fn csv_row(inc: &HistoryIncident) -> String {
+    format!(
+        "{},{},{},{},{},{},{}\n",
+        csv_field(&inc.node),
+        inc.start.to_rfc3339(),
+        inc.end.map(|end| end.to_rfc3339()).unwrap_or_default(),
+        inc.duration_seconds,
+        csv_field(inc.announced_by.as_deref().unwrap_or_default()),
+        inc.silenced,
+        inc.trace.as_deref().unwrap_or_default()
+    )
+}
+
+/// The CSV of incidents, rendered a row at a time as the response is written
+pub struct CsvRows {
+    rows: std::vec::IntoIter<HistoryIncident>,
+    pending: Vec<u8>,
+    offset: usize,
+}
+
+impl CsvRows {
+    pub fn new(incidents: Vec<HistoryIncident>) -> Self {
+        Self {
+            rows: incidents.into_iter(),
+            pending: CSV_HEADER.as_bytes().to_vec(),
+            offset: 0,
+        }
+    }
+}
+
+impl Read for CsvRows {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        while self.offset >= self.pending.len() {
+            let Some(row) = self.rows.next() else {
+                return Ok(0);
+            };
+            self.pending = csv_row(&row).into_bytes();
+            self.offset = 0;
+        }
+        let count = buf.len().min(self.pending.len() - self.offset);
+        buf[..count].copy_from_slice(&self.pending[self.offset..self.offset + count]);
+        self.offset += count;
+        Ok(count)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn incidents_as_csv() {
+        let day = |d: u32| parse_time(&format!("2026-09-{d:02}")).unwrap();
+        let incident = |node: &str, started, ended| Incident {
+            node_name: node.to_string(),
+            started,
+            ended,
+            announced_by: Some("hal9000".to_string()),
+            silenced: false,
+            trace: Some("3f9a1c2e".to_string()),
+        };
+        let incidents = [
+            incident("before", day(1), Some(day(2))),
+            incident("hal, 9001", day(3), Some(day(4))),
+            incident("open", day(5), None),
+            incident("past the end", day(6), Some(day(20))),
+            incident("after", day(12), None),
+        ];
+
+        let found = incidents_between(&incidents, day(3), day(10));
+        assert_eq!(
+            found
+                .iter()
+                .map(|inc| inc.node.as_str())
+                .collect::<Vec<_>>(),
+            ["hal, 9001", "open", "past the end"]
+        );
+        assert_eq!(found[0].duration_seconds, 24 * 60 * 60);
+        assert_eq!(found[2].end, None);
+        assert_eq!(found[2].duration_seconds, 4 * 24 * 60 * 60);
+
+        let mut csv = String::new();
+        CsvRows::new(found).read_to_string(&mut csv).unwrap();
+        let lines = csv.lines().collect::<Vec<_>>();
+        assert_eq!(lines[0], CSV_HEADER.trim_end());
+        assert_eq!(
+            lines[1],
+            "\"hal, 9001\",2026-09-03T00:00:00+00:00,2026-09-04T00:00:00+00:00,86400,hal9000,false,3f9a1c2e"
+        );
+        assert_eq!(lines.len(), 4);
+
+        assert_eq!(parse_time("1788220800"), Some(day(1)));
+        assert_eq!(parse_time("2026-09-01T00:00:00Z"), Some(day(1)));
+        assert_eq!(parse_time("yesterday"), None);
+        assert_eq!(
+            csv_filename(day(1), day(30)),
+            "freecaster-grid-history_2026-09-01_2026-09-30.csv"
+        );
+    }
+}
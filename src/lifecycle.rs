@@ -0,0 +1,115 @@
+use crate::VERSION;
+use crate::announcer::{AnnouncementEvent, AnnouncementKind, Announcers};
+use crate::config::Config;
+use crate::messages;
+use crate::poller::State;
+use std::time::Duration;
+use tracing::{error, warn};
+
+/// Longest the shutdown waits for its announcement
+const SHUTDOWN_ANNOUNCEMENT_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// The announcement of the node's start, `starts` being its starts within the hour, this one included.
+/// Once it restarted too often only that is told, then nothing until the restarts calm down
+fn startup_message(config: &Config, starts: usize) -> Option<String> {
+    let node = config.display_name(&config.name);
+    let restarts = starts.saturating_sub(1);
+    if restarts <= config.max_restarts_per_hour {
+        Some(messages::render(
+            config,
+            "online",
+            &[
+                ("node", node),
+                ("peers", &config.nodes.len().to_string()),
+                ("version", VERSION),
+            ],
+        ))
+    } else if restarts == config.max_restarts_per_hour + 1 {
+        Some(messages::render(
+            config,
+            "crash_loop",
+            &[("node", node), ("count", &restarts.to_string())],
+        ))
+    } else {
+        None
+    }
+}
+
+/// Announces the node starting with `announce_lifecycle`, unless it's in a crash loop
+pub async fn announce_startup(config: &Config, state: &State, starts: usize) {
+    if !config.announce_lifecycle {
+        return;
+    }
+    let Some(message) = startup_message(config, starts) else {
+        warn!(
+            "Restarted {} times within an hour, the start isn't announced",
+            starts - 1
+        );
+        return;
+    };
+    match Announcers::new(config, state) {
+        Ok(announcers) => {
+            announcers
+                .announce(AnnouncementEvent::new(
+                    config,
+                    AnnouncementKind::Started,
+                    &config.name,
+                    message,
+                ))
+                .await
+        }
+        Err(err) => error!("Failed to announce the start: {err:#}"),
+    }
+}
+
+/// Announces the graceful shutdown with `announce_lifecycle`, giving up after a few seconds
+pub async fn announce_shutdown(config: &Config, state: &State) {
+    if !config.announce_lifecycle {
+        return;
+    }
+    let announcers = match Announcers::new(config, state) {
+        Ok(announcers) => announcers,
+        Err(err) => {
+            error!("Failed to announce the shutdown: {err:#}");
+            return;
+        }
+    };
+    let message = messages::render(
+        config,
+        "offline",
+        &[("node", config.display_name(&config.name))],
+    );
+    let event = AnnouncementEvent::new(config, AnnouncementKind::Stopped, &config.name, message);
+    if tokio::time::timeout(SHUTDOWN_ANNOUNCEMENT_TIMEOUT, announcers.announce(event))
+        .await
+        .is_err()
+    {
+        warn!("The shutdown wasn't announced within {SHUTDOWN_ANNOUNCEMENT_TIMEOUT:?}");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::test_config;
+
+    #[test]
+    fn crash_loops_are_told_once() {
+        let config = test_config(
+            "max_restarts_per_hour: 2\nnodes:\n  hal9001:\n    address: http://hal9001.local:3037\n",
+        );
+
+        let online = format!(
+            "Grid announcement, `hal9000` is online, monitoring 1 peers, version {VERSION}"
+        );
+        assert_eq!(startup_message(&config, 1), Some(online.clone()));
+        assert_eq!(startup_message(&config, 3), Some(online));
+        assert_eq!(
+            startup_message(&config, 4).as_deref(),
+            Some(
+                "Grid announcement, `hal9000` restarted 3 times within an hour, its further starts are not announced"
+            )
+        );
+        assert_eq!(startup_message(&config, 5), None);
+    }
+}
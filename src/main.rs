@@ -1,81 +1,184 @@
+mod announcer;
+mod audit;
+mod badge;
+mod cert_check;
+mod cli;
+mod client_ip;
 mod config;
+mod cors;
+mod digest;
+mod discovery;
+mod doctor;
+mod encoding;
+mod events;
+mod exec;
+mod grid_format;
+mod history;
+mod lifecycle;
+mod messages;
+mod mqtt;
+mod peers;
+mod pending;
 mod poller;
+mod proxy;
+mod rate_limit;
+mod reload;
+mod self_check;
+mod silence_id;
+mod state_file;
+mod system_metrics;
+mod systemd;
+mod telegram_bot;
+#[cfg(test)]
+mod testing;
+mod version;
 
-use crate::config::{Config, SSLConfig, load_config};
+use crate::announcer::{AnnouncementKind, Announcers};
+use crate::audit::{AuditAction, AuditEntry, AuditSource};
+use crate::config::{
+    AnnouncementMode, Config, SSLConfig, canonical_node_name, find_config, load_config,
+};
+use crate::events::DEFAULT_EVENTS_PAGE_SIZE;
+use crate::grid_format::{GridFormat, SUPPORTED_FORMATS};
+use crate::history::HistoryResponse;
+use crate::peers::PeerAddresses;
+use crate::pending::{MAX_PENDING, PendingAnnouncementResponse, PendingKind, PendingResponse};
 
-use crate::poller::{NodeSilence, State, poller};
+use crate::poller::{GridMute, NodeSilence, State, StateInner, poller};
+use crate::rate_limit::{RateLimitDecision, RateLimiter};
+use crate::reload::{SharedConfig, reload, set_log_level};
 use anyhow::{Context, Result};
 use chrono::{DateTime, Local, SubsecRound, Utc};
 use env_logger::Builder;
-use log::{LevelFilter, error, info, warn};
-use rand::Rng;
-use rouille::{Request, Server, router, try_or_400};
+pub use freecaster_grid_client::api::{
+    ActiveSilenceResponse, AnnouncedDeathResponse, AnnouncementClaimRequest,
+    AnnouncementClaimResponse, ChannelDeliveries, ConfigResponse, CycleResponse, CycleTimings,
+    DeadNodeResponse, DyingNodeResponse, ErrorResponse, FEATURE_ANNOUNCEMENT_CLAIM,
+    FEATURE_INTROSPECT, FEATURE_MUTE_BROADCAST, FEATURE_NODE_HEADER, FEATURE_OBITUARY_V2,
+    FEATURE_SILENCE_CANCEL, GridHealth, GridNodeResponse, GridNodeStatus, GridResponse,
+    GridSummary, IntrospectResponse, KeyScope, MetricsResponse, MuteBroadcastRequest, MuteResponse,
+    NODE_HEADER, NodeVerdict, NodeVerdictResponse, ObituaryCalls, ObituaryResponse, PollError,
+    ReadinessResponse, SilenceBroadcastRequest, SilenceResponse, SilencesResponse, StatusResponse,
+    TRACE_HEADER, WebuiConfigResponse,
+};
+use log::LevelFilter;
+use rouille::{Request, Server, router};
+use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
+use std::collections::{BTreeSet, HashMap, HashSet};
 use std::env;
-use std::io::Write;
+use std::io::{Read, Write};
 use std::path::PathBuf;
 use std::str::FromStr;
 use std::sync::Arc;
 use tokio::fs;
 use tokio::task::JoinSet;
+use tracing::{debug, error, info, warn};
 
 pub const VERSION: &str = env!("CARGO_PKG_VERSION");
+/// Set by the build script, `unknown` when built without git
+pub const GIT_COMMIT: &str = env!("FREECASTER_GIT_COMMIT");
+const RUSTC_VERSION: &str = env!("FREECASTER_RUSTC_VERSION");
+/// Unix time of the build
+const BUILT_AT: &str = env!("FREECASTER_BUILT_AT");
+/// Comma separated cargo features
+const FEATURES: &str = env!("FREECASTER_FEATURES");
 
+/// What `GET /about` tells about the build and the node
 #[derive(Debug, Serialize, Deserialize)]
-pub struct StatusResponse {
-    pub version: String,
-    pub name: String,
-}
-
-#[derive(Debug, Serialize, Deserialize)]
-pub struct DeadNodeResponse {
+pub struct AboutResponse {
     pub name: String,
-    pub roll: usize,
+    pub version: String,
+    pub commit: String,
+    pub built_at: Option<DateTime<Utc>>,
+    pub rustc: String,
+    pub features: Vec<String>,
+    /// The announcement channel first, then the escalation channels
+    pub announcement_channels: Vec<AnnouncementMode>,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
-pub struct ObituaryResponse {
-    pub dead_nodes: Vec<DeadNodeResponse>,
-}
+impl AboutResponse {
+    fn new(config: &Config) -> Self {
+        let mut announcement_channels = vec![config.announcement_mode];
+        for mode in config
+            .escalation
+            .values()
+            .flat_map(|escalation| escalation.channels.iter())
+        {
+            if !announcement_channels.contains(mode) {
+                announcement_channels.push(*mode);
+            }
+        }
 
-#[derive(Debug, Serialize, Deserialize, Copy, Clone, Eq, PartialEq, Hash)]
-#[serde(rename_all = "snake_case")]
-pub enum GridNodeStatus {
-    Alive,
-    Dying,
-    Dead,
+        Self {
+            name: config.name.clone(),
+            version: VERSION.to_string(),
+            commit: GIT_COMMIT.to_string(),
+            built_at: BUILT_AT
+                .parse()
+                .ok()
+                .and_then(|time| DateTime::from_timestamp(time, 0)),
+            rustc: RUSTC_VERSION.to_string(),
+            features: FEATURES
+                .split(',')
+                .filter(|feature| !feature.is_empty())
+                .map(str::to_string)
+                .collect(),
+            announcement_channels,
+        }
+    }
 }
 
-#[derive(Debug, Serialize, Deserialize)]
-pub struct GridNodeResponse {
-    pub name: String,
-    pub last_poll: Option<DateTime<Utc>>,
-    pub status: GridNodeStatus,
+/// The features this node advertises in its status, some depend on the config
+pub fn protocol_features(config: &Config) -> Vec<String> {
+    let mut features = vec![
+        FEATURE_SILENCE_CANCEL,
+        FEATURE_MUTE_BROADCAST,
+        FEATURE_NODE_HEADER,
+        FEATURE_OBITUARY_V2,
+        FEATURE_ANNOUNCEMENT_CLAIM,
+        FEATURE_INTROSPECT,
+    ];
+    if config.report_system_metrics {
+        features.push("system-metrics");
+    }
+    if config.verify_broadcast_source {
+        features.push("verified-broadcast-source");
+    }
+    features.into_iter().map(str::to_string).collect()
 }
 
 #[derive(Debug, Serialize, Deserialize)]
-pub struct GridResponse {
-    pub nodes: Vec<GridNodeResponse>,
-
-    // totals
-    pub alive_nodes: usize,
-    pub dead_nodes: usize,
-    pub dying_nodes: usize,
-    pub total_nodes: usize,
+pub struct AuditResponse {
+    pub entries: Vec<AuditEntry>,
+    pub total: usize,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
-pub struct SilenceResponse {
-    pub name: String,
-    pub silent_until: DateTime<Utc>,
-}
+const DEFAULT_AUDIT_PAGE_SIZE: usize = 100;
 
-#[derive(Debug, Serialize, Deserialize)]
-pub struct SilenceBroadcastRequest {
-    pub id: usize,
-    pub node_name: String,
-    pub silent_until: DateTime<Utc>,
-}
+const GRID_NODE_FIELDS: &[&str] = &[
+    "name",
+    "display_name",
+    "last_poll",
+    "last_ok",
+    "last_changed",
+    "status",
+    "tags",
+    "cert_days_remaining",
+    "system",
+    "poll_error",
+    "advertised_address",
+    "announced_by",
+    "announced_at",
+    "announcement_roll",
+    "obituary_calls",
+    "last_inbound",
+    "version",
+    "version_mismatch",
+    "flapping",
+    "announced",
+    "features",
+];
 
 #[tokio::main]
 async fn main() -> Result<()> {
@@ -92,7 +195,10 @@ async fn main() -> Result<()> {
             )
         })
         .filter(None, LevelFilter::Info)
+        // own lines pass up to the configured level, set once the config is loaded
+        .filter(Some("freecaster_grid"), LevelFilter::Trace)
         .init();
+    log::set_max_level(LevelFilter::Info);
 
     // Force-exit on any panic so a supervisor can restart cleanly.
     // Without this, a panic while holding the state Mutex poisons it and the
@@ -104,277 +210,2769 @@ async fn main() -> Result<()> {
         std::process::exit(1);
     }));
 
-    info!("Starting freecaster-grid v{VERSION}");
-    let args: Vec<String> = env::args().collect();
-
-    if args.len() != 2 {
-        warn!("Running without config file")
+    let mut args: Vec<String> = env::args().collect();
+    let skip_announce_check = args.iter().any(|arg| arg == "--skip-announce-check");
+    args.retain(|arg| arg != "--skip-announce-check");
+    match args.get(1).map(String::as_str) {
+        Some("grid") => std::process::exit(cli::grid(&args[2..]).await),
+        Some("validate") => std::process::exit(cli::validate(&args[2..]).await),
+        Some("init") => std::process::exit(cli::init(&args[2..])),
+        Some("doctor") => std::process::exit(doctor::doctor(&args[2..]).await),
+        #[cfg(feature = "json_schema")]
+        Some("schema") => std::process::exit(cli::schema(&args[2..])),
+        _ => {}
     }
 
-    let config_path = if args.len() >= 2 {
-        Some(PathBuf::from(&args[1]))
-    } else {
-        None
-    };
+    info!("Starting freecaster-grid v{VERSION} ({GIT_COMMIT})");
 
-    // Load and parse config
-    let mut config = load_config(config_path).await?;
+    let config_path = find_config(args.get(1).map(String::as_str));
+    match config_path.as_ref() {
+        Some(path) => info!("Using config file `{}`", path.display()),
+        None => warn!("Running without config file"),
+    }
 
-    // filter myself out
-    config.nodes.retain(|name, _| *name != config.name);
+    // Load and parse config
+    let mut config = load_config(config_path.clone()).await?;
+    config.skip_announce_check = skip_announce_check;
+    set_log_level(config.log_level);
 
     let config = Arc::new(config);
+    let shared_config = SharedConfig::new(config.clone());
 
     info!("Loaded configuration, this node is: {}", config.name);
+    if config.version_mismatch(&config.name, VERSION) {
+        error!(
+            "This node runs version `{VERSION}`, but `{}` is expected",
+            config.expected_version(&config.name).unwrap_or_default()
+        );
+    }
 
     let mut js = JoinSet::new();
     let server_config = config.clone();
+    let server_shared_config = shared_config.clone();
+    let reload_path = config_path.clone();
 
-    let state = State::new();
+    let state = State::new(&config);
+    let mut starts = vec![];
+    if let Some(state_file) = config.state_file.as_ref() {
+        match state_file::load(&state_file.path, state_file.max_age, Utc::now()) {
+            Ok(saved) => {
+                state.restore_announced(saved.announced);
+                starts = saved.starts;
+            }
+            Err(err) => {
+                error!("Failed to load the state file, announced deaths start over: {err:#}")
+            }
+        }
+    }
+    let starts = state.record_start(&config, starts, Utc::now());
     let server_state = state.clone();
 
     let ssl = server_config.server.ssl.clone();
 
-    let poller_cert = if let Some(SSLConfig { cert_path, .. }) = &server_config.server.ssl {
-        Some(
-            fs::read(cert_path)
+    // peers are never rate limited
+    let peer_addresses = PeerAddresses::default();
+    peer_addresses.refresh(&config.nodes).await;
+    let rate_limiter = Arc::new(RateLimiter::new(
+        config.rate_limit.clone(),
+        peer_addresses.clone(),
+    ));
+    let server_peer_addresses = peer_addresses.clone();
+
+    let trust_anchors = config.load_ca_certificates().await?;
+    let announcers = Announcers::new(&config, &state)?;
+    let mqtt = mqtt::connect(&config)?;
+    announcer::check_telegram(&config, true).await?;
+
+    js.spawn(async move {
+        let listener_address = format!(
+            "{}:{}",
+            server_config.server.ip_address, server_config.server.port
+        );
+        info!("Starting server on {}", listener_address);
+
+        let started_state = server_state.clone();
+        let router = app(
+            server_shared_config,
+            server_state,
+            rate_limiter,
+            server_peer_addresses,
+            reload_path,
+            tokio::runtime::Handle::current(),
+        );
+
+        if let Some(SSLConfig {
+            cert_path,
+            key_path,
+        }) = &ssl
+        {
+            info!("Starting server with SSL");
+            let cert = fs::read(cert_path)
                 .await
                 .with_context(|| format!("Failed to read certificate from {}", cert_path))
-                .expect("Failed to read certificate"),
-        )
-    } else {
-        None
-    };
+                .expect("Failed to read certificate");
+            let key = fs::read(key_path)
+                .await
+                .with_context(|| format!("Failed to read key from {}", key_path))
+                .expect("Failed to read key");
+
+            let server = Server::new_ssl(listener_address, router, cert, key)
+                .expect("Failed to start server");
+            started_state.server_started();
+            // the server loop blocks, keep it off the async workers
+            tokio::task::spawn_blocking(move || server.run())
+                .await
+                .expect("Server failed")
+        } else {
+            info!("Starting server without SSL");
+            let server = Server::new(listener_address, router).expect("Failed to start server");
+            started_state.server_started();
+            // the server loop blocks, keep it off the async workers
+            tokio::task::spawn_blocking(move || server.run())
+                .await
+                .expect("Server failed")
+        }
+    });
 
+    let lifecycle_config = config.clone();
+    let lifecycle_state = state.clone();
     js.spawn(async move {
-        let listener_address = format!("{}:{}", server_config.server.ip_address, server_config.server.port);
-        info!("Starting server on {}", listener_address);
+        lifecycle::announce_startup(&lifecycle_config, &lifecycle_state, starts).await;
+    });
 
-        let webui_enabled = server_config.webui_enabled;
-        let router = move |request: &Request| {
-            // Serve /webui and static files if enabled
-            if webui_enabled {
-                if request.url() == "/webui" || request.url() == "/webui/" {
-                    return rouille::Response::html(include_str!("webui/index.html"));
-                }
-                if let Some(path) = request.url().strip_prefix("/webui/") {
-                    match path {
-                        "app.js" => {
-                            return rouille::Response::from_data("application/javascript", include_str!("webui/app.js").as_bytes());
-                        },
-                        "style.css" => {
-                            return rouille::Response::from_data("text/css", include_str!("webui/style.css"));
-                        },
-                        "freecaster.svg" => {
-                            return rouille::Response::from_data("image/svg+xml", include_bytes!("webui/freecaster.svg").as_ref());
-                        },
-                        "freecaster-dark.svg" => {
-                            return rouille::Response::from_data("image/svg+xml", include_bytes!("webui/freecaster-dark.svg").as_ref());
-                        },
-                        "freecaster-light.svg" => {
-                            return rouille::Response::from_data("image/svg+xml", include_bytes!("webui/freecaster-light.svg").as_ref());
-                        },
-                        _ => {}
+    let poller_config = shared_config.clone();
+    let poller_state = state.clone();
+
+    js.spawn(async move {
+        poller(poller_config, trust_anchors, poller_state, announcers)
+            .await
+            .expect("Poller failed");
+    });
+
+    let discovery_config = shared_config.clone();
+    let discovery_state = state.clone();
+    let discovery_peer_addresses = peer_addresses.clone();
+
+    js.spawn(async move {
+        discovery::discover(discovery_config, discovery_state, discovery_peer_addresses)
+            .await
+            .expect("Discovery failed");
+    });
+
+    let mqtt_config = shared_config.clone();
+    let mqtt_state = state.clone();
+
+    js.spawn(async move {
+        mqtt::run(mqtt, mqtt_config, mqtt_state)
+            .await
+            .expect("MQTT failed");
+    });
+
+    let bot_config = config.clone();
+    let bot_state = state.clone();
+
+    js.spawn(async move {
+        telegram_bot::bot_commands(bot_config, bot_state)
+            .await
+            .expect("Telegram bot failed");
+    });
+
+    #[cfg(unix)]
+    {
+        let reload_config = shared_config.clone();
+        let reload_state = state.clone();
+        js.spawn(async move {
+            let mut hangup = tokio::signal::unix::signal(tokio::signal::unix::SignalKind::hangup())
+                .expect("Failed to listen for SIGHUP");
+            while hangup.recv().await.is_some() {
+                info!("Reloading the config on SIGHUP");
+                let source = AuditSource::Signal {
+                    name: "SIGHUP".to_string(),
+                };
+                // the outcome is logged and audited
+                let _ = reload(
+                    config_path.clone(),
+                    &reload_config,
+                    &reload_state,
+                    &peer_addresses,
+                    source,
+                )
+                .await;
+            }
+        });
+    }
+
+    tokio::select! {
+        _ = js.join_all() => {}
+        _ = shutdown_signal() => {
+            info!("Shutting down");
+            systemd::notify("STOPPING=1");
+            lifecycle::announce_shutdown(&shared_config.get(), &state).await;
+            // the server loop blocks its thread, the runtime can't wind it down
+            std::process::exit(0);
+        }
+    }
+    Ok(())
+}
+
+/// The API of a node, `reload_path` is read again on `POST /reload`
+fn app(
+    config: SharedConfig,
+    server_state: State,
+    rate_limiter: Arc<RateLimiter>,
+    peer_addresses: PeerAddresses,
+    reload_path: Option<PathBuf>,
+    // reloads run on the async side, the server's threads wait for them
+    runtime: tokio::runtime::Handle,
+) -> impl Fn(&Request) -> rouille::Response + Send + Sync + 'static {
+    let router_config = config.clone();
+    let response_config = config;
+    let router = move |request: &Request| {
+        let server_config = router_config.get();
+        // behind a reverse proxy every call would share its limit
+        let client = client_ip::resolve(&server_config.server, request);
+        if let RateLimitDecision::Limited { retry_after } = rate_limiter.check(client) {
+            warn!("Rate limited request from {client}");
+            return rouille::Response::text("Too many requests")
+                .with_status_code(429)
+                .with_additional_header(
+                    "Retry-After",
+                    (retry_after.as_secs_f64().ceil() as u64).max(1).to_string(),
+                );
+        }
+
+        // Serve /webui and static files if enabled
+        if server_config.webui_enabled {
+            if request.url() == "/webui" || request.url() == "/webui/" {
+                return rouille::Response::html(include_str!("webui/index.html"));
+            }
+            if let Some(path) = request.url().strip_prefix("/webui/") {
+                match path {
+                    "app.js" => {
+                        return rouille::Response::from_data(
+                            "application/javascript",
+                            include_str!("webui/app.js").as_bytes(),
+                        );
+                    }
+                    "style.css" => {
+                        return rouille::Response::from_data(
+                            "text/css",
+                            include_str!("webui/style.css"),
+                        );
+                    }
+                    "freecaster.svg" => {
+                        return rouille::Response::from_data(
+                            "image/svg+xml",
+                            include_bytes!("webui/freecaster.svg").as_ref(),
+                        );
+                    }
+                    "freecaster-dark.svg" => {
+                        return rouille::Response::from_data(
+                            "image/svg+xml",
+                            include_bytes!("webui/freecaster-dark.svg").as_ref(),
+                        );
                     }
+                    "freecaster-light.svg" => {
+                        return rouille::Response::from_data(
+                            "image/svg+xml",
+                            include_bytes!("webui/freecaster-light.svg").as_ref(),
+                        );
+                    }
+                    _ => {}
                 }
             }
-            router!(request,
-                (GET) (/) => {
-                    let user_agent = request.header("User-Agent").unwrap_or("Unknown");
-                    info!("Called for status ua: `{user_agent}`");
-
-                    rouille::Response::json(&StatusResponse {
-                        name: server_config.name.clone(),
-                        version: VERSION.to_string(),
-                    })
-                        .with_status_code(200)
-                },
+        }
+        router!(request,
+            (GET) (/) => {
+                handle_status(&server_config, &server_state, request)
+            },
 
-                (GET) (/obituary/{key: String}) => {
-                    info!("Called for obituary");
-                    if key != server_config.secret_key {
-                        warn!("Invalid secret key");
-                        return rouille::Response::empty_406();
-                    }
+            // the server leaves out the body of HEAD responses
+            (HEAD) (/) => {
+                handle_status(&server_config, &server_state, request)
+            },
 
-                    let gr = server_state.lock().expect("Failed to lock state");
-                    let dead_nodes = gr.node_state.iter().filter(|fs| fs.is_dead()).map(|fs| DeadNodeResponse {
-                        name: fs.name.clone(),
-                        roll: fs.local_announcement_roll.unwrap_or(0),
-                    })
-                        .collect();
-
-                    rouille::Response::json(&ObituaryResponse {
-                        dead_nodes,
-                    })
-                        .with_status_code(200)
-                },
+            // liveness probes, too frequent for the logs
+            (GET) (/ping) => {
+                rouille::Response::empty_204().with_unique_header("Cache-Control", "no-store")
+            },
 
-                (POST) (/silence-broadcast/{key: String}) => {
-                    info!("Called for silence broadcast");
-                    if key != server_config.secret_key {
-                        warn!("Invalid secret key");
-                        return rouille::Response::empty_406();
-                    }
+            (HEAD) (/ping) => {
+                rouille::Response::empty_204().with_unique_header("Cache-Control", "no-store")
+            },
 
-                    let body: SilenceBroadcastRequest = try_or_400!(rouille::input::json_input(request));
-                    let mut gr = server_state.lock().expect("Failed to lock state");
-                    let found = gr.silences.iter().any(|sl| sl.id == body.id);
-                    if found {
-                        warn!("Silence already exists");
-                        return rouille::Response::empty_204();
-                    }
+            (GET) (/healthz) => {
+                rouille::Response::text("ok")
+            },
 
-                    // add otherwise
-                    gr.silences.push(NodeSilence {
-                        id: body.id,
-                        node_name: body.node_name,
-                        silent_until: body.silent_until,
-                        broadcasted: true,
-                    });
-                    rouille::Response::empty_204()
-                },
+            (GET) (/about/{key: String}) => {
+                debug!("Called for about");
+                if key_scope(&server_config, &key) != Some(KeyScope::Full) {
+                    warn!("Invalid secret key");
+                    return rouille::Response::empty_406();
+                }
 
-                (GET) (/silence/{key: String}/{time: String}) => {
-                    info!("Called for silence (self)");
-                    handle_silence(&server_config, &server_state, key, time, None)
-                },
+                rouille::Response::json(&AboutResponse::new(&server_config))
+            },
 
-                (GET) (/silence/{key: String}/{time: String}/{target: String}) => {
-                    info!("Called for silence (target: {target})");
-                    handle_silence(&server_config, &server_state, key, time, Some(target))
-                },
+            (GET) (/readyz) => {
+                let last_cycle_completed = server_state.lock().expect("Failed to lock state").last_cycle_completed;
+                let reason = match last_cycle_completed {
+                    None => Some("No poll cycle completed yet"),
+                    Some(completed) if Utc::now() - completed > server_config.stale_after() => {
+                        Some("Last poll cycle is stale")
+                    },
+                    Some(_) => None,
+                };
 
-                (GET) (/grid/{key: String}) => {
-                    info!("Called for grid");
-                    if key != server_config.secret_key {
-                        warn!("Invalid secret key");
-                        return rouille::Response::empty_406();
-                    }
+                rouille::Response::json(&ReadinessResponse {
+                    ready: reason.is_none(),
+                    reason: reason.map(str::to_string),
+                })
+                    .with_status_code(if reason.is_none() { 200 } else { 503 })
+            },
+
+            (GET) (/obituary/v2/{key: String}) => {
+                handle_obituary(&server_config, &server_state, &peer_addresses, request, key, true)
+            },
 
-                    let gr = server_state.lock().expect("Failed to lock state");
-                    let mut resp = GridResponse {
-                        nodes: Default::default(),
-                        alive_nodes: 1,dead_nodes: 0,dying_nodes: 0,total_nodes: 1, // this node included
-                    };
+            (GET) (/obituary/{key: String}) => {
+                handle_obituary(&server_config, &server_state, &peer_addresses, request, key, false)
+            },
 
+            (POST) (/silence-broadcast/{key: String}) => {
+                let peer = calling_peer(&server_config, &server_state, request);
+                let trace = request_trace(request);
+                debug!("Called for silence broadcast by {}{}", peer_display(&peer), trace_display(&trace));
+                if !is_allowed_peer(&server_config, &peer_addresses, request) {
+                    return rouille::Response::text("Forbidden").with_status_code(403);
+                }
+                if key_scope(&server_config, &key) != Some(KeyScope::Full) {
+                    warn!("Invalid secret key");
+                    return rouille::Response::empty_406();
+                }
 
-                    // add this node
-                    resp.nodes.push(GridNodeResponse {
-                        name: server_config.name.clone(),
-                        last_poll: None,
-                        status: GridNodeStatus::Alive,
+                let mut body: SilenceBroadcastRequest = match read_json_body(request, server_config.server.max_body_size) {
+                    Ok(body) => body,
+                    Err(resp) => return resp,
+                };
+                // a misconfigured peer may pad the name or write it in another case
+                let grid = discovery::grid_nodes(&server_config, &server_state);
+                body.node_name = canonical_node_name(
+                    &body.node_name,
+                    grid.keys().map(String::as_str).chain([server_config.name.as_str()]),
+                );
+                let source = AuditSource::Peer {
+                    node_name: body.origin.clone(),
+                    client_ip: client_ip::resolve(&server_config.server, request).to_string(),
+                };
+                let check = check_broadcast_sender(&server_config, &server_state, &peer_addresses, request, body.origin.as_deref())
+                    .and_then(|suspicious| match validate_silence(&server_config, &server_state, &body.node_name, body.silent_until, Utc::now()) {
+                        Ok(()) => Ok(suspicious),
+                        Err(SilenceRejection::UnknownNode) => Err((422, SilenceRejection::UnknownNode.reason().to_string())),
+                        Err(rejection) => Err((400, rejection.reason().to_string())),
                     });
 
-                    for fs in gr.node_state.iter() {
-                        let node_resp = fs.to_api_response();
-                        match node_resp.status {
-                            GridNodeStatus::Alive => {
-                                resp.alive_nodes += 1;
-                            },
-                            GridNodeStatus::Dying => {
-                                resp.dying_nodes += 1;
-                            },
-                            GridNodeStatus::Dead => {
-                                resp.dead_nodes += 1;
-                            }
+                let mut gr = server_state.lock().expect("Failed to lock state");
+                match check {
+                    Err((status, reason)) => {
+                        warn!("Rejected silence broadcast for `{}`: {reason}", body.node_name);
+                        if status == 422 {
+                            gr.audit.record_traced(source, AuditAction::SilenceBroadcastRejected {
+                                node_name: body.node_name,
+                                reason: reason.clone(),
+                            }, trace);
                         }
-                        resp.total_nodes += 1;
-                        resp.nodes.push(node_resp);
-                    }
-                    resp.nodes.sort_by(|a, b| a.name.cmp(&b.name));
+                        return rouille::Response::text(reason).with_status_code(status);
+                    },
+                    Ok(Some(reason)) => {
+                        warn!("Accepting suspicious silence broadcast for `{}`: {reason}", body.node_name);
+                        gr.suspicious_broadcasts += 1;
+                        gr.audit.record_traced(source.clone(), AuditAction::SilenceBroadcastSuspicious {
+                            node_name: body.node_name.clone(),
+                            reason,
+                        }, trace.clone());
+                    },
+                    Ok(None) => {},
+                }
+                receive_silence_broadcast(&mut gr, body, source, trace)
+            },
 
-                    rouille::Response::json(&resp)
-                        .with_status_code(200)
-                },
+            (GET) (/silence/{key: String}/{time: String}) => {
+                info!("Called for silence (self)");
+                handle_silence(&server_config, &server_state, request, key, time, None)
+            },
 
-                _ => rouille::Response::empty_404()
-            )
-        };
+            (GET) (/silence/{key: String}/{time: String}/{target: String}) => {
+                info!("Called for silence (target: {target})");
+                handle_silence(&server_config, &server_state, request, key, time, Some(target))
+            },
 
-        if let Some(SSLConfig { cert_path, key_path}) = &ssl {
-            info!("Starting server with SSL");
-            let cert = fs::read(cert_path).await
-                .with_context(|| format!("Failed to read certificate from {}", cert_path))
-                .expect("Failed to read certificate");
-            let key = fs::read(key_path).await
-                .with_context(|| format!("Failed to read key from {}", key_path))
-                .expect("Failed to read key");
+            (GET) (/unsilence/{key: String}) => {
+                info!("Called for unsilence (self)");
+                handle_unsilence(&server_config, &server_state, request, key, None)
+            },
 
-            Server::new_ssl(listener_address, router , cert, key)
-                .expect("Failed to start server")
-                .run()
-        } else {
-            info!("Starting server without SSL");
-            Server::new(listener_address, router)
-                .expect("Failed to start server")
-                .run()
+            (GET) (/unsilence/{key: String}/{target: String}) => {
+                info!("Called for unsilence (target: {target})");
+                handle_unsilence(&server_config, &server_state, request, key, Some(target))
+            },
+
+            (GET) (/silences/{key: String}) => {
+                debug!("Called for silences");
+                if key_scope(&server_config, &key) != Some(KeyScope::Full) {
+                    warn!("Invalid secret key");
+                    return rouille::Response::empty_406();
+                }
+
+                let now = Utc::now();
+                let gr = server_state.lock().expect("Failed to lock state");
+                let (local, silences) = gr.silences.iter().filter(|sl| !sl.cancelled && sl.silent_until > now).map(|sl| (sl.local, ActiveSilenceResponse {
+                    id: sl.id.clone(),
+                    node_name: sl.node_name.clone(),
+                    silent_until: sl.silent_until,
+                    broadcasted: sl.broadcasted,
+                }))
+                    .partition::<Vec<_>, _>(|(local, _)| *local);
+
+                rouille::Response::json(&SilencesResponse {
+                    silences: silences.into_iter().map(|(_, sl)| sl).collect(),
+                    local: local.into_iter().map(|(_, sl)| sl).collect(),
+                })
+            },
+
+            (POST) (/mute/{key: String}/{time: String}) => {
+                info!("Called for mute");
+                handle_mute(&server_config, &server_state, request, key, time)
+            },
+
+            (DELETE) (/mute/{key: String}) => {
+                info!("Called for unmute");
+                handle_unmute(&server_config, &server_state, request, key)
+            },
+
+            (POST) (/reload/{key: String}) => {
+                info!("Called for reload");
+                if key_scope(&server_config, &key) != Some(KeyScope::Full) {
+                    warn!("Invalid secret key");
+                    return rouille::Response::empty_406();
+                }
+
+                let source = AuditSource::Api { client_ip: client_ip::resolve(&server_config.server, request).to_string() };
+                let result = runtime.block_on(reload(reload_path.clone(), &router_config, &server_state, &peer_addresses, source));
+                let last_reload = server_state.lock().expect("Failed to lock state").last_reload.clone();
+                rouille::Response::json(&last_reload)
+                    .with_status_code(if result.is_ok() { 200 } else { 422 })
+            },
+
+            (POST) (/claim/{key: String}) => {
+                let peer = calling_peer(&server_config, &server_state, request);
+                debug!("Called for an announcement claim by {}{}", peer_display(&peer), trace_display(&request_trace(request)));
+                if !is_allowed_peer(&server_config, &peer_addresses, request) {
+                    return rouille::Response::text("Forbidden").with_status_code(403);
+                }
+                if key_scope(&server_config, &key) != Some(KeyScope::Full) {
+                    warn!("Invalid secret key");
+                    return rouille::Response::empty_406();
+                }
+
+                let body: AnnouncementClaimRequest = match read_json_body(request, server_config.server.max_body_size) {
+                    Ok(body) => body,
+                    Err(resp) => return resp,
+                };
+                let answer: AnnouncementClaimResponse = server_state
+                    .lock()
+                    .expect("Failed to lock state")
+                    .record_claim(&body, Utc::now());
+                rouille::Response::json(&answer)
+            },
+
+            (POST) (/mute-broadcast/{key: String}) => {
+                let peer = calling_peer(&server_config, &server_state, request);
+                let trace = request_trace(request);
+                debug!("Called for mute broadcast by {}{}", peer_display(&peer), trace_display(&trace));
+                if !is_allowed_peer(&server_config, &peer_addresses, request) {
+                    return rouille::Response::text("Forbidden").with_status_code(403);
+                }
+                if key_scope(&server_config, &key) != Some(KeyScope::Full) {
+                    warn!("Invalid secret key");
+                    return rouille::Response::empty_406();
+                }
+
+                let body: MuteBroadcastRequest = match read_json_body(request, server_config.server.max_body_size) {
+                    Ok(body) => body,
+                    Err(resp) => return resp,
+                };
+                let client_ip = client_ip::resolve(&server_config.server, request).to_string();
+                let mut gr = server_state.lock().expect("Failed to lock state");
+                receive_mute_broadcast(&mut gr, body, client_ip, trace)
+            },
+
+            (GET) (/metrics/{key: String}) => {
+                debug!("Called for metrics");
+                if key_scope(&server_config, &key) != Some(KeyScope::Full) {
+                    warn!("Invalid secret key");
+                    return rouille::Response::empty_406();
+                }
+
+                let gr = server_state.lock().expect("Failed to lock state");
+                let obituary_calls = gr.node_state.iter()
+                    .filter_map(|ns| Some((ns.name.clone(), ns.obituary_calls.clone()?)))
+                    .collect();
+                rouille::Response::json(&MetricsResponse {
+                    rate_limited_requests: rate_limiter.rejected(),
+                    suspicious_silence_broadcasts: gr.suspicious_broadcasts,
+                    obituary_calls,
+                    cycle: gr.cycle_response(server_config.poll_interval()),
+                    announcements: gr.announcement_windows.iter()
+                        .map(|(mode, window)| (mode.as_str().to_string(), window.deliveries().clone()))
+                        .collect(),
+                })
+                    .with_status_code(200)
+            },
+
+            (GET) (/audit/{key: String}) => {
+                info!("Called for audit");
+                if key_scope(&server_config, &key) != Some(KeyScope::Full) {
+                    warn!("Invalid secret key");
+                    return rouille::Response::empty_406();
+                }
+
+                let offset = request.get_param("offset").and_then(|v| v.parse().ok()).unwrap_or(0);
+                let limit = request.get_param("limit").and_then(|v| v.parse().ok()).unwrap_or(DEFAULT_AUDIT_PAGE_SIZE);
+
+                let gr = server_state.lock().expect("Failed to lock state");
+                rouille::Response::json(&AuditResponse {
+                    entries: gr.audit.page(offset, limit),
+                    total: gr.audit.len(),
+                })
+                    .with_status_code(200)
+            },
+
+            (GET) (/events/{key: String}) => {
+                debug!("Called for events");
+                if key_scope(&server_config, &key) != Some(KeyScope::Full) {
+                    warn!("Invalid secret key");
+                    return rouille::Response::empty_406();
+                }
+
+                let since = request.get_param("since").and_then(|v| v.parse().ok()).unwrap_or(0);
+                let limit = request.get_param("limit").and_then(|v| v.parse().ok()).unwrap_or(DEFAULT_EVENTS_PAGE_SIZE);
+
+                let gr = server_state.lock().expect("Failed to lock state");
+                rouille::Response::json(&gr.events.since(since, limit))
+                    .with_status_code(200)
+            },
+
+            (GET) (/pending/{key: String}) => {
+                debug!("Called for pending announcements");
+                if key_scope(&server_config, &key) != Some(KeyScope::Full) {
+                    warn!("Invalid secret key");
+                    return rouille::Response::empty_406();
+                }
+
+                let kind = match request.get_param("kind").map(|kind| PendingKind::from_str(&kind)) {
+                    Some(Ok(kind)) => Some(kind),
+                    Some(Err(error)) => return bad_request(error),
+                    None => None,
+                };
+                let nodes = discovery::grid_nodes(&server_config, &server_state);
+                let gr = server_state.lock().expect("Failed to lock state");
+                rouille::Response::json(&pending_announcements(&server_config, &nodes, &gr, kind))
+                    .with_status_code(200)
+            },
+
+            (GET) (/history/{key: String}) => {
+                info!("Called for history");
+                if key_scope(&server_config, &key).is_none() {
+                    warn!("Invalid secret key");
+                    return rouille::Response::empty_406();
+                }
+
+                handle_history(&server_state, request)
+            },
+
+            (GET) (/grid/{key: String}) => {
+                debug!("Called for grid");
+                if key_scope(&server_config, &key).is_none() {
+                    warn!("Invalid secret key");
+                    return rouille::Response::empty_406();
+                }
+
+                handle_grid(&server_config, &server_state, request)
+            },
+
+            (GET) (/introspect/{key: String}) => {
+                handle_introspect(&server_config, &server_state, &peer_addresses, request, key)
+            },
+
+            (GET) (/badge/{file: String}) => {
+                handle_badge(&server_config, &server_state, &file, None)
+            },
+
+            (GET) (/badge/{key: String}/{file: String}) => {
+                handle_badge(&server_config, &server_state, &key, Some(&file))
+            },
+
+            (GET) (/webui-config/{key: String}) => {
+                let Some(scope) = key_scope(&server_config, &key) else {
+                    warn!("Invalid secret key");
+                    return rouille::Response::empty_406();
+                };
+                rouille::Response::json(&webui_config(scope))
+            },
+
+            _ => rouille::Response::empty_404()
+        )
+    };
+    move |request: &Request| {
+        let response_config = response_config.get();
+        let cors_config = response_config.server.cors.as_ref();
+        if let Some(response) = cors::preflight(cors_config, request) {
+            return response;
+        }
+        let response = encoding::finish(&response_config.server, request, router(request))
+            .with_unique_header("Server", format!("freecaster-grid/{VERSION}"));
+        cors::apply(cors_config, request, response)
+    }
+}
+
+/// Which of the keys `key` is, `None` for neither
+fn key_scope(config: &Config, key: &str) -> Option<KeyScope> {
+    if key == config.secret_key {
+        Some(KeyScope::Full)
+    } else if config.readonly_key.as_deref() == Some(key) {
+        Some(KeyScope::ReadOnly)
+    } else {
+        None
+    }
+}
+
+/// Actions of the webui needing the secret key
+const WEBUI_ACTIONS: &[&str] = &["silence", "unsilence", "mute", "reload"];
+
+fn webui_config(scope: KeyScope) -> WebuiConfigResponse {
+    let actions = match scope {
+        KeyScope::Full => WEBUI_ACTIONS
+            .iter()
+            .map(|action| action.to_string())
+            .collect(),
+        KeyScope::ReadOnly => vec![],
+    };
+    WebuiConfigResponse { scope, actions }
+}
+
+async fn shutdown_signal() {
+    #[cfg(unix)]
+    {
+        let mut terminate =
+            tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate())
+                .expect("Failed to listen for SIGTERM");
+        tokio::select! {
+            _ = tokio::signal::ctrl_c() => {}
+            _ = terminate.recv() => {}
         }
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = tokio::signal::ctrl_c().await;
+    }
+}
+
+/// Queued, retried and escalated announcements in one list, the earliest `MAX_PENDING` of `kind`
+fn pending_announcements(
+    config: &Config,
+    nodes: &HashMap<String, config::NodeConfig>,
+    gr: &StateInner,
+    kind: Option<PendingKind>,
+) -> PendingResponse {
+    let escalations = gr.node_state.iter().filter_map(|ns| {
+        let escalation = config.escalation.get(&nodes.get(&ns.name)?.severity)?;
+        Some(PendingAnnouncementResponse {
+            kind: PendingKind::Escalation,
+            event: AnnouncementKind::Escalated.as_str().to_string(),
+            target: ns.name.clone(),
+            channels: escalation.channels.clone(),
+            due: ns.escalation_due?,
+            attempts: 0,
+        })
     });
+    let mut pending = gr
+        .pending_announcements
+        .sorted()
+        .into_iter()
+        .map(PendingAnnouncementResponse::from)
+        .collect::<Vec<_>>();
+    // stable, so the arrival order holds among the same due times
+    pending.extend(escalations);
+    pending.retain(|item| kind.is_none_or(|kind| item.kind == kind));
+    pending.sort_by_key(|item| item.due);
+    let total = pending.len();
+    pending.truncate(MAX_PENDING);
+    PendingResponse {
+        pending,
+        total,
+        capacity: MAX_PENDING,
+    }
+}
 
-    let poller_config = config.clone();
-    let poller_state = state.clone();
+/// `v2` adds this node's verdict on every node, so peers can tell its silence from an alive vote
+fn handle_obituary(
+    server_config: &Config,
+    server_state: &State,
+    peer_addresses: &PeerAddresses,
+    request: &Request,
+    key: String,
+    v2: bool,
+) -> rouille::Response {
+    let peer = calling_peer(server_config, server_state, request);
+    debug!(
+        "Called for obituary{} by {}{}",
+        if v2 { " v2" } else { "" },
+        peer_display(&peer),
+        trace_display(&request_trace(request))
+    );
+    if !is_allowed_peer(server_config, peer_addresses, request) {
+        return rouille::Response::text("Forbidden").with_status_code(403);
+    }
+    if key_scope(server_config, &key) != Some(KeyScope::Full) {
+        warn!("Invalid secret key");
+        return rouille::Response::empty_406();
+    }
 
-    js.spawn(async move {
-        poller(poller_config, poller_cert, poller_state)
-            .await
-            .expect("Poller failed");
+    let gr = server_state.lock().expect("Failed to lock state");
+    rouille::Response::json(&obituary(&gr, Utc::now(), v2)).with_status_code(200)
+}
+
+/// Peers cross-checking their view, either key is enough
+fn handle_introspect(
+    server_config: &Config,
+    server_state: &State,
+    peer_addresses: &PeerAddresses,
+    request: &Request,
+    key: String,
+) -> rouille::Response {
+    let peer = calling_peer(server_config, server_state, request);
+    debug!(
+        "Called for introspect by {}{}",
+        peer_display(&peer),
+        trace_display(&request_trace(request))
+    );
+    if !is_allowed_peer(server_config, peer_addresses, request) {
+        return rouille::Response::text("Forbidden").with_status_code(403);
+    }
+    if key_scope(server_config, &key).is_none() {
+        warn!("Invalid secret key");
+        return rouille::Response::empty_406();
+    }
+
+    let gr = server_state.lock().expect("Failed to lock state");
+    rouille::Response::json(&gr.introspect(&server_config.name, Utc::now()))
+}
+
+fn obituary(gr: &StateInner, now: DateTime<Utc>, v2: bool) -> ObituaryResponse {
+    let broadcast_silenced = |name: &str| {
+        gr.silences
+            .iter()
+            .any(|sl| sl.node_name == name && !sl.cancelled && !sl.local && sl.silent_until > now)
+    };
+    let dead_nodes = gr
+        .node_state
+        .iter()
+        .filter(|fs| fs.is_dead())
+        .map(|fs| DeadNodeResponse {
+            name: fs.name.clone(),
+            // silenced only here, a peer should win the roll and announce it
+            roll: if gr.silenced_locally(&fs.name, now) {
+                0
+            } else {
+                fs.local_announcement_roll.unwrap_or(0)
+            },
+            silenced: broadcast_silenced(&fs.name),
+            rolls: fs.known_rolls(),
+        })
+        .collect();
+    let announced_deaths = gr
+        .node_state
+        .iter()
+        .filter(|fs| fs.is_dead())
+        .filter_map(|fs| {
+            Some(AnnouncedDeathResponse {
+                name: fs.name.clone(),
+                announcer: fs.announced.clone()?,
+                announced_at: fs.announced_at?,
+            })
+        })
+        .collect();
+    let dying_nodes = gr
+        .node_state
+        .iter()
+        .filter(|fs| fs.fail_count > 0 && !fs.is_dead())
+        .map(|fs| DyingNodeResponse {
+            name: fs.name.clone(),
+            fail_count: fs.fail_count,
+            threshold: fs.thresholds.dead_after,
+        })
+        .collect();
+    let verdicts = v2.then(|| {
+        gr.node_state
+            .iter()
+            .map(|fs| NodeVerdictResponse {
+                name: fs.name.clone(),
+                verdict: if broadcast_silenced(&fs.name) {
+                    NodeVerdict::Silenced
+                } else {
+                    match fs.status() {
+                        GridNodeStatus::Alive => NodeVerdict::Alive,
+                        GridNodeStatus::Dying => NodeVerdict::Dying,
+                        GridNodeStatus::Dead => NodeVerdict::Dead,
+                        GridNodeStatus::Unknown => NodeVerdict::Unknown,
+                    }
+                },
+                fail_count: fs.fail_count,
+                last_ok: fs.last_ok,
+            })
+            .collect()
     });
 
-    js.join_all().await;
-    Ok(())
+    ObituaryResponse {
+        dead_nodes,
+        announced_deaths,
+        dying_nodes,
+        verdicts,
+    }
 }
 
 fn handle_silence(
     server_config: &Config,
     server_state: &State,
+    request: &Request,
     key: String,
     time: String,
     target: Option<String>,
 ) -> rouille::Response {
-    if key != server_config.secret_key {
+    if let Some(forbidden) = outside_silence_cidrs(server_config, request, "silence") {
+        return forbidden;
+    }
+    if key_scope(server_config, &key) != Some(KeyScope::Full) {
         warn!("Invalid secret key");
         return rouille::Response::empty_406();
     }
 
+    let client_ip = client_ip::resolve(&server_config.server, request);
     let Some(silent_until) = try_parse_until_time(&time) else {
         return rouille::Response::empty_400();
     };
-    let id = rand::rng().random_range(0usize..usize::MAX);
-    let target = target.unwrap_or_else(|| server_config.name.clone());
+    let target = match resolve_target(server_config, target) {
+        Ok(target) => target,
+        Err(resp) => return resp,
+    };
+    let local = match local_param(request) {
+        Ok(local) => local,
+        Err(resp) => return resp,
+    };
+    let source = AuditSource::Api {
+        client_ip: client_ip.to_string(),
+    };
 
-    let mut gr = server_state.lock().expect("Failed to lock state");
+    // a tag silences every node carrying it, each one broadcast on its own
+    if target.starts_with("tag:") {
+        let mut names = server_config.expand_target(&target);
+        if local {
+            names.retain(|name| *name != server_config.name);
+        }
+        if names.is_empty() {
+            return rouille::Response::empty_404();
+        }
 
-    // check if target is valid
-    if !gr.node_state.iter().any(|fs| fs.name == target) && target != server_config.name {
-        return rouille::Response::empty_404();
+        return match silence_nodes(
+            server_config,
+            server_state,
+            names,
+            silent_until,
+            local,
+            source,
+        ) {
+            Ok(silenced) => rouille::Response::json(&silenced).with_status_code(200),
+            Err(rejection) => rouille::Response::text(rejection.reason()).with_status_code(400),
+        };
     }
 
-    let resp = SilenceResponse {
-        name: target.clone(),
-        silent_until,
-    };
-
-    gr.silences.push(NodeSilence {
-        id,
-        node_name: target.clone(),
+    match silence_node(
+        server_config,
+        server_state,
+        target,
         silent_until,
-        broadcasted: false,
-    });
-    info!("Added silence for {} until `{}`", target, silent_until);
-
-    rouille::Response::json(&resp).with_status_code(200)
+        local,
+        source,
+    ) {
+        Ok(resp) => rouille::Response::json(&resp).with_status_code(200),
+        Err(SilenceRejection::UnknownNode) => rouille::Response::empty_404(),
+        Err(rejection) => rouille::Response::text(rejection.reason()).with_status_code(400),
+    }
 }
 
-fn try_parse_until_time(time: &str) -> Option<DateTime<Utc>> {
-    // try to parse as time, otherwise its duration
-    if let Ok(time) = i64::from_str(time)
-        && let Some(time) = DateTime::<Utc>::from_timestamp(time, 0)
-    {
-        return Some(time);
+/// The `local` query flag of silences, which keeps them on this node
+fn local_param(request: &Request) -> Result<bool, rouille::Response> {
+    match request.get_param("local").as_deref() {
+        None | Some("false") => Ok(false),
+        Some("true") => Ok(true),
+        Some(local) => Err(bad_request(format!("Invalid local `{local}`"))),
     }
+}
 
-    let duration = humantime::parse_duration(time).ok()?;
-    let signed = chrono::Duration::from_std(duration).ok()?;
-    Utc::now().trunc_subsecs(0).checked_add_signed(signed)
+/// This node's status, never cached so probes see the current state
+fn handle_status(
+    server_config: &Config,
+    server_state: &State,
+    request: &Request,
+) -> rouille::Response {
+    let user_agent = request.header("User-Agent").unwrap_or("Unknown");
+    let peer = calling_peer(server_config, server_state, request);
+    debug!(
+        "Called for status by {} ua: `{user_agent}`{}",
+        peer_display(&peer),
+        trace_display(&request_trace(request))
+    );
+
+    let (degraded, muted_until, self_check_ok, revision) = {
+        let gr = server_state.lock().expect("Failed to lock state");
+        (
+            gr.poller_stale(server_config.stale_after()),
+            gr.muted_until(Utc::now()),
+            gr.self_check_ok(),
+            gr.revision,
+        )
+    };
+    rouille::Response::json(&StatusResponse {
+        name: server_config.name.clone(),
+        version: VERSION.to_string(),
+        degraded,
+        system: server_config
+            .report_system_metrics
+            .then(system_metrics::collect),
+        muted_until,
+        self_check_ok,
+        advertised_address: server_config.advertised_address.clone(),
+        config_hash: server_config.hash.clone(),
+        features: protocol_features(server_config),
+        revision,
+    })
+    .with_status_code(200)
+    .with_unique_header("Cache-Control", "no-store")
+}
+
+/// The node a silence means by its name or display name, this node without one, 409 when it could mean several
+fn resolve_target(
+    server_config: &Config,
+    target: Option<String>,
+) -> Result<String, rouille::Response> {
+    let Some(target) = target else {
+        return Ok(server_config.name.clone());
+    };
+    server_config.resolve_target(&target).map_err(|names| {
+        warn!("Silence target `{target}` is ambiguous, it could be any of {names:?}");
+        rouille::Response::json(&ErrorResponse {
+            error: format!("`{target}` could be any of {}", names.join(", ")),
+        })
+        .with_status_code(409)
+    })
+}
+
+/// Applies a peer's mute, or its lifting, unless a later change is known
+fn receive_mute_broadcast(
+    gr: &mut StateInner,
+    body: MuteBroadcastRequest,
+    client_ip: String,
+    trace: Option<String>,
+) -> rouille::Response {
+    if gr
+        .mute
+        .as_ref()
+        .is_some_and(|mute| mute.changed_at >= body.changed_at)
+    {
+        info!("Mute of {} already known", body.changed_at);
+        return rouille::Response::empty_204();
+    }
+
+    let source = AuditSource::Peer {
+        node_name: body.origin,
+        client_ip,
+    };
+    let action = match body.muted_until {
+        Some(muted_until) => AuditAction::MuteSet { muted_until },
+        None => AuditAction::MuteCleared,
+    };
+    gr.audit.record_traced(source, action, trace);
+    gr.mute = Some(GridMute {
+        until: body.muted_until,
+        changed_at: body.changed_at,
+        broadcasted: true,
+    });
+    rouille::Response::empty_204()
+}
+
+/// Applies a silence from a peer, newer revisions of a known silence update it
+fn receive_silence_broadcast(
+    gr: &mut StateInner,
+    body: SilenceBroadcastRequest,
+    source: AuditSource,
+    trace: Option<String>,
+) -> rouille::Response {
+    if let Some(existing) = gr.silences.iter_mut().find(|sl| sl.id == body.id) {
+        // two silences got the same id, only possible with the numeric ids of older nodes
+        if existing.node_name != body.node_name {
+            warn!(
+                "Silence {} conflicts, known for `{}`, broadcast for `{}`",
+                body.id, existing.node_name, body.node_name
+            );
+            return rouille::Response::text("Conflicting silence id").with_status_code(409);
+        }
+        if body.revision <= existing.revision {
+            if body.revision == existing.revision
+                && (body.silent_until != existing.silent_until
+                    || body.cancelled != existing.cancelled)
+            {
+                warn!(
+                    "Silence {} diverged at revision {}, keeping until `{}` over `{}`",
+                    body.id, existing.revision, existing.silent_until, body.silent_until
+                );
+            } else {
+                info!(
+                    "Silence {} already known at revision {}",
+                    body.id, existing.revision
+                );
+            }
+            return rouille::Response::empty_204();
+        }
+
+        existing.silent_until = body.silent_until;
+        existing.revision = body.revision;
+        existing.cancelled = body.cancelled;
+        existing.broadcasted = true;
+        existing.received_by.clear();
+        let action = if body.cancelled {
+            AuditAction::SilenceCancelled {
+                silence_id: body.id,
+                node_name: body.node_name,
+                local: false,
+            }
+        } else {
+            AuditAction::SilenceUpdated {
+                silence_id: body.id,
+                node_name: body.node_name,
+                silent_until: body.silent_until,
+                revision: body.revision,
+                local: false,
+            }
+        };
+        gr.events.record_action(&action);
+        gr.audit.record_traced(source, action, trace);
+        gr.refresh_changes(Utc::now());
+        return rouille::Response::empty_204();
+    }
+
+    // add otherwise, cancelled ones too so an older revision can't revive them
+    let action = if body.cancelled {
+        AuditAction::SilenceCancelled {
+            silence_id: body.id.clone(),
+            node_name: body.node_name.clone(),
+            local: false,
+        }
+    } else {
+        AuditAction::SilenceCreated {
+            silence_id: body.id.clone(),
+            node_name: body.node_name.clone(),
+            silent_until: body.silent_until,
+            local: false,
+        }
+    };
+    gr.events.record_action(&action);
+    gr.audit.record_traced(source, action, trace);
+    gr.silences.push(NodeSilence {
+        id: body.id,
+        node_name: body.node_name,
+        silent_until: body.silent_until,
+        broadcasted: true,
+        revision: body.revision,
+        cancelled: body.cancelled,
+        local: false,
+        received_by: BTreeSet::new(),
+    });
+    gr.refresh_changes(Utc::now());
+    rouille::Response::empty_204()
+}
+
+/// Silences all of `targets` or, when any of them is rejected, none
+fn silence_nodes(
+    server_config: &Config,
+    server_state: &State,
+    targets: Vec<String>,
+    silent_until: DateTime<Utc>,
+    local: bool,
+    source: AuditSource,
+) -> Result<Vec<SilenceResponse>, SilenceRejection> {
+    for target in targets.iter() {
+        check_silence(server_config, server_state, target, silent_until, local)?;
+    }
+    targets
+        .into_iter()
+        .map(|target| {
+            silence_node(
+                server_config,
+                server_state,
+                target,
+                silent_until,
+                local,
+                source.clone(),
+            )
+        })
+        .collect()
+}
+
+fn check_silence(
+    server_config: &Config,
+    server_state: &State,
+    target: &str,
+    silent_until: DateTime<Utc>,
+    local: bool,
+) -> Result<(), SilenceRejection> {
+    if local && target == server_config.name {
+        return Err(SilenceRejection::LocalSelf);
+    }
+    validate_silence(
+        server_config,
+        server_state,
+        target,
+        silent_until,
+        Utc::now(),
+    )
+    .inspect_err(|rejection| {
+        warn!("Rejected silence for `{target}`: {}", rejection.reason());
+    })
+}
+
+/// Silences a node, or moves the end of its running silence, the poller broadcasts it unless it's `local`
+fn silence_node(
+    server_config: &Config,
+    server_state: &State,
+    target: String,
+    silent_until: DateTime<Utc>,
+    local: bool,
+    source: AuditSource,
+) -> Result<SilenceResponse, SilenceRejection> {
+    check_silence(server_config, server_state, &target, silent_until, local)?;
+
+    let mut gr = server_state.lock().expect("Failed to lock state");
+    let resp = SilenceResponse {
+        name: target.clone(),
+        silent_until,
+    };
+
+    // extend or shorten the running silence instead of stacking a new one
+    if let Some(existing) = gr
+        .silences
+        .iter_mut()
+        .find(|sl| sl.node_name == target && !sl.cancelled && sl.local == local)
+    {
+        existing.silent_until = silent_until;
+        existing.revision += 1;
+        existing.broadcasted = false;
+        existing.received_by.clear();
+        let action = AuditAction::SilenceUpdated {
+            silence_id: existing.id.clone(),
+            node_name: target.clone(),
+            silent_until,
+            revision: existing.revision,
+            local,
+        };
+        gr.events.record_action(&action);
+        gr.audit.record(source, action);
+        gr.refresh_changes(Utc::now());
+        info!("Updated silence for {} until `{}`", target, silent_until);
+
+        return Ok(resp);
+    }
+
+    let id = silence_id::new();
+    gr.silences.push(NodeSilence {
+        id: id.clone(),
+        node_name: target.clone(),
+        silent_until,
+        broadcasted: false,
+        revision: 0,
+        cancelled: false,
+        local,
+        received_by: BTreeSet::new(),
+    });
+    let action = AuditAction::SilenceCreated {
+        silence_id: id,
+        node_name: target.clone(),
+        silent_until,
+        local,
+    };
+    gr.events.record_action(&action);
+    gr.audit.record(source, action);
+    gr.refresh_changes(Utc::now());
+    info!("Added silence for {} until `{}`", target, silent_until);
+
+    Ok(resp)
+}
+
+fn grid_response(server_config: &Config, server_state: &State) -> GridResponse {
+    let gr = server_state.lock().expect("Failed to lock state");
+    // a stuck poller means this node can't watch the grid
+    let stale = gr.poller_stale(server_config.stale_after());
+    let now = Utc::now();
+    let silenced = gr
+        .silences
+        .iter()
+        .filter(|sl| !sl.cancelled && sl.silent_until > now)
+        .map(|sl| sl.node_name.as_str())
+        .collect::<HashSet<_>>();
+    let mut resp = GridResponse {
+        nodes: Default::default(),
+        total: 0,
+        revision: gr.revision,
+        summary: Default::default(),
+        tag_totals: Default::default(),
+        announcements_suppressed: gr.announcements_suppressed.is_some(),
+        announcements_suppressed_reason: gr.announcements_suppressed.clone(),
+        muted_until: gr.muted_until(now),
+        cycle: gr.cycle_response(server_config.poll_interval()),
+        config_reload: gr.last_reload.clone(),
+        config: Some(ConfigResponse {
+            source: server_config
+                .source
+                .as_ref()
+                .map(|path| path.display().to_string()),
+            hash: server_config.hash.clone(),
+            loaded_at: server_config.loaded_at,
+            stale: server_config.is_stale(now),
+        }),
+    };
+
+    // add this node
+    resp.nodes.push(GridNodeResponse {
+        name: server_config.name.clone(),
+        display_name: server_config.display_name.clone(),
+        last_poll: gr.last_cycle_completed,
+        last_ok: gr.last_cycle_completed,
+        // this node is only watched by its peers
+        last_changed: Some(gr.started),
+        status: if stale {
+            GridNodeStatus::Dying
+        } else {
+            GridNodeStatus::Alive
+        },
+        tags: server_config.tags.clone(),
+        cert_days_remaining: gr.local_cert.days_remaining(),
+        system: server_config
+            .report_system_metrics
+            .then(system_metrics::collect),
+        poll_error: None,
+        advertised_address: server_config.advertised_address.clone(),
+        announced_by: None,
+        announced_at: None,
+        announcement_roll: None,
+        obituary_calls: None,
+        last_inbound: None,
+        version: Some(VERSION.to_string()),
+        version_mismatch: server_config.version_mismatch(&server_config.name, VERSION),
+        flapping: false,
+        announced: false,
+        features: Some(protocol_features(server_config)),
+    });
+
+    for fs in gr.node_state.iter() {
+        let mut node_resp = fs.to_api_response(server_config.legacy_grid_status);
+        node_resp.tags = server_config.node_tags(&fs.name).to_vec();
+        node_resp.display_name = server_config
+            .node_display_name(&fs.name, &gr.discovered_nodes)
+            .map(str::to_string);
+        resp.nodes.push(node_resp);
+    }
+    resp.nodes.sort_by(|a, b| a.name.cmp(&b.name));
+
+    // this node included
+    for node in resp.nodes.iter() {
+        let silenced = silenced.contains(node.name.as_str());
+        resp.summary.add(node.status, silenced);
+        for tag in node.tags.iter() {
+            resp.tag_totals
+                .entry(tag.clone())
+                .or_default()
+                .add(node.status, silenced);
+        }
+    }
+    resp.total = resp.nodes.len();
+
+    resp
+}
+
+/// `/badge/<key>.svg` of the grid, `/badge/<key>/<node>.svg` of a node, for either key
+fn handle_badge(
+    server_config: &Config,
+    server_state: &State,
+    key: &str,
+    node: Option<&str>,
+) -> rouille::Response {
+    let (key, node) = match node {
+        None => (key.strip_suffix(".svg"), None),
+        Some(file) => match file.strip_suffix(".svg") {
+            Some(node) => (Some(key), Some(node)),
+            None => (None, None),
+        },
+    };
+    let Some(key) = key else {
+        return rouille::Response::empty_404();
+    };
+    if key_scope(server_config, key).is_none() {
+        warn!("Invalid secret key");
+        return rouille::Response::empty_406();
+    }
+
+    let resp = grid_response(server_config, server_state);
+    let badge = match node {
+        None => badge::grid(&resp.summary),
+        Some(node) => {
+            let grid_node = resp.nodes.iter().find(|grid_node| grid_node.name == node);
+            let display_name = grid_node
+                .and_then(|grid_node| grid_node.display_name.as_deref())
+                .unwrap_or_else(|| server_config.display_name(node));
+            badge::node(display_name, grid_node.map(|grid_node| grid_node.status))
+        }
+    };
+    rouille::Response::from_data("image/svg+xml", badge)
+        .with_unique_header("Cache-Control", format!("max-age={}", badge::BADGE_MAX_AGE))
+}
+
+/// `/grid` with optional `tag`, `status`, `changed_since`, `sort`, `offset`, `limit`, `fields`, `format` and `summary` queries,
+/// the totals always cover the whole grid
+fn handle_grid(
+    server_config: &Config,
+    server_state: &State,
+    request: &Request,
+) -> rouille::Response {
+    let Some(format) = GridFormat::negotiate(
+        request.get_param("format").as_deref(),
+        request.header("Accept"),
+    ) else {
+        return rouille::Response::text(format!("Supported formats: {SUPPORTED_FORMATS}"))
+            .with_status_code(406);
+    };
+    let statuses = match request.get_param("status") {
+        Some(statuses) => match statuses
+            .split(',')
+            .map(GridNodeStatus::from_str)
+            .collect::<Result<Vec<_>, _>>()
+        {
+            Ok(statuses) => Some(statuses),
+            Err(error) => return bad_request(error),
+        },
+        None => None,
+    };
+    let fields = match request.get_param("fields") {
+        Some(fields) => {
+            let fields = fields.split(',').map(str::to_string).collect::<Vec<_>>();
+            if let Some(field) = fields
+                .iter()
+                .find(|f| !GRID_NODE_FIELDS.contains(&f.as_str()))
+            {
+                return bad_request(format!("Unknown field `{field}`"));
+            }
+            Some(fields)
+        }
+        None => None,
+    };
+    let changed_since = match request.get_param("changed_since") {
+        Some(time) => match history::parse_time(&time) {
+            Some(time) => Some(time),
+            None => return bad_request(format!("Invalid `changed_since` `{time}`")),
+        },
+        None => None,
+    };
+    let page_param = |name: &str| match request.get_param(name) {
+        None => Ok(None),
+        Some(value) => value
+            .parse::<usize>()
+            .map(Some)
+            .map_err(|_| format!("Invalid `{name}` `{value}`")),
+    };
+    let (offset, limit) = match (page_param("offset"), page_param("limit")) {
+        (Ok(offset), Ok(limit)) => (offset.unwrap_or(0), limit),
+        (Err(error), _) | (_, Err(error)) => return bad_request(error),
+    };
+
+    let mut resp = grid_response(server_config, server_state);
+    match request.get_param("summary").as_deref() {
+        None | Some("false") => {}
+        // cheap enough for high frequency health checks
+        Some("true") => return rouille::Response::json(&resp.summary).with_status_code(200),
+        Some(summary) => return bad_request(format!("Invalid summary `{summary}`")),
+    }
+    if let Some(tag) = request.get_param("tag") {
+        resp.nodes.retain(|node| node.tags.contains(&tag));
+    }
+    if let Some(statuses) = statuses {
+        resp.nodes.retain(|node| statuses.contains(&node.status));
+    }
+    if let Some(since) = changed_since {
+        resp.nodes
+            .retain(|node| node.last_changed.is_some_and(|changed| changed > since));
+    }
+    match request.get_param("sort").as_deref() {
+        None | Some("name") => {}
+        // never polled first, then the stalest
+        Some("last_poll") => resp.nodes.sort_by_key(|node| node.last_poll),
+        Some("last_ok") => resp.nodes.sort_by_key(|node| node.last_ok),
+        Some("status") => resp.nodes.sort_by_key(|node| node.status.severity()),
+        Some(sort) => return bad_request(format!("Unknown sort `{sort}`")),
+    }
+    // the sorts are stable, nodes sorting the same stay in name order across pages
+    resp.total = resp.nodes.len();
+    resp.nodes = resp
+        .nodes
+        .into_iter()
+        .skip(offset)
+        .take(limit.unwrap_or(usize::MAX))
+        .collect();
+
+    match format {
+        GridFormat::Json => {}
+        GridFormat::Text => return rouille::Response::text(grid_format::text(&resp)),
+        GridFormat::Prometheus => {
+            return rouille::Response::from_data(
+                "text/plain; version=0.0.4; charset=utf-8",
+                grid_format::prometheus(&resp),
+            );
+        }
+    }
+
+    let Some(fields) = fields else {
+        return rouille::Response::json(&resp).with_status_code(200);
+    };
+    let mut value = serde_json::to_value(&resp).expect("Grid response is always valid json");
+    if let Some(nodes) = value["nodes"].as_array_mut() {
+        for node in nodes.iter_mut().filter_map(|node| node.as_object_mut()) {
+            node.retain(|key, _| fields.contains(key));
+        }
+    }
+    rouille::Response::json(&value).with_status_code(200)
+}
+
+/// The incidents since this node started, `from` and `to` narrow them down, `format=csv` streams them for spreadsheets
+fn handle_history(server_state: &State, request: &Request) -> rouille::Response {
+    let time_param = |name: &str| match request.get_param(name) {
+        None => Ok(None),
+        Some(time) => history::parse_time(&time)
+            .map(Some)
+            .ok_or_else(|| format!("Invalid `{name}` `{time}`")),
+    };
+    let (from, to) = match (time_param("from"), time_param("to")) {
+        (Ok(from), Ok(to)) => (from, to),
+        (Err(error), _) | (_, Err(error)) => return bad_request(error),
+    };
+
+    let gr = server_state.lock().expect("Failed to lock state");
+    // nothing older is remembered
+    let from = from.unwrap_or(gr.started);
+    let to = to.unwrap_or_else(Utc::now);
+    if from >= to {
+        return bad_request("`from` has to be before `to`".to_string());
+    }
+    let incidents = history::incidents_between(gr.incidents.iter(), from, to);
+    drop(gr);
+
+    match request.get_param("format").as_deref() {
+        None | Some("json") => rouille::Response::json(&HistoryResponse {
+            from,
+            to,
+            incidents,
+        })
+        .with_status_code(200),
+        Some("csv") => rouille::Response {
+            status_code: 200,
+            headers: vec![
+                ("Content-Type".into(), "text/csv; charset=utf-8".into()),
+                (
+                    "Content-Disposition".into(),
+                    format!(
+                        "attachment; filename=\"{}\"",
+                        history::csv_filename(from, to)
+                    )
+                    .into(),
+                ),
+            ],
+            data: rouille::ResponseBody::from_reader(history::CsvRows::new(incidents)),
+            upgrade: None,
+        },
+        Some(_) => rouille::Response::text("Supported formats: json, csv").with_status_code(406),
+    }
+}
+
+fn bad_request(error: String) -> rouille::Response {
+    rouille::Response::json(&ErrorResponse { error }).with_status_code(400)
+}
+
+fn handle_unsilence(
+    server_config: &Config,
+    server_state: &State,
+    request: &Request,
+    key: String,
+    target: Option<String>,
+) -> rouille::Response {
+    if let Some(forbidden) = outside_silence_cidrs(server_config, request, "unsilence") {
+        return forbidden;
+    }
+    if key_scope(server_config, &key) != Some(KeyScope::Full) {
+        warn!("Invalid secret key");
+        return rouille::Response::empty_406();
+    }
+
+    let target = match resolve_target(server_config, target) {
+        Ok(target) => target,
+        Err(resp) => return resp,
+    };
+    let local = match local_param(request) {
+        Ok(local) => local,
+        Err(resp) => return resp,
+    };
+    let client_ip = client_ip::resolve(&server_config.server, request).to_string();
+
+    let mut gr = server_state.lock().expect("Failed to lock state");
+    let mut cancelled = vec![];
+    for sl in gr
+        .silences
+        .iter_mut()
+        .filter(|sl| sl.node_name == target && !sl.cancelled && sl.local == local)
+    {
+        // kept until it expires, so the cancellation gets broadcast
+        sl.cancelled = true;
+        sl.revision += 1;
+        sl.broadcasted = false;
+        sl.received_by.clear();
+        cancelled.push(sl.id.clone());
+    }
+
+    if cancelled.is_empty() {
+        return rouille::Response::empty_404();
+    }
+
+    for silence_id in cancelled {
+        let action = AuditAction::SilenceCancelled {
+            silence_id,
+            node_name: target.clone(),
+            local,
+        };
+        gr.events.record_action(&action);
+        gr.audit.record(
+            AuditSource::Api {
+                client_ip: client_ip.clone(),
+            },
+            action,
+        );
+    }
+    gr.refresh_changes(Utc::now());
+    info!("Cancelled silences for {}", target);
+
+    rouille::Response::empty_204()
+}
+
+/// Mutes every announcement of the grid until `time`, at most `max_silence_duration` from now
+fn handle_mute(
+    server_config: &Config,
+    server_state: &State,
+    request: &Request,
+    key: String,
+    time: String,
+) -> rouille::Response {
+    if let Some(forbidden) = outside_silence_cidrs(server_config, request, "mute") {
+        return forbidden;
+    }
+    if key_scope(server_config, &key) != Some(KeyScope::Full) {
+        warn!("Invalid secret key");
+        return rouille::Response::empty_406();
+    }
+
+    let now = Utc::now();
+    let Some(muted_until) = try_parse_until_time(&time) else {
+        return rouille::Response::empty_400();
+    };
+    let max = chrono::Duration::from_std(server_config.max_silence_duration)
+        .unwrap_or(chrono::Duration::MAX);
+    if muted_until <= now {
+        return rouille::Response::text("Mute must end in the future").with_status_code(400);
+    }
+    if muted_until - now > max {
+        return rouille::Response::text("Mute is longer than max_silence_duration")
+            .with_status_code(400);
+    }
+
+    let mut gr = server_state.lock().expect("Failed to lock state");
+    gr.mute = Some(GridMute {
+        until: Some(muted_until),
+        changed_at: now,
+        broadcasted: false,
+    });
+    gr.audit.record(
+        AuditSource::Api {
+            client_ip: client_ip::resolve(&server_config.server, request).to_string(),
+        },
+        AuditAction::MuteSet { muted_until },
+    );
+    warn!("Grid muted until {muted_until}");
+
+    rouille::Response::json(&MuteResponse { muted_until }).with_status_code(200)
+}
+
+fn handle_unmute(
+    server_config: &Config,
+    server_state: &State,
+    request: &Request,
+    key: String,
+) -> rouille::Response {
+    if let Some(forbidden) = outside_silence_cidrs(server_config, request, "unmute") {
+        return forbidden;
+    }
+    if key_scope(server_config, &key) != Some(KeyScope::Full) {
+        warn!("Invalid secret key");
+        return rouille::Response::empty_406();
+    }
+
+    let now = Utc::now();
+    let mut gr = server_state.lock().expect("Failed to lock state");
+    if gr.muted_until(now).is_none() {
+        return rouille::Response::empty_404();
+    }
+
+    gr.mute = Some(GridMute {
+        until: None,
+        changed_at: now,
+        broadcasted: false,
+    });
+    gr.audit.record(
+        AuditSource::Api {
+            client_ip: client_ip::resolve(&server_config.server, request).to_string(),
+        },
+        AuditAction::MuteCleared,
+    );
+    info!("Grid unmuted");
+
+    rouille::Response::empty_204()
+}
+
+/// Reads a json body, refusing anything over `max_size` bytes
+fn read_json_body<T: DeserializeOwned>(
+    request: &Request,
+    max_size: usize,
+) -> Result<T, rouille::Response> {
+    let Some(body) = request.data() else {
+        return Err(rouille::Response::empty_400());
+    };
+
+    let mut buf = Vec::new();
+    if body
+        .take(max_size as u64 + 1)
+        .read_to_end(&mut buf)
+        .is_err()
+    {
+        return Err(rouille::Response::empty_400());
+    }
+    if buf.len() > max_size {
+        warn!("Request body over {max_size} bytes rejected");
+        return Err(rouille::Response::text("Payload too large").with_status_code(413));
+    }
+
+    serde_json::from_slice(&buf).map_err(|err| {
+        warn!("Invalid json body: {err}");
+        rouille::Response::empty_400()
+    })
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum SilenceRejection {
+    UnknownNode,
+    AlreadyOver,
+    TooLong,
+    /// Local silences only make sense for peers
+    LocalSelf,
+}
+
+impl SilenceRejection {
+    fn reason(&self) -> &'static str {
+        match self {
+            SilenceRejection::UnknownNode => "Unknown node",
+            SilenceRejection::AlreadyOver => "Silence is already over",
+            SilenceRejection::TooLong => "Silence is longer than max_silence_duration",
+            SilenceRejection::LocalSelf => "Local silences are for peers, not this node",
+        }
+    }
+}
+
+/// Silences must target a known node and end in the future, within `max_silence_duration`
+fn validate_silence(
+    config: &Config,
+    state: &State,
+    node_name: &str,
+    silent_until: DateTime<Utc>,
+    now: DateTime<Utc>,
+) -> Result<(), SilenceRejection> {
+    if node_name != config.name
+        && !config.nodes.contains_key(node_name)
+        && !state
+            .lock()
+            .expect("Failed to lock state")
+            .discovered_nodes
+            .contains_key(node_name)
+    {
+        return Err(SilenceRejection::UnknownNode);
+    }
+    if silent_until <= now {
+        return Err(SilenceRejection::AlreadyOver);
+    }
+    let max_duration =
+        chrono::Duration::from_std(config.max_silence_duration).unwrap_or(chrono::Duration::MAX);
+    if silent_until - now > max_duration {
+        return Err(SilenceRejection::TooLong);
+    }
+    Ok(())
+}
+
+/// `403` for silences, mutes and their cancellations from outside `silence_allowed_cidrs`,
+/// undoing one is as good as never having it
+fn outside_silence_cidrs(
+    server_config: &Config,
+    request: &Request,
+    action: &str,
+) -> Option<rouille::Response> {
+    if client_ip::silence_allowed(&server_config.server, request) {
+        return None;
+    }
+    warn!(
+        "Rejected {action} from {}, not in `silence_allowed_cidrs`",
+        client_ip::resolve(&server_config.server, request)
+    );
+    Some(rouille::Response::text("Forbidden").with_status_code(403))
+}
+
+/// Whether a call to a peer endpoint comes from one of the configured nodes
+fn is_allowed_peer(
+    server_config: &Config,
+    peer_addresses: &PeerAddresses,
+    request: &Request,
+) -> bool {
+    if !server_config.restrict_peer_endpoints {
+        return true;
+    }
+
+    let ip = client_ip::resolve(&server_config.server, request);
+    if peer_addresses.contains(ip) {
+        return true;
+    }
+
+    // proxied nodes can't be recognized by IP, only by the name they give
+    let user_agent = request.header("User-Agent").unwrap_or_default();
+    if let Some(name) = claimed_peer_name(request)
+        && server_config
+            .nodes
+            .get(name)
+            .is_some_and(|node| node.skip_peer_ip_check)
+    {
+        return true;
+    }
+
+    warn!("Rejected peer endpoint call from {ip} (ua: `{user_agent}`)");
+    false
+}
+
+/// The name a calling node gives, in `X-Freecaster-Node` or, from older nodes, in its user agent
+fn claimed_peer_name(request: &Request) -> Option<&str> {
+    request
+        .header(NODE_HEADER)
+        .or_else(|| peer_name_from_user_agent(request.header("User-Agent")?))
+}
+
+/// Refuses silence broadcasts of unknown or removed nodes with the status and the reason,
+/// tells why one is suspicious if it's accepted nevertheless
+fn check_broadcast_sender(
+    server_config: &Config,
+    server_state: &State,
+    peer_addresses: &PeerAddresses,
+    request: &Request,
+    origin: Option<&str>,
+) -> Result<Option<String>, (u16, String)> {
+    let nodes = discovery::grid_nodes(server_config, server_state);
+    // a revision of a silence created here comes back with this node as the origin
+    if let Some(origin) = origin
+        && origin != server_config.name
+        && !nodes.contains_key(origin)
+    {
+        return Err((422, format!("Unknown origin `{origin}`")));
+    }
+
+    let Some(sender) = claimed_peer_name(request) else {
+        return Ok(Some("the sender doesn't name itself".to_string()));
+    };
+    let Some(node) = nodes.get(sender) else {
+        return Err((422, format!("Unknown sender `{sender}`")));
+    };
+    if !server_config.verify_broadcast_source || node.skip_peer_ip_check {
+        return Ok(None);
+    }
+    let ip = client_ip::resolve(&server_config.server, request);
+    match peer_addresses.get(sender) {
+        Some(addresses) if addresses.contains(&ip) => Ok(None),
+        Some(_) => Err((422, format!("`{sender}` called from {ip}, not its address"))),
+        None => Ok(Some(format!("the address of `{sender}` is unknown"))),
+    }
+}
+
+/// The grid node making `request`, its call is recorded as its last inbound one.
+/// Names missing from the grid are logged and not trusted
+fn calling_peer(server_config: &Config, server_state: &State, request: &Request) -> Option<String> {
+    let name = claimed_peer_name(request)?;
+    // the self-check
+    if name == server_config.name {
+        return None;
+    }
+
+    let mut gr = server_state.lock().expect("Failed to lock state");
+    let Some(ns) = gr.node_state.iter_mut().find(|ns| ns.name == name) else {
+        warn!(
+            "Call from {} claims to be `{name}`, which is not a node of the grid",
+            client_ip::resolve(&server_config.server, request)
+        );
+        return None;
+    };
+    ns.last_inbound = Some(Utc::now());
+    Some(name.to_string())
+}
+
+/// The caller's trace id, ignored unless it's short and alphanumeric, it goes into the logs
+fn request_trace(request: &Request) -> Option<String> {
+    request
+        .header(TRACE_HEADER)
+        .filter(|trace| trace.len() <= 32 && trace.chars().all(|c| c.is_ascii_alphanumeric()))
+        .map(str::to_string)
+}
+
+fn trace_display(trace: &Option<String>) -> String {
+    trace
+        .as_ref()
+        .map(|trace| format!(" (trace `{trace}`)"))
+        .unwrap_or_default()
+}
+
+fn peer_display(peer: &Option<String>) -> String {
+    match peer {
+        Some(name) => format!("`{name}`"),
+        None => "an unknown caller".to_string(),
+    }
+}
+
+/// Peers identify as `freecaster-grid/<version>/<name>`, the version may carry the commit as `0.3.0+<commit>`
+fn peer_name_from_user_agent(user_agent: &str) -> Option<&str> {
+    let mut parts = user_agent.splitn(3, '/');
+    if parts.next()? != "freecaster-grid" {
+        return None;
+    }
+    parts.next()?;
+    parts.next()
+}
+
+fn try_parse_until_time(time: &str) -> Option<DateTime<Utc>> {
+    // try to parse as time, otherwise its duration
+    if let Ok(time) = i64::from_str(time)
+        && let Some(time) = DateTime::<Utc>::from_timestamp(time, 0)
+    {
+        return Some(time);
+    }
+
+    let duration = humantime::parse_duration(time).ok()?;
+    let signed = chrono::Duration::from_std(duration).ok()?;
+    Utc::now().trunc_subsecs(0).checked_add_signed(signed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::test_config;
+
+    /// `hal9000` with `hal9001` as its peer
+    const WITH_PEER: &str = "  max_body_size: 64\nmax_silence_duration: 1h\nnodes:\n  hal9001:\n    address: http://hal9001:3037\n";
+
+    #[test]
+    fn about_the_build() {
+        let mut config = test_config(WITH_PEER);
+        config.escalation.insert(
+            config::NodeSeverity::Critical,
+            serde_json::from_str(r#"{"after": "1h", "channels": ["exec", "log"]}"#).unwrap(),
+        );
+        let about = AboutResponse::new(&config);
+        assert_eq!(
+            about.announcement_channels,
+            [
+                AnnouncementMode::Telegram,
+                AnnouncementMode::Exec,
+                AnnouncementMode::Log
+            ]
+        );
+        assert!(about.built_at.is_some());
+        assert_eq!(
+            peer_name_from_user_agent(&format!("freecaster-grid/{VERSION}+{GIT_COMMIT}/hal9001")),
+            Some("hal9001")
+        );
+    }
+
+    #[test]
+    fn silence_validation() {
+        let config = test_config(WITH_PEER);
+        let state = State::new(&config);
+        let now = Utc::now();
+        let minute = chrono::Duration::minutes(1);
+
+        assert!(validate_silence(&config, &state, "hal9001", now + minute, now).is_ok());
+        assert!(validate_silence(&config, &state, "hal9000", now + minute, now).is_ok());
+        assert_eq!(
+            validate_silence(&config, &state, "hal9002", now + minute, now),
+            Err(SilenceRejection::UnknownNode)
+        );
+        assert_eq!(
+            validate_silence(&config, &state, "hal9001", now - minute, now),
+            Err(SilenceRejection::AlreadyOver)
+        );
+        assert_eq!(
+            validate_silence(&config, &state, "hal9001", now + minute * 61, now),
+            Err(SilenceRejection::TooLong)
+        );
+    }
+
+    #[test]
+    fn tag_silences_all_or_nothing() {
+        let mut config = test_config(WITH_PEER);
+        config.nodes.get_mut("hal9001").unwrap().tags = vec!["rack".to_string()];
+        let state = State::new(&config);
+        let until = Utc::now() + chrono::Duration::minutes(1);
+        let silence = |targets: &[&str]| {
+            silence_nodes(
+                &config,
+                &state,
+                targets.iter().map(|name| name.to_string()).collect(),
+                until,
+                false,
+                AuditSource::Poller,
+            )
+        };
+
+        // `hal9009` left the grid since the tag was expanded
+        assert!(matches!(
+            silence(&["hal9001", "hal9009"]),
+            Err(SilenceRejection::UnknownNode)
+        ));
+        assert!(state.lock().unwrap().silences.is_empty());
+
+        let request = Request::fake_http("GET", "/", vec![], vec![]);
+        let resp = handle_silence(
+            &config,
+            &state,
+            &request,
+            "key".to_string(),
+            "1m".to_string(),
+            Some("tag:rack".to_string()),
+        );
+        assert_eq!(resp.status_code, 200);
+        assert_eq!(state.lock().unwrap().silences.len(), 1);
+    }
+
+    #[test]
+    fn readonly_key_only_reads() {
+        let mut config = test_config(WITH_PEER);
+        assert_eq!(key_scope(&config, "key"), Some(KeyScope::Full));
+        assert_eq!(key_scope(&config, "family"), None);
+        config.readonly_key = Some("family".to_string());
+        assert_eq!(key_scope(&config, "family"), Some(KeyScope::ReadOnly));
+        assert_eq!(key_scope(&config, ""), None);
+        assert!(webui_config(KeyScope::ReadOnly).actions.is_empty());
+        assert!(
+            webui_config(KeyScope::Full)
+                .actions
+                .contains(&"silence".to_string())
+        );
+
+        let state = State::new(&config);
+        let request = Request::fake_http("GET", "/", vec![], vec![]);
+        let silence = handle_silence(
+            &config,
+            &state,
+            &request,
+            "family".to_string(),
+            "1m".to_string(),
+            None,
+        );
+        assert_eq!(silence.status_code, 406);
+        assert_eq!(
+            handle_mute(
+                &config,
+                &state,
+                &request,
+                "family".to_string(),
+                "1m".to_string()
+            )
+            .status_code,
+            406
+        );
+    }
+
+    #[test]
+    fn silence_cidrs_guard_every_change() {
+        let mut config = test_config(WITH_PEER);
+        config.server.silence_allowed_cidrs = vec!["10.8.0.0/24".to_string()];
+        let state = State::new(&config);
+        let request = Request::fake_http("GET", "/", vec![], vec![]);
+        let key = || "key".to_string();
+
+        let responses = [
+            handle_silence(&config, &state, &request, key(), "1m".to_string(), None),
+            handle_unsilence(&config, &state, &request, key(), None),
+            handle_unsilence(
+                &config,
+                &state,
+                &request,
+                key(),
+                Some("hal9001".to_string()),
+            ),
+            handle_mute(&config, &state, &request, key(), "1m".to_string()),
+            handle_unmute(&config, &state, &request, key()),
+        ];
+        for resp in responses {
+            assert_eq!(resp.status_code, 403);
+        }
+
+        config
+            .server
+            .silence_allowed_cidrs
+            .push("127.0.0.0/8".to_string());
+        assert_eq!(
+            handle_unmute(&config, &state, &request, key()).status_code,
+            404
+        );
+        assert_eq!(
+            handle_mute(&config, &state, &request, key(), "1m".to_string()).status_code,
+            200
+        );
+    }
+
+    #[test]
+    fn badges_of_unknown_nodes() {
+        let mut config = test_config(WITH_PEER);
+        config.readonly_key = Some("family".to_string());
+        let state = State::new(&config);
+
+        let grid = handle_badge(&config, &state, "family.svg", None);
+        assert_eq!(grid.status_code, 200);
+        assert!(
+            grid.headers
+                .iter()
+                .any(|(name, value)| name == "Cache-Control" && value == "max-age=30")
+        );
+        let renamed = handle_badge(&config, &state, "family", Some("hal9009.svg"));
+        assert_eq!(renamed.status_code, 200);
+        assert_eq!(
+            handle_badge(&config, &state, "family", None).status_code,
+            404
+        );
+        assert_eq!(
+            handle_badge(&config, &state, "nope.svg", None).status_code,
+            406
+        );
+    }
+
+    #[test]
+    fn body_size_limit() {
+        let body = br#"{"id":1,"node_name":"hal9001","silent_until":"2030-01-01T00:00:00Z"}"#;
+        let request = |body: &[u8]| {
+            Request::fake_http("POST", "/silence-broadcast/key", vec![], body.to_vec())
+        };
+
+        let parsed: SilenceBroadcastRequest = read_json_body(&request(body), 128).unwrap();
+        assert_eq!(parsed.node_name, "hal9001");
+
+        let err = read_json_body::<SilenceBroadcastRequest>(&request(body), 32).unwrap_err();
+        assert_eq!(err.status_code, 413);
+
+        let err = read_json_body::<SilenceBroadcastRequest>(&request(b"{"), 128).unwrap_err();
+        assert_eq!(err.status_code, 400);
+    }
+
+    #[tokio::test]
+    async fn silence_broadcast_rejections() {
+        let mut config = test_config(WITH_PEER);
+        config.server.max_body_size = 256;
+        let config = Arc::new(config);
+        let state = State::new(&config);
+        let handler = app(
+            SharedConfig::new(config.clone()),
+            state.clone(),
+            Arc::new(RateLimiter::new(
+                config.rate_limit.clone(),
+                PeerAddresses::default(),
+            )),
+            PeerAddresses::default(),
+            None,
+            tokio::runtime::Handle::current(),
+        );
+        let broadcast = |node_name: &str, silent_until: DateTime<Utc>| {
+            let body = serde_json::json!({
+                "id": "1",
+                "node_name": node_name,
+                "silent_until": silent_until,
+            });
+            let request = Request::fake_http(
+                "POST",
+                "/silence-broadcast/key",
+                vec![(NODE_HEADER.to_string(), "hal9001".to_string())],
+                body.to_string().into_bytes(),
+            );
+            handler(&request).status_code
+        };
+        let now = Utc::now();
+        let minutes = chrono::Duration::minutes;
+
+        assert_eq!(broadcast(&"hal9001".repeat(40), now + minutes(30)), 413);
+        assert_eq!(broadcast("hal9001", now - minutes(1)), 400);
+        assert_eq!(broadcast("hal9001", now + minutes(61)), 400);
+        assert_eq!(broadcast("hal9002", now + minutes(30)), 422);
+        assert!(state.lock().unwrap().silences.is_empty());
+        assert!(state.lock().unwrap().audit.page(0, 1).iter().any(|entry| matches!(
+            &entry.action,
+            AuditAction::SilenceBroadcastRejected { node_name, .. } if node_name == "hal9002"
+        )));
+
+        assert_eq!(broadcast("hal9001", now + minutes(30)), 204);
+        assert_eq!(state.lock().unwrap().silences.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn audit_attribution() {
+        let mut config = test_config(WITH_PEER);
+        config.server.max_body_size = 256;
+        let config = Arc::new(config);
+        let handler = app(
+            SharedConfig::new(config.clone()),
+            State::new(&config),
+            Arc::new(RateLimiter::new(
+                config.rate_limit.clone(),
+                PeerAddresses::default(),
+            )),
+            PeerAddresses::default(),
+            None,
+            tokio::runtime::Handle::current(),
+        );
+        let json = |resp: rouille::Response| {
+            let mut body = String::new();
+            resp.data
+                .into_reader_and_size()
+                .0
+                .read_to_string(&mut body)
+                .unwrap();
+            serde_json::from_str::<serde_json::Value>(&body).unwrap()
+        };
+
+        let local = handler(&Request::fake_http(
+            "GET",
+            "/silence/key/30m/hal9001",
+            vec![],
+            vec![],
+        ));
+        assert!(local.is_success());
+        let body = serde_json::json!({
+            "id": "7",
+            "node_name": "hal9000",
+            "silent_until": Utc::now() + chrono::Duration::minutes(30),
+            "origin": "hal9001",
+        });
+        let broadcast = handler(&Request::fake_http(
+            "POST",
+            "/silence-broadcast/key",
+            vec![(NODE_HEADER.to_string(), "hal9001".to_string())],
+            body.to_string().into_bytes(),
+        ));
+        assert_eq!(broadcast.status_code, 204);
+
+        // newest first, a page of one each
+        let audit = |offset: usize| {
+            json(handler(&Request::fake_http(
+                "GET",
+                format!("/audit/key?offset={offset}&limit=1"),
+                vec![],
+                vec![],
+            )))
+        };
+        let newest = audit(0);
+        assert_eq!(newest["total"], 2);
+        assert_eq!(
+            newest["entries"][0]["source"],
+            serde_json::json!({"kind": "peer", "node_name": "hal9001", "client_ip": "127.0.0.1"})
+        );
+        assert_eq!(
+            audit(1)["entries"][0]["source"],
+            serde_json::json!({"kind": "api", "client_ip": "127.0.0.1"})
+        );
+        assert_eq!(audit(2)["entries"], serde_json::json!([]));
+    }
+
+    #[tokio::test]
+    async fn rate_limited_clients_are_told_when_to_return() {
+        let mut config = test_config(WITH_PEER);
+        config.rate_limit.burst = 2;
+        config.rate_limit.per_second = 0.25;
+        let state = State::new(&config);
+        let rate_limiter = Arc::new(RateLimiter::new(
+            config.rate_limit.clone(),
+            PeerAddresses::default(),
+        ));
+        let handler = app(
+            SharedConfig::new(Arc::new(config)),
+            state,
+            rate_limiter.clone(),
+            PeerAddresses::default(),
+            None,
+            tokio::runtime::Handle::current(),
+        );
+        let request = Request::fake_http("GET", "/", vec![], vec![]);
+
+        for _ in 0..2 {
+            assert_ne!(handler(&request).status_code, 429);
+        }
+        let limited = handler(&request);
+        assert_eq!(limited.status_code, 429);
+        let retry_after = limited
+            .headers
+            .iter()
+            .find(|(name, _)| name == "Retry-After")
+            .map(|(_, value)| value.to_string());
+        assert_eq!(retry_after.as_deref(), Some("4"));
+        assert_eq!(rate_limiter.rejected(), 1);
+
+        // peers are never limited
+        let peer = RateLimiter::new(
+            config::RateLimitConfig {
+                enabled: true,
+                burst: 0,
+                per_second: 0.0,
+            },
+            PeerAddresses::new(HashMap::from([(
+                "hal9001".to_string(),
+                HashSet::from([request.remote_addr().ip()]),
+            )])),
+        );
+        assert!(matches!(
+            peer.check(request.remote_addr().ip()),
+            RateLimitDecision::Allowed
+        ));
+    }
+
+    #[test]
+    fn grid_pages() {
+        let mut config = test_config(WITH_PEER);
+        for name in ["hal9002", "hal9003"] {
+            config
+                .nodes
+                .insert(name.to_string(), config.nodes["hal9001"].clone());
+        }
+        let state = State::new(&config);
+        let since = {
+            let mut gr = state.lock().unwrap();
+            gr.reconcile_nodes(&config.nodes, Utc::now());
+            gr.refresh_changes(Utc::now());
+            let since = Utc::now();
+            gr.node_state
+                .iter_mut()
+                .find(|ns| ns.name == "hal9002")
+                .unwrap()
+                .fail_count = 3;
+            gr.refresh_changes(since + chrono::Duration::seconds(1));
+            since
+        };
+        let grid = |query: &str| {
+            let resp = handle_grid(
+                &config,
+                &state,
+                &Request::fake_http("GET", format!("/grid/key?{query}"), vec![], vec![]),
+            );
+            let status = resp.status_code;
+            let mut body = String::new();
+            resp.data
+                .into_reader_and_size()
+                .0
+                .read_to_string(&mut body)
+                .unwrap();
+            (
+                status,
+                serde_json::from_str::<serde_json::Value>(&body).unwrap(),
+            )
+        };
+        let names = |grid: &serde_json::Value| {
+            grid["nodes"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|node| node["name"].as_str().unwrap().to_string())
+                .collect::<Vec<_>>()
+        };
+
+        let (_, page) = grid("offset=1&limit=2");
+        assert_eq!(names(&page), ["hal9001", "hal9002"]);
+        assert_eq!(page["total"], 4);
+        assert_eq!(page["total_nodes"], 4);
+
+        let (_, changed) = grid(&format!(
+            "changed_since={}",
+            since.format("%Y-%m-%dT%H:%M:%S%.fZ")
+        ));
+        assert_eq!(names(&changed), ["hal9002"]);
+        assert_eq!(changed["total"], 1);
+        assert_eq!(changed["dead_nodes"], 1);
+
+        let (status, error) = grid("limit=-1");
+        assert_eq!(status, 400);
+        assert_eq!(error["error"], "Invalid `limit` `-1`");
+    }
+
+    #[test]
+    fn escalations_are_pending_too() {
+        let mut config = test_config(WITH_PEER);
+        config.escalation.insert(
+            config::NodeSeverity::Normal,
+            serde_json::from_str(r#"{"after": "1h", "channels": ["log"]}"#).unwrap(),
+        );
+        let state = State::new(&config);
+        let mut gr = state.lock().unwrap();
+        gr.reconcile_nodes(&config.nodes, Utc::now());
+        let now = Utc::now();
+        gr.node_state[0].escalation_due = Some(now);
+        for i in 0..MAX_PENDING {
+            gr.pending_announcements.push(
+                PendingKind::Queued,
+                AnnouncementMode::Telegram,
+                crate::announcer::AnnouncementEvent::new(
+                    &config,
+                    AnnouncementKind::Returned,
+                    &format!("hal{i}"),
+                    String::new(),
+                ),
+                now + chrono::Duration::minutes(1),
+                0,
+            );
+        }
+
+        let all = pending_announcements(&config, &config.nodes, &gr, None);
+        assert_eq!(all.total, MAX_PENDING + 1);
+        assert_eq!(all.pending.len(), MAX_PENDING);
+        assert_eq!(all.pending[0].kind, PendingKind::Escalation);
+        assert_eq!(all.pending[0].target, "hal9001");
+        assert_eq!(all.pending[0].channels, [AnnouncementMode::Log]);
+
+        let escalations =
+            pending_announcements(&config, &config.nodes, &gr, Some(PendingKind::Escalation));
+        assert_eq!(escalations.total, 1);
+        assert_eq!(escalations.pending.len(), 1);
+        assert_eq!("retry".parse(), Ok(PendingKind::Retry));
+        assert!("escalations".parse::<PendingKind>().is_err());
+    }
+
+    #[test]
+    fn discovered_display_names() {
+        let config = test_config(WITH_PEER);
+        let state = State::new(&config);
+        {
+            let mut gr = state.lock().unwrap();
+            let mut discovered = config.nodes["hal9001"].clone();
+            discovered.display_name = Some("Discovery One".to_string());
+            gr.discovered_nodes
+                .insert("hal9002".to_string(), discovered);
+            let mut nodes = gr.discovered_nodes.clone();
+            nodes.extend(config.nodes.clone());
+            gr.reconcile_nodes(&nodes, Utc::now());
+        }
+
+        let resp = grid_response(&config, &state);
+        let display_name = |name: &str| {
+            resp.nodes
+                .iter()
+                .find(|node| node.name == name)
+                .unwrap()
+                .display_name
+                .clone()
+        };
+        assert_eq!(display_name("hal9001"), None);
+        assert_eq!(display_name("hal9002").as_deref(), Some("Discovery One"));
+    }
+
+    #[test]
+    fn grid_etags() {
+        let config = test_config(WITH_PEER);
+        let state = State::new(&config);
+        state
+            .lock()
+            .unwrap()
+            .reconcile_nodes(&config.nodes, Utc::now());
+        let grid = |if_none_match: Option<&str>| {
+            let headers = if_none_match
+                .map(|etag| vec![("If-None-Match".to_string(), etag.to_string())])
+                .unwrap_or_default();
+            let request = Request::fake_http("GET", "/grid/key", headers, vec![]);
+            let resp = encoding::finish(
+                &config.server,
+                &request,
+                handle_grid(&config, &state, &request),
+            );
+            let etag = resp
+                .headers
+                .iter()
+                .find(|(name, _)| name == "ETag")
+                .map(|(_, value)| value.to_string())
+                .unwrap();
+            (resp.status_code, etag)
+        };
+
+        let (status, etag) = grid(None);
+        assert_eq!(status, 200);
+        assert!(etag.starts_with("W/\""));
+        assert_eq!(grid(Some(&etag)), (304, etag.clone()));
+        assert_eq!(grid(Some(&format!("\"other\", {etag}"))).0, 304);
+
+        state.lock().unwrap().node_state[0].fail_count = 3;
+        let (status, changed) = grid(Some(&etag));
+        assert_eq!(status, 200);
+        assert_ne!(changed, etag);
+        assert_eq!(grid(Some(&changed)).0, 304);
+    }
+
+    #[test]
+    fn traces_of_peer_calls() {
+        let request = |trace: &str| {
+            Request::fake_http(
+                "POST",
+                "/silence-broadcast/key",
+                vec![(TRACE_HEADER.to_string(), trace.to_string())],
+                vec![],
+            )
+        };
+        assert_eq!(
+            request_trace(&request("3f9a1c2e")).as_deref(),
+            Some("3f9a1c2e")
+        );
+        assert_eq!(request_trace(&request("3f9a\nforged log line")), None);
+
+        let config = test_config(WITH_PEER);
+        let state = State::new(&config);
+        let mut gr = state.lock().unwrap();
+        let silence =
+            broadcast(r#"{"id":"1","node_name":"hal9001","silent_until":"2030-01-01T00:00:00Z"}"#);
+        let source = AuditSource::Peer {
+            node_name: Some("hal9001".to_string()),
+            client_ip: "10.0.0.2".to_string(),
+        };
+        receive_silence_broadcast(&mut gr, silence, source, Some("3f9a1c2e".to_string()));
+        gr.audit.cycle_trace = Some("0badf00d".to_string());
+        gr.audit
+            .record(AuditSource::Poller, AuditAction::MuteCleared);
+        let entries = gr.audit.page(0, 2);
+        assert_eq!(entries[0].trace.as_deref(), Some("0badf00d"));
+        assert_eq!(entries[1].trace.as_deref(), Some("3f9a1c2e"));
+    }
+
+    #[test]
+    fn introspect_only_for_peers() {
+        let mut config = test_config(WITH_PEER);
+        config.readonly_key = Some("family".to_string());
+        config.restrict_peer_endpoints = true;
+        let state = State::new(&config);
+        {
+            let mut gr = state.lock().unwrap();
+            gr.reconcile_nodes(&config.nodes, Utc::now());
+            gr.node_state[0].fail_count = 3;
+        }
+        let request = Request::fake_http("GET", "/introspect/family", vec![], vec![]);
+        let introspect = |peer_addresses: &PeerAddresses| {
+            handle_introspect(
+                &config,
+                &state,
+                peer_addresses,
+                &request,
+                "family".to_string(),
+            )
+        };
+
+        assert_eq!(introspect(&PeerAddresses::default()).status_code, 403);
+
+        let peer_addresses = PeerAddresses::new(HashMap::from([(
+            "hal9001".to_string(),
+            HashSet::from([request.remote_addr().ip()]),
+        )]));
+        let resp = introspect(&peer_addresses);
+        assert_eq!(resp.status_code, 200);
+        let mut body = String::new();
+        resp.data
+            .into_reader_and_size()
+            .0
+            .read_to_string(&mut body)
+            .unwrap();
+        let view: IntrospectResponse = serde_json::from_str(&body).unwrap();
+        assert_eq!(
+            GridNodeStatus::from_code(view.statuses["hal9001"]),
+            Some(GridNodeStatus::Dead)
+        );
+        assert!(
+            view.statuses
+                .values()
+                .all(|code| GridNodeStatus::from_code(*code).is_some())
+        );
+    }
+
+    #[tokio::test]
+    async fn peer_endpoints_only_for_peers() {
+        let mut config = test_config(WITH_PEER);
+        config.restrict_peer_endpoints = true;
+        config.verify_broadcast_source = true;
+        config.rate_limit.enabled = false;
+        for (name, skip_peer_ip_check) in [("hal9002", false), ("proxied", true)] {
+            let mut node = config.nodes["hal9001"].clone();
+            node.skip_peer_ip_check = skip_peer_ip_check;
+            config.nodes.insert(name.to_string(), node);
+        }
+        let config = Arc::new(config);
+        let state = State::new(&config);
+        let handler = |peer_addresses: &PeerAddresses| {
+            app(
+                SharedConfig::new(config.clone()),
+                state.clone(),
+                Arc::new(RateLimiter::new(
+                    config.rate_limit.clone(),
+                    PeerAddresses::default(),
+                )),
+                peer_addresses.clone(),
+                None,
+                tokio::runtime::Handle::current(),
+            )
+        };
+        // calls let through stop at the wrong key
+        let statuses = |peer_addresses: &PeerAddresses, name: Option<&str>| {
+            let handler = handler(peer_addresses);
+            let headers = name
+                .map(|name| vec![(NODE_HEADER.to_string(), name.to_string())])
+                .unwrap_or_default();
+            [
+                ("GET", "/obituary/wrong"),
+                ("GET", "/obituary/v2/wrong"),
+                ("GET", "/introspect/wrong"),
+                ("POST", "/silence-broadcast/wrong"),
+                ("POST", "/mute-broadcast/wrong"),
+                ("POST", "/claim/wrong"),
+            ]
+            .map(|(method, url)| {
+                handler(&Request::fake_http(method, url, headers.clone(), vec![])).status_code
+            })
+        };
+
+        let strangers = PeerAddresses::default();
+        assert_eq!(statuses(&strangers, None), [403; 6]);
+        // the name a caller gives only counts for nodes with `skip_peer_ip_check`
+        assert_eq!(statuses(&strangers, Some("hal9001")), [403; 6]);
+        assert_eq!(statuses(&strangers, Some("proxied")), [406; 6]);
+
+        // nodes behind the same NAT call from one address
+        let local = HashSet::from([std::net::IpAddr::from([127, 0, 0, 1])]);
+        let nat = PeerAddresses::new(HashMap::from([
+            ("hal9001".to_string(), local.clone()),
+            ("hal9002".to_string(), local),
+        ]));
+        assert_eq!(statuses(&nat, None), [406; 6]);
+        for name in ["hal9001", "hal9002"] {
+            let request = Request::fake_http(
+                "POST",
+                "/silence-broadcast/key",
+                vec![(NODE_HEADER.to_string(), name.to_string())],
+                vec![],
+            );
+            assert_eq!(
+                check_broadcast_sender(&config, &state, &nat, &request, Some(name)),
+                Ok(None)
+            );
+        }
+    }
+
+    #[test]
+    fn latest_mute_change_wins() {
+        let config = test_config(WITH_PEER);
+        let state = State::new(&config);
+        let mut gr = state.lock().unwrap();
+        let now = Utc::now();
+        let minutes = chrono::Duration::minutes;
+        let mute = |muted_until: Option<DateTime<Utc>>, changed_at| MuteBroadcastRequest {
+            muted_until,
+            changed_at,
+            origin: Some("hal9001".to_string()),
+        };
+        let receive = |gr: &mut StateInner, body| {
+            receive_mute_broadcast(gr, body, "10.0.0.2".to_string(), None).status_code
+        };
+
+        assert_eq!(receive(&mut gr, mute(Some(now + minutes(30)), now)), 204);
+        assert_eq!(gr.muted_until(now), Some(now + minutes(30)));
+
+        // lifted before the mute was made, arriving late
+        assert_eq!(receive(&mut gr, mute(None, now - minutes(1))), 204);
+        assert_eq!(gr.muted_until(now), Some(now + minutes(30)));
+        // an older, longer mute doesn't take over either
+        receive(&mut gr, mute(Some(now + minutes(60)), now - minutes(2)));
+        assert_eq!(gr.muted_until(now), Some(now + minutes(30)));
+
+        receive(&mut gr, mute(None, now + minutes(1)));
+        assert_eq!(gr.muted_until(now), None);
+        let mute = gr.mute.as_ref().unwrap();
+        assert!(mute.broadcasted);
+        assert_eq!(mute.changed_at, now + minutes(1));
+    }
+
+    fn broadcast(json: &str) -> SilenceBroadcastRequest {
+        serde_json::from_str(json).unwrap()
+    }
+
+    #[test]
+    fn silence_broadcasts_from_mixed_versions() {
+        let config = test_config(WITH_PEER);
+        let state = State::new(&config);
+        let mut gr = state.lock().unwrap();
+        let source = || AuditSource::Poller;
+
+        // an older node with a numeric id, then a newer one with a uuid
+        let legacy =
+            broadcast(r#"{"id":42,"node_name":"hal9001","silent_until":"2030-01-01T00:00:00Z"}"#);
+        assert_eq!(legacy.id, "42");
+        assert_eq!(
+            serde_json::to_value(&legacy).unwrap()["id"],
+            serde_json::json!(42)
+        );
+        let uuid = broadcast(
+            r#"{"id":"0b5e2a52-3c4d-4e6f-8a9b-0c1d2e3f4a5b","node_name":"hal9000","silent_until":"2030-01-01T00:00:00Z"}"#,
+        );
+        assert_eq!(
+            receive_silence_broadcast(&mut gr, legacy, source(), None).status_code,
+            204
+        );
+        assert_eq!(
+            receive_silence_broadcast(&mut gr, uuid, source(), None).status_code,
+            204
+        );
+        assert_eq!(gr.silences.len(), 2);
+
+        // same numeric id, another node: a collision, not silently dropped
+        let colliding =
+            broadcast(r#"{"id":42,"node_name":"hal9000","silent_until":"2030-01-01T00:00:00Z"}"#);
+        assert_eq!(
+            receive_silence_broadcast(&mut gr, colliding, source(), None).status_code,
+            409
+        );
+
+        // a newer revision updates it
+        let updated = broadcast(
+            r#"{"id":"42","node_name":"hal9001","silent_until":"2031-01-01T00:00:00Z","revision":1}"#,
+        );
+        assert_eq!(
+            receive_silence_broadcast(&mut gr, updated, source(), None).status_code,
+            204
+        );
+        let silence = gr.silences.iter().find(|sl| sl.id == "42").unwrap();
+        assert_eq!(silence.revision, 1);
+        assert_eq!(
+            silence.silent_until.to_rfc3339(),
+            "2031-01-01T00:00:00+00:00"
+        );
+        assert_eq!(gr.silences.len(), 2);
+    }
+
+    #[test]
+    fn silence_revisions_and_cancellations() {
+        let config = test_config(WITH_PEER);
+        let state = State::new(&config);
+        let mut gr = state.lock().unwrap();
+        let receive = |gr: &mut StateInner, json: &str| {
+            receive_silence_broadcast(gr, broadcast(json), AuditSource::Poller, None).status_code
+        };
+        let silence = |gr: &StateInner| {
+            let sl = gr.silences.iter().find(|sl| sl.id == "7").unwrap();
+            (sl.revision, sl.silent_until.to_rfc3339(), sl.cancelled)
+        };
+
+        // an older node sends neither `revision` nor `cancelled`
+        let legacy = r#"{"id":"7","node_name":"hal9001","silent_until":"2030-01-01T00:00:00Z"}"#;
+        assert_eq!(receive(&mut gr, legacy), 204);
+        assert_eq!(
+            silence(&gr),
+            (0, "2030-01-01T00:00:00+00:00".to_string(), false)
+        );
+        assert_eq!(receive(&mut gr, legacy), 204);
+        assert_eq!(gr.silences.len(), 1);
+        gr.silences[0].received_by.insert("hal9001".to_string());
+
+        assert_eq!(
+            receive(
+                &mut gr,
+                r#"{"id":"7","node_name":"hal9001","silent_until":"2031-01-01T00:00:00Z","revision":2}"#
+            ),
+            204
+        );
+        assert_eq!(
+            silence(&gr),
+            (2, "2031-01-01T00:00:00+00:00".to_string(), false)
+        );
+        // every peer gets the new revision again
+        assert!(gr.silences[0].received_by.is_empty());
+
+        // an older revision arriving late changes nothing
+        assert_eq!(
+            receive(
+                &mut gr,
+                r#"{"id":"7","node_name":"hal9001","silent_until":"2032-01-01T00:00:00Z","revision":1}"#
+            ),
+            204
+        );
+        assert_eq!(
+            silence(&gr),
+            (2, "2031-01-01T00:00:00+00:00".to_string(), false)
+        );
+
+        // cancelled, it's kept so the revision before can't bring it back
+        assert_eq!(
+            receive(
+                &mut gr,
+                r#"{"id":"7","node_name":"hal9001","silent_until":"2031-01-01T00:00:00Z","revision":3,"cancelled":true}"#
+            ),
+            204
+        );
+        assert_eq!(
+            receive(
+                &mut gr,
+                r#"{"id":"7","node_name":"hal9001","silent_until":"2031-01-01T00:00:00Z","revision":2}"#
+            ),
+            204
+        );
+        assert_eq!(
+            silence(&gr),
+            (3, "2031-01-01T00:00:00+00:00".to_string(), true)
+        );
+        assert!(gr.introspect("hal9000", Utc::now()).silences.is_empty());
+
+        assert_eq!(
+            receive(
+                &mut gr,
+                r#"{"id":"7","node_name":"hal9000","silent_until":"2031-01-01T00:00:00Z","revision":4}"#
+            ),
+            409
+        );
+        assert_eq!(silence(&gr).0, 3);
+    }
 }
@@ -0,0 +1,245 @@
+use crate::config::{Config, Language};
+use crate::exec;
+
+/// Built-in announcement texts by message key, `{name}` placeholders are filled in on rendering
+type Catalog = &'static [(&'static str, &'static str)];
+
+/// Every message has a text here, other languages fall back to it
+const EN: Catalog = &[
+    (
+        "dead",
+        "Grid announcement, `{node}`{group} has unfortunately died{because}{last_seen}{failing}{grid}, announced by: `{announcer}`{chosen}{mention}",
+    ),
+    (
+        "recovered",
+        "Grid announcement, `{node}`{group} has fortunately RETURNED, was announced by `{announced_by}` at {announced_at}, down for {down_for}{grid}, announced by: `{announcer}`{mention}",
+    ),
+    (
+        "escalated",
+        "Grid ESCALATION, `{node}`{group} is still dead {duration} after its death was announced, announced by: `{announcer}`{mention}",
+    ),
+    (
+        "joined",
+        "Grid announcement, `{node}` joined the grid and is reachable",
+    ),
+    (
+        "reminder",
+        "Grid announcement, `{node}` is still dead after its silence",
+    ),
+    (
+        "cert_expiry",
+        "Grid announcement, the certificate of `{node}` expires in {days} days",
+    ),
+    (
+        "disk_full",
+        "Grid announcement, the disk of `{node}` is {used}% full",
+    ),
+    (
+        "version_mismatch",
+        "Grid announcement, `{node}` runs version {version}, expected {expected}",
+    ),
+    (
+        "dying",
+        "Grid announcement, `{node}` doesn't answer, {count} polls failed in a row",
+    ),
+    (
+        "flapping",
+        "Grid announcement, `{node}` is flapping, it died or recovered {count} times within {window}, these are not announced until it's stable",
+    ),
+    (
+        "stabilized",
+        "Grid announcement, `{node}` is stable again, it neither died nor recovered within {window}",
+    ),
+    ("preferred_announcer", " as its preferred announcer"),
+    (
+        "preferred_unavailable",
+        ", chosen by roll as its preferred announcer `{preferred}` didn't confirm the death",
+    ),
+    ("last_seen", ", last seen {time}"),
+    ("failing", ", failing for {down_for}"),
+    ("grid_dead", ", {dead}/{total} nodes of the grid dead"),
+    (
+        "summary",
+        "Grid announcement, {count} further announcements were suppressed, grid status: {alive}/{total} alive",
+    ),
+    (
+        "self_check",
+        "Grid announcement, `{node}` can't reach its own listener at {url} ({error}), peers will soon declare it dead",
+    ),
+    (
+        "monitoring_started",
+        "Grid announcement, `{node}` started monitoring the grid",
+    ),
+    (
+        "online",
+        "Grid announcement, `{node}` is online, monitoring {peers} peers, version {version}",
+    ),
+    (
+        "crash_loop",
+        "Grid announcement, `{node}` restarted {count} times within an hour, its further starts are not announced",
+    ),
+    ("offline", "Grid announcement, `{node}` is shutting down"),
+    ("digest", "Grid report: {alive}/{total} alive"),
+    ("digest_quiet", ", no incidents {period}"),
+    (
+        "digest_incidents",
+        ", {incidents} {period} totalling {minutes} minutes, noisiest node: {node}",
+    ),
+    ("incident", "1 incident"),
+    ("incidents", "{count} incidents"),
+    ("today", "today"),
+    ("this_week", "this week"),
+];
+
+const HU: Catalog = &[
+    (
+        "dead",
+        "Grid bejelentés, `{node}`{group} sajnos meghalt{because}{last_seen}{failing}{grid}, bejelentette: `{announcer}`{chosen}{mention}",
+    ),
+    (
+        "recovered",
+        "Grid bejelentés, `{node}`{group} szerencsére VISSZATÉRT, `{announced_by}` jelentette be {announced_at}-kor, {down_for} volt halott{grid}, bejelentette: `{announcer}`{mention}",
+    ),
+    (
+        "escalated",
+        "Grid ESZKALÁCIÓ, `{node}`{group} még mindig halott {duration} a halála bejelentése óta, bejelentette: `{announcer}`{mention}",
+    ),
+    (
+        "joined",
+        "Grid bejelentés, `{node}` csatlakozott a gridhez és elérhető",
+    ),
+    (
+        "reminder",
+        "Grid bejelentés, `{node}` a némítása után is halott",
+    ),
+    (
+        "cert_expiry",
+        "Grid bejelentés, `{node}` tanúsítványa {days} nap múlva lejár",
+    ),
+    (
+        "disk_full",
+        "Grid bejelentés, `{node}` lemeze {used}%-ig megtelt",
+    ),
+    (
+        "version_mismatch",
+        "Grid bejelentés, `{node}` verziója {version}, az elvárt {expected}",
+    ),
+    (
+        "dying",
+        "Grid bejelentés, `{node}` nem válaszol, {count} lekérdezés sikertelen egymás után",
+    ),
+    (
+        "flapping",
+        "Grid bejelentés, `{node}` instabil, {window} alatt {count} alkalommal halt meg vagy tért vissza, amíg nem stabil, ezek nem lesznek bejelentve",
+    ),
+    (
+        "stabilized",
+        "Grid bejelentés, `{node}` újra stabil, {window} alatt nem halt meg és nem tért vissza",
+    ),
+    ("preferred_announcer", " mint kijelölt bejelentő"),
+    (
+        "preferred_unavailable",
+        ", sorsolással, mert a kijelölt bejelentő, `{preferred}` nem erősítette meg a halált",
+    ),
+    ("last_seen", ", utoljára elérhető: {time}"),
+    ("failing", ", {down_for} óta nem válaszol"),
+    ("grid_dead", ", a gridben {dead}/{total} node halott"),
+    (
+        "summary",
+        "Grid bejelentés, további {count} bejelentés maradt ki, a grid állapota: {alive}/{total} él",
+    ),
+    (
+        "self_check",
+        "Grid bejelentés, `{node}` nem éri el a saját szerverét itt: {url} ({error}), a többiek hamarosan halottnak nyilvánítják",
+    ),
+    (
+        "monitoring_started",
+        "Grid bejelentés, `{node}` elkezdte figyelni a gridet",
+    ),
+    (
+        "online",
+        "Grid bejelentés, `{node}` elindult, {peers} node-ot figyel, verzió: {version}",
+    ),
+    (
+        "crash_loop",
+        "Grid bejelentés, `{node}` egy órán belül {count} alkalommal indult újra, a további indulásai nem lesznek bejelentve",
+    ),
+    ("offline", "Grid bejelentés, `{node}` leáll"),
+    ("digest", "Grid jelentés: {alive}/{total} él"),
+    ("digest_quiet", ", {period} nem volt incidens"),
+    (
+        "digest_incidents",
+        ", {incidents} {period}, összesen {minutes} perc, legzajosabb node: {node}",
+    ),
+    ("incident", "1 incidens"),
+    ("incidents", "{count} incidens"),
+    ("today", "ma"),
+    ("this_week", "ezen a héten"),
+];
+
+fn catalog(language: Language) -> Catalog {
+    match language {
+        Language::En => EN,
+        Language::Hu => HU,
+    }
+}
+
+/// The custom template of `key`, or its text in the configured language, or in English
+pub fn render(config: &Config, key: &str, values: &[(&str, &str)]) -> String {
+    let template = config
+        .announcement_templates
+        .get(key)
+        .map(String::as_str)
+        .or_else(|| lookup(catalog(config.language), key))
+        .or_else(|| lookup(EN, key))
+        .unwrap_or(key);
+    exec::render(template, values)
+}
+
+fn lookup(catalog: Catalog, key: &str) -> Option<&'static str> {
+    catalog
+        .iter()
+        .find(|(k, _)| *k == key)
+        .map(|(_, text)| *text)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::MESSAGE_KEYS;
+    use crate::testing::test_config;
+
+    #[test]
+    fn catalogs_only_translate_known_keys() {
+        assert_eq!(
+            EN.iter().map(|(key, _)| *key).collect::<Vec<_>>(),
+            MESSAGE_KEYS
+        );
+        for language in [Language::En, Language::Hu] {
+            for (key, _) in catalog(language) {
+                assert!(
+                    MESSAGE_KEYS.contains(key),
+                    "{language:?} `{key}` is unknown"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn language_with_fallback_and_override() {
+        let config = test_config("language: hu");
+        assert_eq!(
+            render(&config, "joined", &[("node", "hal9001")]),
+            "Grid bejelentés, `hal9001` csatlakozott a gridhez és elérhető"
+        );
+        assert_eq!(lookup(HU, "nonexistent"), None);
+        assert_eq!(render(&config, "nonexistent", &[]), "nonexistent");
+
+        let config =
+            test_config("language: hu\nannouncement_templates:\n  joined: \"{node} is here\"");
+        assert_eq!(
+            render(&config, "joined", &[("node", "hal9001")]),
+            "hal9001 is here"
+        );
+    }
+}
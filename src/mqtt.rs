@@ -0,0 +1,413 @@
+use crate::config::{Config, MqttConfig};
+use crate::poller::{NodeState, State};
+use crate::reload::SharedConfig;
+use crate::{GridNodeStatus, VERSION};
+use anyhow::{Context, Result, bail};
+use chrono::{DateTime, Utc};
+use rumqttc::{
+    AsyncClient, Event, EventLoop, LastWill, MqttOptions, Packet, QoS, TlsConfiguration, Transport,
+};
+use serde::Serialize;
+use serde_json::json;
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::mpsc::{UnboundedReceiver, UnboundedSender, unbounded_channel};
+use tokio_rustls::rustls::pki_types::CertificateDer;
+use tokio_rustls::rustls::pki_types::pem::PemObject;
+use tokio_rustls::rustls::{ClientConfig, RootCertStore, crypto::ring};
+use tracing::{debug, info, warn};
+
+/// Publishes not yet sent wait in this, the rest are tried again on the next tick
+const REQUEST_CAPACITY: usize = 256;
+const PUBLISH_INTERVAL: Duration = Duration::from_secs(1);
+const MIN_RECONNECT_DELAY: Duration = Duration::from_secs(1);
+const MAX_RECONNECT_DELAY: Duration = Duration::from_secs(60);
+
+/// What the connection tells the publisher
+enum BrokerEvent {
+    /// Everything is published again, the broker may have lost it
+    Connected,
+    /// A discovery config of this node found on the broker, by its object id
+    Retained(String),
+}
+
+/// The connection to the broker, driven by `run`
+pub struct Mqtt {
+    client: AsyncClient,
+    eventloop: EventLoop,
+    topics: Topics,
+}
+
+/// Node names in topics and ids, anything but `[A-Za-z0-9_-]` replaced by `_`
+fn topic_id(name: &str) -> String {
+    name.chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '_' || c == '-' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
+
+struct Topics {
+    base: String,
+    discovery_prefix: String,
+    me: String,
+}
+
+impl Topics {
+    fn new(config: &Config, mqtt: &MqttConfig) -> Self {
+        Self {
+            base: mqtt.base_topic.trim_end_matches('/').to_string(),
+            discovery_prefix: mqtt.discovery_prefix.trim_end_matches('/').to_string(),
+            me: topic_id(&config.name),
+        }
+    }
+
+    /// `online` while connected, `offline` by the last will
+    fn availability(&self) -> String {
+        format!("{}/{}/availability", self.base, self.me)
+    }
+
+    fn state(&self, node: &str) -> String {
+        format!("{}/{}/{}/state", self.base, self.me, node)
+    }
+
+    fn object_id(&self, node: &str) -> String {
+        format!("freecaster_{}_{}", self.me, node)
+    }
+
+    fn discovery(&self, node: &str) -> String {
+        format!(
+            "{}/binary_sensor/{}/config",
+            self.discovery_prefix,
+            self.object_id(node)
+        )
+    }
+
+    /// The node of one of this node's discovery configs
+    fn discovered_node<'a>(&self, topic: &'a str) -> Option<&'a str> {
+        topic
+            .strip_prefix(&self.discovery_prefix)?
+            .strip_prefix("/binary_sensor/")?
+            .strip_suffix("/config")?
+            .strip_prefix("freecaster_")?
+            .strip_prefix(&self.me)?
+            .strip_prefix('_')
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct NodeStatePayload {
+    status: GridNodeStatus,
+    last_poll: Option<DateTime<Utc>>,
+    last_ok: Option<DateTime<Utc>>,
+    latency_ms: Option<u64>,
+    fail_count: usize,
+}
+
+impl From<&NodeState> for NodeStatePayload {
+    fn from(ns: &NodeState) -> Self {
+        Self {
+            status: ns.status(),
+            last_poll: ns.last_poll,
+            last_ok: ns.last_ok,
+            latency_ms: ns.latency.map(|latency| latency.as_millis() as u64),
+            fail_count: ns.fail_count,
+        }
+    }
+}
+
+/// Home Assistant's discovery config of a node, a connectivity sensor of this node's device
+fn discovery_payload(config: &Config, topics: &Topics, node: &str, id: &str) -> String {
+    json!({
+        "name": config.display_name(node),
+        "unique_id": topics.object_id(id),
+        "device_class": "connectivity",
+        "state_topic": topics.state(id),
+        "value_template": "{% if value_json.status == 'unknown' %}None{% elif value_json.status == 'alive' %}ON{% else %}OFF{% endif %}",
+        "json_attributes_topic": topics.state(id),
+        "availability_topic": topics.availability(),
+        "device": {
+            "identifiers": [format!("freecaster_grid_{}", topics.me)],
+            "name": config.display_name(&config.name),
+            "manufacturer": "freecaster-grid",
+            "sw_version": VERSION,
+        },
+    })
+    .to_string()
+}
+
+fn tls_config(mqtt: &MqttConfig) -> Result<ClientConfig> {
+    let mut roots = RootCertStore::empty();
+    match mqtt.ca_path.as_ref() {
+        Some(path) => {
+            let certificates = CertificateDer::pem_file_iter(path)
+                .with_context(|| format!("Failed to read mqtt.ca_path from `{path}`"))?
+                .collect::<Result<Vec<_>, _>>()
+                .with_context(|| format!("Invalid certificate in mqtt.ca_path `{path}`"))?;
+            if certificates.is_empty() {
+                bail!("No certificate in mqtt.ca_path `{path}`");
+            }
+            for certificate in certificates {
+                roots.add(certificate)?;
+            }
+        }
+        None => roots.extend(webpki_roots::TLS_SERVER_ROOTS.iter().cloned()),
+    }
+    Ok(
+        ClientConfig::builder_with_provider(Arc::new(ring::default_provider()))
+            .with_safe_default_protocol_versions()?
+            .with_root_certificates(roots)
+            .with_no_client_auth(),
+    )
+}
+
+/// Sets up the connection to the broker, `None` without an `mqtt` section. It's only made once `run` polls it
+pub fn connect(config: &Config) -> Result<Option<Mqtt>> {
+    let Some(mqtt) = config.mqtt.as_ref() else {
+        return Ok(None);
+    };
+    let url = reqwest::Url::parse(&mqtt.url)
+        .with_context(|| format!("Invalid mqtt.url `{}`", mqtt.url))?;
+    let tls = match url.scheme() {
+        "mqtt" => false,
+        "mqtts" => true,
+        scheme => bail!("mqtt.url has scheme `{scheme}`, expected `mqtt` or `mqtts`"),
+    };
+    let host = url.host_str().context("mqtt.url has no host")?;
+    let port = url.port().unwrap_or(if tls { 8883 } else { 1883 });
+
+    let topics = Topics::new(config, mqtt);
+    let mut options = MqttOptions::new(format!("freecaster-grid-{}", topics.me), host, port);
+    options.set_keep_alive(Duration::from_secs(30));
+    options.set_last_will(LastWill::new(
+        topics.availability(),
+        "offline",
+        QoS::AtLeastOnce,
+        true,
+    ));
+    if let Some(username) = mqtt.username.as_ref() {
+        options.set_credentials(username, mqtt.password.clone().unwrap_or_default());
+    }
+    if tls {
+        options.set_transport(Transport::tls_with_config(TlsConfiguration::Rustls(
+            Arc::new(tls_config(mqtt)?),
+        )));
+    }
+
+    let (client, eventloop) = AsyncClient::new(options, REQUEST_CAPACITY);
+    Ok(Some(Mqtt {
+        client,
+        eventloop,
+        topics,
+    }))
+}
+
+/// Keeps the broker up to date with the nodes' states, reconnecting with a backoff
+pub async fn run(mqtt: Option<Mqtt>, config: SharedConfig, state: State) -> Result<()> {
+    let Some(Mqtt {
+        client,
+        eventloop,
+        topics,
+    }) = mqtt
+    else {
+        return Ok(());
+    };
+    info!("Publishing the grid to MQTT as `{}`", topics.me);
+
+    let (events, received) = unbounded_channel();
+    tokio::spawn(connection(
+        eventloop,
+        client.clone(),
+        topics.availability(),
+        format!("{}/binary_sensor/+/config", topics.discovery_prefix),
+        events,
+    ));
+    publish(client, topics, config, state, received).await;
+    Ok(())
+}
+
+/// Drives the connection, the publisher is told about connects and retained configs
+async fn connection(
+    mut eventloop: EventLoop,
+    client: AsyncClient,
+    availability: String,
+    discovery_filter: String,
+    events: UnboundedSender<BrokerEvent>,
+) {
+    let mut delay = MIN_RECONNECT_DELAY;
+    loop {
+        match eventloop.poll().await {
+            Ok(Event::Incoming(Packet::ConnAck(_))) => {
+                info!("Connected to the MQTT broker");
+                delay = MIN_RECONNECT_DELAY;
+                if let Err(err) =
+                    client.try_publish(&availability, QoS::AtLeastOnce, true, "online")
+                {
+                    warn!("Failed to publish the availability: {err}");
+                }
+                if let Err(err) = client.try_subscribe(&discovery_filter, QoS::AtLeastOnce) {
+                    warn!("Failed to subscribe to `{discovery_filter}`: {err}");
+                }
+                if events.send(BrokerEvent::Connected).is_err() {
+                    return;
+                }
+            }
+            // cleared ones come back empty
+            Ok(Event::Incoming(Packet::Publish(publish))) if !publish.payload.is_empty() => {
+                if events.send(BrokerEvent::Retained(publish.topic)).is_err() {
+                    return;
+                }
+            }
+            Ok(_) => {}
+            Err(err) => {
+                warn!("MQTT connection failed, reconnecting in {delay:?}: {err}");
+                tokio::time::sleep(delay).await;
+                delay = (delay * 2).min(MAX_RECONNECT_DELAY);
+            }
+        }
+    }
+}
+
+async fn publish(
+    client: AsyncClient,
+    topics: Topics,
+    config: SharedConfig,
+    state: State,
+    mut received: UnboundedReceiver<BrokerEvent>,
+) {
+    let mut connected = false;
+    // state payloads by topic id as last handed to the client
+    let mut published: HashMap<String, String> = HashMap::new();
+    // configs of nodes maybe no longer in the grid, from before a restart
+    let mut retained: HashSet<String> = HashSet::new();
+    let mut interval = tokio::time::interval(PUBLISH_INTERVAL);
+    loop {
+        tokio::select! {
+            event = received.recv() => match event {
+                Some(BrokerEvent::Connected) => {
+                    connected = true;
+                    published.clear();
+                }
+                Some(BrokerEvent::Retained(topic)) => {
+                    if let Some(id) = topics.discovered_node(&topic) {
+                        retained.insert(id.to_string());
+                    }
+                }
+                None => return,
+            },
+            _ = interval.tick() => {}
+        }
+        if !connected {
+            continue;
+        }
+
+        let config = config.get();
+        let (nodes, polled) = {
+            let gr = state.lock().expect("Failed to lock state");
+            let nodes = gr
+                .node_state
+                .iter()
+                .map(|ns| {
+                    (
+                        topic_id(&ns.name),
+                        (
+                            ns.name.clone(),
+                            serde_json::to_string(&NodeStatePayload::from(ns))
+                                .expect("Failed to serialize the node state"),
+                        ),
+                    )
+                })
+                .collect::<HashMap<_, _>>();
+            (nodes, gr.last_cycle_completed.is_some())
+        };
+
+        for (id, (name, payload)) in nodes.iter() {
+            if published.get(id) == Some(payload) {
+                continue;
+            }
+            let discovery = discovery_payload(&config, &topics, name, id);
+            let sent = client
+                .try_publish(topics.discovery(id), QoS::AtLeastOnce, true, discovery)
+                .and_then(|_| {
+                    client.try_publish(topics.state(id), QoS::AtLeastOnce, true, payload.clone())
+                });
+            match sent {
+                Ok(()) => {
+                    published.insert(id.clone(), payload.clone());
+                }
+                Err(err) => debug!("Publishing `{name}` again on the next tick: {err}"),
+            }
+        }
+
+        // the node list is only complete once the poller went around
+        let removed = published
+            .keys()
+            .chain(retained.iter().filter(|_| polled))
+            .filter(|id| !nodes.contains_key(*id))
+            .cloned()
+            .collect::<HashSet<_>>();
+        for id in removed {
+            let cleared = client
+                .try_publish(topics.discovery(&id), QoS::AtLeastOnce, true, "")
+                .and_then(|_| client.try_publish(topics.state(&id), QoS::AtLeastOnce, true, ""));
+            match cleared {
+                Ok(()) => {
+                    info!("Removed `{id}` from MQTT");
+                    published.remove(&id);
+                    retained.remove(&id);
+                }
+                Err(err) => debug!("Removing `{id}` from MQTT again on the next tick: {err}"),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::test_config;
+
+    #[test]
+    fn topics_of_nodes() {
+        let config = test_config(
+            r#"
+nodes:
+  hal.9001:
+    address: http://hal9001.local:3037
+    display_name: Discovery One
+mqtt:
+  url: mqtt://broker.local
+  base_topic: grid/
+"#,
+        );
+        let topics = Topics::new(&config, config.mqtt.as_ref().unwrap());
+
+        let id = topic_id("hal.9001");
+        assert_eq!(id, "hal_9001");
+        assert_eq!(topics.state(&id), "grid/hal9000/hal_9001/state");
+        assert_eq!(
+            topics.discovery(&id),
+            "homeassistant/binary_sensor/freecaster_hal9000_hal_9001/config"
+        );
+        assert_eq!(
+            topics.discovered_node(&topics.discovery(&id)),
+            Some(id.as_str())
+        );
+        assert_eq!(
+            topics.discovered_node("homeassistant/binary_sensor/freecaster_hal90000_x/config"),
+            None
+        );
+
+        let discovery: serde_json::Value =
+            serde_json::from_str(&discovery_payload(&config, &topics, "hal.9001", &id)).unwrap();
+        assert_eq!(discovery["name"], "Discovery One");
+        assert_eq!(discovery["unique_id"], "freecaster_hal9000_hal_9001");
+        assert_eq!(discovery["availability_topic"], "grid/hal9000/availability");
+        assert!(connect(&config).unwrap().is_some());
+    }
+}
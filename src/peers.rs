@@ -0,0 +1,81 @@
+use crate::config::NodeConfig;
+use std::collections::{HashMap, HashSet};
+use std::net::IpAddr;
+use std::sync::{Arc, RwLock};
+use tracing::warn;
+
+/// The IPs each node of the grid resolves to, for peer endpoints and the rate limiter,
+/// resolved again on reloads and when discovery finds other nodes
+#[derive(Clone, Default)]
+pub struct PeerAddresses(Arc<RwLock<HashMap<String, HashSet<IpAddr>>>>);
+
+impl PeerAddresses {
+    #[cfg(test)]
+    pub fn new(addresses: HashMap<String, HashSet<IpAddr>>) -> Self {
+        Self(Arc::new(RwLock::new(addresses)))
+    }
+
+    pub fn get(&self, name: &str) -> Option<HashSet<IpAddr>> {
+        self.0
+            .read()
+            .expect("Failed to lock peer addresses")
+            .get(name)
+            .cloned()
+    }
+
+    /// Whether any node resolves to `ip`, nodes behind the same NAT share one
+    pub fn contains(&self, ip: IpAddr) -> bool {
+        self.0
+            .read()
+            .expect("Failed to lock peer addresses")
+            .values()
+            .any(|ips| ips.contains(&ip))
+    }
+
+    /// Resolves `nodes` and drops the addresses of the others,
+    /// a node failing to resolve keeps its last known addresses
+    pub async fn refresh(&self, nodes: &HashMap<String, NodeConfig>) {
+        let mut addresses = HashMap::new();
+        for (name, node) in nodes {
+            match node.resolve_ips().await {
+                Ok(ips) => {
+                    addresses.insert(name.clone(), ips.into_iter().collect());
+                }
+                Err(err) => {
+                    warn!("Failed to resolve node `{name}`, it may get rate limited: {err:?}");
+                    if let Some(ips) = self.get(name) {
+                        addresses.insert(name.clone(), ips);
+                    }
+                }
+            }
+        }
+        *self.0.write().expect("Failed to lock peer addresses") = addresses;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::test_config;
+
+    #[tokio::test]
+    async fn refresh_follows_the_nodes() {
+        let config = test_config(
+            "nodes:\n  hal9001:\n    address: http://127.0.0.1:3037\n  hal9002:\n    address: http://127.0.0.2:3037\n",
+        );
+        let peers = PeerAddresses::default();
+        peers.refresh(&config.nodes).await;
+        assert!(peers.contains("127.0.0.2".parse().unwrap()));
+
+        let mut nodes = config.nodes.clone();
+        nodes.remove("hal9002");
+        // unresolvable, keeps what it had
+        nodes.get_mut("hal9001").unwrap().address = "not an address".to_string();
+        peers.refresh(&nodes).await;
+        assert!(!peers.contains("127.0.0.2".parse().unwrap()));
+        assert_eq!(
+            peers.get("hal9001"),
+            Some(HashSet::from(["127.0.0.1".parse().unwrap()]))
+        );
+    }
+}
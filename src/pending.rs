@@ -0,0 +1,228 @@
+use crate::announcer::{AnnouncementEvent, AnnouncementKind};
+use crate::config::AnnouncementMode;
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::collections::VecDeque;
+use std::str::FromStr;
+use tracing::{error, warn};
+
+/// Announcements waiting over this drop the oldest one, deaths last
+pub const MAX_PENDING: usize = 200;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PendingKind {
+    /// Held back by a channel's `announcement_limits`
+    Queued,
+    /// Its delivery timed out, tried again by the next cycle
+    Retry,
+    /// A death to be escalated, these stay with the dead node and are only listed
+    Escalation,
+}
+
+impl FromStr for PendingKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "queued" => Ok(PendingKind::Queued),
+            "retry" => Ok(PendingKind::Retry),
+            "escalation" => Ok(PendingKind::Escalation),
+            _ => Err(format!("Unknown kind `{s}`")),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct PendingAnnouncement {
+    pub kind: PendingKind,
+    pub mode: AnnouncementMode,
+    pub event: AnnouncementEvent,
+    /// Not sent before this
+    pub due: DateTime<Utc>,
+    /// Deliveries tried so far
+    pub attempts: u32,
+    /// Order of arrival, ties of `due` are broken by it
+    seq: u64,
+}
+
+impl PendingAnnouncement {
+    fn is_death(&self) -> bool {
+        matches!(
+            self.event.kind,
+            AnnouncementKind::Died | AnnouncementKind::Escalated
+        )
+    }
+}
+
+/// The announcements of every channel still to be sent, oldest first
+#[derive(Debug, Default)]
+pub struct PendingAnnouncements {
+    items: VecDeque<PendingAnnouncement>,
+    next_seq: u64,
+}
+
+impl PendingAnnouncements {
+    pub fn push(
+        &mut self,
+        kind: PendingKind,
+        mode: AnnouncementMode,
+        event: AnnouncementEvent,
+        due: DateTime<Utc>,
+        attempts: u32,
+    ) {
+        if self.items.len() >= MAX_PENDING {
+            self.drop_oldest();
+        }
+        self.items.push_back(PendingAnnouncement {
+            kind,
+            mode,
+            event,
+            due,
+            attempts,
+            seq: self.next_seq,
+        });
+        self.next_seq += 1;
+    }
+
+    /// The oldest one which isn't a death, the oldest death if they all are
+    fn drop_oldest(&mut self) {
+        let index = self
+            .items
+            .iter()
+            .position(|item| !item.is_death())
+            .unwrap_or(0);
+        let Some(dropped) = self.items.remove(index) else {
+            return;
+        };
+        let message = format!(
+            "Over {MAX_PENDING} pending announcements, dropping the {:?} announcement of `{}` ({}) on {:?}",
+            dropped.kind,
+            dropped.event.node,
+            dropped.event.kind.as_str(),
+            dropped.mode
+        );
+        if dropped.is_death() {
+            error!("{message}");
+        } else {
+            warn!("{message}");
+        }
+    }
+
+    /// Takes the oldest of `kind` on `mode`
+    pub fn pop(
+        &mut self,
+        kind: PendingKind,
+        mode: AnnouncementMode,
+    ) -> Option<PendingAnnouncement> {
+        let index = self
+            .items
+            .iter()
+            .position(|item| item.kind == kind && item.mode == mode)?;
+        self.items.remove(index)
+    }
+
+    pub fn has(&self, kind: PendingKind, mode: AnnouncementMode) -> bool {
+        self.items
+            .iter()
+            .any(|item| item.kind == kind && item.mode == mode)
+    }
+
+    /// Announcements of `nodes` are not sent anymore
+    pub fn drop_nodes(&mut self, nodes: &[String]) {
+        self.items.retain(|item| !nodes.contains(&item.event.node));
+    }
+
+    /// By due time, then by arrival
+    pub fn sorted(&self) -> Vec<&PendingAnnouncement> {
+        let mut items = self.items.iter().collect::<Vec<_>>();
+        items.sort_by_key(|item| (item.due, item.seq));
+        items
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct PendingAnnouncementResponse {
+    pub kind: PendingKind,
+    /// Like `dead` or `recovered`, as in exec arguments
+    pub event: String,
+    pub target: String,
+    pub channels: Vec<AnnouncementMode>,
+    pub due: DateTime<Utc>,
+    pub attempts: u32,
+}
+
+impl From<&PendingAnnouncement> for PendingAnnouncementResponse {
+    fn from(item: &PendingAnnouncement) -> Self {
+        Self {
+            kind: item.kind,
+            event: item.event.kind.as_str().to_string(),
+            target: item.event.node.clone(),
+            channels: vec![item.mode],
+            due: item.due,
+            attempts: item.attempts,
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct PendingResponse {
+    /// The earliest `capacity` of them
+    pub pending: Vec<PendingAnnouncementResponse>,
+    /// Pending ones, escalations included, listed or not
+    pub total: usize,
+    pub capacity: usize,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::test_config;
+
+    fn event(kind: AnnouncementKind, node: &str) -> AnnouncementEvent {
+        AnnouncementEvent::new(&test_config(""), kind, node, String::new())
+    }
+
+    #[test]
+    fn overflow_drops_the_oldest_but_deaths() {
+        let now = Utc::now();
+        let mut pending = PendingAnnouncements::default();
+        pending.push(
+            PendingKind::Retry,
+            AnnouncementMode::Telegram,
+            event(AnnouncementKind::Died, "hal9001"),
+            now,
+            1,
+        );
+        for i in 1..MAX_PENDING {
+            pending.push(
+                PendingKind::Queued,
+                AnnouncementMode::Telegram,
+                event(AnnouncementKind::Returned, &format!("hal{i}")),
+                now,
+                0,
+            );
+        }
+        pending.push(
+            PendingKind::Queued,
+            AnnouncementMode::Telegram,
+            event(AnnouncementKind::Returned, "hal9002"),
+            now,
+            0,
+        );
+        let sorted = pending.sorted();
+        assert_eq!(sorted.len(), MAX_PENDING);
+        assert_eq!(sorted[0].event.node, "hal9001");
+        assert_eq!(sorted[1].event.node, "hal2");
+        assert_eq!(sorted[MAX_PENDING - 1].event.node, "hal9002");
+
+        pending.drop_nodes(&["hal9002".to_string()]);
+        assert!(pending.has(PendingKind::Retry, AnnouncementMode::Telegram));
+        let retry = pending
+            .pop(PendingKind::Retry, AnnouncementMode::Telegram)
+            .unwrap();
+        assert_eq!((retry.event.node.as_str(), retry.attempts), ("hal9001", 1));
+        assert!(!pending.has(PendingKind::Retry, AnnouncementMode::Telegram));
+        assert!(!pending.has(PendingKind::Queued, AnnouncementMode::Gotify));
+    }
+}
@@ -1,24 +1,143 @@
 use crate::{
-    GridNodeResponse, GridNodeStatus, ObituaryResponse, SilenceBroadcastRequest, StatusResponse,
-    config::{AnnouncementMode, Config, NamedNodeConfig, TelegramConfig},
+    AnnouncedDeathResponse, AnnouncementClaimRequest, AnnouncementClaimResponse, CycleResponse,
+    CycleTimings, FEATURE_ANNOUNCEMENT_CLAIM, FEATURE_INTROSPECT, FEATURE_MUTE_BROADCAST,
+    FEATURE_OBITUARY_V2, FEATURE_SILENCE_CANCEL, GIT_COMMIT, GridNodeResponse, GridNodeStatus,
+    IntrospectResponse, MuteBroadcastRequest, NODE_HEADER, NodeVerdict, ObituaryCalls,
+    ObituaryResponse, PollError, SilenceBroadcastRequest, TRACE_HEADER, VERSION,
+    announcer::{
+        AnnouncementEvent, AnnouncementKind, AnnouncerChoice, Announcers, ChannelWindow,
+        DeathDetails, GridSnapshot,
+    },
+    audit::{AuditAction, AuditLog, AuditSource},
+    cert_check::{self, CertStatus},
+    config::{
+        AnnouncementMode, Config, DEFAULT_POLL_INTERVAL, FlappingConfig, NamedNodeConfig,
+        NodeConfig, canonical_node_name,
+    },
+    digest, discovery,
+    events::{EventLog, GridEvent},
+    grid_format::status_name,
+    messages,
+    pending::PendingAnnouncements,
+    proxy,
+    reload::{ConfigReload, SharedConfig},
+    self_check::{self, SELF_CHECK_FAILURES},
+    state_file::{self, AnnouncedIncident, StateFile},
+    system_metrics::{self, SystemMetrics},
+    systemd,
 };
 use anyhow::Result;
 use chrono::{DateTime, Utc};
-use log::{error, info, warn};
+use freecaster_grid_client::{Error as ClientError, FreecasterClient};
 use rand::Rng;
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue, USER_AGENT};
 use reqwest::{Certificate, Client};
-use serde::de::DeserializeOwned;
-use std::collections::HashMap;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet, VecDeque};
 use std::ops::Deref;
 use std::sync::{Arc, Mutex};
-use std::time::Duration;
+use tokio::task::JoinSet;
+use tracing::{Instrument, Span, debug, debug_span, error, info, info_span, warn};
 
-const DEAD_AFTER: usize = 3;
-const DEFAULT_POLL_INTERVAL: Duration = Duration::from_secs(10);
+const CERT_CHECK_INTERVAL: chrono::Duration = chrono::Duration::days(1);
+/// Claims are best effort, a peer not answering by then doesn't hold the announcement back
+const CLAIM_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(3);
+/// Poll cycles a peer's claim holds this node back, without an announcement by then the death is rolled again
+const CLAIM_EXPIRY_CYCLES: u32 = 2;
+const WARNING_REPEAT_INTERVAL: chrono::Duration = chrono::Duration::days(1);
+const MAX_INCIDENTS: usize = 1000;
+/// Poll cycles the average cycle timings are taken over
+const CYCLE_HISTORY: usize = 20;
 
 pub struct StateInner {
     pub node_state: Vec<NodeState>,
     pub silences: Vec<NodeSilence>,
+    pub audit: AuditLog,
+    pub started: DateTime<Utc>,
+    pub last_cycle_completed: Option<DateTime<Utc>>,
+    pub server_listening: bool,
+    pub local_cert: CertStatus,
+    pub local_disk_warned: Option<DateTime<Utc>>,
+    /// (node, event) of exec announcements still running
+    pub running_commands: HashSet<(String, String)>,
+    /// Deaths seen since this node started, newest last
+    pub incidents: VecDeque<Incident>,
+    /// Nodes found by discovery, the configured ones are not included
+    pub discovered_nodes: HashMap<String, NodeConfig>,
+    pub last_digest: DateTime<Utc>,
+    /// Why death announcements are held back, `None` while they go out as usual
+    pub announcements_suppressed: Option<String>,
+    /// The latest mute of the grid, `None` if there never was one
+    pub mute: Option<GridMute>,
+    /// Of the channels with `announcement_limits`
+    pub announcement_windows: HashMap<AnnouncementMode, ChannelWindow>,
+    /// Queued and timed out announcements of every channel, capped at `MAX_PENDING`
+    pub pending_announcements: PendingAnnouncements,
+    /// Served at `/events`
+    pub events: EventLog,
+    /// Failed checks of this node's own listener in a row, `None` before the first check
+    pub self_check_failures: Option<u32>,
+    /// Timings of the last poll cycles, newest last
+    pub cycles: VecDeque<CycleTimings>,
+    pub last_reload: Option<ConfigReload>,
+    /// Last time the peers were asked for their views, with `cross_check`
+    pub last_cross_check: Option<DateTime<Utc>>,
+    /// Silence broadcasts accepted from callers which couldn't be verified
+    pub suspicious_broadcasts: u64,
+    /// Of the config in effect, given to every node's state
+    pub thresholds: FailThresholds,
+    /// Announced deaths loaded from the state file, continued if the node is still dead when first polled
+    pub restored_announcements: HashMap<String, AnnouncedIncident>,
+    /// Announced deaths last written to the state file
+    pub saved_announcements: Vec<AnnouncedIncident>,
+    /// Starts of this node within the hour before its latest one, that one included, kept in the state file
+    pub starts: Vec<DateTime<Utc>>,
+    /// Bumped on every change of a node's status, announcement or silence, never on a quiet cycle
+    pub revision: u64,
+    /// Of every node at the latest `refresh_changes`
+    change_keys: HashMap<String, ChangeKey>,
+}
+
+/// What moves a node's `last_changed` and the revision when it changes
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct ChangeKey {
+    status: GridNodeStatus,
+    announced: bool,
+    silenced: bool,
+}
+
+/// Failed polls in a row making a node dying and dead
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct FailThresholds {
+    pub dying_after: usize,
+    pub dead_after: usize,
+}
+
+impl Default for FailThresholds {
+    fn default() -> Self {
+        Self {
+            dying_after: 1,
+            dead_after: 3,
+        }
+    }
+}
+
+impl FailThresholds {
+    pub fn of(config: &Config) -> Self {
+        Self {
+            dying_after: config.dying_after,
+            dead_after: config.dead_after,
+        }
+    }
+}
+
+/// No announcements of any kind go out until `until`, shared with every peer
+#[derive(Clone, Debug)]
+pub struct GridMute {
+    /// `None` once lifted
+    pub until: Option<DateTime<Utc>>,
+    /// Peers keep the most recently changed mute
+    pub changed_at: DateTime<Utc>,
+    pub broadcasted: bool,
 }
 
 #[derive(Clone)]
@@ -33,590 +152,4470 @@ impl Deref for State {
 }
 
 impl State {
-    pub fn new() -> Self {
+    pub fn new(config: &Config) -> Self {
         Self(Arc::new(Mutex::new(StateInner {
             node_state: vec![],
             silences: vec![],
+            audit: AuditLog::new(&config.audit),
+            started: Utc::now(),
+            last_cycle_completed: None,
+            server_listening: false,
+            local_cert: CertStatus::default(),
+            local_disk_warned: None,
+            running_commands: HashSet::new(),
+            incidents: VecDeque::new(),
+            discovered_nodes: HashMap::new(),
+            // the first digest goes out at the next scheduled time, not on startup
+            last_digest: Utc::now(),
+            announcements_suppressed: None,
+            mute: None,
+            announcement_windows: HashMap::new(),
+            pending_announcements: PendingAnnouncements::default(),
+            events: EventLog::default(),
+            self_check_failures: None,
+            cycles: VecDeque::new(),
+            last_reload: None,
+            last_cross_check: None,
+            suspicious_broadcasts: 0,
+            thresholds: FailThresholds::of(config),
+            restored_announcements: HashMap::new(),
+            saved_announcements: vec![],
+            starts: vec![],
+            revision: 0,
+            change_keys: HashMap::new(),
         })))
     }
-}
-
-#[derive(Clone, Debug)]
-pub struct NodeSilence {
-    pub id: usize,
-    pub node_name: String,
-    pub silent_until: DateTime<Utc>,
-    pub broadcasted: bool,
-}
-
-#[derive(Clone, Debug)]
-pub struct DeadConfirmation {
-    pub confirmed_roll: Option<usize>,
-}
-
-#[derive(Clone)]
-pub struct NodeState {
-    pub name: String,
-    pub last_poll: Option<DateTime<Utc>>,
-    pub last_fail: Option<DateTime<Utc>>,
-    pub fail_count: usize,
-    pub confirmations: HashMap<String, DeadConfirmation>,
-    pub announcement_rolls: HashMap<String, usize>,
-    pub local_announcement_roll: Option<usize>,
-    pub announced: Option<String>,
-}
 
-impl NodeState {
-    pub fn new(name: String) -> Self {
-        Self {
-            name,
-            last_poll: None,
-            last_fail: None,
-            fail_count: 0,
-            confirmations: Default::default(),
-            announcement_rolls: Default::default(),
-            local_announcement_roll: None,
-            announced: None,
+    /// Announced deaths from the state file, taken over by the nodes found still dead
+    pub fn restore_announced(&self, announced: Vec<AnnouncedIncident>) {
+        let mut gr = self.lock().expect("Failed to lock state");
+        if !announced.is_empty() {
+            info!(
+                "Restored {} announced deaths from the state file",
+                announced.len()
+            );
         }
+        gr.saved_announcements = announced.clone();
+        gr.restored_announcements = announced
+            .into_iter()
+            .map(|inc| (inc.node_name.clone(), inc))
+            .collect();
     }
 
-    pub fn is_dead(&self) -> bool {
-        self.fail_count >= DEAD_AFTER
+    /// Counts this start with the ones of the last hour from the state file, which is written right away,
+    /// so a node crashing before its first poll cycle counts too. Answers with the starts within the hour
+    pub fn record_start(
+        &self,
+        config: &Config,
+        mut starts: Vec<DateTime<Utc>>,
+        now: DateTime<Utc>,
+    ) -> usize {
+        starts.retain(|start| now - *start < chrono::Duration::hours(1));
+        starts.push(now);
+        let file = {
+            let mut gr = self.lock().expect("Failed to lock state");
+            gr.starts = starts;
+            StateFile {
+                announced: gr.saved_announcements.clone(),
+                starts: gr.starts.clone(),
+            }
+        };
+        if let Some(state_file) = config.state_file.as_ref()
+            && let Err(err) = state_file::save(&state_file.path, &file)
+        {
+            error!("Failed to save the state file: {err:#}");
+        }
+        file.starts.len()
     }
 
-    pub fn reset(&mut self) {
-        self.fail_count = 0;
-        self.confirmations.clear();
-        self.announcement_rolls.clear();
-        self.local_announcement_roll = None;
-        self.last_fail = None;
-        self.announced = None;
+    /// Writes the announced deaths to the state file, if they changed since the last time
+    pub fn save_announced(&self, config: &Config) {
+        let Some(state_file) = config.state_file.as_ref() else {
+            return;
+        };
+        let (announced, file) = {
+            let gr = self.lock().expect("Failed to lock state");
+            let announced = gr.announced_incidents(state_file.max_age, Utc::now());
+            if announced == gr.saved_announcements {
+                return;
+            }
+            let file = StateFile {
+                announced: announced.clone(),
+                starts: gr.starts.clone(),
+            };
+            (announced, file)
+        };
+        match state_file::save(&state_file.path, &file) {
+            Ok(()) => {
+                self.lock()
+                    .expect("Failed to lock state")
+                    .saved_announcements = announced
+            }
+            Err(err) => error!("Failed to save the state file: {err:#}"),
+        }
     }
 
-    pub fn to_api_response(&self) -> GridNodeResponse {
-        let status = if self.is_dead() && self.announced.is_some() {
-            GridNodeStatus::Dead
-        } else if self.is_dead() {
-            GridNodeStatus::Dying
-        } else {
-            GridNodeStatus::Alive
-        };
+    /// Ready once the server is listening and the first poll cycle is done
+    pub fn server_started(&self) {
+        let mut gr = self.lock().expect("Failed to lock state");
+        gr.server_listening = true;
+        if gr.last_cycle_completed.is_some() {
+            systemd::notify("READY=1");
+        }
+    }
 
-        GridNodeResponse {
-            name: self.name.clone(),
-            last_poll: self.last_poll,
-            status,
+    pub fn cycle_completed(&self, timings: CycleTimings) {
+        let mut gr = self.lock().expect("Failed to lock state");
+        let first = gr.last_cycle_completed.is_none();
+        gr.last_cycle_completed = Some(Utc::now());
+        if gr.cycles.len() >= CYCLE_HISTORY {
+            gr.cycles.pop_front();
+        }
+        gr.cycles.push_back(timings);
+        if first && gr.server_listening {
+            systemd::notify("READY=1");
         }
+        systemd::notify("WATCHDOG=1");
     }
 }
 
-pub async fn poller(poller_config: Arc<Config>, cert: Option<Vec<u8>>, state: State) -> Result<()> {
-    info!("Starting poller `{}`", poller_config.name);
-
-    let mut client = Client::builder().use_rustls_tls();
+#[derive(Clone, Debug)]
+pub struct NodeSilence {
+    pub id: String,
+    pub node_name: String,
+    pub silent_until: DateTime<Utc>,
+    pub broadcasted: bool,
+    pub revision: u64,
+    /// Cancelled silences are kept until they expire, so the cancellation reaches every peer
+    pub cancelled: bool,
+    /// Only keeps this node from announcing the node, it is still polled and never broadcast
+    pub local: bool,
+    /// Peers which received its current revision, left out of its broadcast
+    pub received_by: BTreeSet<String>,
+}
 
-    if let Some(cert) = cert {
-        client = client.add_root_certificate(Certificate::from_pem(&cert)?);
+impl StateInner {
+    /// A local silence of `node` is in effect
+    pub fn silenced_locally(&self, node: &str, now: DateTime<Utc>) -> bool {
+        self.silences
+            .iter()
+            .any(|sl| sl.local && sl.node_name == node && !sl.cancelled && sl.silent_until > now)
     }
 
-    let client = client.danger_accept_invalid_certs(true).build()?;
+    /// `peers` received revision `revision` of the silence, with `complete` every peer did.
+    /// A silence changed locally while it went out keeps its new revision to send
+    pub fn record_silence_receipts(
+        &mut self,
+        id: &str,
+        revision: u64,
+        peers: impl IntoIterator<Item = String>,
+        complete: bool,
+    ) {
+        let Some(sl) = self
+            .silences
+            .iter_mut()
+            .find(|sl| sl.id == id && sl.revision == revision)
+        else {
+            return;
+        };
+        sl.received_by.extend(peers);
+        sl.broadcasted |= complete;
+    }
 
-    // init state
-    {
-        let mut gr = state.lock().expect("Failed to lock state");
-        for (name, _) in poller_config.nodes.iter() {
-            gr.node_state.push(NodeState::new(name.clone()));
+    /// This node's view in brief, as served by `/introspect`
+    pub fn introspect(&self, me: &str, now: DateTime<Utc>) -> IntrospectResponse {
+        let mut silences = self
+            .silences
+            .iter()
+            .filter(|sl| !sl.local && !sl.cancelled && sl.silent_until > now)
+            .map(|sl| sl.id.clone())
+            .collect::<Vec<_>>();
+        silences.sort();
+        IntrospectResponse {
+            name: me.to_string(),
+            version: VERSION.to_string(),
+            revision: self.revision,
+            statuses: self
+                .node_state
+                .iter()
+                .map(|ns| (ns.name.clone(), ns.status().code()))
+                .collect(),
+            silences,
+            announcements_suppressed: self.announcements_suppressed.is_some(),
+            muted: self.muted_until(now).is_some(),
         }
     }
 
-    loop {
-        let time = Utc::now();
-
-        let has_net = check_internet_connection().await;
-        if !has_net {
-            warn!("No internet connection, skipping poll");
-            tokio::time::sleep(DEFAULT_POLL_INTERVAL).await;
-            continue;
+    /// Dead nodes of the grid as this node sees it, counting itself alive
+    pub fn grid_snapshot(&self) -> GridSnapshot {
+        GridSnapshot {
+            dead: self.node_state.iter().filter(|ns| ns.is_dead()).count(),
+            total: self.node_state.len() + 1, // plus me
         }
+    }
 
-        // process silences
-        let silenced_nodes_clone = {
-            let mut gr = state.lock().expect("Failed to lock state");
-            // expire silences
-            gr.silences.retain(|sl| sl.silent_until > time);
-
-            gr.silences.clone()
-        };
-
-        // broadcast silences — fan out to every peer; the receive handler is
-        // idempotent on `id`.
-        let mut broadcast_silences = vec![];
-        for sl in silenced_nodes_clone.iter() {
-            if sl.broadcasted {
-                continue;
-            }
-
-            let mut all_ok = true;
-            for (node_name, node) in poller_config.nodes.iter() {
-                let done = call_silence_broadcast(
-                    &client,
-                    &poller_config.name,
-                    node.with_name(node_name),
-                    &poller_config.secret_key,
-                    sl,
-                )
-                .await;
+    pub fn muted_until(&self, now: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        self.mute
+            .as_ref()
+            .and_then(|mute| mute.until)
+            .filter(|until| *until > now)
+    }
 
-                if !done {
-                    all_ok = false;
-                }
-            }
+    /// The listener answered recently enough, `None` before the first check
+    pub fn self_check_ok(&self) -> Option<bool> {
+        self.self_check_failures
+            .map(|failures| failures < SELF_CHECK_FAILURES)
+    }
 
-            if all_ok {
-                broadcast_silences.push(sl.clone());
-            }
+    /// The latest cycle's timings and their average, `None` before the first cycle
+    pub fn cycle_response(&self, poll_time: std::time::Duration) -> Option<CycleResponse> {
+        let latest = self.cycles.back()?.clone();
+        let count = self.cycles.len() as f64;
+        let mut average = CycleTimings::default();
+        for cycle in self.cycles.iter() {
+            average.internet_check += cycle.internet_check / count;
+            average.silences += cycle.silences / count;
+            average.polling += cycle.polling / count;
+            average.checks += cycle.checks / count;
+            average.obituaries += cycle.obituaries / count;
+            average.announcements += cycle.announcements / count;
+            average.total += cycle.total / count;
         }
+        Some(CycleResponse {
+            latest,
+            average,
+            cycles: self.cycles.len(),
+            poll_time: poll_time.as_secs_f64(),
+        })
+    }
 
-        // set broadcast state
-        {
-            let mut gr = state.lock().expect("Failed to lock state");
-            for sl in gr.silences.iter_mut() {
-                if broadcast_silences.iter().any(|bs| bs.id == sl.id) {
-                    sl.broadcasted = true;
-                }
+    /// Moves `last_changed` of the nodes whose status, announcement or silence changed since the last refresh,
+    /// bumping the revision for each of them and for nodes gone from the grid. Returns whether anything changed
+    pub fn refresh_changes(&mut self, now: DateTime<Utc>) -> bool {
+        let silenced = self
+            .silences
+            .iter()
+            .filter(|sl| !sl.cancelled && sl.silent_until > now)
+            .map(|sl| sl.node_name.as_str())
+            .collect::<HashSet<_>>();
+        let revision = self.revision;
+        let mut keys = HashMap::new();
+        for ns in self.node_state.iter_mut() {
+            let key = ChangeKey {
+                status: ns.status(),
+                announced: ns.is_dead() && ns.announced.is_some(),
+                silenced: silenced.contains(ns.name.as_str()),
+            };
+            let before = self.change_keys.get(&ns.name);
+            if before != Some(&key) {
+                ns.last_changed = now;
+                self.revision += 1;
             }
-        }
-
-        info!("Polling nodes @`{time:?}`");
-        let mut poll_res = HashMap::new();
-        for (node_name, node) in poller_config.nodes.iter() {
-            if silenced_nodes_clone
-                .iter()
-                .any(|sl| sl.node_name == *node_name)
+            if let Some(before) = before
+                && before.status != key.status
             {
-                info!("Silenced node {}", node_name);
-                continue;
+                self.events.record(GridEvent::StatusChanged {
+                    node_name: ns.name.clone(),
+                    from: before.status,
+                    to: key.status,
+                });
             }
-
-            info!("Checking node {}: {}", node_name, node.address);
-            let time = Utc::now();
-            let res = poll_node(&client, &poller_config.name, node.with_name(node_name)).await;
-            poll_res.insert((node_name, node.clone()), (res, time));
+            keys.insert(ns.name.clone(), key);
         }
-
-        let mut up_announcements = vec![];
-        let dead_copies = {
-            let mut gr = state.lock().expect("Failed to lock state");
-            for ((node_name, node), (res, time)) in poll_res {
-                let Some(fail_state) = gr.node_state.iter_mut().find(|fs| fs.name == *node_name)
-                else {
-                    warn!("Polled node `{}` missing from state, skipping", *node_name);
-                    continue;
-                };
-
-                fail_state.last_poll = Some(time);
-
-                if res.failing {
-                    fail_state.last_fail = Some(time);
-
-                    if !fail_state.is_dead() {
-                        fail_state.fail_count += 1;
-                        if fail_state.is_dead() {
-                            let roll = rand::rng().random_range(0usize..usize::MAX);
-                            fail_state.local_announcement_roll = Some(roll);
-                            warn!(
-                                "Node `{}` is dead my roll: `{}`, last fail: {:?}",
-                                node_name, roll, fail_state.last_fail
-                            );
-                        }
-                    }
-                } else {
-                    // back up
-                    if fail_state.is_dead() {
-                        if fail_state.announced == Some(poller_config.name.clone()) {
-                            up_announcements.push((node_name.clone(), node.clone()));
-                        }
-                        fail_state.reset();
-                        info!("Node `{}` is back up", node_name);
-                    }
-                }
-            }
-
-            gr.node_state
-                .iter()
-                .filter_map(|fs| fs.is_dead().then_some(fs.clone()))
-                .collect::<Vec<_>>()
-        };
-
-        // announce up
-        for (up_name, up_node) in up_announcements {
-            match poller_config.announcement_mode {
-                AnnouncementMode::Telegram => {
-                    announce_telegram(
-                        &poller_config.name,
-                        up_node.with_name(&up_name),
-                        &poller_config,
-                        false,
-                    )
-                    .await;
-                }
-                AnnouncementMode::Log => {
-                    error!("Announcement!!!: `{}` is back.", up_name);
-                }
-            }
+        if self.change_keys.keys().any(|name| !keys.contains_key(name)) {
+            self.revision += 1;
         }
+        self.change_keys = keys;
+        self.revision != revision
+    }
 
-        // check deaths
-        let mut obi_response = HashMap::new();
-
-        // any dead nodes need announcement
-        if dead_copies
+    /// Takes in the answers to this cycle's obituary calls, a failed call leaves the peer's vote unreachable.
+    /// Returns the announcements of deaths learned from them
+    fn record_obituaries(
+        &mut self,
+        me: &str,
+        responses: HashMap<String, ObituaryResponse>,
+        failures: HashMap<String, String>,
+    ) -> Vec<AnnouncedDeathResponse> {
+        let mut learned = vec![];
+        let started = self.started;
+        let known = self
+            .node_state
             .iter()
-            .any(|fs| fs.is_dead() && fs.announced.is_none())
-        {
-            for (node_name, node) in poller_config.nodes.iter() {
-                if dead_copies.iter().any(|fs| fs.name == *node_name) {
-                    continue;
-                }
-
-                let Some(orb) = call_obituary(
-                    &client,
-                    &poller_config.name,
-                    node.with_name(node_name),
-                    &poller_config.secret_key,
-                )
-                .await
-                else {
-                    error!("Failed to call Obituary for node `{}`", node_name);
-                    continue;
-                };
-
-                obi_response.insert(node_name.clone(), orb);
+            .map(|fs| fs.name.clone())
+            .chain([me.to_string()])
+            .collect::<Vec<_>>();
+        let responses = responses
+            .into_iter()
+            .map(|(from, mut orb)| {
+                canonical_obituary(&mut orb, &known);
+                (from, orb)
+            })
+            .collect::<HashMap<_, _>>();
+        for fs in self.node_state.iter_mut() {
+            if responses.contains_key(&fs.name) {
+                let calls = fs.obituary_calls.get_or_insert_default();
+                calls.succeeded += 1;
+            } else if let Some(err) = failures.get(&fs.name) {
+                let calls = fs.obituary_calls.get_or_insert_default();
+                calls.failed += 1;
+                calls.last_error = Some(err.clone());
             }
         }
-
-        let announcements = {
-            // process obi responses
-            let mut gr = state.lock().expect("Failed to lock state");
-            for (from, orb) in obi_response {
-                for dead_resp in orb.dead_nodes {
-                    let Some(fs) = gr
-                        .node_state
-                        .iter_mut()
-                        .find(|fs| fs.name == dead_resp.name && fs.is_dead())
-                    else {
-                        warn!(
-                            "Obituary from `{from}` references `{}` which is not known-dead locally, skipping",
-                            dead_resp.name
-                        );
-                        continue;
-                    };
-
-                    warn!("Node `{}` is confirmed dead by `{from}`", dead_resp.name);
+        // an earlier answer of the peer is kept, it's only unreachable on this path now
+        for from in failures.keys() {
+            for fs in self.node_state.iter_mut() {
+                if fs.is_dead() && !fs.confirmations.contains_key(from) {
                     fs.confirmations.insert(
                         from.clone(),
                         DeadConfirmation {
-                            confirmed_roll: Some(dead_resp.roll),
+                            confirmed_roll: None,
+                            silenced: false,
+                            unreachable: true,
+                            dying: false,
+                            abstained: false,
                         },
                     );
                 }
-
-                // if node didnt confirm death we mark as failed confirmation of all our dead
-                for fs in gr.node_state.iter_mut() {
-                    if !fs.is_dead() {
-                        continue;
-                    }
-
-                    if !fs.confirmations.contains_key(&from) {
-                        fs.confirmations.insert(
-                            from.clone(),
-                            DeadConfirmation {
-                                confirmed_roll: None,
-                            },
-                        );
-                    }
-                }
             }
-
-            // check death quorum and rolls
-            let mut announcements = vec![];
-
-            for fs in gr.node_state.iter_mut() {
-                if !fs.is_dead() {
+        }
+        for (from, orb) in responses {
+            // already announced, possibly by this node before a restart
+            for announced in orb.announced_deaths {
+                // since the start it's only the peer's decision, this node still has to announce it
+                if announced.announcer == me && announced.announced_at >= started {
                     continue;
                 }
-                if fs.announced.is_some() {
+                let Some(fs) = self
+                    .node_state
+                    .iter_mut()
+                    .find(|fs| fs.name == announced.name && fs.is_dead() && fs.announced.is_none())
+                else {
                     continue;
-                }
+                };
+
+                info!(
+                    "Node `{}`'s death was already announced by `{}` at {}, learned from `{from}`",
+                    announced.name, announced.announcer, announced.announced_at
+                );
+                fs.announced = Some(announced.announcer.clone());
+                fs.announced_at = Some(announced.announced_at);
+                learned.push(announced);
+            }
 
-                let Some(my_roll) = fs.local_announcement_roll else {
+            for dead_resp in orb.dead_nodes {
+                let Some(fs) = self
+                    .node_state
+                    .iter_mut()
+                    .find(|fs| fs.name == dead_resp.name && fs.is_dead())
+                else {
+                    warn!(
+                        "Obituary from `{from}` references `{}` which is not known-dead locally, skipping",
+                        dead_resp.name
+                    );
                     continue;
                 };
 
-                let true_confirmations = fs
-                    .confirmations
-                    .iter()
-                    .filter(|(_, val)| val.confirmed_roll.is_some())
-                    .count()
-                    + 1; // plus me
-                let false_confirmations = fs
-                    .confirmations
-                    .iter()
-                    .filter(|(_, val)| val.confirmed_roll.is_none())
-                    .count();
-                info!(
-                    "Death consideration votes: `{true_confirmations}` dead, `{false_confirmations}` live"
+                warn!("Node `{}` is confirmed dead by `{from}`", dead_resp.name);
+                fs.confirmations.insert(
+                    from.clone(),
+                    DeadConfirmation {
+                        confirmed_roll: Some(dead_resp.roll),
+                        silenced: dead_resp.silenced,
+                        unreachable: false,
+                        dying: false,
+                        abstained: false,
+                    },
                 );
-                info!("Rolls: {:#?} (my roll: {})", fs.confirmations, my_roll);
+                fs.merge_relayed_rolls(me, &from, dead_resp.rolls);
+            }
 
-                if true_confirmations <= false_confirmations {
-                    info!("Node `{}`'s death is not confirmed by quorum", fs.name);
+            // if node didnt confirm death we mark as failed confirmation of all our dead
+            for fs in self.node_state.iter_mut() {
+                if !fs.is_dead() {
                     continue;
                 }
 
-                warn!("Node `{}` is confirmed dead by quorum", fs.name);
-                let mut confirmations_rolls = fs
+                // an abstention gives way to the peer's vote once it has one
+                if fs
                     .confirmations
-                    .iter()
-                    .filter_map(|(from, val)| val.confirmed_roll.map(|roll| (from.clone(), roll)))
-                    .collect::<Vec<_>>();
-                confirmations_rolls.push((poller_config.name.clone(), my_roll));
-                confirmations_rolls.sort_by(|(name1, roll1), (name2, roll2)| {
-                    roll1.cmp(roll2).then_with(|| name1.cmp(name2))
-                });
-
-                let Some(winner) = confirmations_rolls.last() else {
-                    error!("No confirmations for node `{}`, skipping", fs.name);
-                    continue;
-                };
-                if winner.0 == poller_config.name {
-                    warn!(
-                        "Node `{}`'s death to be announced by this node death rolled: {}",
-                        fs.name, winner.1
-                    );
-                    if let Some(node) = poller_config
-                        .nodes
-                        .iter()
-                        .find(|(n_name, _)| **n_name == fs.name)
-                    {
-                        announcements.push(node);
-                    } else {
-                        error!("Node `{}` missing from config, cannot announce", fs.name);
+                    .get(&from)
+                    .is_none_or(|c| c.unreachable || c.abstained)
+                {
+                    let dying = orb.dying_nodes.iter().any(|dying| {
+                        dying.name == fs.name && dying.fail_count * 2 >= dying.threshold
+                    });
+                    if dying {
+                        debug!("Node `{}` is dying for `{from}`", fs.name);
                     }
-                } else {
-                    warn!(
-                        "Node `{}`'s death to be announced by `{}` death rolled: {}",
-                        fs.name, winner.0, winner.1
+                    // with verdicts, a node the peer hasn't polled yet, doesn't know of, or has silenced isn't an alive vote
+                    let verdict = orb.verdicts.as_ref().map(|verdicts| {
+                        verdicts
+                            .iter()
+                            .find(|verdict| verdict.name == fs.name)
+                            .map_or(NodeVerdict::Unknown, |verdict| verdict.verdict)
+                    });
+                    let silenced = verdict == Some(NodeVerdict::Silenced);
+                    let abstained = silenced || verdict == Some(NodeVerdict::Unknown);
+                    if silenced {
+                        debug!("`{from}` has `{}` silenced, abstaining", fs.name);
+                    } else if abstained {
+                        debug!("`{from}` has no verdict on `{}`, abstaining", fs.name);
+                    }
+                    fs.confirmations.insert(
+                        from.clone(),
+                        DeadConfirmation {
+                            confirmed_roll: None,
+                            silenced,
+                            unreachable: false,
+                            dying,
+                            abstained,
+                        },
                     );
                 }
-
-                fs.announced = Some(winner.0.clone()); // announced death
-            }
-
-            announcements
-        };
-
-        for (anc_name, anc) in announcements {
-            match poller_config.announcement_mode {
-                AnnouncementMode::Telegram => {
-                    announce_telegram(
-                        &poller_config.name,
-                        anc.with_name(anc_name),
-                        &poller_config,
-                        true,
-                    )
-                    .await;
-                }
-                AnnouncementMode::Log => {
-                    error!("Announcement!!!: `{}` is dead.", anc_name);
-                }
             }
         }
+        learned
+    }
 
-        tokio::time::sleep(poller_config.poll_time.unwrap_or(DEFAULT_POLL_INTERVAL)).await;
+    /// Holds back death announcements while fewer than `min_peers_reachable` peers answered,
+    /// too little of the grid is visible from here to judge deaths
+    fn check_participation(&mut self, config: &Config, reachable_peers: usize) {
+        let suppression = (reachable_peers < config.min_peers_reachable).then(|| {
+            format!(
+                "only {reachable_peers} of the required {} peers reachable",
+                config.min_peers_reachable
+            )
+        });
+        match (&self.announcements_suppressed, &suppression) {
+            (None, Some(reason)) => warn!("Holding back death announcements, {reason}"),
+            (Some(_), None) => info!("Enough peers reachable, death announcements resume"),
+            _ => {}
+        }
+        self.announcements_suppressed = suppression;
     }
-}
 
-struct NodeResult {
-    failing: bool,
-}
+    /// Decides the deaths confirmed by quorum, suppressing the silenced ones and holding back the rest
+    /// while too few peers are reachable, the grid is muted or the node flaps
+    fn decide_deaths(
+        &mut self,
+        config: &Config,
+        nodes: &HashMap<String, NodeConfig>,
+        now: DateTime<Utc>,
+    ) -> DeathDecisions {
+        let mut decided = DeathDecisions::default();
+        let mut decisions = vec![];
+        let mut suppressed = vec![];
+        let silenced_here = self
+            .silences
+            .iter()
+            .filter(|sl| !sl.cancelled && sl.silent_until > now)
+            .map(|sl| sl.node_name.clone())
+            .collect::<HashSet<_>>();
 
-async fn check_internet_connection() -> bool {
-    let Ok(resp) = reqwest::get("http://clients3.google.com/generate_204").await else {
-        return false;
-    };
-    resp.status() == reqwest::StatusCode::NO_CONTENT
-}
+        let held_back = self.announcements_suppressed.is_some();
+        let claim_expiry = chrono::Duration::from_std(config.poll_interval() * CLAIM_EXPIRY_CYCLES)
+            .unwrap_or(chrono::Duration::MAX);
+        // decided after the mute, otherwise announced into the mute and dropped
+        let muted = self.muted_until(now).is_some() && config.announce_after_mute;
+        let grid = self.grid_snapshot();
+        for fs in self.node_state.iter_mut() {
+            if !fs.is_dead() {
+                continue;
+            }
+            if fs.announced.is_some() {
+                continue;
+            }
+            // died under a silence, kept quiet with `announce_after_silence: false`
+            if fs.silenced_death && !config.announce_after_silence {
+                continue;
+            }
 
-async fn make_whatever_logged_http_call<T: DeserializeOwned>(
-    client: &Client,
-    me: &str,
-    node: NamedNodeConfig<'_>,
-    endpoint: &str,
-    purpose: &str,
-) -> Result<Option<T>> {
-    match client
-        .get(format!("{}{}", node.config.address, endpoint))
-        .header(
-            "User-Agent",
-            format!("freecaster-grid/{}/{}", env!("CARGO_PKG_VERSION"), me,),
-        )
-        .timeout(Duration::from_secs(5))
-        .send()
-        .await
-    {
-        Ok(response) => {
-            if response.status().is_success() {
-                let Some(correct_response) = response
-                    .json::<T>()
-                    .await
-                    .inspect_err(|err| {
-                        error!("Failed to parse response for `{purpose}`: {err:?}");
-                    })
-                    .ok()
-                else {
-                    return Ok(None);
-                };
+            let Some(my_roll) = fs.local_announcement_roll else {
+                continue;
+            };
 
-                info!(
-                    "Node `{}` returned a fine response for `{purpose}`",
-                    node.name
+            // confirmations may have been learned before a peer left the grid
+            let forgotten = fs.forget_strangers(nodes);
+            if forgotten > 0 {
+                debug!(
+                    "Ignoring {forgotten} confirmations of `{}` from nodes no longer in the grid",
+                    fs.name
                 );
-                Ok(Some(correct_response))
-            } else {
-                error!(
-                    "Node `{}` returned error status: {}",
-                    node.name,
-                    response.status()
-                );
-                Err(anyhow::anyhow!(
-                    "Node returned error status: {}",
-                    response.status()
-                ))
             }
-        }
-        Err(e) => {
-            error!("Failed to connect to node {}: {:?}", node.name, e);
-            Err(e.into())
-        }
-    }
+
+            let (true_confirmations, false_confirmations) =
+                fs.death_votes(config.count_dying_as_confirmation);
+            let unreachable = fs.confirmations.values().filter(|c| c.unreachable).count();
+            let abstained = fs.confirmations.values().filter(|c| c.abstained).count();
+            debug!(
+                "Death consideration votes: `{true_confirmations}` dead, `{false_confirmations}` live, `{unreachable}` unreachable, `{abstained}` abstained"
+            );
+            debug!("Rolls: {:#?} (my roll: {})", fs.confirmations, my_roll);
+
+            if true_confirmations <= false_confirmations {
+                info!("Node `{}`'s death is not confirmed by quorum", fs.name);
+                continue;
+            }
+
+            warn!("Node `{}` is confirmed dead by quorum", fs.name);
+
+            // checked last, a silence may have arrived since the node died, or not reached us yet
+            if silenced_here.contains(&fs.name) || fs.confirmations.values().any(|c| c.silenced) {
+                if !fs.silenced_death {
+                    warn!("Node `{}`'s death is silenced, not announcing", fs.name);
+                    fs.silenced_death = true;
+                    suppressed.push(fs.name.clone());
+                }
+                continue;
+            }
+            fs.silenced_death = false;
+            // left unannounced, decided again once enough peers are reachable
+            if held_back {
+                info!(
+                    "Node `{}`'s death is not announced while too few peers are reachable",
+                    fs.name
+                );
+                continue;
+            }
+            if muted {
+                info!(
+                    "Node `{}`'s death is not announced while the grid is muted",
+                    fs.name
+                );
+                continue;
+            }
+            // left unannounced, decided again once it's stable, if it's still dead
+            if fs.flapping_since.is_some() {
+                debug!(
+                    "Node `{}`'s death is not announced while it's flapping",
+                    fs.name
+                );
+                continue;
+            }
+            let preferred = nodes
+                .get(&fs.name)
+                .and_then(|node| node.preferred_announcer.as_deref());
+            let (winner, choice) = fs.choose_announcer(&config.name, my_roll, preferred);
+            match &choice {
+                AnnouncerChoice::Roll => {}
+                AnnouncerChoice::Preferred => info!(
+                    "Node `{}`'s death goes to its preferred announcer `{}`",
+                    fs.name, winner.0
+                ),
+                AnnouncerChoice::PreferredUnavailable(preferred) => info!(
+                    "Node `{}`'s preferred announcer `{preferred}` didn't confirm its death, it's rolled",
+                    fs.name
+                ),
+            }
+            if let Some((claimer, roll)) =
+                fs.pending_claim(&config.name, &winner, now, claim_expiry)
+            {
+                info!(
+                    "Node `{}`'s death is left to `{claimer}`, which claimed it with roll {roll}",
+                    fs.name
+                );
+                continue;
+            }
+            if winner.0 == config.name {
+                warn!(
+                    "Node `{}`'s death to be announced by this node death rolled: {}",
+                    fs.name, winner.1
+                );
+                if let Some((n_name, node)) = nodes.iter().find(|(n_name, _)| **n_name == fs.name) {
+                    // the usual unreachable death needs no explanation
+                    let reason = match &fs.poll_error {
+                        Some(error @ PollError::IdentityMismatch { .. }) => Some(error.to_string()),
+                        _ => None,
+                    };
+                    let death = DeathDetails {
+                        reason,
+                        last_ok: fs.last_ok,
+                        down_for: fs
+                            .first_fail
+                            .and_then(|first_fail| (now - first_fail).to_std().ok()),
+                        failure: fs.failure.clone(),
+                        grid: Some(grid),
+                    };
+                    decided
+                        .announcements
+                        .push((n_name.clone(), node.clone(), death, choice));
+                    decided.claims.push((
+                        AnnouncementClaimRequest {
+                            node: fs.name.clone(),
+                            announcer: winner.0.clone(),
+                            roll: winner.1,
+                        },
+                        fs.claim_peers(),
+                    ));
+                    fs.claim = Some(winner.clone());
+                    fs.escalation_due = config.escalation.get(&node.severity).map(|escalation| {
+                        now + chrono::Duration::from_std(escalation.after)
+                            .unwrap_or(chrono::Duration::MAX)
+                    });
+                } else {
+                    error!("Node `{}` missing from config, cannot announce", fs.name);
+                }
+            } else {
+                warn!(
+                    "Node `{}`'s death to be announced by `{}` death rolled: {}",
+                    fs.name, winner.0, winner.1
+                );
+            }
+
+            fs.announced = Some(winner.0.clone()); // announced death
+            fs.announced_at = Some(now);
+            fs.announcement_roll = Some(winner.1);
+            decided
+                .changes
+                .push(format!("`{}` announced by `{}`", fs.name, winner.0));
+            decisions.push((fs.name.clone(), winner.0.clone()));
+        }
+
+        for node_name in suppressed {
+            if let Some(inc) = self.open_incident_mut(&node_name) {
+                inc.silenced = true;
+            }
+            self.audit.record(
+                AuditSource::Poller,
+                AuditAction::DeathAnnouncementSuppressed { node_name },
+            );
+        }
+
+        for (node_name, announcer) in decisions {
+            if let Some(inc) = self.open_incident_mut(&node_name) {
+                inc.announced_by = Some(announcer.clone());
+            }
+            self.audit.record(
+                AuditSource::Poller,
+                AuditAction::DeathAnnouncementDecided {
+                    node_name,
+                    announcer,
+                },
+            );
+        }
+
+        decided
+    }
+
+    /// Follows the nodes of the grid, whatever of an incident still refers to a node gone from it is dropped
+    pub fn reconcile_nodes(&mut self, nodes: &HashMap<String, NodeConfig>, now: DateTime<Utc>) {
+        let mut left = vec![];
+        self.node_state.retain(|ns| {
+            let known = nodes.contains_key(&ns.name);
+            if !known {
+                info!("Node `{}` left the grid", ns.name);
+                left.push(ns.name.clone());
+            }
+            known
+        });
+        for name in nodes.keys() {
+            if !self.node_state.iter().any(|ns| ns.name == *name) {
+                self.node_state.push(NodeState::new(name.clone()));
+            }
+        }
+        for ns in self.node_state.iter_mut() {
+            ns.thresholds = self.thresholds;
+            // a node gone can't vote on the deaths of others anymore
+            ns.forget_strangers(nodes);
+        }
+        if left.is_empty() {
+            return;
+        }
+
+        for inc in self
+            .incidents
+            .iter_mut()
+            .filter(|inc| inc.ended.is_none() && left.contains(&inc.node_name))
+        {
+            inc.ended = Some(now);
+        }
+        self.pending_announcements.drop_nodes(&left);
+    }
+
+    /// Deaths of nodes still dead which were announced, the restored ones not polled since too, by node name
+    pub fn announced_incidents(
+        &self,
+        max_age: std::time::Duration,
+        now: DateTime<Utc>,
+    ) -> Vec<AnnouncedIncident> {
+        let mut announced = self
+            .node_state
+            .iter()
+            .filter(|ns| ns.is_dead())
+            .filter_map(|ns| {
+                let announced_at = ns.announced_at?;
+                Some(AnnouncedIncident {
+                    node_name: ns.name.clone(),
+                    started: self
+                        .incidents
+                        .iter()
+                        .rev()
+                        .find(|inc| inc.node_name == ns.name && inc.ended.is_none())
+                        .map_or(announced_at, |inc| inc.started),
+                    announcer: ns.announced.clone()?,
+                    announced_at,
+                })
+            })
+            .chain(self.restored_announcements.values().cloned())
+            .filter(|inc| !inc.expired(max_age, now))
+            .collect::<Vec<_>>();
+        announced.sort_by(|a, b| a.node_name.cmp(&b.node_name));
+        announced
+    }
+
+    /// The incident of `node_name` still going on
+    fn open_incident_mut(&mut self, node_name: &str) -> Option<&mut Incident> {
+        self.incidents
+            .iter_mut()
+            .rev()
+            .find(|inc| inc.node_name == node_name && inc.ended.is_none())
+    }
+
+    /// Takes a peer's claim to announce a death, unless a stronger claim came first.
+    /// The death is then left to the claimer for a few cycles, it's only known announced from an obituary
+    pub fn record_claim(
+        &mut self,
+        claim: &AnnouncementClaimRequest,
+        now: DateTime<Utc>,
+    ) -> AnnouncementClaimResponse {
+        let Some(ns) = self
+            .node_state
+            .iter_mut()
+            .find(|ns| ns.name == claim.node && ns.is_dead())
+        else {
+            // no opinion on a node alive or unknown here
+            return AnnouncementClaimResponse {
+                accepted: true,
+                claimed_by: None,
+                roll: None,
+            };
+        };
+        if let Some((claimer, roll)) = &ns.claim
+            && *claimer != claim.announcer
+            && outranks((claimer, *roll), (&claim.announcer, claim.roll))
+        {
+            info!(
+                "Rejecting `{}`'s claim on `{}` with roll {}, `{claimer}` claimed it with {roll}",
+                claim.announcer, claim.node, claim.roll
+            );
+            return AnnouncementClaimResponse {
+                accepted: false,
+                claimed_by: Some(claimer.clone()),
+                roll: Some(*roll),
+            };
+        }
+
+        debug!(
+            "`{}` claims the announcement of `{}` with roll {}",
+            claim.announcer, claim.node, claim.roll
+        );
+        ns.claim = Some((claim.announcer.clone(), claim.roll));
+        ns.claimed_at = Some(now);
+        // only the announcer escalates
+        ns.escalation_due = None;
+        AnnouncementClaimResponse {
+            accepted: true,
+            claimed_by: Some(claim.announcer.clone()),
+            roll: Some(claim.roll),
+        }
+    }
+
+    /// Backs off from the deaths of `claimed` a peer knows a stronger claim of,
+    /// or whose stronger claim reached this node meanwhile. Returns their names
+    pub fn resolve_claims(
+        &mut self,
+        me: &str,
+        claimed: &[String],
+        answers: impl IntoIterator<Item = (String, AnnouncementClaimResponse)>,
+    ) -> HashSet<String> {
+        for (node, answer) in answers {
+            let (Some(claimer), Some(roll)) = (answer.claimed_by, answer.roll) else {
+                continue;
+            };
+            let Some(ns) = self.node_state.iter_mut().find(|ns| ns.name == node) else {
+                continue;
+            };
+            if ns
+                .claim
+                .as_ref()
+                .is_none_or(|(mine, my_roll)| outranks((&claimer, roll), (mine, *my_roll)))
+            {
+                ns.claim = Some((claimer, roll));
+            }
+        }
+
+        let now = Utc::now();
+        let mut lost = HashSet::new();
+        let mut learned = vec![];
+        for ns in self
+            .node_state
+            .iter_mut()
+            .filter(|ns| claimed.contains(&ns.name))
+        {
+            let Some((claimer, roll)) = ns.claim.clone() else {
+                continue;
+            };
+            if claimer == me {
+                continue;
+            }
+            warn!(
+                "Backing off from announcing `{}`'s death, `{claimer}` claimed it with a stronger roll: {roll}",
+                ns.name
+            );
+            ns.announced = Some(claimer.clone());
+            ns.announcement_roll = Some(roll);
+            ns.escalation_due = None;
+            lost.insert(ns.name.clone());
+            learned.push((ns.name.clone(), claimer));
+        }
+        for (node_name, announcer) in learned {
+            if let Some(inc) = self.open_incident_mut(&node_name) {
+                inc.announced_by = Some(announcer.clone());
+            }
+            self.audit.record(
+                AuditSource::Poller,
+                AuditAction::DeathAnnouncementLearned {
+                    node_name,
+                    announcer,
+                    announced_at: now,
+                },
+            );
+        }
+        lost
+    }
+
+    /// No poll cycle completed within `stale_after`, counting from startup
+    pub fn poller_stale(&self, stale_after: chrono::Duration) -> bool {
+        Utc::now() - self.last_cycle_completed.unwrap_or(self.started) > stale_after
+    }
+}
+
+/// The deaths the quorum decided in a cycle, the announcements and claims are this node's to make
+#[derive(Default)]
+struct DeathDecisions {
+    announcements: Vec<(String, NodeConfig, DeathDetails, AnnouncerChoice)>,
+    /// With the peers to claim each from
+    claims: Vec<(AnnouncementClaimRequest, BTreeSet<String>)>,
+    /// For the cycle's summary
+    changes: Vec<String>,
+}
+
+#[derive(Clone, Debug)]
+pub struct Incident {
+    pub node_name: String,
+    pub started: DateTime<Utc>,
+    pub ended: Option<DateTime<Utc>>,
+    /// The node which announced the death, `None` while unannounced
+    pub announced_by: Option<String>,
+    /// Its death was held back by a silence
+    pub silenced: bool,
+    /// Of the poll cycle which found the node dead
+    pub trace: Option<String>,
+}
+
+#[derive(Clone, Debug)]
+pub struct DeadConfirmation {
+    pub confirmed_roll: Option<usize>,
+    /// The peer has an active silence for the node
+    pub silenced: bool,
+    /// The peer's obituary couldn't be called, its vote counts neither way
+    pub unreachable: bool,
+    /// Not dead for the peer yet, but failing for at least half of its dead threshold
+    pub dying: bool,
+    /// The peer answered, but hasn't polled the node yet or has it silenced, its vote counts neither way
+    pub abstained: bool,
+}
+
+#[derive(Clone)]
+pub struct NodeState {
+    pub name: String,
+    pub last_poll: Option<DateTime<Utc>>,
+    /// Last time the node answered, `last_poll` is updated by failed polls too
+    pub last_ok: Option<DateTime<Utc>>,
+    /// How long its last answer took
+    pub latency: Option<std::time::Duration>,
+    /// Last time its status, announcement or silence changed, for `changed_since` queries
+    pub last_changed: DateTime<Utc>,
+    pub last_fail: Option<DateTime<Utc>>,
+    /// First failed poll of the current outage
+    pub first_fail: Option<DateTime<Utc>>,
+    pub fail_count: usize,
+    pub thresholds: FailThresholds,
+    pub confirmations: HashMap<String, DeadConfirmation>,
+    /// Rolls of confirmers learned from other peers' obituaries, a peer's own answer overrides them
+    pub relayed_rolls: HashMap<String, usize>,
+    pub local_announcement_roll: Option<usize>,
+    pub announced: Option<String>,
+    pub announced_at: Option<DateTime<Utc>>,
+    /// Roll that won the announcement, `None` when learned from a peer
+    pub announcement_roll: Option<usize>,
+    /// When the death this node announced gets escalated, `None` once escalated or without escalation
+    pub escalation_due: Option<DateTime<Utc>>,
+    /// The strongest claim to announce the death, by this node or a peer, with its roll
+    pub claim: Option<(String, usize)>,
+    /// When a peer's claim was taken, it expires unless the announcement is learned meanwhile
+    pub claimed_at: Option<DateTime<Utc>>,
+    /// Death confirmed while silenced, announced through the usual path once no longer silenced
+    pub silenced_death: bool,
+    pub cert: CertStatus,
+    pub system: Option<SystemMetrics>,
+    pub disk_warned: Option<DateTime<Utc>>,
+    /// First successful poll, `None` while the node was never reachable
+    pub joined: Option<DateTime<Utc>>,
+    pub poll_error: Option<PollError>,
+    /// How the last poll failed, like `connection refused`
+    pub failure: Option<String>,
+    /// As reported in the node's last status
+    pub advertised_address: Option<String>,
+    /// This node's obituary calls to the node
+    pub obituary_calls: Option<ObituaryCalls>,
+    /// The node's last call to this one
+    pub last_inbound: Option<DateTime<Utc>>,
+    /// As reported in the node's last status
+    pub version: Option<String>,
+    /// The version doesn't satisfy `expected_version`, announced at most once until it does again
+    pub version_mismatch: bool,
+    /// Deaths and recoveries within the flapping window, oldest first
+    pub transitions: VecDeque<DateTime<Utc>>,
+    /// Its deaths and recoveries are not announced while flapping
+    pub flapping_since: Option<DateTime<Utc>>,
+    /// Protocol features advertised in the node's last status, `None` before the first one
+    pub features: Option<Vec<String>>,
+}
+
+impl NodeState {
+    pub fn new(name: String) -> Self {
+        Self {
+            name,
+            last_poll: None,
+            last_ok: None,
+            latency: None,
+            last_changed: Utc::now(),
+            last_fail: None,
+            first_fail: None,
+            fail_count: 0,
+            thresholds: FailThresholds::default(),
+            confirmations: Default::default(),
+            relayed_rolls: Default::default(),
+            local_announcement_roll: None,
+            announced: None,
+            announced_at: None,
+            announcement_roll: None,
+            escalation_due: None,
+            claim: None,
+            claimed_at: None,
+            silenced_death: false,
+            cert: CertStatus::default(),
+            system: None,
+            disk_warned: None,
+            joined: None,
+            poll_error: None,
+            failure: None,
+            advertised_address: None,
+            obituary_calls: None,
+            last_inbound: None,
+            version: None,
+            version_mismatch: false,
+            transitions: VecDeque::new(),
+            flapping_since: None,
+            features: None,
+        }
+    }
+
+    /// Advertised `feature`, nodes not yet polled are assumed to support everything
+    pub fn supports(&self, feature: &str) -> bool {
+        self.features
+            .as_ref()
+            .is_none_or(|features| features.iter().any(|f| f == feature))
+    }
+
+    pub fn is_dead(&self) -> bool {
+        self.fail_count >= self.thresholds.dead_after
+    }
+
+    /// Failing, but not yet dead
+    pub fn is_dying(&self) -> bool {
+        self.fail_count >= self.thresholds.dying_after && !self.is_dead()
+    }
+
+    pub fn reset(&mut self) {
+        self.fail_count = 0;
+        self.confirmations.clear();
+        self.relayed_rolls.clear();
+        self.local_announcement_roll = None;
+        self.last_fail = None;
+        self.first_fail = None;
+        self.announced = None;
+        self.announced_at = None;
+        self.announcement_roll = None;
+        self.escalation_due = None;
+        self.claim = None;
+        self.claimed_at = None;
+        self.silenced_death = false;
+    }
+
+    /// Forgets the transitions past the window, `Some(true)` when the node starts flapping, `Some(false)` when it stops
+    fn update_flapping(
+        &mut self,
+        flapping: Option<&FlappingConfig>,
+        now: DateTime<Utc>,
+    ) -> Option<bool> {
+        let Some(flapping) = flapping else {
+            self.transitions.clear();
+            return self.flapping_since.take().map(|_| false);
+        };
+        let window = chrono::Duration::from_std(flapping.window).unwrap_or(chrono::Duration::MAX);
+        while self
+            .transitions
+            .front()
+            .is_some_and(|transition| now - *transition >= window)
+        {
+            self.transitions.pop_front();
+        }
+        match self.flapping_since {
+            None if self.transitions.len() >= flapping.transitions => {
+                self.flapping_since = Some(now);
+                Some(true)
+            }
+            Some(_) if self.transitions.is_empty() => {
+                self.flapping_since = None;
+                Some(false)
+            }
+            _ => None,
+        }
+    }
+
+    /// Drops the confirmations of peers no longer in the grid, returning how many
+    fn forget_strangers(&mut self, nodes: &HashMap<String, NodeConfig>) -> usize {
+        let before = self.confirmations.len();
+        self.confirmations
+            .retain(|from, _| nodes.contains_key(from));
+        self.relayed_rolls
+            .retain(|from, _| nodes.contains_key(from));
+        before - self.confirmations.len()
+    }
+
+    /// Peers which may have found themselves the winner of the death too: the confirmers,
+    /// the ones this node couldn't ask and the ones with no verdict yet
+    fn claim_peers(&self) -> BTreeSet<String> {
+        self.confirmations
+            .iter()
+            .filter(|(_, c)| c.confirmed_roll.is_some() || c.unreachable || c.abstained)
+            .map(|(peer, _)| peer.clone())
+            .chain(self.relayed_rolls.keys().cloned())
+            .collect()
+    }
+
+    /// Rolls of the peers confirming the death, their own answers first, relayed ones for peers this node can't ask
+    pub fn known_rolls(&self) -> BTreeMap<String, usize> {
+        let mut rolls = self
+            .relayed_rolls
+            .iter()
+            .filter(|(peer, _)| self.confirmations.get(*peer).is_none_or(|c| c.unreachable))
+            .map(|(peer, roll)| (peer.clone(), *roll))
+            .collect::<BTreeMap<_, _>>();
+        rolls.extend(
+            self.confirmations
+                .iter()
+                .filter_map(|(peer, c)| Some((peer.clone(), c.confirmed_roll?))),
+        );
+        rolls
+    }
+
+    /// Takes the rolls `from` knows of, conflicting with a peer's own answer or this node's roll they are left out
+    fn merge_relayed_rolls(&mut self, me: &str, from: &str, rolls: BTreeMap<String, usize>) {
+        for (peer, roll) in rolls {
+            if peer == from {
+                continue;
+            }
+            let known = if peer == me {
+                self.local_announcement_roll
+            } else {
+                self.confirmations
+                    .get(&peer)
+                    .filter(|c| !c.unreachable)
+                    .and_then(|c| c.confirmed_roll)
+            };
+            match known {
+                Some(known) if known != roll => warn!(
+                    "`{from}` knows `{peer}`'s roll for `{}` as {roll}, but it's {known}, `{peer}` may have restarted",
+                    self.name
+                ),
+                Some(_) => {}
+                None => {
+                    if let Some(old) = self.relayed_rolls.insert(peer.clone(), roll)
+                        && old != roll
+                    {
+                        warn!(
+                            "`{peer}`'s roll for `{}` was relayed as {old}, now as {roll} by `{from}`",
+                            self.name
+                        );
+                    }
+                }
+            }
+        }
+    }
+
+    /// The node's `preferred_announcer` if it's this node or confirmed the death, the winner of the rolls otherwise
+    fn choose_announcer(
+        &self,
+        me: &str,
+        my_roll: usize,
+        preferred: Option<&str>,
+    ) -> ((String, usize), AnnouncerChoice) {
+        let Some(preferred) = preferred else {
+            return (self.announcement_winner(me, my_roll), AnnouncerChoice::Roll);
+        };
+        let roll = if preferred == me {
+            Some(my_roll)
+        } else {
+            self.confirmations
+                .get(preferred)
+                .filter(|c| !c.unreachable)
+                .and_then(|c| c.confirmed_roll)
+        };
+        match roll {
+            Some(roll) => ((preferred.to_string(), roll), AnnouncerChoice::Preferred),
+            None => (
+                self.announcement_winner(me, my_roll),
+                AnnouncerChoice::PreferredUnavailable(preferred.to_string()),
+            ),
+        }
+    }
+
+    /// A peer's claim at least as strong as `winner` the death is left to,
+    /// one not followed by an announcement within `expiry` is dropped
+    fn pending_claim(
+        &mut self,
+        me: &str,
+        winner: &(String, usize),
+        now: DateTime<Utc>,
+        expiry: chrono::Duration,
+    ) -> Option<(String, usize)> {
+        let (claimer, roll) = self.claim.clone()?;
+        let claimed_at = self.claimed_at?;
+        if claimer == me || outranks((&winner.0, winner.1), (&claimer, roll)) {
+            return None;
+        }
+        if now - claimed_at < expiry {
+            return Some((claimer, roll));
+        }
+        warn!(
+            "`{claimer}` claimed `{}`'s death at {claimed_at}, but no announcement followed, rolling it again",
+            self.name
+        );
+        self.claim = None;
+        self.claimed_at = None;
+        None
+    }
+
+    /// The confirmer with the highest roll, this node included, the name breaks ties
+    fn announcement_winner(&self, me: &str, my_roll: usize) -> (String, usize) {
+        let mut rolls = self.known_rolls();
+        rolls.insert(me.to_string(), my_roll);
+        rolls
+            .into_iter()
+            .max_by(|(name1, roll1), (name2, roll2)| {
+                roll1.cmp(roll2).then_with(|| name1.cmp(name2))
+            })
+            .unwrap_or((me.to_string(), my_roll))
+    }
+
+    /// Peers confirming the death, plus this node, and peers saying the node is alive
+    /// With `count_dying`, peers about to call the node dead count for its death
+    fn death_votes(&self, count_dying: bool) -> (usize, usize) {
+        let confirms =
+            |val: &DeadConfirmation| val.confirmed_roll.is_some() || count_dying && val.dying;
+        let dead = self
+            .confirmations
+            .values()
+            .filter(|val| confirms(val))
+            .count();
+        let alive = self
+            .confirmations
+            .values()
+            .filter(|val| !confirms(val) && !val.unreachable && !val.abstained)
+            .count();
+        (dead + 1, alive)
+    }
+
+    /// `dying` from `dying_after` failures, `dead` from `dead_after`, announced or not
+    pub fn status(&self) -> GridNodeStatus {
+        if self.is_dead() {
+            GridNodeStatus::Dead
+        } else if self.is_dying() {
+            GridNodeStatus::Dying
+        } else if self.last_poll.is_none() {
+            GridNodeStatus::Unknown
+        } else {
+            GridNodeStatus::Alive
+        }
+    }
+
+    /// With `legacy_status`, dead nodes are only `dead` once announced, `dying` until then,
+    /// and dying nodes are `alive`, as before `dying` was a status of its own
+    pub fn to_api_response(&self, legacy_status: bool) -> GridNodeResponse {
+        let announced = self.is_dead() && self.announced.is_some();
+        let status = if legacy_status {
+            if announced {
+                GridNodeStatus::Dead
+            } else if self.is_dead() {
+                GridNodeStatus::Dying
+            } else if self.last_poll.is_none() {
+                GridNodeStatus::Unknown
+            } else {
+                GridNodeStatus::Alive
+            }
+        } else {
+            self.status()
+        };
+
+        GridNodeResponse {
+            name: self.name.clone(),
+            // known from the config, filled in by the caller
+            display_name: None,
+            last_poll: self.last_poll,
+            last_ok: self.last_ok,
+            last_changed: Some(self.last_changed),
+            status,
+            // known from the config, filled in by the caller
+            tags: vec![],
+            cert_days_remaining: self.cert.days_remaining(),
+            system: self.system.clone(),
+            poll_error: self.poll_error.clone(),
+            advertised_address: self.advertised_address.clone(),
+            announced,
+            announced_by: self.announced.clone().filter(|_| announced),
+            announced_at: self.announced_at.filter(|_| announced),
+            announcement_roll: self.announcement_roll.filter(|_| announced),
+            obituary_calls: self.obituary_calls.clone(),
+            last_inbound: self.last_inbound,
+            version: self.version.clone(),
+            version_mismatch: self.version_mismatch,
+            flapping: self.flapping_since.is_some(),
+            features: self.features.clone(),
+        }
+    }
+}
+
+/// The clients of the poller, nodes whose address may move get one resolving it on every request
+struct Clients {
+    reusing: Client,
+    fresh: Client,
+}
+
+impl Clients {
+    fn for_node(&self, node: &NodeConfig) -> &Client {
+        if node.reuse_connections {
+            &self.reusing
+        } else {
+            &self.fresh
+        }
+    }
+}
+
+/// What the phases of a poll cycle share
+struct Cycle<'a> {
+    config: &'a Config,
+    state: &'a State,
+    clients: &'a Clients,
+    /// Nodes may come and go with discovery
+    nodes: HashMap<String, NodeConfig>,
+    concurrency: usize,
+    trace: String,
+    /// Peer calls and announcements of the cycle happen within it
+    span: Span,
+}
+
+/// What this cycle's polls changed, for announcing it and calling the obituaries
+struct PollOutcome {
+    /// (name, node, announced at, down since) of the nodes back up whose death this node announced
+    recovered: Vec<(String, NodeConfig, DateTime<Utc>, DateTime<Utc>)>,
+    joined: Vec<String>,
+    dying: Vec<String>,
+    /// Nodes starting to flap with their transitions, or stable again
+    flaps: Vec<(String, Option<usize>)>,
+    dead: Vec<NodeState>,
+    grid: GridSnapshot,
+}
+
+pub async fn poller(
+    config: SharedConfig,
+    trust_anchors: Vec<Certificate>,
+    state: State,
+    mut announcers: Announcers,
+) -> Result<()> {
+    let mut poller_config = config.get();
+    info!("Starting poller `{}`", poller_config.name);
+
+    info!(
+        "HTTP client: request timeout {:?}, connect timeout {:?}, tcp keepalive {:?}, max idle per host {:?}, idle timeout {:?}",
+        poller_config.http_client.request_timeout,
+        poller_config.http_client.connect_timeout,
+        poller_config.http_client.tcp_keepalive,
+        poller_config.http_client.pool_max_idle_per_host,
+        poller_config.http_client.pool_idle_timeout,
+    );
+    let clients = Clients {
+        reusing: build_client(&poller_config, &trust_anchors, true)?,
+        fresh: build_client(&poller_config, &trust_anchors, false)?,
+    };
+
+    let self_check_client = self_check::client(&poller_config)?;
+    let self_check_url = self_check::url(&poller_config);
+
+    let concurrency = poller_config.http_client.max_concurrent_calls.max(1);
+
+    let mut silenced_before = HashSet::new();
+    let mut stale_config_warned = false;
+    loop {
+        let time = Utc::now();
+
+        // reloaded, clients and the self-check keep the config they were built with
+        let current = config.get();
+        if !Arc::ptr_eq(&current, &poller_config) {
+            match Announcers::new(&current, &state) {
+                Ok(reloaded) => announcers = reloaded,
+                Err(err) => error!("Keeping the announcers, the reloaded ones failed: {err:#}"),
+            }
+            poller_config = current;
+            stale_config_warned = false;
+        }
+        if !stale_config_warned && poller_config.is_stale(time) {
+            warn!(
+                "The config was loaded at {} from {:?}, longer ago than `config_stale_after`",
+                poller_config.loaded_at, poller_config.source
+            );
+            stale_config_warned = true;
+        }
+
+        let has_net =
+            check_internet_connection(&clients.reusing, &poller_config.internet_check_url).await;
+        let internet_check_took = Utc::now() - time;
+        if !has_net {
+            warn!("No internet connection, skipping poll");
+            // not hung, just offline
+            systemd::notify("WATCHDOG=1");
+            tokio::time::sleep(DEFAULT_POLL_INTERVAL).await;
+            continue;
+        }
+
+        let trace = new_trace_id();
+        let cycle = Cycle {
+            config: &poller_config,
+            state: &state,
+            clients: &clients,
+            nodes: discovery::grid_nodes(&poller_config, &state),
+            concurrency,
+            span: info_span!("poll_cycle", trace = %trace),
+            trace,
+        };
+        {
+            let mut gr = state.lock().expect("Failed to lock state");
+            gr.audit.cycle_trace = Some(cycle.trace.clone());
+            gr.thresholds = FailThresholds::of(&poller_config);
+            gr.reconcile_nodes(&cycle.nodes, time);
+        }
+
+        let silences_started = Utc::now();
+        let silences = expire_silences(&cycle, &announcers, &mut silenced_before, time).await;
+        broadcast_silences(&cycle, &silences).await;
+        broadcast_mute(&cycle).await;
+        let silences_took = Utc::now() - silences_started;
+
+        debug!("Polling nodes @`{time:?}`, trace `{}`", cycle.trace);
+        let polling_started = Utc::now();
+        let polls = poll_nodes(&cycle, &silences).await;
+        let polling_took = Utc::now() - polling_started;
+        let reachable_peers = polls
+            .iter()
+            .filter(|(_, res, _)| res.error.is_none())
+            .count();
+        let polled = polls.len();
+        // state transitions of this cycle, for its summary
+        let mut changes = vec![];
+        let outcome = record_polls(&cycle, polls, reachable_peers, time, &mut changes);
+
+        let announcements_started = Utc::now();
+        announce_changes(&cycle, &announcers, &outcome, time).await;
+        let mut announcements_took = Utc::now() - announcements_started;
+
+        let checks_started = Utc::now();
+        check_listener(
+            &poller_config,
+            &self_check_client,
+            &self_check_url,
+            &state,
+            &announcers,
+        )
+        .await;
+        check_certificates(&poller_config, &cycle.nodes, &state, &announcers).await;
+        check_disk_usage(&poller_config, &state, &announcers).await;
+        check_versions(&poller_config, &state, &announcers).await;
+        check_digest(&poller_config, &state, &announcers).await;
+        cross_check(
+            &poller_config,
+            &state,
+            &cycle.nodes,
+            |node| clients.for_node(node),
+            &cycle.trace,
+        )
+        .instrument(cycle.span.clone())
+        .await;
+        let checks_took = Utc::now() - checks_started;
+
+        let obituaries_started = Utc::now();
+        let (obituaries, obituary_failures) = call_obituaries(&cycle, &outcome.dead).await;
+        let obituaries_took = Utc::now() - obituaries_started;
+        let decided = judge_deaths(&cycle, obituaries, obituary_failures);
+        changes.extend(decided.changes);
+
+        let announcements_started = Utc::now();
+        announce_deaths(&cycle, &announcers, decided.announcements, decided.claims).await;
+        escalate(&cycle, &announcers).await;
+        announcements_took += Utc::now() - announcements_started;
+
+        let timings = CycleTimings {
+            internet_check: internet_check_took.as_seconds_f64(),
+            silences: silences_took.as_seconds_f64(),
+            polling: polling_took.as_seconds_f64(),
+            checks: checks_took.as_seconds_f64(),
+            obituaries: obituaries_took.as_seconds_f64(),
+            announcements: announcements_took.as_seconds_f64(),
+            total: (Utc::now() - time).as_seconds_f64(),
+        };
+        let poll_time = poller_config.poll_interval().as_secs_f64();
+        if timings.total > poll_time {
+            warn!(
+                "Poll cycle took {:.1}s, longer than the {poll_time:.1}s poll time, deaths are noticed later than configured",
+                timings.total
+            );
+        }
+        let summary = format!(
+            "Poll cycle `{}` done in {:.1}s (polling {:.1}s, obituaries {:.1}s, announcements {:.1}s)",
+            cycle.trace, timings.total, timings.polling, timings.obituaries, timings.announcements
+        );
+        state
+            .lock()
+            .expect("Failed to lock state")
+            .refresh_changes(Utc::now());
+        state.cycle_completed(timings);
+        state.save_announced(&poller_config);
+        {
+            let gr = state.lock().expect("Failed to lock state");
+            let dead = gr.node_state.iter().filter(|ns| ns.is_dead()).count();
+            info!(
+                "{summary}: {reachable_peers}/{polled} polled nodes answered, {dead} dead, {} silenced, {}",
+                cycle.nodes.len() - polled,
+                if changes.is_empty() {
+                    "no changes".to_string()
+                } else {
+                    format!("changes: {}", changes.join(", "))
+                }
+            );
+        }
+
+        tokio::time::sleep(poller_config.poll_interval()).await;
+    }
+}
+
+/// Drops the expired silences and moves the nodes whose silence ended on, reminding of the ones
+/// this node announced dead. Returns the silences left
+async fn expire_silences(
+    cycle: &Cycle<'_>,
+    announcers: &Announcers,
+    silenced_before: &mut HashSet<String>,
+    time: DateTime<Utc>,
+) -> Vec<NodeSilence> {
+    let config = cycle.config;
+    let (silences, reminders) = {
+        let mut gr = cycle.state.lock().expect("Failed to lock state");
+        let (silences, expired) = std::mem::take(&mut gr.silences)
+            .into_iter()
+            .partition(|sl| sl.silent_until > time);
+        gr.silences = silences;
+        for sl in expired
+            .into_iter()
+            .filter(|sl: &NodeSilence| !sl.cancelled && !sl.local)
+        {
+            gr.events.record(GridEvent::SilenceExpired {
+                silence_id: sl.id,
+                node_name: sl.node_name,
+            });
+        }
+
+        let silenced_now = gr
+            .silences
+            .iter()
+            .filter(|sl| !sl.cancelled)
+            .map(|sl| sl.node_name.clone())
+            .collect::<HashSet<_>>();
+        let mut reminders = vec![];
+        for ns in gr
+            .node_state
+            .iter_mut()
+            .filter(|ns| silenced_before.contains(&ns.name) && !silenced_now.contains(&ns.name))
+        {
+            if silence_ended(ns, config) == SilenceEnd::Remind {
+                reminders.push(ns.name.clone());
+            }
+        }
+        *silenced_before = silenced_now;
+
+        (gr.silences.clone(), reminders)
+    };
+
+    for name in reminders {
+        announcers
+            .announce(AnnouncementEvent::new(
+                config,
+                AnnouncementKind::Reminder,
+                &name,
+                messages::render(config, "reminder", &[("node", config.display_name(&name))]),
+            ))
+            .await;
+    }
+    silences
+}
+
+/// Fans the silences out to every peer which hasn't received their current revision,
+/// the receive handler is idempotent on `id`
+async fn broadcast_silences(cycle: &Cycle<'_>, silences: &[NodeSilence]) {
+    let config = cycle.config;
+    let mut broadcast_silences = vec![];
+    let no_silence_cancel = lacking_feature(cycle.state, FEATURE_SILENCE_CANCEL);
+    for sl in silences {
+        if sl.broadcasted || sl.local {
+            continue;
+        }
+
+        // to an older node a cancellation is a new silence, it keeps its copy until it expires
+        let peers = cycle.nodes.iter().filter(|(node_name, _)| {
+            !sl.received_by.contains(*node_name)
+                && (!sl.cancelled || !no_silence_cancel.contains(*node_name))
+        });
+        let results = fan_out(peers, cycle.concurrency, |node_name, node| {
+            let client = cycle.clients.for_node(&node).clone();
+            let me = config.name.clone();
+            let key = config.secret_key.clone();
+            let sl = sl.clone();
+            let trace = cycle.trace.clone();
+            async move {
+                call_silence_broadcast(&client, &me, &trace, node.with_name(&node_name), &key, &sl)
+                    .await
+            }
+        })
+        .instrument(cycle.span.clone())
+        .await;
+
+        let (received, failed): (Vec<_>, Vec<_>) = results.into_iter().partition(|(_, done)| *done);
+        if !failed.is_empty() {
+            debug!(
+                "Silence {} not yet received by {:?}",
+                sl.id,
+                failed
+                    .iter()
+                    .map(|(node_name, _)| node_name)
+                    .collect::<Vec<_>>()
+            );
+        }
+        broadcast_silences.push((
+            sl,
+            received.into_iter().map(|(node_name, _)| node_name),
+            failed.is_empty(),
+        ));
+    }
+
+    let mut gr = cycle.state.lock().expect("Failed to lock state");
+    for (bs, received, complete) in broadcast_silences {
+        gr.record_silence_receipts(&bs.id, bs.revision, received, complete);
+    }
+}
+
+/// Broadcasts the mute until every peer has it, the latest change wins on every peer
+async fn broadcast_mute(cycle: &Cycle<'_>) {
+    let config = cycle.config;
+    let mute = cycle
+        .state
+        .lock()
+        .expect("Failed to lock state")
+        .mute
+        .clone()
+        .filter(|mute| !mute.broadcasted);
+    let Some(mute) = mute else {
+        return;
+    };
+
+    let no_mute_broadcast = lacking_feature(cycle.state, FEATURE_MUTE_BROADCAST);
+    let peers = cycle
+        .nodes
+        .iter()
+        .filter(|(node_name, _)| !no_mute_broadcast.contains(*node_name));
+    let all_ok = fan_out(peers, cycle.concurrency, |node_name, node| {
+        let client = cycle.clients.for_node(&node).clone();
+        let me = config.name.clone();
+        let key = config.secret_key.clone();
+        let mute = mute.clone();
+        let trace = cycle.trace.clone();
+        async move {
+            call_mute_broadcast(
+                &client,
+                &me,
+                &trace,
+                node.with_name(&node_name),
+                &key,
+                &mute,
+            )
+            .await
+        }
+    })
+    .instrument(cycle.span.clone())
+    .await
+    .iter()
+    .all(|(_, done)| *done);
+
+    let mut gr = cycle.state.lock().expect("Failed to lock state");
+    if let Some(current) = gr.mute.as_mut()
+        && all_ok
+        && current.changed_at == mute.changed_at
+    {
+        current.broadcasted = true;
+    }
+}
+
+/// Polls every node not silenced, one after the other, with the time of each poll
+async fn poll_nodes(
+    cycle: &Cycle<'_>,
+    silences: &[NodeSilence],
+) -> Vec<(String, NodeResult, DateTime<Utc>)> {
+    let config = cycle.config;
+    let mut polls = vec![];
+    let mut reported_names: HashMap<String, Vec<String>> = HashMap::new();
+    for (node_name, node) in cycle.nodes.iter() {
+        if silences
+            .iter()
+            .any(|sl| sl.node_name == *node_name && !sl.cancelled && !sl.local)
+        {
+            debug!("Silenced node {}", node_name);
+            continue;
+        }
+
+        debug!("Checking node {}: {}", node_name, node.address);
+        let time = Utc::now();
+        let res = poll_node(
+            cycle.clients.for_node(node),
+            &config.name,
+            &cycle.trace,
+            node.with_name(node_name),
+            &config.secret_key,
+            config.strict_name_check,
+        )
+        .instrument(cycle.span.clone())
+        .await;
+        if let Some(reported_name) = res.reported_name.as_ref() {
+            reported_names
+                .entry(reported_name.clone())
+                .or_default()
+                .push(node_name.clone());
+        }
+        polls.push((node_name.clone(), res, time));
+    }
+    for (reported_name, mut node_names) in reported_names {
+        if node_names.len() > 1 {
+            node_names.sort();
+            warn!(
+                "Nodes {:?} all report the name `{}`, they may be the same machine",
+                node_names, reported_name
+            );
+        }
+    }
+    polls
+}
+
+/// Moves the polled nodes through dying, dead and back up, and follows who joined and who flaps.
+/// `changes` gets the state transitions for the cycle's summary
+fn record_polls(
+    cycle: &Cycle<'_>,
+    polls: Vec<(String, NodeResult, DateTime<Utc>)>,
+    reachable_peers: usize,
+    time: DateTime<Utc>,
+    changes: &mut Vec<String>,
+) -> PollOutcome {
+    let config = cycle.config;
+    let mut up_announcements = vec![];
+    let mut join_announcements = vec![];
+    let mut flap_notices = vec![];
+    let mut dying_announcements = vec![];
+    let mut gr = cycle.state.lock().expect("Failed to lock state");
+
+    gr.check_participation(config, reachable_peers);
+    let mut died = vec![];
+    let mut recovered = vec![];
+    let mut answered = vec![];
+    for (node_name, res, time) in polls {
+        let Some(fail_state) = gr.node_state.iter_mut().find(|fs| fs.name == node_name) else {
+            warn!("Polled node `{}` missing from state, skipping", node_name);
+            continue;
+        };
+
+        fail_state.last_poll = Some(time);
+        fail_state.poll_error = res.error.clone();
+        fail_state.failure = res.failure.clone();
+
+        if let Some(error) = res.error.as_ref() {
+            fail_state.last_fail = Some(time);
+
+            if !fail_state.is_dead() {
+                fail_state.fail_count += 1;
+                if fail_state.fail_count == 1 {
+                    fail_state.first_fail = Some(time);
+                    warn!("Node `{node_name}` failed to answer: {error}");
+                }
+                if fail_state.is_dying()
+                    && fail_state.fail_count == fail_state.thresholds.dying_after
+                {
+                    changes.push(format!("`{node_name}` dying"));
+                    if fail_state.flapping_since.is_none() {
+                        dying_announcements.push(node_name.clone());
+                    }
+                }
+                if fail_state.is_dead() {
+                    changes.push(format!("`{node_name}` dead"));
+                    let roll = rand::rng().random_range(0usize..usize::MAX);
+                    fail_state.local_announcement_roll = Some(roll);
+                    if config.flapping.is_some() {
+                        fail_state.transitions.push_back(time);
+                    }
+                    died.push((node_name.clone(), time));
+                    warn!(
+                        "Node `{}` is dead my roll: `{}`, last fail: {:?}",
+                        node_name, roll, fail_state.last_fail
+                    );
+                }
+            }
+        } else {
+            answered.push(node_name.clone());
+            fail_state.last_ok = Some(time);
+            fail_state.latency = res.latency;
+            fail_state.system = res.system;
+            fail_state.advertised_address = res.advertised_address;
+            if res.version.is_some() {
+                fail_state.version = res.version;
+            }
+            if res.features.is_some() {
+                fail_state.features = res.features;
+            }
+            if fail_state.joined.is_none() {
+                fail_state.joined = Some(time);
+                info!("Node `{}` joined the grid", node_name);
+                changes.push(format!("`{node_name}` joined"));
+                join_announcements.push(node_name.clone());
+            }
+
+            // back up
+            if fail_state.is_dead() {
+                if fail_state.announced == Some(config.name.clone()) {
+                    let announced_at = fail_state.announced_at.unwrap_or(time);
+                    // the death is found in the incidents below
+                    up_announcements.push((
+                        node_name.clone(),
+                        cycle.nodes[&node_name].clone(),
+                        announced_at,
+                        fail_state.first_fail.unwrap_or(announced_at),
+                    ));
+                }
+                fail_state.reset();
+                if config.flapping.is_some() {
+                    fail_state.transitions.push_back(time);
+                }
+                recovered.push((node_name.clone(), time));
+                info!("Node `{}` is back up", node_name);
+                changes.push(format!("`{node_name}` recovered"));
+            }
+        }
+    }
+
+    for node_name in answered {
+        if gr.restored_announcements.remove(&node_name).is_some() {
+            info!(
+                "Node `{node_name}` recovered while this node was down, its announced death is over"
+            );
+        }
+    }
+    for (node_name, started) in died {
+        // the outage outlived a restart, its announcement still holds
+        let restored = gr.restored_announcements.remove(&node_name);
+        if let Some(restored) = restored.as_ref()
+            && let Some(fs) = gr.node_state.iter_mut().find(|fs| fs.name == node_name)
+        {
+            info!(
+                "Node `{node_name}` is still dead, its death was announced by `{}` at {} before the restart",
+                restored.announcer, restored.announced_at
+            );
+            fs.announced = Some(restored.announcer.clone());
+            fs.announced_at = Some(restored.announced_at);
+        }
+        if gr.incidents.len() >= MAX_INCIDENTS {
+            gr.incidents.pop_front();
+        }
+        gr.incidents.push_back(Incident {
+            node_name,
+            started: restored.as_ref().map_or(started, |inc| inc.started),
+            ended: None,
+            announced_by: restored.map(|inc| inc.announcer),
+            silenced: false,
+            trace: Some(cycle.trace.clone()),
+        });
+    }
+    for (node_name, ended) in recovered {
+        if let Some(inc) = gr.open_incident_mut(&node_name) {
+            inc.ended = Some(ended);
+            if let Some(up) = up_announcements.iter_mut().find(|up| up.0 == node_name) {
+                // an outage outliving a restart started before its first failure here
+                up.3 = up.3.min(inc.started);
+            }
+        }
+    }
+
+    if !config.announce_new_nodes || !is_warning_announcer(&gr, config) {
+        join_announcements.clear();
+    }
+    if !config.announce_dying || !is_warning_announcer(&gr, config) {
+        dying_announcements.clear();
+    }
+
+    for ns in gr.node_state.iter_mut() {
+        match ns.update_flapping(config.flapping.as_ref(), time) {
+            Some(true) => {
+                warn!(
+                    "Node `{}` is flapping, {} deaths and recoveries within the window",
+                    ns.name,
+                    ns.transitions.len()
+                );
+                changes.push(format!("`{}` flapping", ns.name));
+                flap_notices.push((ns.name.clone(), Some(ns.transitions.len())));
+            }
+            Some(false) => {
+                info!("Node `{}` is stable again", ns.name);
+                changes.push(format!("`{}` stable", ns.name));
+                flap_notices.push((ns.name.clone(), None));
+            }
+            None => {}
+        }
+    }
+    // a recovery of a flapping node is told by its stabilized notice
+    up_announcements.retain(|(up_name, ..)| {
+        gr.node_state
+            .iter()
+            .find(|ns| ns.name == **up_name)
+            .is_none_or(|ns| ns.flapping_since.is_none())
+    });
+    for (node_name, transitions) in flap_notices.iter() {
+        gr.audit.record(
+            AuditSource::Poller,
+            match transitions {
+                Some(transitions) => AuditAction::NodeFlapping {
+                    node_name: node_name.clone(),
+                    transitions: *transitions,
+                },
+                None => AuditAction::NodeStabilized {
+                    node_name: node_name.clone(),
+                },
+            },
+        );
+    }
+    if !is_warning_announcer(&gr, config) {
+        flap_notices.clear();
+    }
+
+    for (up_name, ..) in up_announcements.iter() {
+        gr.audit.record(
+            AuditSource::Poller,
+            AuditAction::RecoveryAnnouncementDecided {
+                node_name: up_name.clone(),
+            },
+        );
+    }
+
+    PollOutcome {
+        recovered: up_announcements,
+        joined: join_announcements,
+        dying: dying_announcements,
+        flaps: flap_notices,
+        dead: gr
+            .node_state
+            .iter()
+            .filter(|fs| fs.is_dead())
+            .cloned()
+            .collect(),
+        grid: gr.grid_snapshot(),
+    }
+}
+
+/// Announces the recoveries, joins, dying nodes and flapping of this cycle's polls
+async fn announce_changes(
+    cycle: &Cycle<'_>,
+    announcers: &Announcers,
+    outcome: &PollOutcome,
+    time: DateTime<Utc>,
+) {
+    let config = cycle.config;
+    for (up_name, up_node, announced_at, down_since) in outcome.recovered.iter() {
+        let event = AnnouncementEvent::returned(
+            config,
+            up_node.with_name(up_name),
+            *announced_at,
+            (time - *down_since).to_std().unwrap_or_default(),
+            Some(outcome.grid),
+        );
+        announcers.announce(event.clone()).await;
+        announcers
+            .notify_node(config, up_node.with_name(up_name), *down_since, &event)
+            .await;
+    }
+
+    for joined_name in outcome.joined.iter() {
+        announcers
+            .announce(AnnouncementEvent::new(
+                config,
+                AnnouncementKind::Joined,
+                joined_name,
+                messages::render(
+                    config,
+                    "joined",
+                    &[("node", config.display_name(joined_name))],
+                ),
+            ))
+            .await;
+    }
+
+    for dying_name in outcome.dying.iter() {
+        announcers
+            .announce(AnnouncementEvent::new(
+                config,
+                AnnouncementKind::Warning,
+                dying_name,
+                messages::render(
+                    config,
+                    "dying",
+                    &[
+                        ("node", config.display_name(dying_name)),
+                        ("count", &config.dying_after.to_string()),
+                    ],
+                ),
+            ))
+            .await;
+    }
+
+    let window = config
+        .flapping
+        .as_ref()
+        .map(|flapping| humantime::format_duration(flapping.window).to_string())
+        .unwrap_or_default();
+    for (name, transitions) in outcome.flaps.iter() {
+        let display_name = config.display_name(name);
+        let message = match transitions {
+            Some(count) => messages::render(
+                config,
+                "flapping",
+                &[
+                    ("node", display_name),
+                    ("count", &count.to_string()),
+                    ("window", &window),
+                ],
+            ),
+            None => messages::render(
+                config,
+                "stabilized",
+                &[("node", display_name), ("window", &window)],
+            ),
+        };
+        announcers
+            .announce(AnnouncementEvent::new(
+                config,
+                AnnouncementKind::Warning,
+                name,
+                message,
+            ))
+            .await;
+    }
+}
+
+/// Asks the peers for their obituaries while a death is unannounced, the silences of the dead
+/// still going out are sent along, as a peer not having them yet would vote the node alive.
+/// Returns the answers and the failures by peer
+async fn call_obituaries(
+    cycle: &Cycle<'_>,
+    dead: &[NodeState],
+) -> (HashMap<String, ObituaryResponse>, HashMap<String, String>) {
+    let config = cycle.config;
+    let mut responses = HashMap::new();
+    let mut failures = HashMap::new();
+    if !dead.iter().any(|fs| fs.is_dead() && fs.announced.is_none()) {
+        return (responses, failures);
+    }
+
+    let dead_peers = dead
+        .iter()
+        .map(|fs| fs.name.as_str())
+        .collect::<HashSet<_>>();
+    // a dead peer answering votes as usual, failing it's unreachable
+    let candidates = cycle
+        .nodes
+        .iter()
+        .filter(|(node_name, _)| config.ask_dead_peers || !dead_peers.contains(node_name.as_str()));
+    let no_obituary_v2 = lacking_feature(cycle.state, FEATURE_OBITUARY_V2);
+    let no_silence_cancel = lacking_feature(cycle.state, FEATURE_SILENCE_CANCEL);
+    let unsent_silences = cycle
+        .state
+        .lock()
+        .expect("Failed to lock state")
+        .silences
+        .iter()
+        .filter(|sl| !sl.local && !sl.broadcasted && dead_peers.contains(sl.node_name.as_str()))
+        .cloned()
+        .collect::<Vec<_>>();
+    let results = fan_out(candidates, cycle.concurrency, |node_name, node| {
+        let client = cycle.clients.for_node(&node).clone();
+        let me = config.name.clone();
+        let key = config.secret_key.clone();
+        let trace = cycle.trace.clone();
+        let timeout = dead_peers
+            .contains(node_name.as_str())
+            .then_some(config.dead_peer_timeout);
+        let v2 = !no_obituary_v2.contains(&node_name);
+        let silences = unsent_silences
+            .iter()
+            .filter(|sl| {
+                !sl.received_by.contains(&node_name)
+                    && (!sl.cancelled || !no_silence_cancel.contains(&node_name))
+            })
+            .cloned()
+            .collect::<Vec<_>>();
+        async move {
+            let mut received = vec![];
+            let call = async {
+                for sl in silences {
+                    let node = node.with_name(&node_name);
+                    if call_silence_broadcast(&client, &me, &trace, node, &key, &sl).await {
+                        received.push((sl.id, sl.revision));
+                    }
+                }
+                call_obituary(&client, &me, &trace, node.with_name(&node_name), &key, v2).await
+            };
+            let res = match timeout {
+                Some(timeout) => tokio::time::timeout(timeout, call)
+                    .await
+                    .unwrap_or_else(|_| Err(anyhow::anyhow!("No answer within {timeout:?}"))),
+                None => call.await,
+            };
+            (res, received)
+        }
+    })
+    .instrument(cycle.span.clone())
+    .await;
+
+    let mut gr = cycle.state.lock().expect("Failed to lock state");
+    for (node_name, (res, received)) in results {
+        for (id, revision) in received {
+            gr.record_silence_receipts(&id, revision, [node_name.clone()], false);
+        }
+        match res {
+            Ok(orb) => {
+                responses.insert(node_name, orb);
+            }
+            Err(err) => {
+                error!("Failed to call Obituary for node `{node_name}`: {err}");
+                failures.insert(node_name, err.to_string());
+            }
+        }
+    }
+    (responses, failures)
+}
+
+/// Takes in the obituaries and decides the deaths confirmed by quorum
+fn judge_deaths(
+    cycle: &Cycle<'_>,
+    responses: HashMap<String, ObituaryResponse>,
+    failures: HashMap<String, String>,
+) -> DeathDecisions {
+    let mut gr = cycle.state.lock().expect("Failed to lock state");
+    let learned = gr.record_obituaries(&cycle.config.name, responses, failures);
+    for announced in learned {
+        if let Some(inc) = gr.open_incident_mut(&announced.name) {
+            inc.announced_by = Some(announced.announcer.clone());
+        }
+        gr.audit.record(
+            AuditSource::Poller,
+            AuditAction::DeathAnnouncementLearned {
+                node_name: announced.name,
+                announcer: announced.announcer,
+                announced_at: announced.announced_at,
+            },
+        );
+    }
+    gr.decide_deaths(cycle.config, &cycle.nodes, Utc::now())
+}
+
+/// Claims the deaths this node won from the peers which may have decided them too,
+/// then announces the ones no peer holds a stronger claim of
+async fn announce_deaths(
+    cycle: &Cycle<'_>,
+    announcers: &Announcers,
+    announcements: Vec<(String, NodeConfig, DeathDetails, AnnouncerChoice)>,
+    claims: Vec<(AnnouncementClaimRequest, BTreeSet<String>)>,
+) {
+    let config = cycle.config;
+    let lost = if claims.is_empty() {
+        HashSet::new()
+    } else {
+        let no_claims = lacking_feature(cycle.state, FEATURE_ANNOUNCEMENT_CLAIM);
+        let answers = fan_out(
+            cycle.nodes.iter().filter(|(node_name, _)| {
+                !no_claims.contains(*node_name)
+                    && claims.iter().any(|(_, peers)| peers.contains(*node_name))
+            }),
+            cycle.concurrency,
+            |node_name, node| {
+                let client = cycle.clients.for_node(&node).clone();
+                let me = config.name.clone();
+                let key = config.secret_key.clone();
+                let trace = cycle.trace.clone();
+                let claims = claims
+                    .iter()
+                    .filter(|(_, peers)| peers.contains(&node_name))
+                    .map(|(claim, _)| claim.clone())
+                    .collect::<Vec<_>>();
+                async move {
+                    let api = peer_api(&client, &me, &trace, &node.with_name(&node_name), &key);
+                    let mut answers = vec![];
+                    for claim in claims {
+                        let call = make_whatever_logged_http_call(
+                            &node_name,
+                            &trace,
+                            "claim",
+                            api.claim(&claim),
+                        );
+                        // best effort, an unanswered claim doesn't hold the announcement back
+                        if let Ok(Ok(Some(answer))) =
+                            tokio::time::timeout(CLAIM_TIMEOUT, call).await
+                        {
+                            answers.push((claim.node, answer));
+                        }
+                    }
+                    answers
+                }
+            },
+        )
+        .instrument(cycle.span.clone())
+        .await;
+        let mut gr = cycle.state.lock().expect("Failed to lock state");
+        gr.resolve_claims(
+            &config.name,
+            &claims
+                .iter()
+                .map(|(claim, _)| claim.node.clone())
+                .collect::<Vec<_>>(),
+            answers.into_iter().flat_map(|(_, answers)| answers),
+        )
+    };
+    for (anc_name, anc, death, choice) in announcements
+        .into_iter()
+        .filter(|(anc_name, ..)| !lost.contains(anc_name))
+    {
+        announcers
+            .announce(AnnouncementEvent::died(
+                config,
+                anc.with_name(&anc_name),
+                death,
+                &choice,
+            ))
+            .instrument(cycle.span.clone())
+            .await;
+    }
+}
+
+/// Escalates the deaths past their `escalation.after`, then sends what the channels held back
+async fn escalate(cycle: &Cycle<'_>, announcers: &Announcers) {
+    check_escalations(cycle.config, &cycle.nodes, cycle.state, announcers)
+        .instrument(cycle.span.clone())
+        .await;
+    announcers
+        .flush(cycle.config)
+        .instrument(cycle.span.clone())
+        .await;
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum SilenceEnd {
+    /// The node is alive, or someone else handles it
+    Nothing,
+    /// Dead and unannounced, goes through the quorum this cycle
+    Announce,
+    /// Dead and unannounced, but kept quiet
+    Quiet,
+    /// Dead and announced by this node, reminding it is still dead
+    Remind,
+}
+
+/// Moves a node whose silence just ended (expired or cancelled) on
+fn silence_ended(ns: &mut NodeState, config: &Config) -> SilenceEnd {
+    if !ns.is_dead() {
+        info!("Silence of `{}` is over, it is alive", ns.name);
+        ns.silenced_death = false;
+        return SilenceEnd::Nothing;
+    }
+
+    if let Some(announcer) = ns.announced.as_ref() {
+        info!("Silence of `{}` is over, it is still dead", ns.name);
+        return if config.remind_after_silence && *announcer == config.name {
+            SilenceEnd::Remind
+        } else {
+            SilenceEnd::Nothing
+        };
+    }
+
+    if config.announce_after_silence {
+        warn!(
+            "Silence of `{}` is over, it is dead and will be announced",
+            ns.name
+        );
+        ns.silenced_death = false;
+        SilenceEnd::Announce
+    } else {
+        warn!(
+            "Silence of `{}` is over, it is dead but `announce_after_silence` is off",
+            ns.name
+        );
+        ns.silenced_death = true;
+        SilenceEnd::Quiet
+    }
+}
+
+/// Peers known not to support `feature`, optional calls are left out for them
+fn lacking_feature(state: &State, feature: &str) -> HashSet<String> {
+    let gr = state.lock().expect("Failed to lock state");
+    let lacking = gr
+        .node_state
+        .iter()
+        .filter(|ns| !ns.supports(feature))
+        .map(|ns| ns.name.clone())
+        .collect::<HashSet<_>>();
+    if !lacking.is_empty() {
+        debug!("Peers without `{feature}`: {lacking:?}");
+    }
+    lacking
+}
+
+/// Where a peer's view differs from this node's, nodes one of them hasn't polled yet are left out
+fn view_discrepancies(mine: &IntrospectResponse, theirs: &IntrospectResponse) -> Vec<String> {
+    let status = |code: u8| GridNodeStatus::from_code(code).unwrap_or(GridNodeStatus::Unknown);
+    let mut discrepancies = vec![];
+    for (node, code) in mine.statuses.iter() {
+        if *node == theirs.name {
+            continue;
+        }
+        let Some(their_code) = theirs.statuses.get(node) else {
+            discrepancies.push(format!("`{node}` is unknown to it"));
+            continue;
+        };
+        let (mine, theirs) = (status(*code), status(*their_code));
+        if mine != theirs && mine != GridNodeStatus::Unknown && theirs != GridNodeStatus::Unknown {
+            discrepancies.push(format!(
+                "it sees `{node}` {}, this node {}",
+                status_name(theirs),
+                status_name(mine)
+            ));
+        }
+    }
+    if let Some(code) = theirs.statuses.get(&mine.name)
+        && !matches!(
+            status(*code),
+            GridNodeStatus::Alive | GridNodeStatus::Unknown
+        )
+    {
+        discrepancies.push(format!("it sees this node {}", status_name(status(*code))));
+    }
+    let missing = |of: &IntrospectResponse, from: &IntrospectResponse| {
+        of.silences
+            .iter()
+            .filter(|id| !from.silences.contains(id))
+            .cloned()
+            .collect::<Vec<_>>()
+    };
+    let (lacking, extra) = (missing(mine, theirs), missing(theirs, mine));
+    if !lacking.is_empty() {
+        discrepancies.push(format!("it lacks silences {lacking:?}"));
+    }
+    if !extra.is_empty() {
+        discrepancies.push(format!("this node lacks its silences {extra:?}"));
+    }
+    if mine.muted != theirs.muted {
+        discrepancies.push(format!(
+            "it is {}muted, this node {}",
+            if theirs.muted { "" } else { "not " },
+            if mine.muted { "is" } else { "isn't" }
+        ));
+    }
+    discrepancies
+}
+
+/// With `cross_check`, asks the alive peers for their view every `interval` and logs where it differs from this node's
+async fn cross_check<'c>(
+    config: &Config,
+    state: &State,
+    nodes: &HashMap<String, NodeConfig>,
+    client_for: impl Fn(&NodeConfig) -> &'c Client,
+    trace: &str,
+) {
+    let Some(cross_check) = config.cross_check.as_ref() else {
+        return;
+    };
+    let now = Utc::now();
+    let (mine, peers) = {
+        let mut gr = state.lock().expect("Failed to lock state");
+        if gr
+            .last_cross_check
+            .is_some_and(|last| (now - last).to_std().unwrap_or_default() < cross_check.interval)
+        {
+            return;
+        }
+        gr.last_cross_check = Some(now);
+        let peers = gr
+            .node_state
+            .iter()
+            .filter(|ns| ns.status() == GridNodeStatus::Alive && ns.supports(FEATURE_INTROSPECT))
+            .map(|ns| ns.name.clone())
+            .collect::<HashSet<_>>();
+        (gr.introspect(&config.name, now), peers)
+    };
+
+    let peers = nodes
+        .iter()
+        .filter(|(node_name, _)| peers.contains(*node_name));
+    let concurrency = config.http_client.max_concurrent_calls.max(1);
+    let results = fan_out(peers, concurrency, |node_name, node| {
+        let client = client_for(&node).clone();
+        let me = config.name.clone();
+        let key = config.secret_key.clone();
+        let trace = trace.to_string();
+        async move {
+            let api = peer_api(&client, &me, &trace, &node.with_name(&node_name), &key);
+            make_whatever_logged_http_call(&node_name, &trace, "introspect", api.introspect()).await
+        }
+    })
+    .await;
+
+    for (node_name, res) in results {
+        match res {
+            Ok(Some(theirs)) => {
+                let discrepancies = view_discrepancies(&mine, &theirs);
+                if discrepancies.is_empty() {
+                    debug!("`{node_name}` sees the grid as this node does");
+                } else {
+                    warn!(
+                        "`{node_name}` sees the grid differently: {}",
+                        discrepancies.join(", ")
+                    );
+                }
+            }
+            Ok(None) => {}
+            Err(err) => debug!("Failed to cross-check with `{node_name}`: {err}"),
+        }
+    }
+}
+
+/// Names in a peer's obituary spelled as `known`, a misconfigured peer may pad them or write them in another case
+fn canonical_obituary(orb: &mut ObituaryResponse, known: &[String]) {
+    let canonical = |name: &str| canonical_node_name(name, known.iter().map(String::as_str));
+    for dead in orb.dead_nodes.iter_mut() {
+        dead.name = canonical(&dead.name);
+        dead.rolls = std::mem::take(&mut dead.rolls)
+            .into_iter()
+            .map(|(name, roll)| (canonical(&name), roll))
+            .collect();
+    }
+    for announced in orb.announced_deaths.iter_mut() {
+        announced.name = canonical(&announced.name);
+        announced.announcer = canonical(&announced.announcer);
+    }
+    for dying in orb.dying_nodes.iter_mut() {
+        dying.name = canonical(&dying.name);
+    }
+    for verdict in orb.verdicts.iter_mut().flatten() {
+        verdict.name = canonical(&verdict.name);
+    }
+}
+
+/// The higher roll wins, the name breaks ties, as in the roll for the announcer
+fn outranks(claim: (&str, usize), other: (&str, usize)) -> bool {
+    (claim.1, claim.0) > (other.1, other.0)
+}
+
+/// Grid wide warnings are announced by the alive node with the lowest name, so they are only sent once
+fn is_warning_announcer(gr: &StateInner, config: &Config) -> bool {
+    gr.node_state
+        .iter()
+        .filter(|ns| !ns.is_dead())
+        .map(|ns| &ns.name)
+        .chain([&config.name])
+        .min()
+        == Some(&config.name)
+}
+
+/// Tracks the expiry of every certificate in the grid
+async fn check_certificates(
+    config: &Config,
+    nodes: &HashMap<String, NodeConfig>,
+    state: &State,
+    announcers: &Announcers,
+) {
+    let now = Utc::now();
+    let (due_nodes, local_due) = {
+        let gr = state.lock().expect("Failed to lock state");
+        let due_nodes = gr
+            .node_state
+            .iter()
+            .filter(|ns| ns.fail_count == 0 && ns.cert.needs_check(now, CERT_CHECK_INTERVAL))
+            .map(|ns| ns.name.clone())
+            .collect::<Vec<_>>();
+        (
+            due_nodes,
+            gr.local_cert.needs_check(now, CERT_CHECK_INTERVAL),
+        )
+    };
+
+    let mut results = vec![];
+    for name in due_nodes {
+        let Some(node) = nodes.get(&name) else {
+            continue;
+        };
+        match cert_check::peer_not_after(&node.address).await {
+            Ok(not_after) => results.push((name, not_after)),
+            Err(err) => warn!("Failed to check certificate of `{name}`: {err:?}"),
+        }
+    }
+
+    let local_result = match &config.server.ssl {
+        Some(ssl) if local_due => match tokio::fs::read(&ssl.cert_path).await {
+            Ok(pem) => cert_check::pem_not_after(&pem)
+                .inspect_err(|err| error!("Failed to parse own certificate: {err:?}"))
+                .ok(),
+            Err(err) => {
+                error!("Failed to read own certificate: {err:?}");
+                None
+            }
+        },
+        _ => None,
+    };
+
+    let threshold =
+        chrono::Duration::from_std(config.cert_expiry_warning).unwrap_or(chrono::Duration::MAX);
+    let warnings = {
+        let mut gr = state.lock().expect("Failed to lock state");
+        for (name, not_after) in results {
+            if let Some(ns) = gr.node_state.iter_mut().find(|ns| ns.name == name) {
+                ns.cert.not_after = not_after;
+                ns.cert.checked = Some(now);
+            }
+        }
+        if local_due {
+            gr.local_cert.not_after = local_result;
+            gr.local_cert.checked = Some(now);
+        }
+
+        let i_announce = is_warning_announcer(&gr, config);
+
+        let mut warnings = vec![];
+        let local_cert = &mut gr.local_cert;
+        if local_cert.needs_warning(now, threshold, WARNING_REPEAT_INTERVAL) {
+            warn!(
+                "Own certificate expires in {} days",
+                local_cert.days_remaining().unwrap_or_default()
+            );
+            local_cert.warned = Some(now);
+            if i_announce {
+                warnings.push((config.name.clone(), local_cert.days_remaining()));
+            }
+        }
+        for ns in gr.node_state.iter_mut() {
+            if ns
+                .cert
+                .needs_warning(now, threshold, WARNING_REPEAT_INTERVAL)
+            {
+                warn!(
+                    "Certificate of `{}` expires in {} days",
+                    ns.name,
+                    ns.cert.days_remaining().unwrap_or_default()
+                );
+                ns.cert.warned = Some(now);
+                if i_announce {
+                    warnings.push((ns.name.clone(), ns.cert.days_remaining()));
+                }
+            }
+        }
+        warnings
+    };
+
+    for (name, days) in warnings {
+        announcers
+            .announce(AnnouncementEvent::new(
+                config,
+                AnnouncementKind::Warning,
+                &name,
+                messages::render(
+                    config,
+                    "cert_expiry",
+                    &[
+                        ("node", config.display_name(&name)),
+                        ("days", &days.unwrap_or_default().to_string()),
+                    ],
+                ),
+            ))
+            .await;
+    }
+}
+
+/// Escalates the deaths this node announced which lasted past their `escalation.after`,
+/// silenced nodes aren't escalated
+async fn check_escalations(
+    config: &Config,
+    nodes: &HashMap<String, NodeConfig>,
+    state: &State,
+    announcers: &Announcers,
+) {
+    let now = Utc::now();
+    let due = {
+        let mut gr = state.lock().expect("Failed to lock state");
+        let silenced = gr
+            .silences
+            .iter()
+            .filter(|sl| !sl.cancelled && sl.silent_until > now)
+            .map(|sl| sl.node_name.clone())
+            .collect::<HashSet<_>>();
+
+        let mut due = vec![];
+        for ns in gr.node_state.iter_mut() {
+            if ns.escalation_due.is_none_or(|due| due > now) {
+                continue;
+            }
+            ns.escalation_due = None;
+            if silenced.contains(&ns.name) {
+                info!("Node `{}` is silenced, not escalating its death", ns.name);
+                continue;
+            }
+            due.push(ns.name.clone());
+        }
+        due
+    };
+
+    for name in due {
+        let Some(node) = nodes.get(&name) else {
+            warn!("Node `{name}` left the grid, not escalating its death");
+            continue;
+        };
+        let Some(escalation) = config.escalation.get(&node.severity) else {
+            continue;
+        };
+        warn!("Escalating the death of `{name}`");
+        announcers
+            .escalate(
+                &escalation.channels,
+                AnnouncementEvent::escalated(config, node.with_name(&name), escalation.after),
+            )
+            .await;
+    }
+}
+
+/// Warns about nodes, this one included, whose root filesystem is getting full
+async fn check_disk_usage(config: &Config, state: &State, announcers: &Announcers) {
+    let now = Utc::now();
+    let local = config
+        .report_system_metrics
+        .then(system_metrics::collect)
+        .and_then(|m| m.disk_used_percent());
+    let due = |warned: Option<DateTime<Utc>>| {
+        warned.is_none_or(|warned| now - warned >= WARNING_REPEAT_INTERVAL)
+    };
+
+    let warnings = {
+        let mut gr = state.lock().expect("Failed to lock state");
+        let i_announce = is_warning_announcer(&gr, config);
+        let mut warnings = vec![];
+
+        if let Some(used) = local
+            && used >= config.disk_usage_warning
+            && due(gr.local_disk_warned)
+        {
+            warn!("Own disk is {used:.0}% full");
+            gr.local_disk_warned = Some(now);
+            if i_announce {
+                warnings.push((config.name.clone(), used));
+            }
+        }
+        for ns in gr.node_state.iter_mut() {
+            let Some(used) = ns.system.as_ref().and_then(|m| m.disk_used_percent()) else {
+                continue;
+            };
+            if used >= config.disk_usage_warning && due(ns.disk_warned) {
+                warn!("Disk of `{}` is {used:.0}% full", ns.name);
+                ns.disk_warned = Some(now);
+                if i_announce {
+                    warnings.push((ns.name.clone(), used));
+                }
+            }
+        }
+        warnings
+    };
+
+    for (name, used) in warnings {
+        announcers
+            .announce(AnnouncementEvent::new(
+                config,
+                AnnouncementKind::Warning,
+                &name,
+                messages::render(
+                    config,
+                    "disk_full",
+                    &[
+                        ("node", config.display_name(&name)),
+                        ("used", &format!("{used:.0}")),
+                    ],
+                ),
+            ))
+            .await;
+    }
+}
+
+/// Flags nodes running another version than `expected_version`, announcing each once until it runs an expected one again
+async fn check_versions(config: &Config, state: &State, announcers: &Announcers) {
+    let mismatches = {
+        let mut gr = state.lock().expect("Failed to lock state");
+        let i_announce = config.announce_version_mismatch && is_warning_announcer(&gr, config);
+        let mut mismatches = vec![];
+        for ns in gr.node_state.iter_mut() {
+            let (Some(version), Some(expected)) =
+                (ns.version.as_ref(), config.expected_version(&ns.name))
+            else {
+                ns.version_mismatch = false;
+                continue;
+            };
+            let mismatch = config.version_mismatch(&ns.name, version);
+            if mismatch && !ns.version_mismatch {
+                warn!(
+                    "Node `{}` runs version `{version}`, expected `{expected}`",
+                    ns.name
+                );
+                if i_announce {
+                    mismatches.push((ns.name.clone(), version.clone(), expected.to_string()));
+                }
+            } else if !mismatch && ns.version_mismatch {
+                info!(
+                    "Node `{}` runs an expected version again, `{version}`",
+                    ns.name
+                );
+            }
+            ns.version_mismatch = mismatch;
+        }
+        mismatches
+    };
+
+    for (name, version, expected) in mismatches {
+        announcers
+            .announce(AnnouncementEvent::new(
+                config,
+                AnnouncementKind::Warning,
+                &name,
+                messages::render(
+                    config,
+                    "version_mismatch",
+                    &[
+                        ("node", config.display_name(&name)),
+                        ("version", &version),
+                        ("expected", &expected),
+                    ],
+                ),
+            ))
+            .await;
+    }
+}
+
+/// Calls this node's own status endpoint, announcing when it keeps failing while the process runs
+async fn check_listener(
+    config: &Config,
+    client: &Client,
+    url: &str,
+    state: &State,
+    announcers: &Announcers,
+) {
+    if !state.lock().expect("Failed to lock state").server_listening {
+        return;
+    }
+
+    let result = self_check::check(client, url, &config.name).await;
+    let failures = {
+        let mut gr = state.lock().expect("Failed to lock state");
+        let failures = match result {
+            Ok(()) => 0,
+            Err(_) => gr.self_check_failures.unwrap_or_default() + 1,
+        };
+        if failures == 0 && gr.self_check_ok() == Some(false) {
+            info!("Own listener at {url} answers again");
+        }
+        gr.self_check_failures = Some(failures);
+        failures
+    };
+
+    let Err(err) = result else {
+        return;
+    };
+    warn!("Self-check of {url} failed ({failures} in a row): {err:#}");
+    // announced once, when it starts counting as broken
+    if failures == SELF_CHECK_FAILURES {
+        error!(
+            "!!! Own listener at {url} is BROKEN, peers can't reach this node and will declare it dead: {err:#}"
+        );
+        announcers
+            .announce(AnnouncementEvent::new(
+                config,
+                AnnouncementKind::SelfCheck,
+                &config.name,
+                messages::render(
+                    config,
+                    "self_check",
+                    &[
+                        ("node", config.display_name(&config.name)),
+                        ("url", url),
+                        ("error", &format!("{err:#}")),
+                    ],
+                ),
+            ))
+            .await;
+    }
+}
+
+/// Sends the grid digest once its scheduled time has passed
+async fn check_digest(config: &Config, state: &State, announcers: &Announcers) {
+    let Some(digest_config) = config.digest.as_ref() else {
+        return;
+    };
+    let now = Utc::now();
+    let Some(scheduled) = digest::last_scheduled(digest_config, now) else {
+        error!("Invalid digest hour `{}`", digest_config.hour);
+        return;
+    };
+
+    let report = {
+        let mut gr = state.lock().expect("Failed to lock state");
+        if gr.last_digest >= scheduled {
+            return;
+        }
+        // every node moves on, so a later announcer doesn't send an old digest
+        gr.last_digest = now;
+        if !is_warning_announcer(&gr, config) {
+            return;
+        }
+
+        let alive = gr.node_state.iter().filter(|ns| !ns.is_dead()).count() + 1; // plus me
+        let total = gr.node_state.len() + 1;
+        digest::render(
+            config,
+            digest_config,
+            alive,
+            total,
+            gr.incidents.make_contiguous(),
+            now,
+        )
+    };
+
+    info!("Sending digest: {report}");
+    announcers
+        .announce(AnnouncementEvent::new(
+            config,
+            AnnouncementKind::Digest,
+            &config.name,
+            report,
+        ))
+        .await;
+}
+
+/// Peers are verified against the configured CAs, without any every certificate is accepted
+pub fn build_client(
+    config: &Config,
+    trust_anchors: &[Certificate],
+    reuse_connections: bool,
+) -> Result<Client> {
+    let tuning = &config.http_client;
+    let mut client = proxy::client_builder(config)?
+        .timeout(tuning.request_timeout)
+        .tcp_keepalive(tuning.tcp_keepalive);
+
+    for cert in trust_anchors {
+        client = client.add_root_certificate(cert.clone());
+    }
+    if let Some(timeout) = tuning.connect_timeout {
+        client = client.connect_timeout(timeout);
+    }
+    if !reuse_connections {
+        client = client.pool_max_idle_per_host(0);
+    } else {
+        if let Some(max_idle) = tuning.pool_max_idle_per_host {
+            client = client.pool_max_idle_per_host(max_idle);
+        }
+        if let Some(timeout) = tuning.pool_idle_timeout {
+            client = client.pool_idle_timeout(timeout);
+        }
+    }
+
+    Ok(client
+        .danger_accept_invalid_certs(trust_anchors.is_empty())
+        .build()?)
+}
+
+struct NodeResult {
+    error: Option<PollError>,
+    /// How the poll failed, for the death announcement
+    failure: Option<String>,
+    system: Option<SystemMetrics>,
+    /// The name the node answered with
+    reported_name: Option<String>,
+    advertised_address: Option<String>,
+    version: Option<String>,
+    features: Option<Vec<String>>,
+    /// How long the node took to answer
+    latency: Option<std::time::Duration>,
+}
+
+async fn check_internet_connection(client: &Client, url: &str) -> bool {
+    let Ok(resp) = client.get(url).send().await else {
+        return false;
+    };
+    resp.status() == reqwest::StatusCode::NO_CONTENT
+}
+
+/// Short random id of a poll cycle, sent along with its calls to the nodes
+fn new_trace_id() -> String {
+    format!("{:08x}", rand::rng().random::<u32>())
+}
+
+/// Names the exact build, so peers' logs tell which one called
+fn user_agent(me: &str) -> String {
+    format!("freecaster-grid/{VERSION}+{GIT_COMMIT}/{me}")
+}
+
+/// The API of `node`, called by `me` within the poll cycle `trace`
+pub fn peer_api(
+    client: &Client,
+    me: &str,
+    trace: &str,
+    node: &NamedNodeConfig<'_>,
+    key: &str,
+) -> FreecasterClient {
+    let mut headers = HeaderMap::new();
+    for (name, value) in [
+        (USER_AGENT.as_str(), user_agent(me)),
+        (NODE_HEADER, me.to_string()),
+        (TRACE_HEADER, trace.to_string()),
+    ] {
+        if let (Ok(name), Ok(value)) = (
+            HeaderName::from_bytes(name.as_bytes()),
+            HeaderValue::from_str(&value),
+        ) {
+            headers.insert(name, value);
+        }
+    }
+    FreecasterClient::with_client(client.clone(), &node.config.address, key)
+        .headers(headers)
+        .headers(node.config.header_map())
+}
+
+/// `None` when the node answered, but not with what the API promises
+async fn make_whatever_logged_http_call<T>(
+    node_name: &str,
+    trace: &str,
+    purpose: &str,
+    call: impl Future<Output = freecaster_grid_client::Result<T>>,
+) -> Result<Option<T>> {
+    debug!("Calling node `{node_name}` for `{purpose}`, trace `{trace}`");
+    match call
+        .instrument(debug_span!("peer_call", node = %node_name, purpose, trace))
+        .await
+    {
+        Ok(correct_response) => {
+            debug!("Node `{node_name}` returned a fine response for `{purpose}`");
+            Ok(Some(correct_response))
+        }
+        Err(ClientError::Decode(err)) => {
+            error!("Failed to parse response for `{purpose}`: {err:?}");
+            Ok(None)
+        }
+        Err(ClientError::Status(status)) => {
+            debug!("Node `{node_name}` returned error status: {status}");
+            Err(ClientError::Status(status).into())
+        }
+        Err(e) => {
+            debug!("Failed to connect to node {node_name}: {e:?}");
+            Err(e.into())
+        }
+    }
+}
+
+/// How a call failed, in a few words for the death announcement
+fn classify_failure(err: &anyhow::Error) -> String {
+    let err = match err.downcast_ref::<ClientError>() {
+        Some(ClientError::Request(err)) => err,
+        Some(ClientError::Status(status)) => return format!("HTTP {status}"),
+        _ => return "request failed".to_string(),
+    };
+    if err.is_timeout() {
+        return "timed out".to_string();
+    }
+    // reqwest only tells that connecting failed, its causes tell why
+    let causes = std::iter::successors(std::error::Error::source(err), |cause| cause.source())
+        .map(|cause| cause.to_string().to_lowercase())
+        .collect::<Vec<_>>()
+        .join(": ");
+    let failure = if causes.contains("dns error") {
+        "DNS lookup failed"
+    } else if causes.contains("connection refused") {
+        "connection refused"
+    } else if causes.contains("certificate") || causes.contains("tls") {
+        "TLS handshake failed"
+    } else if err.is_connect() {
+        "connection failed"
+    } else {
+        "connection broke"
+    };
+    failure.to_string()
 }
 
-async fn poll_node(client: &Client, me: &str, node: NamedNodeConfig<'_>) -> NodeResult {
+async fn poll_node(
+    client: &Client,
+    me: &str,
+    trace: &str,
+    node: NamedNodeConfig<'_>,
+    key: &str,
+    strict_name_check: bool,
+) -> NodeResult {
     let node_name = node.name.clone();
-    match make_whatever_logged_http_call::<StatusResponse>(client, me, node, "/", "poll status")
-        .await
-    {
+    let configured_address = node.config.address.clone();
+    let api = peer_api(client, me, trace, &node, key);
+    let started = std::time::Instant::now();
+    match make_whatever_logged_http_call(node.name, trace, "poll status", api.status()).await {
         Ok(Some(correct_response)) => {
-            info!(
+            debug!(
                 "Node `{}`@`{}` is up",
                 correct_response.name, correct_response.version
             );
+            if correct_response.degraded {
+                warn!(
+                    "Node `{}` reports its poller is stuck",
+                    correct_response.name
+                );
+            }
+            let mut error = None;
             if node_name != correct_response.name {
                 warn!(
                     "Node name mismatch: `{}` != `{}`",
                     node_name, correct_response.name
                 );
+                if strict_name_check {
+                    error = Some(PollError::IdentityMismatch {
+                        reported_name: correct_response.name.clone(),
+                    });
+                }
+            }
+
+            if let Some(advertised) = correct_response.advertised_address.as_ref()
+                && advertised.trim_end_matches('/') != configured_address.trim_end_matches('/')
+            {
+                warn!(
+                    "Node `{node_name}` advertises `{advertised}`, but is configured at `{configured_address}`"
+                );
             }
 
-            NodeResult { failing: false }
+            NodeResult {
+                failure: error.as_ref().map(ToString::to_string),
+                error,
+                system: correct_response.system,
+                reported_name: Some(correct_response.name),
+                advertised_address: correct_response.advertised_address,
+                version: Some(correct_response.version),
+                features: Some(correct_response.features),
+                latency: Some(started.elapsed()),
+            }
         }
         Ok(None) => {
             warn!("Node `{}` is up but weird", node_name);
 
-            NodeResult { failing: false }
+            NodeResult {
+                error: None,
+                failure: None,
+                system: None,
+                reported_name: None,
+                advertised_address: None,
+                version: None,
+                features: None,
+                latency: Some(started.elapsed()),
+            }
         }
-        Err(_) => NodeResult { failing: true },
+        Err(err) => NodeResult {
+            error: Some(PollError::Unreachable),
+            failure: Some(classify_failure(&err)),
+            system: None,
+            reported_name: None,
+            advertised_address: None,
+            version: None,
+            features: None,
+            latency: None,
+        },
     }
 }
 
 async fn call_obituary(
     client: &Client,
     me: &str,
+    trace: &str,
     node: NamedNodeConfig<'_>,
     key: &str,
-) -> Option<ObituaryResponse> {
-    make_whatever_logged_http_call::<ObituaryResponse>(
-        client,
-        me,
-        node,
-        &format!("/obituary/{key}"),
-        "obituary",
-    )
-    .await
-    .ok()
-    .flatten()
+    v2: bool,
+) -> Result<ObituaryResponse> {
+    let api = peer_api(client, me, trace, &node, key);
+    let obituary = if v2 {
+        make_whatever_logged_http_call(node.name, trace, "obituary", api.obituary_v2()).await?
+    } else {
+        make_whatever_logged_http_call(node.name, trace, "obituary", api.obituary()).await?
+    };
+    obituary.ok_or_else(|| anyhow::anyhow!("Invalid obituary response"))
+}
+
+/// Calls every node with `call`, at most `limit` at once, answers in no particular order
+async fn fan_out<'a, T, F, Fut>(
+    nodes: impl Iterator<Item = (&'a String, &'a NodeConfig)>,
+    limit: usize,
+    call: F,
+) -> Vec<(String, T)>
+where
+    F: Fn(String, NodeConfig) -> Fut,
+    Fut: Future<Output = T> + Send + 'static,
+    T: Send + 'static,
+{
+    let mut calls = JoinSet::new();
+    let mut results = vec![];
+    for (node_name, node) in nodes {
+        if calls.len() >= limit
+            && let Some(Ok(res)) = calls.join_next().await
+        {
+            results.push(res);
+        }
+        let node_name = node_name.clone();
+        let call = call(node_name.clone(), node.clone());
+        calls.spawn(async move { (node_name, call.await) }.in_current_span());
+    }
+    while let Some(res) = calls.join_next().await {
+        if let Ok(res) = res {
+            results.push(res);
+        }
+    }
+    results
 }
 
 async fn call_silence_broadcast(
     client: &Client,
     me: &str,
+    trace: &str,
     node: NamedNodeConfig<'_>,
     key: &str,
     silence: &NodeSilence,
 ) -> bool {
-    info!(
-        "Broadcasting silence {}: {}, to node `{}`",
+    debug!(
+        "Broadcasting silence {}: {}, to node `{}`, trace `{trace}`",
         silence.id, silence.silent_until, node.name
     );
-    let res = client
-        .post(format!("{}/silence-broadcast/{key}", node.config.address))
-        .json(&SilenceBroadcastRequest {
-            id: silence.id,
+    let res = peer_api(client, me, trace, &node, key)
+        .silence_broadcast(&SilenceBroadcastRequest {
+            id: silence.id.clone(),
             node_name: silence.node_name.clone(),
             silent_until: silence.silent_until,
+            origin: Some(me.to_string()),
+            revision: silence.revision,
+            cancelled: silence.cancelled,
         })
-        .header(
-            "User-Agent",
-            format!("freecaster-grid/{}/{}", env!("CARGO_PKG_VERSION"), me,),
+        .instrument(
+            debug_span!("peer_call", node = %node.name, purpose = "silence broadcast", trace),
         )
-        .timeout(Duration::from_secs(5))
-        .send()
         .await;
+    silence_broadcast_done(node.name, &silence.id, res)
+}
 
-    let Ok(res) = res else {
-        error!("Failed to connect to node {}: {:?}", node.name, res);
-        return false;
-    };
-
-    res.status().is_success()
+/// A peer knowing the id for another node never takes the silence, it counts as received so it's not sent again
+fn silence_broadcast_done(
+    node_name: &str,
+    id: &str,
+    res: freecaster_grid_client::Result<()>,
+) -> bool {
+    match res {
+        Err(ClientError::Status(reqwest::StatusCode::CONFLICT)) => {
+            warn!("Node `{node_name}` knows silence {id} for another node, not sending it again");
+            true
+        }
+        res => broadcast_done(node_name, res),
+    }
 }
 
-async fn announce_telegram(
+async fn call_mute_broadcast(
+    client: &Client,
     me: &str,
-    target: NamedNodeConfig<'_>,
-    config: &Arc<Config>,
-    is_dead: bool,
-) {
-    let end = if let Some(tg) = target.config.telegram_handle.as_ref() {
-        format!("- @{tg}")
-    } else {
-        "".to_string()
-    };
+    trace: &str,
+    node: NamedNodeConfig<'_>,
+    key: &str,
+    mute: &GridMute,
+) -> bool {
+    debug!(
+        "Broadcasting mute until {:?}, to node `{}`, trace `{trace}`",
+        mute.until, node.name
+    );
+    let res = peer_api(client, me, trace, &node, key)
+        .mute_broadcast(&MuteBroadcastRequest {
+            muted_until: mute.until,
+            changed_at: mute.changed_at,
+            origin: Some(me.to_string()),
+        })
+        .instrument(debug_span!("peer_call", node = %node.name, purpose = "mute broadcast", trace))
+        .await;
+    broadcast_done(node.name, res)
+}
 
-    let TelegramConfig { token, chat_id } = if let Some(telegram) = config.telegram.as_ref() {
-        telegram
-    } else {
-        error!("Telegram announcement requested but no telegram config");
-        return;
-    };
+/// Peers answering with an error status refused the broadcast, no need to log it
+fn broadcast_done(node_name: &str, res: freecaster_grid_client::Result<()>) -> bool {
+    match res {
+        Ok(()) => true,
+        Err(ClientError::Status(_)) => false,
+        Err(err) => {
+            error!("Failed to connect to node {node_name}: {err:?}");
+            false
+        }
+    }
+}
 
-    let res = telegram_notifyrs::send_message(
-        if is_dead {
-            format!(
-                "Grid announcement, `{}` has unfortunately died, announced by: `{me}`{end}",
-                target.name
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::test_config;
+    use crate::{DeadNodeResponse, NodeVerdictResponse};
+
+    fn dead_node() -> NodeState {
+        let mut ns = NodeState::new("hal9001".to_string());
+        ns.fail_count = ns.thresholds.dead_after;
+        ns.local_announcement_roll = Some(1);
+        ns
+    }
+
+    #[test]
+    fn dying_before_dead() {
+        let mut ns = NodeState::new("hal9001".to_string());
+        ns.thresholds = FailThresholds {
+            dying_after: 2,
+            dead_after: 4,
+        };
+        ns.last_poll = Some(Utc::now());
+        let statuses = (0..=4)
+            .map(|fail_count| {
+                ns.fail_count = fail_count;
+                ns.status()
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            statuses,
+            [
+                GridNodeStatus::Alive,
+                GridNodeStatus::Alive,
+                GridNodeStatus::Dying,
+                GridNodeStatus::Dying,
+                GridNodeStatus::Dead,
+            ]
+        );
+        ns.reset();
+        assert_eq!(ns.status(), GridNodeStatus::Alive);
+    }
+
+    #[test]
+    fn legacy_statuses() {
+        let mut ns = NodeState::new("hal9001".to_string());
+        ns.last_poll = Some(Utc::now());
+        let status = |ns: &NodeState, legacy| ns.to_api_response(legacy).status;
+
+        ns.fail_count = ns.thresholds.dying_after;
+        assert_eq!(status(&ns, false), GridNodeStatus::Dying);
+        assert_eq!(status(&ns, true), GridNodeStatus::Alive);
+
+        ns.fail_count = ns.thresholds.dead_after;
+        assert_eq!(status(&ns, false), GridNodeStatus::Dead);
+        assert_eq!(status(&ns, true), GridNodeStatus::Dying);
+        assert!(!ns.to_api_response(false).announced);
+
+        ns.announced = Some("hal9002".to_string());
+        assert_eq!(status(&ns, true), GridNodeStatus::Dead);
+        let response = ns.to_api_response(false);
+        assert!(response.announced);
+        assert_eq!(response.announced_by.as_deref(), Some("hal9002"));
+    }
+
+    #[test]
+    fn features_of_peers() {
+        let mut ns = NodeState::new("hal9001".to_string());
+        assert!(ns.supports(FEATURE_SILENCE_CANCEL));
+        // an older node answers without features
+        ns.features = Some(vec![]);
+        assert!(!ns.supports(FEATURE_SILENCE_CANCEL));
+        ns.features = Some(vec![FEATURE_SILENCE_CANCEL.to_string()]);
+        assert!(ns.supports(FEATURE_SILENCE_CANCEL));
+        assert!(!ns.supports(FEATURE_MUTE_BROADCAST));
+    }
+
+    #[test]
+    fn flapping_starts_and_stops() {
+        let config = test_config("flapping:\n  transitions: 3\n  window: 1h");
+        let flapping = config.flapping.as_ref();
+        let start = Utc::now();
+        let minutes = |m: i64| start + chrono::Duration::minutes(m);
+        let mut ns = NodeState::new("hal9001".to_string());
+
+        ns.transitions.extend([minutes(0), minutes(10)]);
+        assert_eq!(ns.update_flapping(flapping, minutes(10)), None);
+        ns.transitions.push_back(minutes(20));
+        assert_eq!(ns.update_flapping(flapping, minutes(20)), Some(true));
+        assert_eq!(ns.flapping_since, Some(minutes(20)));
+
+        // still flapping while any transition is within the window
+        assert_eq!(ns.update_flapping(flapping, minutes(75)), None);
+        assert_eq!(ns.transitions.len(), 1);
+        assert_eq!(ns.update_flapping(flapping, minutes(80)), Some(false));
+        assert!(ns.flapping_since.is_none());
+
+        ns.transitions
+            .extend([minutes(80), minutes(81), minutes(82)]);
+        assert_eq!(ns.update_flapping(flapping, minutes(82)), Some(true));
+        // turned off by a reload
+        assert_eq!(ns.update_flapping(None, minutes(83)), Some(false));
+        assert!(ns.transitions.is_empty());
+    }
+
+    #[test]
+    fn silence_over_alive_node() {
+        let config = test_config("");
+        let mut ns = NodeState::new("hal9001".to_string());
+        ns.silenced_death = true;
+
+        assert_eq!(silence_ended(&mut ns, &config), SilenceEnd::Nothing);
+        assert!(!ns.silenced_death);
+    }
+
+    #[test]
+    fn silence_over_unannounced_dead_node() {
+        let mut ns = dead_node();
+        ns.silenced_death = true;
+        assert_eq!(
+            silence_ended(&mut ns, &test_config("")),
+            SilenceEnd::Announce
+        );
+        assert!(!ns.silenced_death);
+        assert!(ns.announced.is_none());
+
+        let mut ns = dead_node();
+        assert_eq!(
+            silence_ended(&mut ns, &test_config("announce_after_silence: false")),
+            SilenceEnd::Quiet
+        );
+        assert!(ns.silenced_death);
+        assert!(ns.announced.is_none());
+    }
+
+    #[test]
+    fn silence_over_announced_dead_node() {
+        let mut ns = dead_node();
+        ns.announced = Some("hal9000".to_string());
+        assert_eq!(
+            silence_ended(&mut ns, &test_config("")),
+            SilenceEnd::Nothing
+        );
+
+        let config = test_config("remind_after_silence: true");
+        assert_eq!(silence_ended(&mut ns, &config), SilenceEnd::Remind);
+        assert_eq!(ns.announced.as_deref(), Some("hal9000"));
+
+        // only the node that announced the death reminds
+        ns.announced = Some("hal9002".to_string());
+        assert_eq!(silence_ended(&mut ns, &config), SilenceEnd::Nothing);
+    }
+
+    #[test]
+    fn quiet_death_is_forgotten_on_recovery() {
+        let mut ns = dead_node();
+        silence_ended(&mut ns, &test_config("announce_after_silence: false"));
+        assert!(ns.silenced_death);
+
+        ns.reset();
+        assert!(!ns.silenced_death);
+        assert!(!ns.is_dead());
+    }
+
+    #[test]
+    fn silence_arriving_before_the_announcement() {
+        let config = test_config("");
+        let nodes = grid_of(&["hal9001", "hal9002"]);
+        let state = confirmed_death(&config, &nodes);
+        let mut gr = state.lock().unwrap();
+        let now = Utc::now();
+
+        // broadcast after the node died, before its death was decided
+        gr.silences
+            .push(silence_of("hal9001", now + chrono::Duration::hours(1)));
+        assert!(
+            gr.decide_deaths(&config, &nodes, now)
+                .announcements
+                .is_empty()
+        );
+        assert!(node_mut(&mut gr, "hal9001").silenced_death);
+        assert!(node_mut(&mut gr, "hal9001").announced.is_none());
+        assert!(
+            gr.audit
+                .page(0, gr.audit.len())
+                .iter()
+                .any(|entry| matches!(
+                    &entry.action,
+                    AuditAction::DeathAnnouncementSuppressed { node_name } if node_name == "hal9001"
+                ))
+        );
+
+        // only the peer has it silenced, its obituary tells
+        gr.silences.clear();
+        let ns = node_mut(&mut gr, "hal9001");
+        ns.silenced_death = false;
+        ns.confirmations.insert(
+            "hal9002".to_string(),
+            DeadConfirmation {
+                silenced: true,
+                abstained: true,
+                ..confirmed(None)
+            },
+        );
+        assert!(
+            gr.decide_deaths(&config, &nodes, now)
+                .announcements
+                .is_empty()
+        );
+        assert!(node_mut(&mut gr, "hal9001").silenced_death);
+    }
+
+    #[test]
+    fn announced_once_the_silence_is_over() {
+        let nodes = grid_of(&["hal9001", "hal9002"]);
+        for announce_after_silence in [true, false] {
+            let config = test_config(&format!("announce_after_silence: {announce_after_silence}"));
+            let state = confirmed_death(&config, &nodes);
+            let mut gr = state.lock().unwrap();
+            let now = Utc::now();
+            gr.silences
+                .push(silence_of("hal9001", now + chrono::Duration::minutes(1)));
+            assert!(
+                gr.decide_deaths(&config, &nodes, now)
+                    .announcements
+                    .is_empty()
+            );
+
+            // expired by the next cycle, the node still dead
+            gr.silences.clear();
+            let end = silence_ended(node_mut(&mut gr, "hal9001"), &config);
+            let later = now + chrono::Duration::minutes(2);
+            let decided = gr.decide_deaths(&config, &nodes, later);
+            if announce_after_silence {
+                assert_eq!(end, SilenceEnd::Announce);
+                assert_eq!(decided.announcements.len(), 1);
+                assert_eq!(
+                    node_mut(&mut gr, "hal9001").announced.as_deref(),
+                    Some("hal9000")
+                );
+            } else {
+                assert_eq!(end, SilenceEnd::Quiet);
+                assert!(decided.announcements.is_empty());
+                assert!(node_mut(&mut gr, "hal9001").announced.is_none());
+            }
+        }
+    }
+
+    #[test]
+    fn too_few_peers_reachable() {
+        let config = test_config("min_peers_reachable: 2");
+        let nodes = grid_of(&["hal9001", "hal9002", "hal9003"]);
+        let state = confirmed_death(&config, &nodes);
+        let now = Utc::now();
+        {
+            let mut gr = state.lock().unwrap();
+            gr.check_participation(&config, 1);
+            assert!(
+                gr.decide_deaths(&config, &nodes, now)
+                    .announcements
+                    .is_empty()
+            );
+            assert!(node_mut(&mut gr, "hal9001").announced.is_none());
+        }
+
+        let grid = crate::grid_response(&config, &state);
+        assert!(grid.announcements_suppressed);
+        assert_eq!(
+            grid.announcements_suppressed_reason.as_deref(),
+            Some("only 1 of the required 2 peers reachable")
+        );
+
+        // the held back death is decided again once enough peers answer
+        let mut gr = state.lock().unwrap();
+        gr.check_participation(&config, 2);
+        assert!(gr.announcements_suppressed.is_none());
+        assert_eq!(
+            gr.decide_deaths(&config, &nodes, now).announcements.len(),
+            1
+        );
+        assert_eq!(
+            node_mut(&mut gr, "hal9001").announced.as_deref(),
+            Some("hal9000")
+        );
+        drop(gr);
+        assert!(!crate::grid_response(&config, &state).announcements_suppressed);
+    }
+
+    #[test]
+    fn announcement_shown_for_dead_nodes_only() {
+        let mut ns = dead_node();
+        ns.announced = Some("hal9000".to_string());
+        ns.announced_at = Some(Utc::now());
+        ns.announcement_roll = Some(42);
+        let resp = ns.to_api_response(false);
+        assert_eq!(resp.announced_by.as_deref(), Some("hal9000"));
+        assert_eq!(resp.announcement_roll, Some(42));
+
+        assert!(resp.announced);
+        assert_eq!(resp.status, GridNodeStatus::Dead);
+
+        ns.announced = None;
+        assert_eq!(ns.to_api_response(false).status, GridNodeStatus::Dead);
+        assert_eq!(ns.to_api_response(true).status, GridNodeStatus::Dying);
+
+        ns.reset();
+        ns.fail_count = ns.thresholds.dying_after;
+        ns.last_poll = Some(Utc::now());
+        assert_eq!(ns.to_api_response(false).status, GridNodeStatus::Dying);
+        assert_eq!(ns.to_api_response(true).status, GridNodeStatus::Alive);
+
+        ns.reset();
+        assert_eq!(ns.announcement_roll, None);
+        let resp = ns.to_api_response(false);
+        assert_eq!(resp.announced_by, None);
+        assert_eq!(resp.announced_at, None);
+    }
+
+    fn grid_of(names: &[&str]) -> HashMap<String, NodeConfig> {
+        if names.is_empty() {
+            return HashMap::new();
+        }
+        let nodes = names
+            .iter()
+            .map(|name| format!("  {name}:\n    address: http://{name}:3037\n"))
+            .collect::<String>();
+        test_config(&format!("nodes:\n{nodes}")).nodes
+    }
+
+    fn confirmed(roll: Option<usize>) -> DeadConfirmation {
+        DeadConfirmation {
+            confirmed_roll: roll,
+            silenced: false,
+            unreachable: false,
+            dying: false,
+            abstained: false,
+        }
+    }
+
+    /// `hal9001` dead and confirmed by `hal9002`, this node winning the roll
+    fn confirmed_death(config: &Config, nodes: &HashMap<String, NodeConfig>) -> State {
+        let state = State::new(config);
+        let mut gr = state.lock().unwrap();
+        gr.reconcile_nodes(nodes, Utc::now());
+        let ns = node_mut(&mut gr, "hal9001");
+        *ns = dead_node();
+        ns.confirmations
+            .insert("hal9002".to_string(), confirmed(Some(0)));
+        drop(gr);
+        state
+    }
+
+    fn silence_of(node_name: &str, silent_until: DateTime<Utc>) -> NodeSilence {
+        NodeSilence {
+            id: "7".to_string(),
+            node_name: node_name.to_string(),
+            silent_until,
+            broadcasted: true,
+            revision: 0,
+            cancelled: false,
+            local: false,
+            received_by: BTreeSet::new(),
+        }
+    }
+
+    fn node_mut<'a>(gr: &'a mut StateInner, name: &str) -> &'a mut NodeState {
+        gr.node_state.iter_mut().find(|ns| ns.name == name).unwrap()
+    }
+
+    #[test]
+    fn removed_while_dying_or_dead() {
+        let config = test_config("");
+        let state = State::new(&config);
+        let mut gr = state.lock().unwrap();
+        let now = Utc::now();
+        gr.reconcile_nodes(&grid_of(&["hal9001", "hal9002"]), now);
+
+        // dying, then dead with an open incident
+        gr.node_state[0].fail_count = 1;
+        gr.node_state[1] = dead_node();
+        gr.node_state[1].name = "hal9002".to_string();
+        gr.incidents.push_back(Incident {
+            node_name: "hal9002".to_string(),
+            started: now,
+            ended: None,
+            announced_by: None,
+            silenced: false,
+            trace: None,
+        });
+
+        gr.reconcile_nodes(&grid_of(&[]), now);
+        assert!(gr.node_state.is_empty());
+        assert_eq!(gr.incidents[0].ended, Some(now));
+    }
+
+    #[test]
+    fn removed_after_its_death_was_announced() {
+        let config = test_config("");
+        let state = State::new(&config);
+        let mut gr = state.lock().unwrap();
+        let now = Utc::now();
+        gr.reconcile_nodes(&grid_of(&["hal9001"]), now);
+
+        let ns = &mut gr.node_state[0];
+        *ns = dead_node();
+        ns.announced = Some("hal9000".to_string());
+        ns.announced_at = Some(now);
+        ns.escalation_due = Some(now);
+
+        gr.reconcile_nodes(&grid_of(&["hal9002"]), now);
+        assert_eq!(gr.node_state.len(), 1);
+        // a new node of the same name starts over
+        gr.reconcile_nodes(&grid_of(&["hal9001"]), now);
+        let ns = gr
+            .node_state
+            .iter()
+            .find(|ns| ns.name == "hal9001")
+            .unwrap();
+        assert!(!ns.is_dead());
+        assert_eq!(ns.escalation_due, None);
+    }
+
+    #[test]
+    fn removed_peer_loses_its_votes() {
+        let config = test_config("");
+        let state = State::new(&config);
+        let mut gr = state.lock().unwrap();
+        let now = Utc::now();
+        gr.reconcile_nodes(&grid_of(&["hal9001", "hal9002", "hal9003"]), now);
+
+        let ns = gr
+            .node_state
+            .iter_mut()
+            .find(|ns| ns.name == "hal9001")
+            .unwrap();
+        let name = ns.name.clone();
+        *ns = dead_node();
+        ns.name = name;
+        ns.confirmations
+            .insert("hal9002".to_string(), confirmed(Some(7)));
+        ns.confirmations
+            .insert("hal9003".to_string(), confirmed(None));
+
+        gr.reconcile_nodes(&grid_of(&["hal9001", "hal9003"]), now);
+        let ns = gr
+            .node_state
+            .iter()
+            .find(|ns| ns.name == "hal9001")
+            .unwrap();
+        assert!(ns.is_dead());
+        assert_eq!(ns.confirmations.keys().collect::<Vec<_>>(), vec!["hal9003"]);
+    }
+
+    #[test]
+    fn dead_peers_answering_obituaries_vote() {
+        // `hal9002` only fails its status, `hal9003` is down
+        let dead_peers = || {
+            let state = State::new(&test_config(""));
+            {
+                let mut gr = state.lock().unwrap();
+                gr.reconcile_nodes(&grid_of(&["hal9002", "hal9003"]), Utc::now());
+                for ns in gr.node_state.iter_mut() {
+                    let name = ns.name.clone();
+                    *ns = dead_node();
+                    ns.name = name;
+                }
+            }
+            state
+        };
+        let votes = |gr: &StateInner, name: &str| {
+            gr.node_state
+                .iter()
+                .find(|ns| ns.name == name)
+                .unwrap()
+                .death_votes(false)
+        };
+
+        // skipping dead peers, both deaths rest on this node alone
+        let state = dead_peers();
+        let mut gr = state.lock().unwrap();
+        gr.record_obituaries("hal9000", HashMap::new(), HashMap::new());
+        assert_eq!(votes(&gr, "hal9002"), (1, 0));
+        assert_eq!(votes(&gr, "hal9003"), (1, 0));
+
+        // asked, `hal9002` answers and confirms `hal9003`, which doesn't answer
+        let state = dead_peers();
+        let mut gr = state.lock().unwrap();
+        let obituary = ObituaryResponse {
+            dead_nodes: vec![DeadNodeResponse {
+                name: "hal9003".to_string(),
+                roll: 7,
+                silenced: false,
+                rolls: Default::default(),
+            }],
+            announced_deaths: vec![],
+            dying_nodes: vec![],
+            verdicts: None,
+        };
+        gr.record_obituaries(
+            "hal9000",
+            HashMap::from([("hal9002".to_string(), obituary)]),
+            HashMap::from([("hal9003".to_string(), "No answer within 3s".to_string())]),
+        );
+        // no quorum for the death of a node answering
+        assert_eq!(votes(&gr, "hal9002"), (1, 1));
+        assert_eq!(votes(&gr, "hal9003"), (2, 0));
+        let ns = gr
+            .node_state
+            .iter()
+            .find(|ns| ns.name == "hal9003")
+            .unwrap();
+        assert!(ns.confirmations["hal9003"].unreachable);
+    }
+
+    #[test]
+    fn quorum_ignores_stale_confirmers() {
+        let mut ns = dead_node();
+        for peer in ["hal9002", "hal9003", "hal9004"] {
+            ns.confirmations
+                .insert(peer.to_string(), confirmed(Some(7)));
+        }
+        ns.confirmations
+            .insert("hal9005".to_string(), confirmed(None));
+        assert_eq!(ns.death_votes(false), (4, 1));
+
+        // only hal9005 is left of the peers
+        let nodes = grid_of(&["hal9001", "hal9005"]);
+        assert_eq!(ns.forget_strangers(&nodes), 3);
+        assert_eq!(ns.death_votes(false), (1, 1));
+        assert_eq!(ns.forget_strangers(&nodes), 0);
+    }
+
+    #[test]
+    fn announced_deaths_saved() {
+        let config = test_config("");
+        let state = State::new(&config);
+        let now = Utc::now();
+        let restored = AnnouncedIncident {
+            node_name: "hal9002".to_string(),
+            started: now - chrono::Duration::hours(2),
+            announcer: "hal9003".to_string(),
+            announced_at: now - chrono::Duration::hours(1),
+        };
+        state.restore_announced(vec![restored.clone()]);
+
+        let mut gr = state.lock().unwrap();
+        gr.reconcile_nodes(&grid_of(&["hal9001", "hal9002"]), now);
+        let ns = gr
+            .node_state
+            .iter_mut()
+            .find(|ns| ns.name == "hal9001")
+            .unwrap();
+        ns.fail_count = ns.thresholds.dead_after;
+        ns.announced = Some("hal9000".to_string());
+        ns.announced_at = Some(now);
+
+        let day = std::time::Duration::from_secs(24 * 60 * 60);
+        let announced = gr.announced_incidents(day, now);
+        assert_eq!(announced.len(), 2);
+        assert_eq!(announced[0].node_name, "hal9001");
+        assert_eq!(announced[0].started, now);
+        assert_eq!(announced[1], restored);
+        // hal9002's announcement is too old
+        assert_eq!(
+            gr.announced_incidents(day, now + chrono::Duration::hours(24))
+                .len(),
+            1
+        );
+    }
+
+    #[test]
+    fn relayed_rolls_yield_to_direct_ones() {
+        let mut ns = dead_node();
+        ns.local_announcement_roll = Some(5);
+
+        // hal9003 is out of reach, hal9002 knows its roll
+        ns.confirmations
+            .insert("hal9002".to_string(), confirmed(Some(3)));
+        ns.confirmations.insert(
+            "hal9003".to_string(),
+            DeadConfirmation {
+                unreachable: true,
+                ..confirmed(None)
+            },
+        );
+        let relayed = BTreeMap::from([
+            ("hal9000".to_string(), 5),
+            ("hal9002".to_string(), 1),
+            ("hal9003".to_string(), 9),
+        ]);
+        ns.merge_relayed_rolls("hal9000", "hal9002", relayed.clone());
+        assert_eq!(
+            ns.known_rolls(),
+            BTreeMap::from([("hal9002".to_string(), 3), ("hal9003".to_string(), 9)])
+        );
+        assert_eq!(
+            ns.announcement_winner("hal9000", 5),
+            ("hal9003".to_string(), 9)
+        );
+
+        // hal9003 restarted with a new roll, hal9002 still relays the old one
+        ns.confirmations
+            .insert("hal9003".to_string(), confirmed(Some(2)));
+        ns.merge_relayed_rolls("hal9000", "hal9002", relayed);
+        assert_eq!(ns.known_rolls()["hal9003"], 2);
+        assert_eq!(
+            ns.announcement_winner("hal9000", 5),
+            ("hal9000".to_string(), 5)
+        );
+
+        let nodes = grid_of(&["hal9000", "hal9002"]);
+        ns.forget_strangers(&nodes);
+        assert!(ns.relayed_rolls.is_empty());
+    }
+
+    #[tokio::test]
+    async fn fan_out_bounded() {
+        let nodes = grid_of(&["hal9001", "hal9002", "hal9003"]);
+        let running = Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        let mut results = fan_out(nodes.iter(), 2, |node_name, _| {
+            let running = running.clone();
+            async move {
+                let at_once = running.fetch_add(1, std::sync::atomic::Ordering::SeqCst) + 1;
+                tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+                running.fetch_sub(1, std::sync::atomic::Ordering::SeqCst);
+                (node_name, at_once)
+            }
+        })
+        .await;
+        results.sort();
+        assert_eq!(results.len(), 3);
+        assert!(
+            results
+                .iter()
+                .all(|(name, (node_name, at_once))| name == node_name && *at_once <= 2)
+        );
+    }
+
+    #[tokio::test]
+    async fn failures_are_classified() {
+        // nothing listens on a port just freed
+        let port = std::net::TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port();
+        let refused = Client::new()
+            .get(format!("http://127.0.0.1:{port}/"))
+            .send()
+            .await
+            .unwrap_err();
+        assert_eq!(
+            classify_failure(&ClientError::Request(refused).into()),
+            "connection refused"
+        );
+        assert_eq!(
+            classify_failure(&ClientError::Status(reqwest::StatusCode::BAD_GATEWAY).into()),
+            "HTTP 502 Bad Gateway"
+        );
+        assert_eq!(
+            classify_failure(&anyhow::anyhow!("no answer")),
+            "request failed"
+        );
+    }
+
+    #[test]
+    fn only_changes_bump_the_revision() {
+        let config = test_config("");
+        let state = State::new(&config);
+        let mut gr = state.lock().unwrap();
+        let now = Utc::now();
+        let later = |secs| now + chrono::Duration::seconds(secs);
+        gr.reconcile_nodes(&grid_of(&["hal9001", "hal9002"]), now);
+        assert!(gr.refresh_changes(now));
+        let revision = gr.revision;
+
+        // polled and answering, a poll which changes nothing
+        gr.node_state[0].last_poll = Some(later(1));
+        assert!(gr.refresh_changes(later(1)));
+        gr.node_state[0].last_poll = Some(later(2));
+        gr.node_state[0].last_ok = Some(later(2));
+        assert!(!gr.refresh_changes(later(2)));
+        assert_eq!(gr.revision, revision + 1);
+        assert_eq!(gr.node_state[0].last_changed, later(1));
+
+        gr.node_state[0].fail_count = 1;
+        assert!(gr.refresh_changes(later(3)));
+        gr.node_state[0].fail_count = config.dead_after;
+        gr.node_state[0].announced = Some("hal9000".to_string());
+        assert!(gr.refresh_changes(later(4)));
+        assert!(!gr.refresh_changes(later(5)));
+        assert_eq!(gr.node_state[0].last_changed, later(4));
+
+        gr.silences.push(NodeSilence {
+            id: "1".to_string(),
+            node_name: "hal9002".to_string(),
+            silent_until: later(10),
+            broadcasted: false,
+            revision: 0,
+            cancelled: false,
+            local: false,
+            received_by: BTreeSet::new(),
+        });
+        assert!(gr.refresh_changes(later(6)));
+        assert!(!gr.refresh_changes(later(7)));
+        // the silence ran out
+        assert!(gr.refresh_changes(later(11)));
+
+        let revision = gr.revision;
+        gr.reconcile_nodes(&grid_of(&["hal9001"]), later(12));
+        assert!(gr.refresh_changes(later(12)));
+        assert_eq!(gr.revision, revision + 1);
+    }
+
+    #[test]
+    fn cycle_average() {
+        let config = test_config("");
+        let state = State::new(&config);
+        assert!(
+            state
+                .lock()
+                .unwrap()
+                .cycle_response(DEFAULT_POLL_INTERVAL)
+                .is_none()
+        );
+
+        for total in 0..=CYCLE_HISTORY {
+            state.cycle_completed(CycleTimings {
+                polling: 1.0,
+                total: total as f64,
+                ..Default::default()
+            });
+        }
+        let cycle = state
+            .lock()
+            .unwrap()
+            .cycle_response(DEFAULT_POLL_INTERVAL)
+            .unwrap();
+        assert_eq!(cycle.cycles, CYCLE_HISTORY);
+        assert_eq!(cycle.latest.total, CYCLE_HISTORY as f64);
+        assert!((cycle.average.polling - 1.0).abs() < 1e-9);
+        // the first cycle fell out
+        assert!((cycle.average.total - 10.5).abs() < 1e-9);
+        assert_eq!(cycle.poll_time, 10.0);
+    }
+
+    #[test]
+    fn unreachable_peers_vote_neither_way() {
+        let mut ns = dead_node();
+        ns.confirmations
+            .insert("hal9002".to_string(), confirmed(Some(7)));
+        ns.confirmations
+            .insert("hal9003".to_string(), confirmed(None));
+        ns.confirmations.insert(
+            "hal9004".to_string(),
+            DeadConfirmation {
+                unreachable: true,
+                ..confirmed(None)
+            },
+        );
+        assert_eq!(ns.death_votes(false), (2, 1));
+    }
+
+    #[test]
+    fn divergent_winners_settle_by_claims() {
+        // `hal9000` couldn't ask `hal9001` for its obituary, so both find themselves the winner
+        let decided = |me: &str, my_roll: usize, confirmations: &[(&str, DeadConfirmation)]| {
+            let state = State::new(&test_config(""));
+            {
+                let mut gr = state.lock().unwrap();
+                gr.reconcile_nodes(
+                    &grid_of(&["hal9000", "hal9001", "hal9002", "hal9003"]),
+                    Utc::now(),
+                );
+                let ns = gr
+                    .node_state
+                    .iter_mut()
+                    .find(|ns| ns.name == "hal9003")
+                    .unwrap();
+                *ns = dead_node();
+                ns.name = "hal9003".to_string();
+                for (peer, confirmation) in confirmations {
+                    ns.confirmations
+                        .insert(peer.to_string(), confirmation.clone());
+                }
+                let (winner, _) = ns.choose_announcer(me, my_roll, None);
+                assert_eq!(winner.0, me);
+                ns.claim = Some(winner.clone());
+                ns.announced = Some(winner.0);
+            }
+            state
+        };
+        let a = decided(
+            "hal9000",
+            5,
+            &[
+                (
+                    "hal9001",
+                    DeadConfirmation {
+                        unreachable: true,
+                        ..confirmed(None)
+                    },
+                ),
+                ("hal9002", confirmed(Some(3))),
+            ],
+        );
+        let b = decided(
+            "hal9001",
+            7,
+            &[
+                ("hal9000", confirmed(Some(5))),
+                ("hal9002", confirmed(Some(3))),
+            ],
+        );
+        let claim = |announcer: &str, roll| AnnouncementClaimRequest {
+            node: "hal9003".to_string(),
+            announcer: announcer.to_string(),
+            roll,
+        };
+        let now = Utc::now();
+        let claimed = ["hal9003".to_string()];
+
+        let a_at_b = b.lock().unwrap().record_claim(&claim("hal9000", 5), now);
+        assert!(!a_at_b.accepted);
+        assert_eq!(a_at_b.claimed_by.as_deref(), Some("hal9001"));
+        let b_at_a = a.lock().unwrap().record_claim(&claim("hal9001", 7), now);
+        assert!(b_at_a.accepted);
+
+        // `hal9000` backs off, the stronger roll announces
+        let mut gr = a.lock().unwrap();
+        let lost = gr.resolve_claims("hal9000", &claimed, [("hal9003".to_string(), a_at_b)]);
+        assert_eq!(lost, HashSet::from(["hal9003".to_string()]));
+        let ns = gr
+            .node_state
+            .iter()
+            .find(|ns| ns.name == "hal9003")
+            .unwrap();
+        assert_eq!(ns.announced.as_deref(), Some("hal9001"));
+        assert_eq!(ns.escalation_due, None);
+        drop(gr);
+        let lost = b.lock().unwrap().resolve_claims(
+            "hal9001",
+            &claimed,
+            [("hal9003".to_string(), b_at_a)],
+        );
+        assert!(lost.is_empty());
+
+        // unanswered claims leave it to the roll, as before
+        let c = decided("hal9000", 5, &[("hal9002", confirmed(Some(3)))]);
+        assert!(
+            c.lock()
+                .unwrap()
+                .resolve_claims("hal9000", &claimed, [])
+                .is_empty()
+        );
+    }
+
+    #[test]
+    fn claims_are_not_announcements() {
+        let state = State::new(&test_config(""));
+        let mut gr = state.lock().unwrap();
+        gr.reconcile_nodes(&grid_of(&["hal9003"]), Utc::now());
+        gr.node_state[0] = dead_node();
+        gr.node_state[0].name = "hal9003".to_string();
+        let now = Utc::now();
+        let answer = gr.record_claim(
+            &AnnouncementClaimRequest {
+                node: "hal9003".to_string(),
+                announcer: "hal9001".to_string(),
+                roll: 7,
+            },
+            now,
+        );
+        assert!(answer.accepted);
+        let expiry = chrono::Duration::seconds(40);
+        let ns = &mut gr.node_state[0];
+        assert_eq!(ns.announced, None);
+
+        // held back while the claim is fresh, a weaker roll here doesn't take over
+        let winner = ("hal9000".to_string(), 5);
+        assert_eq!(
+            ns.pending_claim(
+                "hal9000",
+                &winner,
+                now + chrono::Duration::seconds(20),
+                expiry
+            ),
+            Some(("hal9001".to_string(), 7))
+        );
+        assert_eq!(
+            ns.pending_claim("hal9000", &("hal9000".to_string(), 9), now, expiry),
+            None
+        );
+        // the claimer never announced, rolled again
+        assert_eq!(
+            ns.pending_claim("hal9000", &winner, now + expiry, expiry),
+            None
+        );
+        assert_eq!(ns.claim, None);
+
+        // known announced only once an obituary tells it
+        gr.node_state[0].claim = Some(("hal9001".to_string(), 7));
+        let obituary = ObituaryResponse {
+            dead_nodes: vec![],
+            announced_deaths: vec![AnnouncedDeathResponse {
+                name: "hal9003".to_string(),
+                announcer: "hal9001".to_string(),
+                announced_at: now,
+            }],
+            dying_nodes: vec![],
+            verdicts: None,
+        };
+        gr.record_obituaries(
+            "hal9000",
+            HashMap::from([("hal9001".to_string(), obituary)]),
+            HashMap::new(),
+        );
+        assert_eq!(gr.node_state[0].announced.as_deref(), Some("hal9001"));
+    }
+
+    #[test]
+    fn unknown_verdicts_abstain() {
+        let state = State::new(&test_config(""));
+        let mut gr = state.lock().unwrap();
+        gr.reconcile_nodes(
+            &grid_of(&["hal9001", "hal9002", "hal9003", "hal9004"]),
+            Utc::now(),
+        );
+        for ns in gr.node_state.iter_mut() {
+            if ns.name == "hal9004" {
+                let name = ns.name.clone();
+                *ns = dead_node();
+                ns.name = name;
+            }
+        }
+        let verdict = |verdict| {
+            Some(vec![NodeVerdictResponse {
+                name: "hal9004".to_string(),
+                verdict,
+                fail_count: 0,
+                last_ok: None,
+            }])
+        };
+        let obituary = |verdicts| ObituaryResponse {
+            dead_nodes: vec![],
+            announced_deaths: vec![],
+            dying_nodes: vec![],
+            verdicts,
+        };
+        gr.record_obituaries(
+            "hal9000",
+            HashMap::from([
+                // an older peer, not confirming is an alive vote
+                ("hal9001".to_string(), obituary(None)),
+                (
+                    "hal9002".to_string(),
+                    obituary(verdict(NodeVerdict::Unknown)),
+                ),
+                // doesn't know of the node yet
+                ("hal9003".to_string(), obituary(Some(vec![]))),
+            ]),
+            HashMap::new(),
+        );
+        let ns = gr
+            .node_state
+            .iter()
+            .find(|ns| ns.name == "hal9004")
+            .unwrap();
+        assert!(ns.confirmations["hal9002"].abstained);
+        assert!(ns.confirmations["hal9003"].abstained);
+        assert_eq!(ns.death_votes(false), (1, 1));
+
+        gr.record_obituaries(
+            "hal9000",
+            HashMap::from([("hal9002".to_string(), obituary(verdict(NodeVerdict::Alive)))]),
+            HashMap::new(),
+        );
+        let ns = gr
+            .node_state
+            .iter()
+            .find(|ns| ns.name == "hal9004")
+            .unwrap();
+        assert_eq!(ns.death_votes(false), (1, 2));
+    }
+
+    #[test]
+    fn cross_checked_views() {
+        let state = State::new(&test_config(""));
+        let mut gr = state.lock().unwrap();
+        let now = Utc::now();
+        gr.node_state = vec![
+            NodeState::new("hal9001".to_string()),
+            NodeState {
+                name: "hal9002".to_string(),
+                ..dead_node()
+            },
+            NodeState::new("hal9003".to_string()),
+        ];
+        gr.node_state[0].last_poll = Some(now);
+        gr.silences.push(NodeSilence {
+            id: "1".to_string(),
+            node_name: "hal9002".to_string(),
+            silent_until: now + chrono::Duration::hours(1),
+            broadcasted: true,
+            revision: 0,
+            cancelled: false,
+            local: false,
+            received_by: BTreeSet::new(),
+        });
+        let mine = gr.introspect("hal9000", now);
+        assert_eq!(
+            mine.statuses,
+            BTreeMap::from([
+                ("hal9001".to_string(), 0),
+                ("hal9002".to_string(), 2),
+                ("hal9003".to_string(), 3),
+            ])
+        );
+        assert_eq!(mine.silences, ["1"]);
+        assert!(view_discrepancies(&mine, &mine.clone()).is_empty());
+
+        let theirs = IntrospectResponse {
+            name: "hal9001".to_string(),
+            statuses: BTreeMap::from([
+                ("hal9000".to_string(), GridNodeStatus::Dying.code()),
+                ("hal9002".to_string(), GridNodeStatus::Alive.code()),
+                // not polled here yet
+                ("hal9003".to_string(), GridNodeStatus::Alive.code()),
+            ]),
+            silences: vec![],
+            ..mine.clone()
+        };
+        assert_eq!(
+            view_discrepancies(&mine, &theirs),
+            [
+                "it sees `hal9002` alive, this node dead",
+                "it sees this node dying",
+                "it lacks silences [\"1\"]",
+            ]
+        );
+    }
+
+    #[test]
+    fn messy_names_in_obituaries() {
+        let state = State::new(&test_config(""));
+        let mut gr = state.lock().unwrap();
+        gr.node_state = vec![
+            NodeState::new("hal9001".to_string()),
+            NodeState {
+                name: "Node-1".to_string(),
+                ..dead_node()
+            },
+        ];
+        gr.record_obituaries(
+            "hal9000",
+            HashMap::from([(
+                "hal9001".to_string(),
+                ObituaryResponse {
+                    dead_nodes: vec![DeadNodeResponse {
+                        name: "node-1 ".to_string(),
+                        roll: 7,
+                        silenced: false,
+                        rolls: BTreeMap::from([(" HAL9000".to_string(), 3)]),
+                    }],
+                    announced_deaths: vec![],
+                    dying_nodes: vec![],
+                    verdicts: None,
+                },
+            )]),
+            HashMap::new(),
+        );
+        let ns = &gr.node_state[1];
+        assert_eq!(ns.confirmations["hal9001"].confirmed_roll, Some(7));
+        assert_eq!(ns.death_votes(false), (2, 0));
+    }
+
+    #[test]
+    fn silenced_observer_abstains() {
+        // hal9001 has hal9002 silenced, which hasn't reached this node yet
+        let state = State::new(&test_config(""));
+        let mut gr = state.lock().unwrap();
+        gr.node_state = vec![
+            NodeState::new("hal9001".to_string()),
+            NodeState {
+                name: "hal9002".to_string(),
+                ..dead_node()
+            },
+        ];
+        gr.record_obituaries(
+            "hal9000",
+            HashMap::from([(
+                "hal9001".to_string(),
+                ObituaryResponse {
+                    dead_nodes: vec![],
+                    announced_deaths: vec![],
+                    dying_nodes: vec![],
+                    verdicts: Some(vec![NodeVerdictResponse {
+                        name: "hal9002".to_string(),
+                        verdict: NodeVerdict::Silenced,
+                        fail_count: 0,
+                        last_ok: None,
+                    }]),
+                },
+            )]),
+            HashMap::new(),
+        );
+        let confirmation = &gr.node_state[1].confirmations["hal9001"];
+        assert!(confirmation.abstained && confirmation.silenced);
+        // the death is confirmed, and not announced for the silence
+        assert_eq!(gr.node_state[1].death_votes(false), (1, 0));
+
+        // a silence of this node goes to hal9001 before its obituary is asked
+        gr.silences.push(NodeSilence {
+            id: "1".to_string(),
+            node_name: "hal9002".to_string(),
+            silent_until: Utc::now() + chrono::Duration::hours(1),
+            broadcasted: false,
+            revision: 1,
+            cancelled: false,
+            local: false,
+            received_by: BTreeSet::new(),
+        });
+        gr.record_silence_receipts("1", 1, ["hal9001".to_string()], false);
+        // an older revision went out
+        gr.record_silence_receipts("1", 0, ["hal9002".to_string()], true);
+        assert_eq!(
+            gr.silences[0].received_by,
+            BTreeSet::from(["hal9001".to_string()])
+        );
+        assert!(!gr.silences[0].broadcasted);
+        gr.record_silence_receipts("1", 1, [], true);
+        assert!(gr.silences[0].broadcasted);
+    }
+
+    #[tokio::test]
+    async fn mute_expiry_resumes_announcements() {
+        let mut config = test_config("");
+        config.announcement_mode = AnnouncementMode::Log;
+        let state = State::new(&config);
+        let announcers = Announcers::new(&config, &state).unwrap();
+        let now = Utc::now();
+        let until = now + chrono::Duration::milliseconds(300);
+        let event = || {
+            AnnouncementEvent::new(
+                &config,
+                AnnouncementKind::Died,
+                "hal9001",
+                "`hal9001` died".to_string(),
             )
-        } else {
-            format!(
-                "Grid announcement, `{}` has fortunately RETURNED, announced by: `{me}`{end}",
-                target.name
+        };
+        {
+            let mut gr = state.lock().unwrap();
+            gr.mute = Some(GridMute {
+                until: Some(until),
+                changed_at: now,
+                broadcasted: true,
+            });
+            gr.pending_announcements.push(
+                crate::pending::PendingKind::Retry,
+                AnnouncementMode::Log,
+                event(),
+                now,
+                1,
+            );
+            assert_eq!(gr.muted_until(now), Some(until));
+            assert!(!gr.introspect("hal9000", until).muted);
+        }
+        let muted_events = |state: &State| {
+            state
+                .lock()
+                .unwrap()
+                .audit
+                .page(0, 10)
+                .iter()
+                .filter(|entry| matches!(entry.action, AuditAction::AnnouncementMuted { .. }))
+                .count()
+        };
+
+        // muted, the new one is only audited and the retry waits
+        announcers.announce(event()).await;
+        announcers.flush(&config).await;
+        assert_eq!(muted_events(&state), 1);
+        assert_eq!(
+            state.lock().unwrap().pending_announcements.sorted().len(),
+            1
+        );
+
+        tokio::time::sleep(std::time::Duration::from_millis(350)).await;
+        assert_eq!(state.lock().unwrap().muted_until(Utc::now()), None);
+        announcers.flush(&config).await;
+        announcers.announce(event()).await;
+        assert!(
+            state
+                .lock()
+                .unwrap()
+                .pending_announcements
+                .sorted()
+                .is_empty()
+        );
+        assert_eq!(muted_events(&state), 1);
+        let gr = state.lock().unwrap();
+        assert_eq!(
+            gr.announcement_windows[&AnnouncementMode::Log]
+                .deliveries()
+                .delivered,
+            2
+        );
+    }
+
+    #[test]
+    fn conflicting_silence_ids() {
+        let conflict = || Err(ClientError::Status(reqwest::StatusCode::CONFLICT));
+        assert!(silence_broadcast_done("hal9001", "42", conflict()));
+        assert!(!silence_broadcast_done(
+            "hal9001",
+            "42",
+            Err(ClientError::Status(reqwest::StatusCode::BAD_REQUEST))
+        ));
+        assert!(!broadcast_done("hal9001", conflict()));
+
+        // the conflicting peer is done with, the silence is broadcasted to the rest
+        let state = State::new(&test_config(""));
+        let mut gr = state.lock().unwrap();
+        gr.silences.push(NodeSilence {
+            id: "42".to_string(),
+            node_name: "hal9002".to_string(),
+            silent_until: Utc::now() + chrono::Duration::hours(1),
+            broadcasted: false,
+            revision: 0,
+            cancelled: false,
+            local: false,
+            received_by: BTreeSet::new(),
+        });
+        let received = [("hal9001", conflict()), ("hal9003", Ok(()))]
+            .into_iter()
+            .filter_map(|(node_name, res)| {
+                silence_broadcast_done(node_name, "42", res).then(|| node_name.to_string())
+            })
+            .collect::<Vec<_>>();
+        gr.record_silence_receipts("42", 0, received, true);
+        assert!(gr.silences[0].broadcasted);
+        assert_eq!(gr.silences[0].received_by.len(), 2);
+    }
+
+    #[test]
+    fn silences_changed_while_broadcast() {
+        let state = State::new(&test_config(""));
+        let mut gr = state.lock().unwrap();
+        gr.silences.push(NodeSilence {
+            id: "7".to_string(),
+            node_name: "hal9002".to_string(),
+            silent_until: Utc::now() + chrono::Duration::hours(1),
+            broadcasted: false,
+            revision: 1,
+            cancelled: false,
+            local: false,
+            received_by: BTreeSet::new(),
+        });
+
+        // revision 0 went out, then the silence changed here
+        gr.record_silence_receipts("7", 0, ["hal9001".to_string()], true);
+        assert!(!gr.silences[0].broadcasted);
+        assert!(gr.silences[0].received_by.is_empty());
+
+        gr.record_silence_receipts("7", 1, ["hal9001".to_string()], false);
+        assert!(!gr.silences[0].broadcasted);
+        gr.record_silence_receipts("7", 1, ["hal9003".to_string()], true);
+        assert!(gr.silences[0].broadcasted);
+        assert_eq!(gr.silences[0].received_by.len(), 2);
+    }
+
+    #[test]
+    fn dying_peers_confirm_when_counted() {
+        let mut ns = dead_node();
+        ns.confirmations
+            .insert("hal9002".to_string(), confirmed(None));
+        ns.confirmations.insert(
+            "hal9003".to_string(),
+            DeadConfirmation {
+                dying: true,
+                ..confirmed(None)
+            },
+        );
+        assert_eq!(ns.death_votes(false), (1, 2));
+        assert_eq!(ns.death_votes(true), (2, 1));
+        // no roll to compete with
+        assert_eq!(ns.announcement_winner("hal9000", 1).0, "hal9000");
+    }
+
+    #[test]
+    fn preferred_announcer_wins_once_it_confirms() {
+        let mut ns = dead_node();
+        ns.confirmations
+            .insert("hal9002".to_string(), confirmed(Some(9)));
+        ns.confirmations
+            .insert("hal9003".to_string(), confirmed(None));
+
+        assert_eq!(
+            ns.choose_announcer("hal9000", 5, None),
+            (("hal9002".to_string(), 9), AnnouncerChoice::Roll)
+        );
+        assert_eq!(
+            ns.choose_announcer("hal9000", 5, Some("hal9000")),
+            (("hal9000".to_string(), 5), AnnouncerChoice::Preferred)
+        );
+        // hal9003 thinks the node is alive
+        assert_eq!(
+            ns.choose_announcer("hal9000", 5, Some("hal9003")),
+            (
+                ("hal9002".to_string(), 9),
+                AnnouncerChoice::PreferredUnavailable("hal9003".to_string())
             )
-        },
-        token,
-        *chat_id,
-    );
-    if res.error() {
-        error!("Telegram notification failed: {}", res.status());
+        );
+
+        ns.confirmations
+            .insert("hal9003".to_string(), confirmed(Some(1)));
+        assert_eq!(
+            ns.choose_announcer("hal9000", 5, Some("hal9003")),
+            (("hal9003".to_string(), 1), AnnouncerChoice::Preferred)
+        );
     }
 }
@@ -0,0 +1,33 @@
+use crate::config::Config;
+use anyhow::{Context, Result};
+use reqwest::{Client, ClientBuilder, NoProxy, Proxy};
+
+/// Client builder for every outgoing call, going through the configured proxy.
+/// Without one, reqwest honors `HTTPS_PROXY`, `NO_PROXY` and friends
+pub fn client_builder(config: &Config) -> Result<ClientBuilder> {
+    let builder = Client::builder().use_rustls_tls();
+    let Some(proxy_config) = config.proxy.as_ref() else {
+        return Ok(builder);
+    };
+
+    let mut proxy = Proxy::all(&proxy_config.url)
+        .with_context(|| format!("Invalid proxy `{}`", proxy_config.url))?;
+    if let Some(username) = proxy_config.username.as_ref() {
+        proxy = proxy.basic_auth(
+            username,
+            proxy_config.password.as_deref().unwrap_or_default(),
+        );
+    }
+
+    let mut no_proxy = proxy_config.no_proxy.clone();
+    if !proxy_config.proxy_peers {
+        no_proxy.extend(config.nodes.values().filter_map(|node| {
+            reqwest::Url::parse(&node.address)
+                .ok()
+                .and_then(|url| url.host_str().map(str::to_string))
+        }));
+    }
+    proxy = proxy.no_proxy(NoProxy::from_string(&no_proxy.join(",")));
+
+    Ok(builder.proxy(proxy))
+}
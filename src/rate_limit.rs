@@ -0,0 +1,83 @@
+use crate::config::RateLimitConfig;
+use crate::peers::PeerAddresses;
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+/// Above this many tracked clients, idle buckets get evicted
+const MAX_TRACKED_CLIENTS: usize = 4096;
+
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+pub enum RateLimitDecision {
+    Allowed,
+    Limited { retry_after: Duration },
+}
+
+/// Token bucket per client IP, peers of the grid are never throttled
+pub struct RateLimiter {
+    config: RateLimitConfig,
+    allowlist: PeerAddresses,
+    buckets: Mutex<HashMap<IpAddr, Bucket>>,
+    rejected: AtomicU64,
+}
+
+impl RateLimiter {
+    pub fn new(config: RateLimitConfig, allowlist: PeerAddresses) -> Self {
+        Self {
+            config,
+            allowlist,
+            buckets: Mutex::new(HashMap::new()),
+            rejected: AtomicU64::new(0),
+        }
+    }
+
+    pub fn check(&self, ip: IpAddr) -> RateLimitDecision {
+        if !self.config.enabled || self.allowlist.contains(ip) {
+            return RateLimitDecision::Allowed;
+        }
+
+        let now = Instant::now();
+        let burst = self.config.burst as f64;
+        let per_second = self.config.per_second;
+        let mut buckets = self.buckets.lock().expect("Failed to lock rate limiter");
+
+        if buckets.len() > MAX_TRACKED_CLIENTS {
+            buckets.retain(|_, b| {
+                b.tokens + now.duration_since(b.updated).as_secs_f64() * per_second < burst
+            });
+        }
+
+        let bucket = buckets.entry(ip).or_insert(Bucket {
+            tokens: burst,
+            updated: now,
+        });
+        bucket.tokens = (bucket.tokens
+            + now.duration_since(bucket.updated).as_secs_f64() * per_second)
+            .min(burst);
+        bucket.updated = now;
+
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            return RateLimitDecision::Allowed;
+        }
+
+        self.rejected.fetch_add(1, Ordering::Relaxed);
+        let missing = 1.0 - bucket.tokens;
+        let retry_after = if per_second > 0.0 {
+            Duration::from_secs_f64(missing / per_second)
+        } else {
+            Duration::MAX
+        };
+        RateLimitDecision::Limited { retry_after }
+    }
+
+    pub fn rejected(&self) -> u64 {
+        self.rejected.load(Ordering::Relaxed)
+    }
+}
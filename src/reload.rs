@@ -0,0 +1,163 @@
+use crate::announcer::{self, Announcers};
+use crate::audit::{AuditAction, AuditSource};
+use crate::config::{Config, LogLevel, load_config};
+use crate::discovery;
+use crate::peers::PeerAddresses;
+use crate::poller::State;
+use anyhow::{Result, bail};
+use chrono::Utc;
+use log::LevelFilter;
+use std::path::PathBuf;
+use std::sync::{Arc, RwLock};
+use tracing::{error, info};
+
+pub use freecaster_grid_client::api::ConfigReload;
+
+/// The config in effect, replaced on reloads
+#[derive(Clone)]
+pub struct SharedConfig(Arc<RwLock<Arc<Config>>>);
+
+impl SharedConfig {
+    pub fn new(config: Arc<Config>) -> Self {
+        Self(Arc::new(RwLock::new(config)))
+    }
+
+    pub fn get(&self) -> Arc<Config> {
+        self.0.read().expect("Failed to lock config").clone()
+    }
+}
+
+pub fn set_log_level(level: LogLevel) {
+    log::set_max_level(match level {
+        LogLevel::Error => LevelFilter::Error,
+        LogLevel::Warn => LevelFilter::Warn,
+        LogLevel::Info => LevelFilter::Info,
+        LogLevel::Debug => LevelFilter::Debug,
+        LogLevel::Trace => LevelFilter::Trace,
+    });
+}
+
+/// Loads the config file again and puts it in effect, unless it changes something only a restart can,
+/// the nodes are resolved again for `peer_addresses`
+pub async fn reload(
+    path: Option<PathBuf>,
+    config: &SharedConfig,
+    state: &State,
+    peer_addresses: &PeerAddresses,
+    source: AuditSource,
+) -> Result<()> {
+    let result = load(path, &config.get(), state).await;
+
+    {
+        let mut gr = state.lock().expect("Failed to lock state");
+        let error = result.as_ref().err().map(|err| format!("{err:#}"));
+        gr.last_reload = Some(ConfigReload {
+            at: Utc::now(),
+            error: error.clone(),
+        });
+        let action = match error.clone() {
+            None => AuditAction::ConfigReloaded,
+            Some(reason) => AuditAction::ConfigReloadRejected { reason },
+        };
+        gr.events.record_action(&action);
+        gr.audit.record(source, action);
+    }
+
+    let new = match result {
+        Ok(new) => new,
+        Err(err) => {
+            error!("Config not reloaded: {err:#}");
+            return Err(err);
+        }
+    };
+    set_log_level(new.log_level);
+    peer_addresses
+        .refresh(&discovery::grid_nodes(&new, state))
+        .await;
+    *config.0.write().expect("Failed to lock config") = Arc::new(new);
+    info!("Config reloaded");
+    Ok(())
+}
+
+async fn load(path: Option<PathBuf>, current: &Config, state: &State) -> Result<Config> {
+    let mut new = load_config(path).await?;
+    new.skip_announce_check = current.skip_announce_check;
+    needs_restart(current, &new)?;
+    // built again by the poller, failing here keeps the working ones
+    Announcers::new(&new, state)?;
+    let telegram = |config: &Config| {
+        config
+            .telegram
+            .as_ref()
+            .map(|telegram| (telegram.token.clone(), telegram.chat_id))
+    };
+    if telegram(current) != telegram(&new) || !current.announces_to_telegram() {
+        announcer::check_telegram(&new, false).await?;
+    }
+    Ok(new)
+}
+
+fn needs_restart(current: &Config, new: &Config) -> Result<()> {
+    if current.name != new.name {
+        bail!(
+            "`name` changed from `{}` to `{}`, which needs a restart",
+            current.name,
+            new.name
+        );
+    }
+    let bind = |config: &Config| format!("{}:{}", config.server.ip_address, config.server.port);
+    if bind(current) != bind(new) {
+        bail!(
+            "The bind address changed from `{}` to `{}`, which needs a restart",
+            bind(current),
+            bind(new)
+        );
+    }
+    let ssl = |config: &Config| {
+        config
+            .server
+            .ssl
+            .as_ref()
+            .map(|ssl| (ssl.cert_path.clone(), ssl.key_path.clone()))
+    };
+    if ssl(current) != ssl(new) {
+        bail!("`server.ssl` changed, which needs a restart");
+    }
+    if current.mqtt != new.mqtt {
+        bail!("`mqtt` changed, which needs a restart");
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::test_config;
+
+    #[test]
+    fn restart_only_changes() {
+        let current = test_config("");
+        assert!(
+            needs_restart(
+                &current,
+                &test_config("poll_time: 30s\nwebui_enabled: true")
+            )
+            .is_ok()
+        );
+        assert!(needs_restart(&current, &test_config("  ip_address: 10.0.0.2")).is_err());
+        assert!(
+            needs_restart(
+                &current,
+                &test_config("  ssl:\n    cert_path: cert.pem\n    key_path: key.pem")
+            )
+            .is_err()
+        );
+        assert!(
+            needs_restart(
+                &current,
+                &test_config("mqtt:\n  url: mqtt://broker.local:1883")
+            )
+            .is_err()
+        );
+    }
+}
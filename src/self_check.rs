@@ -0,0 +1,81 @@
+use crate::StatusResponse;
+use crate::config::Config;
+use anyhow::{Context, Result, bail};
+use reqwest::Client;
+
+/// Failed self-checks in a row before the listener counts as broken
+pub const SELF_CHECK_FAILURES: u32 = 3;
+
+/// Where this node's own status endpoint answers, the advertised address if there is one,
+/// otherwise over the loopback for wildcard binds
+pub fn url(config: &Config) -> String {
+    if let Some(address) = config.advertised_address.as_ref() {
+        return format!("{}/", address.trim_end_matches('/'));
+    }
+    let scheme = if config.server.ssl.is_some() {
+        "https"
+    } else {
+        "http"
+    };
+    let host = match config.server.ip_address.as_str() {
+        "0.0.0.0" => "127.0.0.1",
+        "::" | "[::]" => "[::1]",
+        host => host,
+    };
+    format!("{scheme}://{host}:{}/", config.server.port)
+}
+
+/// Skips the proxy, and the certificate which is issued for the name peers use
+pub fn client(config: &Config) -> Result<Client> {
+    Ok(Client::builder()
+        .use_rustls_tls()
+        .no_proxy()
+        .danger_accept_invalid_certs(true)
+        .timeout(config.http_client.request_timeout)
+        .build()?)
+}
+
+/// Fails unless this node answers its status under its own name
+pub async fn check(client: &Client, url: &str, me: &str) -> Result<()> {
+    let res = client
+        .get(url)
+        .send()
+        .await
+        .map_err(|err| err.without_url())?;
+    if !res.status().is_success() {
+        bail!("status {}", res.status());
+    }
+    let status = res
+        .json::<StatusResponse>()
+        .await
+        .context("invalid status response")?;
+    if status.name != me {
+        bail!("answered by `{}`", status.name);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::test_config;
+
+    #[test]
+    fn wildcard_binds_go_over_the_loopback() {
+        assert_eq!(url(&test_config("")), "http://127.0.0.1:3037/");
+        assert_eq!(
+            url(&test_config("  ip_address: \"::\"")),
+            "http://[::1]:3037/"
+        );
+        assert_eq!(
+            url(&test_config("  ip_address: 10.0.0.2")),
+            "http://10.0.0.2:3037/"
+        );
+        assert_eq!(
+            url(&test_config(
+                "advertised_address: https://hal9000.example.com:4242"
+            )),
+            "https://hal9000.example.com:4242/"
+        );
+    }
+}
@@ -0,0 +1,90 @@
+//! Announced deaths kept across restarts, so an outage outliving a restart of the grid isn't announced again,
+//! and the node's recent starts, so a crash loop isn't announced on every start
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AnnouncedIncident {
+    pub node_name: String,
+    pub started: DateTime<Utc>,
+    pub announcer: String,
+    pub announced_at: DateTime<Utc>,
+}
+
+impl AnnouncedIncident {
+    pub fn expired(&self, max_age: std::time::Duration, now: DateTime<Utc>) -> bool {
+        now - self.announced_at
+            > chrono::Duration::from_std(max_age).unwrap_or(chrono::Duration::MAX)
+    }
+}
+
+#[derive(Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct StateFile {
+    #[serde(default)]
+    pub announced: Vec<AnnouncedIncident>,
+    /// When the node started within the last hour
+    #[serde(default)]
+    pub starts: Vec<DateTime<Utc>>,
+}
+
+/// The announced deaths not older than `max_age`, nothing if the file doesn't exist yet
+pub fn load(path: &Path, max_age: std::time::Duration, now: DateTime<Utc>) -> Result<StateFile> {
+    let content = match std::fs::read(path) {
+        Ok(content) => content,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(StateFile::default()),
+        Err(err) => {
+            return Err(err).with_context(|| format!("Failed to read `{}`", path.display()));
+        }
+    };
+    let mut file: StateFile = serde_json::from_slice(&content)
+        .with_context(|| format!("Invalid state file `{}`", path.display()))?;
+    file.announced.retain(|inc| !inc.expired(max_age, now));
+    Ok(file)
+}
+
+/// Written next to `path` first, a crash while saving leaves the previous state
+pub fn save(path: &Path, state: &StateFile) -> Result<()> {
+    let content = serde_json::to_vec_pretty(state)?;
+    let partial = path.with_extension("partial");
+    std::fs::write(&partial, content)
+        .with_context(|| format!("Failed to write `{}`", partial.display()))?;
+    std::fs::rename(&partial, path)
+        .with_context(|| format!("Failed to replace `{}`", path.display()))?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn announced_deaths_expire() {
+        let now = Utc::now();
+        let announced = |node: &str, days| AnnouncedIncident {
+            node_name: node.to_string(),
+            started: now - chrono::Duration::days(days),
+            announcer: "hal9000".to_string(),
+            announced_at: now - chrono::Duration::days(days),
+        };
+        let path =
+            std::env::temp_dir().join(format!("freecaster-grid-state-{}.json", std::process::id()));
+        let week = std::time::Duration::from_secs(7 * 24 * 60 * 60);
+        assert_eq!(load(&path, week, now).unwrap(), StateFile::default());
+
+        save(
+            &path,
+            &StateFile {
+                announced: vec![announced("hal9001", 1), announced("hal9002", 30)],
+                starts: vec![now],
+            },
+        )
+        .unwrap();
+        let loaded = load(&path, week, now).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(loaded.announced, vec![announced("hal9001", 1)]);
+        assert_eq!(loaded.starts, vec![now]);
+    }
+}
@@ -0,0 +1,69 @@
+pub use freecaster_grid_client::api::SystemMetrics;
+
+#[cfg(target_os = "linux")]
+pub fn collect() -> SystemMetrics {
+    let (memory_used_bytes, memory_total_bytes) = memory().unzip();
+    let (disk_free_bytes, disk_total_bytes) = root_disk().unzip();
+
+    SystemMetrics {
+        load_average: load_average(),
+        memory_used_bytes,
+        memory_total_bytes,
+        disk_free_bytes,
+        disk_total_bytes,
+        uptime_secs: uptime_secs(),
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn collect() -> SystemMetrics {
+    SystemMetrics::default()
+}
+
+#[cfg(target_os = "linux")]
+fn load_average() -> Option<[f64; 3]> {
+    let loadavg = std::fs::read_to_string("/proc/loadavg").ok()?;
+    let mut parts = loadavg.split_whitespace().map(|p| p.parse().ok());
+    Some([parts.next()??, parts.next()??, parts.next()??])
+}
+
+/// (used, total) in bytes
+#[cfg(target_os = "linux")]
+fn memory() -> Option<(u64, u64)> {
+    let meminfo = std::fs::read_to_string("/proc/meminfo").ok()?;
+    let field = |name: &str| -> Option<u64> {
+        let line = meminfo.lines().find(|l| l.starts_with(name))?;
+        let kb: u64 = line.split_whitespace().nth(1)?.parse().ok()?;
+        Some(kb * 1024)
+    };
+
+    let total = field("MemTotal:")?;
+    let available = field("MemAvailable:")?;
+    Some((total.saturating_sub(available), total))
+}
+
+/// (free, total) in bytes of the root filesystem
+#[cfg(target_os = "linux")]
+fn root_disk() -> Option<(u64, u64)> {
+    let mut stat = std::mem::MaybeUninit::<libc::statvfs>::uninit();
+    // SAFETY: the path is a valid C string and `stat` is only read after statvfs succeeded
+    let stat = unsafe {
+        if libc::statvfs(c"/".as_ptr(), stat.as_mut_ptr()) != 0 {
+            return None;
+        }
+        stat.assume_init()
+    };
+
+    let block_size = stat.f_frsize as u64;
+    Some((
+        stat.f_bavail as u64 * block_size,
+        stat.f_blocks as u64 * block_size,
+    ))
+}
+
+#[cfg(target_os = "linux")]
+fn uptime_secs() -> Option<u64> {
+    let uptime = std::fs::read_to_string("/proc/uptime").ok()?;
+    let secs: f64 = uptime.split_whitespace().next()?.parse().ok()?;
+    Some(secs as u64)
+}
@@ -0,0 +1,32 @@
+//! sd_notify support, a no-op unless systemd started us with a `NOTIFY_SOCKET`
+
+#[cfg(target_os = "linux")]
+pub fn notify(state: &str) {
+    let Some(path) = std::env::var_os("NOTIFY_SOCKET") else {
+        return;
+    };
+
+    if let Err(err) = send(&path, state) {
+        tracing::warn!("Failed to notify systemd `{state}`: {err:?}");
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn send(path: &std::ffi::OsStr, state: &str) -> std::io::Result<()> {
+    use std::os::linux::net::SocketAddrExt;
+    use std::os::unix::ffi::OsStrExt;
+    use std::os::unix::net::{SocketAddr, UnixDatagram};
+
+    // `@` marks a socket in the abstract namespace
+    let addr = match path.as_bytes().strip_prefix(b"@") {
+        Some(name) => SocketAddr::from_abstract_name(name)?,
+        None => SocketAddr::from_pathname(path)?,
+    };
+
+    let socket = UnixDatagram::unbound()?;
+    socket.send_to_addr(state.as_bytes(), &addr)?;
+    Ok(())
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn notify(_state: &str) {}
@@ -0,0 +1,225 @@
+use crate::audit::AuditSource;
+use crate::config::{BotCommandsConfig, Config};
+use crate::poller::State;
+use crate::proxy;
+use crate::{GridNodeStatus, grid_response, silence_node, try_parse_until_time};
+use anyhow::Result;
+use serde::Deserialize;
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::{error, info, warn};
+
+const LONG_POLL_TIMEOUT_SECS: u64 = 30;
+const HELP: &str = "Commands: /status, /grid, /silence <node|tag:name> <duration>";
+
+#[derive(Debug, Deserialize)]
+struct UpdatesResponse {
+    ok: bool,
+    #[serde(default)]
+    result: Vec<Update>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Update {
+    update_id: i64,
+    #[serde(default)]
+    message: Option<Message>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Message {
+    chat: Chat,
+    #[serde(default)]
+    from: Option<User>,
+    #[serde(default)]
+    text: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Chat {
+    id: i64,
+}
+
+#[derive(Debug, Deserialize)]
+struct User {
+    id: i64,
+}
+
+/// Long polls the bot for commands, only runs on the node designated by `bot_commands.node`
+pub async fn bot_commands(config: Arc<Config>, state: State) -> Result<()> {
+    let Some(telegram) = config.telegram.as_ref() else {
+        return Ok(());
+    };
+    let Some(bot) = telegram.bot_commands.as_ref() else {
+        return Ok(());
+    };
+    if bot.node != config.name {
+        info!("Bot commands are handled by `{}`", bot.node);
+        return Ok(());
+    }
+
+    info!("Handling telegram bot commands");
+    let client = proxy::client_builder(&config)?
+        .timeout(Duration::from_secs(LONG_POLL_TIMEOUT_SECS + 10))
+        .build()?;
+    let base = format!("https://api.telegram.org/bot{}", telegram.token);
+    let mut offset = 0;
+
+    loop {
+        let updates = client
+            .get(format!("{base}/getUpdates"))
+            .query(&[
+                ("offset", offset.to_string()),
+                ("timeout", LONG_POLL_TIMEOUT_SECS.to_string()),
+                ("allowed_updates", r#"["message"]"#.to_string()),
+            ])
+            .send()
+            .await;
+        let updates = match updates {
+            Ok(resp) => resp.json::<UpdatesResponse>().await,
+            Err(err) => Err(err),
+        };
+        let updates = match updates {
+            Ok(updates) if updates.ok => updates.result,
+            Ok(_) => {
+                error!("Telegram refused getUpdates");
+                tokio::time::sleep(Duration::from_secs(10)).await;
+                continue;
+            }
+            Err(err) => {
+                // the url holds the token
+                error!("Failed to get telegram updates: {:?}", err.without_url());
+                tokio::time::sleep(Duration::from_secs(10)).await;
+                continue;
+            }
+        };
+
+        for update in updates {
+            offset = offset.max(update.update_id + 1);
+            let Some(message) = update.message else {
+                continue;
+            };
+            let Some(text) = message.text.as_deref() else {
+                continue;
+            };
+            if !text.starts_with('/') {
+                continue;
+            }
+
+            let reply = handle_command(&config, &state, bot, message.from.map(|u| u.id), text);
+            let sent = client
+                .post(format!("{base}/sendMessage"))
+                .json(&serde_json::json!({
+                    "chat_id": message.chat.id,
+                    "text": reply,
+                }))
+                .send()
+                .await;
+            if let Err(err) = sent {
+                error!("Failed to answer telegram command: {:?}", err.without_url());
+            }
+        }
+    }
+}
+
+fn handle_command(
+    config: &Config,
+    state: &State,
+    bot: &BotCommandsConfig,
+    user_id: Option<i64>,
+    text: &str,
+) -> String {
+    let Some(user_id) = user_id.filter(|id| bot.allowed_user_ids.contains(id)) else {
+        warn!("Refused telegram command from user {user_id:?}");
+        return "Sorry, you are not allowed to command this grid.".to_string();
+    };
+
+    let mut parts = text.split_whitespace();
+    // commands in groups come as `/status@botname`
+    let command = parts
+        .next()
+        .unwrap_or_default()
+        .split('@')
+        .next()
+        .unwrap_or_default();
+    info!("Telegram command `{command}` from {user_id}");
+
+    match command {
+        "/status" => {
+            let grid = grid_response(config, state);
+            format!(
+                "Alive: {}, Dying: {}, Dead: {}, Unknown: {}, Total: {}",
+                grid.summary.alive_nodes,
+                grid.summary.dying_nodes,
+                grid.summary.dead_nodes,
+                grid.summary.unknown_nodes,
+                grid.summary.total_nodes
+            )
+        }
+        "/grid" => {
+            let grid = grid_response(config, state);
+            grid.nodes
+                .iter()
+                .map(|node| {
+                    let status = match node.status {
+                        GridNodeStatus::Alive => "alive",
+                        GridNodeStatus::Dying => "dying",
+                        GridNodeStatus::Dead => "dead",
+                        GridNodeStatus::Unknown => "not polled yet",
+                    };
+                    match node.last_poll {
+                        Some(last_poll) => format!(
+                            "{}: {status}, polled {}",
+                            node.display_name.as_ref().unwrap_or(&node.name),
+                            last_poll.format("%Y-%m-%d %H:%M:%S")
+                        ),
+                        None => format!(
+                            "{}: {status}",
+                            node.display_name.as_ref().unwrap_or(&node.name)
+                        ),
+                    }
+                })
+                .collect::<Vec<_>>()
+                .join("\n")
+        }
+        "/silence" => {
+            let (Some(node), Some(time)) = (parts.next(), parts.next()) else {
+                return "Usage: /silence <node|tag:name> <duration>".to_string();
+            };
+            let Some(silent_until) = try_parse_until_time(time) else {
+                return format!("Sorry, `{time}` is not a duration or timestamp.");
+            };
+
+            let node = match config.resolve_target(node) {
+                Ok(node) => node,
+                Err(names) => {
+                    return format!("Sorry, `{node}` could be any of {}.", names.join(", "));
+                }
+            };
+            let names = config.expand_target(&node);
+            if names.is_empty() {
+                return format!("Sorry, no node has the tag `{node}`.");
+            }
+
+            let mut silenced = vec![];
+            for name in names {
+                match silence_node(
+                    config,
+                    state,
+                    name,
+                    silent_until,
+                    false,
+                    AuditSource::Telegram { user_id },
+                ) {
+                    Ok(resp) => silenced.push(format!("`{}`", resp.name)),
+                    Err(rejection) => {
+                        return format!("Sorry, {}.", rejection.reason().to_lowercase());
+                    }
+                }
+            }
+            format!("Silenced {} until {silent_until}", silenced.join(", "))
+        }
+        "/ack" => "Sorry, acknowledgements are not supported by this grid.".to_string(),
+        _ => format!("Sorry, I don't know that command. {HELP}"),
+    }
+}
@@ -0,0 +1,562 @@
+//! A grid of nodes running in the test process, each with its listener on the loopback and its poller
+
+use crate::announcer::Announcers;
+use crate::app;
+use crate::audit::AuditAction;
+use crate::config::Config;
+use crate::peers::PeerAddresses;
+use crate::poller::{State, StateInner, poller};
+use crate::rate_limit::RateLimiter;
+use crate::reload::SharedConfig;
+use rouille::{Request, Response, Server};
+use std::collections::HashSet;
+use std::net::{SocketAddr, TcpListener};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, MutexGuard};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+pub const SECRET_KEY: &str = "almafa";
+/// Longest wait of `TestGrid::wait_for`
+const WAIT_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// The config of `hal9000` with the key `key` on port 3037, `extra` is appended, indented lines go in `server`
+pub fn test_config(extra: &str) -> Config {
+    ::config::Config::builder()
+        .add_source(::config::File::from_str(
+            &format!("name: hal9000\nsecret_key: key\nserver:\n  port: 3037\n{extra}"),
+            ::config::FileFormat::Yaml,
+        ))
+        .build()
+        .unwrap()
+        .try_deserialize()
+        .unwrap()
+}
+
+/// A server which can be stopped, unlike `Server::stoppable` even while it's busy
+struct Listener {
+    handle: JoinHandle<()>,
+    stop: Arc<AtomicBool>,
+}
+
+impl Listener {
+    fn start(
+        address: SocketAddr,
+        handler: impl Fn(&Request) -> Response + Send + Sync + 'static,
+    ) -> Self {
+        let server = Server::new(address, handler).expect("Failed to start listener");
+        let stop = Arc::new(AtomicBool::new(false));
+        let stopped = stop.clone();
+        let handle = std::thread::spawn(move || {
+            while !stopped.load(Ordering::Relaxed) {
+                server.poll();
+                std::thread::sleep(Duration::from_millis(5));
+            }
+        });
+        Self { handle, stop }
+    }
+
+    /// The address is free again once it returns
+    fn stop(self) {
+        self.stop.store(true, Ordering::Relaxed);
+        self.handle.join().expect("Listener panicked");
+    }
+}
+
+pub struct Instance {
+    pub name: String,
+    pub address: SocketAddr,
+    pub config: SharedConfig,
+    pub state: State,
+    listener: Option<Listener>,
+    poller: tokio::task::JoinHandle<anyhow::Result<()>>,
+}
+
+impl Instance {
+    pub fn url(&self, path: &str) -> String {
+        format!("http://{}{path}", self.address)
+    }
+
+    pub fn lock(&self) -> MutexGuard<'_, StateInner> {
+        self.state.lock().expect("Failed to lock state")
+    }
+
+    /// Who this node thinks announces `node`'s death
+    pub fn announced(&self, node: &str) -> Option<String> {
+        self.lock()
+            .node_state
+            .iter()
+            .find(|ns| ns.name == node && ns.is_dead())
+            .and_then(|ns| ns.announced.clone())
+    }
+
+    pub fn audited(&self, matches: impl Fn(&AuditAction) -> bool) -> bool {
+        let gr = self.lock();
+        gr.audit
+            .page(0, gr.audit.len())
+            .iter()
+            .any(|entry| matches(&entry.action))
+    }
+
+    fn listen(&self) -> Listener {
+        let config = self.config.get();
+        // every node calls from the loopback, this one included
+        let peer_ips = HashSet::from([self.address.ip()]);
+        let peer_addresses = PeerAddresses::new(
+            config
+                .nodes
+                .keys()
+                .chain([&config.name])
+                .map(|name| (name.clone(), peer_ips.clone()))
+                .collect(),
+        );
+        let handler = app(
+            self.config.clone(),
+            self.state.clone(),
+            Arc::new(RateLimiter::new(
+                config.rate_limit.clone(),
+                peer_addresses.clone(),
+            )),
+            peer_addresses,
+            None,
+            tokio::runtime::Handle::current(),
+        );
+        Listener::start(self.address, handler)
+    }
+}
+
+/// Nodes polling each other every 100ms, announcing to the log, stopped when dropped
+pub struct TestGrid {
+    pub nodes: Vec<Instance>,
+    /// Answers the internet checks
+    internet: Option<Listener>,
+}
+
+impl TestGrid {
+    /// Starts a node for each of `names`, `extra` is added to the config of every node
+    pub async fn start(names: &[&str], extra: &str) -> Self {
+        let internet_address = free_address();
+        let internet = Listener::start(internet_address, |_| Response::empty_204());
+        let addresses = names.iter().map(|_| free_address()).collect::<Vec<_>>();
+        let node_list = names
+            .iter()
+            .zip(addresses.iter())
+            .map(|(name, address)| format!("  {name}:\n    address: http://{address}\n"))
+            .collect::<String>();
+
+        let mut nodes = vec![];
+        for (name, address) in names.iter().zip(addresses) {
+            let config: Config = ::config::Config::builder()
+                .add_source(::config::File::from_str(
+                    &format!(
+                        r#"
+name: {name}
+secret_key: {SECRET_KEY}
+poll_time: 100ms
+announcement_mode: log
+internet_check_url: http://{}/
+http_client:
+  request_timeout: 1s
+server:
+  ip_address: 127.0.0.1
+  port: {}
+nodes:
+{node_list}{extra}
+"#,
+                        internet_address,
+                        address.port()
+                    ),
+                    ::config::FileFormat::Yaml,
+                ))
+                .build()
+                .expect("Failed to build config")
+                .try_deserialize()
+                .expect("Invalid config");
+            let config = config.without_self();
+            config.validate().expect("Invalid config");
+
+            let state = State::new(&config);
+            let announcers = Announcers::new(&config, &state).expect("Failed to build announcers");
+            let config = SharedConfig::new(Arc::new(config));
+            let poller = tokio::spawn(poller(config.clone(), vec![], state.clone(), announcers));
+
+            let mut node = Instance {
+                name: name.to_string(),
+                address,
+                config,
+                state,
+                listener: None,
+                poller,
+            };
+            node.listener = Some(node.listen());
+            nodes.push(node);
+        }
+
+        Self {
+            nodes,
+            internet: Some(internet),
+        }
+    }
+
+    pub fn node(&self, name: &str) -> &Instance {
+        self.nodes
+            .iter()
+            .find(|node| node.name == name)
+            .unwrap_or_else(|| panic!("No node `{name}`"))
+    }
+
+    /// Stops the listener of `name`, its poller keeps going
+    pub async fn kill(&mut self, name: &str) {
+        let node = self
+            .nodes
+            .iter_mut()
+            .find(|node| node.name == name)
+            .unwrap_or_else(|| panic!("No node `{name}`"));
+        if let Some(listener) = node.listener.take() {
+            tokio::task::spawn_blocking(move || listener.stop())
+                .await
+                .expect("Failed to stop listener");
+        }
+    }
+
+    /// Starts the listener of `name` again, on its old address
+    pub fn revive(&mut self, name: &str) {
+        let node = self
+            .nodes
+            .iter_mut()
+            .find(|node| node.name == name)
+            .unwrap_or_else(|| panic!("No node `{name}`"));
+        if node.listener.is_none() {
+            node.listener = Some(node.listen());
+        }
+    }
+
+    /// Waits until `check` holds, panics with `what` if it doesn't in time
+    pub async fn wait_for(&self, what: &str, check: impl Fn(&TestGrid) -> bool) {
+        let started = Instant::now();
+        while !check(self) {
+            if started.elapsed() > WAIT_TIMEOUT {
+                panic!("Timed out waiting for {what}");
+            }
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        }
+    }
+
+    /// Waits until every running poller of `names` completed a whole cycle after now
+    pub async fn wait_cycles(&self, names: &[&str]) {
+        let after = chrono::Utc::now();
+        self.wait_for("poll cycles", |grid| {
+            names.iter().all(|name| {
+                grid.node(name)
+                    .lock()
+                    .last_cycle_completed
+                    .is_some_and(|completed| completed > after)
+            })
+        })
+        .await;
+    }
+}
+
+impl Drop for TestGrid {
+    fn drop(&mut self) {
+        for node in self.nodes.iter_mut() {
+            node.poller.abort();
+            if let Some(listener) = node.listener.take() {
+                listener.stop.store(true, Ordering::Relaxed);
+            }
+        }
+        if let Some(internet) = self.internet.take() {
+            internet.stop.store(true, Ordering::Relaxed);
+        }
+    }
+}
+
+/// A loopback address nothing listens on right now
+fn free_address() -> SocketAddr {
+    TcpListener::bind("127.0.0.1:0")
+        .and_then(|listener| listener.local_addr())
+        .expect("Failed to find a free port")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The dead node is announced by one node, the others agree on which
+    async fn announced_death(grid: &TestGrid, dead: &str, survivors: &[&str]) -> String {
+        grid.wait_for(&format!("the death of `{dead}` to be announced"), |grid| {
+            let announcers = survivors
+                .iter()
+                .map(|name| grid.node(name).announced(dead))
+                .collect::<Vec<_>>();
+            announcers[0].is_some() && announcers.iter().all(|a| *a == announcers[0])
+        })
+        .await;
+        grid.node(survivors[0]).announced(dead).unwrap()
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn death_is_announced_by_quorum() {
+        let mut grid = TestGrid::start(&["alpha", "bravo", "charlie"], "").await;
+        grid.wait_cycles(&["alpha", "bravo", "charlie"]).await;
+
+        grid.kill("charlie").await;
+        let announcer = announced_death(&grid, "charlie", &["alpha", "bravo"]).await;
+        assert!(["alpha", "bravo"].contains(&announcer.as_str()));
+        assert!(grid.node(&announcer).audited(|action| matches!(
+            action,
+            AuditAction::DeathAnnouncementDecided { node_name, announcer: by }
+                if node_name == "charlie" && *by == announcer
+        )));
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn silence_broadcast_suppresses_the_announcement() {
+        let mut grid = TestGrid::start(&["delta", "echo", "foxtrot"], "").await;
+        let client = reqwest::Client::new();
+        let res = client
+            .get(
+                grid.node("delta")
+                    .url(&format!("/silence/{SECRET_KEY}/1h/foxtrot")),
+            )
+            .send()
+            .await
+            .unwrap();
+        assert!(res.status().is_success());
+        grid.wait_for("the silence to reach `echo`", |grid| {
+            grid.node("echo")
+                .lock()
+                .silences
+                .iter()
+                .any(|sl| sl.node_name == "foxtrot" && !sl.cancelled)
+        })
+        .await;
+
+        grid.kill("foxtrot").await;
+        for _ in 0..5 {
+            grid.wait_cycles(&["delta", "echo"]).await;
+        }
+        for name in ["delta", "echo"] {
+            assert_eq!(grid.node(name).announced("foxtrot"), None);
+            assert!(
+                !grid.node(name).audited(|action| matches!(
+                    action,
+                    AuditAction::DeathAnnouncementDecided { .. }
+                ))
+            );
+        }
+
+        // still dead once the silence is lifted
+        let res = client
+            .get(
+                grid.node("delta")
+                    .url(&format!("/unsilence/{SECRET_KEY}/foxtrot")),
+            )
+            .send()
+            .await
+            .unwrap();
+        assert!(res.status().is_success());
+        announced_death(&grid, "foxtrot", &["delta", "echo"]).await;
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn local_silence_stays_on_the_node() {
+        let mut grid = TestGrid::start(&["tango", "uniform", "victor"], "").await;
+        let res = reqwest::Client::new()
+            .get(
+                grid.node("tango")
+                    .url(&format!("/silence/{SECRET_KEY}/1h/victor?local=true")),
+            )
+            .send()
+            .await
+            .unwrap();
+        assert!(res.status().is_success());
+        grid.wait_cycles(&["tango", "uniform"]).await;
+        assert!(grid.node("uniform").lock().silences.is_empty());
+
+        // still polled by `tango`, but `uniform` announces it
+        grid.kill("victor").await;
+        assert_eq!(
+            announced_death(&grid, "victor", &["tango", "uniform"]).await,
+            "uniform"
+        );
+        assert!(grid.node("uniform").lock().silences.is_empty());
+        assert!(!grid.node("tango").audited(|action| matches!(
+            action,
+            AuditAction::DeathAnnouncementDecided { announcer, .. } if announcer == "tango"
+        )));
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn quiet_cycles_keep_the_revision() {
+        let mut grid = TestGrid::start(&["romeo", "sierra"], "").await;
+        grid.wait_cycles(&["romeo", "sierra"]).await;
+        grid.wait_cycles(&["romeo", "sierra"]).await;
+
+        let revision = grid.node("romeo").lock().revision;
+        for _ in 0..3 {
+            grid.wait_cycles(&["romeo"]).await;
+        }
+        assert_eq!(grid.node("romeo").lock().revision, revision);
+
+        grid.kill("sierra").await;
+        grid.wait_for("`sierra` failing to bump the revision", |grid| {
+            grid.node("romeo").lock().revision > revision
+        })
+        .await;
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn recovery_is_announced_by_the_announcer() {
+        let mut grid = TestGrid::start(&["golf", "hotel", "india"], "").await;
+        grid.wait_cycles(&["golf", "hotel", "india"]).await;
+
+        grid.kill("india").await;
+        let announcer = announced_death(&grid, "india", &["golf", "hotel"]).await;
+
+        grid.revive("india");
+        grid.wait_for("`india` to recover", |grid| {
+            ["golf", "hotel"].iter().all(|name| {
+                grid.node(name)
+                    .lock()
+                    .node_state
+                    .iter()
+                    .any(|ns| ns.name == "india" && !ns.is_dead() && ns.fail_count == 0)
+            })
+        })
+        .await;
+        assert!(grid.node(&announcer).audited(|action| matches!(
+            action,
+            AuditAction::RecoveryAnnouncementDecided { node_name } if node_name == "india"
+        )));
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn wrong_key_is_rejected_everywhere() {
+        let grid = TestGrid::start(&["juliett", "kilo"], "").await;
+        let node = grid.node("juliett");
+        let client = reqwest::Client::new();
+
+        for (method, path) in [
+            ("GET", "/obituary/{key}"),
+            ("POST", "/silence-broadcast/{key}"),
+            ("GET", "/silence/{key}/1h"),
+            ("GET", "/silence/{key}/1h/kilo"),
+            ("GET", "/unsilence/{key}"),
+            ("GET", "/unsilence/{key}/kilo"),
+            ("POST", "/mute/{key}/1h"),
+            ("DELETE", "/mute/{key}"),
+            ("POST", "/reload/{key}"),
+            ("POST", "/mute-broadcast/{key}"),
+            ("GET", "/metrics/{key}"),
+            ("GET", "/audit/{key}"),
+            ("GET", "/pending/{key}"),
+            ("GET", "/grid/{key}"),
+            ("GET", "/about/{key}"),
+        ] {
+            let method = reqwest::Method::from_bytes(method.as_bytes()).unwrap();
+            let res = client
+                .request(method.clone(), node.url(&path.replace("{key}", "wrong")))
+                .send()
+                .await
+                .unwrap();
+            assert_eq!(res.status(), 406, "{method} {path}");
+        }
+
+        let res = client
+            .get(node.url(&format!("/grid/{SECRET_KEY}")))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(res.status(), 200);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn silence_broadcasts_of_strangers() {
+        let grid = TestGrid::start(&["oscar", "papa"], "").await;
+        let node = grid.node("oscar");
+        let broadcast = |sender: &str, body: serde_json::Value| {
+            let request = reqwest::Client::new()
+                .post(node.url(&format!("/silence-broadcast/{SECRET_KEY}")))
+                .json(&body);
+            let request = match sender {
+                "" => request,
+                sender => request.header(crate::NODE_HEADER, sender),
+            };
+            async move { request.send().await.unwrap() }
+        };
+        let silence = |id: &str, node_name: &str, origin: &str| {
+            serde_json::json!({
+                "id": id,
+                "node_name": node_name,
+                "silent_until": chrono::Utc::now() + chrono::Duration::hours(1),
+                "origin": origin,
+            })
+        };
+
+        let res = broadcast("papa", silence("1", "papa", "quebec")).await;
+        assert_eq!(res.status(), 422);
+        assert_eq!(res.text().await.unwrap(), "Unknown origin `quebec`");
+        let res = broadcast("quebec", silence("2", "papa", "papa")).await;
+        assert_eq!(res.status(), 422);
+        let res = broadcast("papa", silence("3", "quebec", "papa")).await;
+        assert_eq!(res.status(), 422);
+        assert_eq!(res.text().await.unwrap(), "Unknown node");
+
+        let res = broadcast("", silence("4", "papa", "papa")).await;
+        assert_eq!(res.status(), 204);
+        let gr = node.lock();
+        assert_eq!(gr.suspicious_broadcasts, 1);
+        assert_eq!(
+            gr.silences
+                .iter()
+                .map(|sl| sl.id.as_str())
+                .collect::<Vec<_>>(),
+            ["4"]
+        );
+        let actions = gr.audit.page(0, 10);
+        assert!(matches!(
+            actions[1].action,
+            AuditAction::SilenceBroadcastSuspicious { .. }
+        ));
+        assert_eq!(
+            actions
+                .iter()
+                .filter(|entry| matches!(
+                    entry.action,
+                    AuditAction::SilenceBroadcastRejected { .. }
+                ))
+                .count(),
+            3
+        );
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn inbound_calls_are_attributed() {
+        let grid = TestGrid::start(&["lima", "mike"], "").await;
+        grid.wait_for("the polls of `mike` to reach `lima`", |grid| {
+            grid.node("lima")
+                .lock()
+                .node_state
+                .iter()
+                .any(|ns| ns.name == "mike" && ns.last_inbound.is_some())
+        })
+        .await;
+
+        // a name outside the grid isn't recorded
+        let res = reqwest::Client::new()
+            .get(grid.node("lima").url("/"))
+            .header(crate::NODE_HEADER, "november")
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(res.status(), 200);
+        assert!(
+            grid.node("lima")
+                .lock()
+                .node_state
+                .iter()
+                .all(|ns| ns.name != "november")
+        );
+    }
+}
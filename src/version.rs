@@ -0,0 +1,160 @@
+//! Version requirements of nodes, like `0.3.1`, `^0.3` or `>=0.3.0, <0.5`.
+//! A bare version is exact, missing parts match anything, pre-release and build suffixes are ignored
+
+use anyhow::{Context, Result, bail};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Op {
+    Exact,
+    Greater,
+    GreaterEq,
+    Less,
+    LessEq,
+    Caret,
+    Tilde,
+}
+
+/// `major.minor.patch`, `None` parts were left out
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Partial([Option<u64>; 3]);
+
+impl Partial {
+    fn parse(version: &str) -> Result<Self> {
+        let version = version
+            .split(['-', '+'])
+            .next()
+            .unwrap_or_default()
+            .trim_start_matches('v');
+        let mut parts = [None; 3];
+        let mut iter = version.split('.');
+        for part in parts.iter_mut() {
+            match iter.next() {
+                None | Some("*" | "x" | "X") => break,
+                Some(number) => {
+                    *part = Some(
+                        number
+                            .parse()
+                            .with_context(|| format!("Invalid version `{version}`"))?,
+                    )
+                }
+            }
+        }
+        if parts[0].is_none() || iter.next().is_some() {
+            bail!("Invalid version `{version}`");
+        }
+        Ok(Self(parts))
+    }
+
+    /// The missing parts as 0
+    fn floor(self) -> [u64; 3] {
+        self.0.map(|part| part.unwrap_or(0))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VersionReq(Vec<(Op, Partial)>);
+
+impl VersionReq {
+    pub fn parse(req: &str) -> Result<Self> {
+        let mut comparators = vec![];
+        for comparator in req.split(',') {
+            let comparator = comparator.trim();
+            let (op, version) = [
+                (">=", Op::GreaterEq),
+                ("<=", Op::LessEq),
+                (">", Op::Greater),
+                ("<", Op::Less),
+                ("=", Op::Exact),
+                ("^", Op::Caret),
+                ("~", Op::Tilde),
+            ]
+            .into_iter()
+            .find_map(|(prefix, op)| comparator.strip_prefix(prefix).map(|rest| (op, rest)))
+            .unwrap_or((Op::Exact, comparator));
+            comparators.push((op, Partial::parse(version.trim())?));
+        }
+        Ok(Self(comparators))
+    }
+
+    /// Whether `version`, as nodes report it, satisfies every comparator
+    pub fn matches(&self, version: &str) -> bool {
+        let Ok(Partial([Some(major), minor, patch])) = Partial::parse(version) else {
+            return false;
+        };
+        let version = [major, minor.unwrap_or(0), patch.unwrap_or(0)];
+        self.0
+            .iter()
+            .all(|(op, req)| matches_comparator(*op, *req, version))
+    }
+}
+
+fn matches_comparator(op: Op, req: Partial, version: [u64; 3]) -> bool {
+    let floor = req.floor();
+    match op {
+        Op::Exact => req
+            .0
+            .iter()
+            .zip(version)
+            .all(|(req, part)| req.is_none_or(|req| req == part)),
+        Op::Greater => version >= ceiling(req, req.0.iter().filter(|p| p.is_some()).count()),
+        Op::GreaterEq => version >= floor,
+        Op::Less => version < floor,
+        Op::LessEq => version < ceiling(req, req.0.iter().filter(|p| p.is_some()).count()),
+        // the leftmost non-zero part stays the same
+        Op::Caret => {
+            let fixed = match req.0 {
+                [Some(0), Some(0), Some(_)] => 3,
+                [Some(0), Some(_), _] => 2,
+                _ => 1,
+            };
+            version >= floor && version < ceiling(req, fixed)
+        }
+        Op::Tilde => {
+            let fixed = if req.0[1].is_some() { 2 } else { 1 };
+            version >= floor && version < ceiling(req, fixed)
+        }
+    }
+}
+
+/// The lowest version past `req` with its first `fixed` parts bumped
+fn ceiling(req: Partial, fixed: usize) -> [u64; 3] {
+    let mut ceiling = req.floor();
+    if fixed == 0 || fixed > 3 {
+        return ceiling;
+    }
+    ceiling[fixed - 1] += 1;
+    for part in ceiling.iter_mut().skip(fixed) {
+        *part = 0;
+    }
+    ceiling
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn requirements() {
+        let matches = |req: &str, version: &str| VersionReq::parse(req).unwrap().matches(version);
+        assert!(matches("0.3.0", "0.3.0"));
+        assert!(!matches("0.3.0", "0.3.1"));
+        assert!(matches("0.3", "0.3.7"));
+        assert!(matches("0.3.*", "0.3.7"));
+        assert!(matches("0.3.0", "0.3.0+d1829d2f0c3a"));
+        assert!(matches("^0.3.1", "0.3.9"));
+        assert!(!matches("^0.3.1", "0.4.0"));
+        assert!(!matches("^0.3.1", "0.3.0"));
+        assert!(matches("^1.2", "1.9.0"));
+        assert!(!matches("^1.2", "2.0.0"));
+        assert!(matches("~1.2.3", "1.2.9"));
+        assert!(!matches("~1.2.3", "1.3.0"));
+        assert!(matches(">=0.3.0, <0.5", "0.4.2"));
+        assert!(!matches(">=0.3.0, <0.5", "0.5.0"));
+        assert!(matches("<=0.4", "0.4.9"));
+        assert!(!matches(">0.4", "0.4.9"));
+        assert!(matches(">0.4", "0.5.0"));
+        assert!(!matches("0.3.0", "unknown"));
+        assert!(VersionReq::parse("latest").is_err());
+        assert!(VersionReq::parse("0.3.0.1").is_err());
+    }
+}
@@ -20,16 +20,34 @@ document.getElementById('change-key').onclick = function() {
     location.reload();
 };
 
+// hides the actions the key may not take, a read-only key sees the grid only
+fetch(`/webui-config/${key}`)
+    .then(r => r.json())
+    .then(config => {
+        document.querySelectorAll('[data-action]').forEach(el => {
+            el.hidden = !config.actions.includes(el.dataset.action);
+        });
+        document.getElementById('scope').hidden = config.scope !== 'read_only';
+    })
+    .catch(() => {});
+
 function fetchGrid() {
     fetch(`/grid/${key}`)
         .then(r => r.json())
         .then(data => {
-            document.getElementById('status').textContent = `Alive: ${data.alive_nodes}, Dead: ${data.dead_nodes}, Dying: ${data.dying_nodes}, Total: ${data.total_nodes}`;
+            document.getElementById('status').textContent = `Alive: ${data.alive_nodes}, Dead: ${data.dead_nodes}, Dying: ${data.dying_nodes}, Unknown: ${data.unknown_nodes ?? 0}, Total: ${data.total_nodes}`;
             const tbody = document.querySelector('#nodes tbody');
             tbody.innerHTML = '';
             data.nodes.forEach(node => {
                 const tr = document.createElement('tr');
-                tr.innerHTML = `<td>${node.name}</td><td>${node.last_poll ? node.last_poll : ''}</td><td>${node.status}</td>`;
+                const address = node.advertised_address;
+                const label = node.display_name
+                    ? `<span title="${node.name}">${node.display_name}</span>`
+                    : node.name;
+                const name = address && /^https?:\/\//.test(address)
+                    ? `<a href="${encodeURI(address.replace(/\/$/, ''))}/webui">${label}</a>`
+                    : label;
+                tr.innerHTML = `<td>${name}</td><td>${node.last_poll ? node.last_poll : ''}</td><td>${node.last_ok ? node.last_ok : ''}</td><td>${node.status}</td>`;
                 tr.className = node.status;
                 tbody.appendChild(tr);
             });